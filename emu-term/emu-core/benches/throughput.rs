@@ -0,0 +1,164 @@
+//! Throughput of the parser, in bytes, and of the grid updates, in cells painted, over output
+//! captured from real programs on a pty when the benchmarks start:
+//!
+//!     cargo bench -p emu-core
+//!
+//! `ls` is a long recursive listing with colors, `vim` the startup screen of vim on a source
+//! file and `log` a colored log going through `cat`. A corpus whose program isn't installed is
+//! left out
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use emu_core::ansi::{AnsiParser, Output as Piece};
+use emu_core::{Headless, Output, Terminal};
+use std::hint::black_box;
+use std::process::Command;
+use std::time::Duration;
+
+const ROWS: usize = 24;
+const COLS: usize = 80;
+/// How much of the listing is kept, `/usr` alone can be tens of megabytes
+const LS_BYTES: usize = 1 << 20;
+const LOG_LINES: usize = 20_000;
+/// vim draws its screen in a few goes, it's done once it's been quiet this long
+const QUIET: Duration = Duration::from_millis(500);
+
+fn corpora() -> Vec<(&'static str, Vec<u8>)> {
+    let mut corpora = vec![];
+    let ls = format!(
+        "ls -R --color=always /usr 2>/dev/null | head -c {}",
+        LS_BYTES
+    );
+    match capture(shell(&ls), false) {
+        Some(bytes) => corpora.push(("ls", bytes)),
+        None => eprintln!("skipping ls, it printed nothing"),
+    }
+
+    let source = concat!(env!("CARGO_MANIFEST_DIR"), "/src/terminal.rs");
+    let mut vim = Command::new("vim");
+    vim.args(["-u", "DEFAULTS", "-n", "-R", source]);
+    match capture(vim, true) {
+        Some(bytes) => corpora.push(("vim", bytes)),
+        None => eprintln!("skipping vim, it didn't come up"),
+    }
+
+    let path = std::env::temp_dir().join(format!("emu-core-bench-{}.log", std::process::id()));
+    let written = std::fs::write(&path, log());
+    let bytes = written
+        .ok()
+        .and_then(|_| capture(shell(&format!("cat '{}'", path.display())), false));
+    let _ = std::fs::remove_file(&path);
+    match bytes {
+        Some(bytes) => corpora.push(("log", bytes)),
+        None => eprintln!("skipping log, cat printed nothing"),
+    }
+    corpora
+}
+
+fn shell(script: &str) -> Command {
+    let mut command = Command::new("/bin/sh");
+    command.args(["-c", script]);
+    command
+}
+
+/// Output of `command` on a pty, everything until it exits or, for a full screen program, what
+/// it drew until it went quiet
+fn capture(command: Command, full_screen: bool) -> Option<Vec<u8>> {
+    let mut headless = Headless::spawn(command, ROWS, COLS).ok()?;
+    headless.keep_output();
+    if full_screen {
+        headless.settle(QUIET).ok()?;
+        if !headless.terminal().is_alt_screen() {
+            return None;
+        }
+    } else {
+        headless.wait().ok()?;
+    }
+    Some(headless.output().to_vec()).filter(|bytes| !bytes.is_empty())
+}
+
+/// A service log with a colored level on every line, like the ones piped through `cat` and
+/// `less -R` the most
+fn log() -> String {
+    let levels = [
+        ("\x1b[32mINFO\x1b[0m ", 6),
+        ("\x1b[1;33mWARN\x1b[0m ", 1),
+        ("\x1b[36mDEBUG\x1b[0m", 2),
+        ("\x1b[1;31mERROR\x1b[0m", 1),
+    ];
+    let levels: Vec<&str> = levels
+        .iter()
+        .flat_map(|&(level, weight)| std::iter::repeat_n(level, weight))
+        .collect();
+
+    let mut log = String::new();
+    for i in 0..LOG_LINES {
+        let level = levels[i * 7 % levels.len()];
+        log.push_str(&format!(
+            "2025-01-{:02}T{:02}:{:02}:{:02}.{:03}Z {} \x1b[2mworker-{}\x1b[0m request {} took {}ms path=/api/v1/items/{}\n",
+            1 + i / 86_400 % 28,
+            i / 3600 % 24,
+            i / 60 % 60,
+            i % 60,
+            i * 37 % 1000,
+            level,
+            i % 8,
+            i * 7919,
+            i * 13 % 500,
+            i % 1000,
+        ));
+    }
+    log
+}
+
+/// Cells the output paints, every char that isn't a control
+fn cells(bytes: &[u8]) -> u64 {
+    AnsiParser::new(bytes)
+        .filter_map(|piece| match piece {
+            Piece::Bytes(b) => Some(b),
+            _ => None,
+        })
+        .map(|b| {
+            String::from_utf8_lossy(b)
+                .chars()
+                .filter(|c| !c.is_control())
+                .count() as u64
+        })
+        .sum()
+}
+
+fn throughput(c: &mut Criterion) {
+    let corpora = corpora();
+
+    let mut parse = c.benchmark_group("parse");
+    for (name, bytes) in &corpora {
+        parse.throughput(Throughput::Bytes(bytes.len() as u64));
+        parse.bench_function(*name, |b| {
+            b.iter(|| AnsiParser::new(black_box(bytes)).count())
+        });
+    }
+    parse.finish();
+
+    // parsed in the setup, so only the grid updates are timed
+    let mut paint = c.benchmark_group("paint");
+    for (name, bytes) in &corpora {
+        paint.throughput(Throughput::Elements(cells(bytes)));
+        paint.bench_function(*name, |b| {
+            b.iter_batched(
+                || {
+                    let outputs = Output::parse(&Bytes::copy_from_slice(bytes));
+                    (Terminal::new(ROWS, COLS), outputs)
+                },
+                |(mut terminal, outputs)| {
+                    terminal.handle_output(outputs);
+                    terminal
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    paint.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
@@ -0,0 +1,141 @@
+//! Records a case for the golden tests:
+//!
+//!     cargo run --example golden -- <case> [<rows>x<cols>] [--type <keys>...] [-- command [args...]]
+//!
+//! The command runs on a pty until it exits or has been quiet for a second. Each `--type`
+//! is then typed in turn, once the screen settled again after the one before, which is how
+//! the menus of vttest and esctest get walked to the screen to capture:
+//!
+//!     cargo run --example golden -- vttest_origin --type '1\r' --type '\r' -- vttest
+//!
+//! `\r`, `\n`, `\t`, `\e`, `\\` and `\xHH` in the keys stand for the bytes they name. Without a
+//! command the output is read from standard input instead, like a log from
+//! `emu-term --log-pty`. Check the snapshot it writes before committing it, it's only what the
+//! terminal does today
+
+use emu_core::{Headless, Terminal};
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+const QUIET: Duration = Duration::from_secs(1);
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (args, command) = match args.iter().position(|arg| arg == "--") {
+        Some(i) => (&args[..i], &args[i + 1..]),
+        None => (&args[..], &[][..]),
+    };
+    let mut typed = vec![];
+    let mut positional = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--type" => match args.next().map(|keys| unescape(keys)) {
+                Some(Some(keys)) => typed.push(keys),
+                _ => usage(),
+            },
+            _ => positional.push(arg),
+        }
+    }
+    let (name, size) = match positional[..] {
+        [name] => (name, None),
+        [name, size] => (name, Some(size)),
+        _ => usage(),
+    };
+    let (rows, cols) = match size.map(|size| size.split_once('x')) {
+        None => (24, 80),
+        Some(Some((rows, cols))) => match (rows.parse(), cols.parse()) {
+            (Ok(rows), Ok(cols)) => (rows, cols),
+            _ => usage(),
+        },
+        Some(None) => usage(),
+    };
+
+    let bytes = match command {
+        [] if !typed.is_empty() => usage(),
+        [] => {
+            let mut bytes = vec![];
+            if let Err(e) = std::io::stdin().read_to_end(&mut bytes) {
+                fail(format!("failed to read standard input: {}", e));
+            }
+            bytes
+        }
+        [program, args @ ..] => run(program, args, &typed, rows, cols)
+            .unwrap_or_else(|e| fail(format!("failed to run {}: {}", program, e))),
+    };
+
+    let mut terminal = Terminal::new(rows, cols);
+    terminal.process_bytes(&bytes);
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let case = dir.join(format!("{}.bytes", name));
+    let golden = dir.join(format!("{}.golden", name));
+    for (path, contents) in [
+        (&case, &bytes[..]),
+        (&golden, terminal.snapshot().as_bytes()),
+    ] {
+        if let Err(e) = std::fs::write(path, contents) {
+            fail(format!("failed to write {}: {}", path.display(), e));
+        }
+    }
+    println!("{}\n{}", case.display(), golden.display());
+}
+
+/// Output of `program` until it exits or goes quiet, with `typed` typed in turn after that
+fn run(
+    program: &str,
+    args: &[String],
+    typed: &[Vec<u8>],
+    rows: usize,
+    cols: usize,
+) -> std::io::Result<Vec<u8>> {
+    let mut command = Command::new(program);
+    command.args(args);
+    let mut headless = Headless::spawn(command, rows, cols)?;
+    headless.keep_output();
+    headless.settle(QUIET)?;
+    for keys in typed {
+        headless.write(keys)?;
+        headless.settle(QUIET)?;
+    }
+    Ok(headless.output().to_vec())
+}
+
+/// The bytes `keys` spells out with its escapes, `None` for an escape that isn't one
+fn unescape(keys: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut chars = keys.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let byte = match chars.next()? {
+            'r' => b'\r',
+            'n' => b'\n',
+            't' => b'\t',
+            'e' => 0x1b,
+            '\\' => b'\\',
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&hex, 16).ok()?
+            }
+            _ => return None,
+        };
+        bytes.push(byte);
+    }
+    Some(bytes)
+}
+
+fn usage() -> ! {
+    fail(
+        "usage: golden <case> [<rows>x<cols>] [--type <keys>...] [-- command [args...]]".to_owned(),
+    )
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2)
+}
@@ -0,0 +1,22 @@
+//! Arbitrary output through `AnsiParser`. Every piece it hands out has to take at least one
+//! byte off the input, otherwise it spins on the same escape forever
+
+#![no_main]
+
+use emu_core::ansi::{AnsiParser, Output};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut outputs = 0;
+    let mut raw = 0;
+    for output in AnsiParser::new(data) {
+        outputs += 1;
+        assert!(outputs <= data.len(), "more pieces than bytes");
+
+        if let Output::Bytes(b) | Output::Unknown(b) = output {
+            assert!(!b.is_empty(), "empty piece");
+            raw += b.len();
+        }
+    }
+    assert!(raw <= data.len());
+});
@@ -0,0 +1,15 @@
+//! Arbitrary sixel data through `sixel::decode`. Whatever the data asks for, the image has to
+//! stay within `MAX_SIDE` and hand out exactly the pixels its size says
+
+#![no_main]
+
+use emu_core::sixel::{self, MAX_SIDE};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some(image) = sixel::decode(data) else {
+        return;
+    };
+    assert!(image.width <= MAX_SIDE && image.height <= MAX_SIDE);
+    assert_eq!(image.rgba.len(), image.width * image.height * 4);
+});
@@ -0,0 +1,39 @@
+//! Arbitrary output applied to a `Terminal`, the way a frontend does it: in reads of some size,
+//! trimming the scrollback after each. Debug assertions are on under `cargo fuzz`, so any
+//! underflow in the cursor arithmetic shows up as a crash. The grid has to stay within the
+//! screen and the scrollback limit however the output moves the cursor
+
+#![no_main]
+
+use emu_core::Terminal;
+use libfuzzer_sys::fuzz_target;
+
+/// Enough for the oldest rows to get packed, which starts a few hundred rows up
+const SCROLLBACK: usize = 1000;
+
+fuzz_target!(|data: &[u8]| {
+    let [rows, cols, read, output @ ..] = data else {
+        return;
+    };
+    let rows = 1 + *rows as usize % 64;
+    let cols = 1 + *cols as usize % 256;
+    let read = 1 + *read as usize;
+
+    let mut terminal = Terminal::new(rows, cols);
+    for chunk in output.chunks(read) {
+        terminal.process_bytes(chunk);
+        terminal.trim_scrollback(SCROLLBACK);
+
+        // the primary screen isn't trimmed while the alternate one is up, it doesn't grow then
+        let grid = terminal.grid();
+        let limit = match terminal.is_alt_screen() {
+            true => rows,
+            false => SCROLLBACK + rows,
+        };
+        assert!(grid.len() <= limit, "{} rows", grid.len());
+        for row in grid.iter() {
+            assert!(row.cells.len() <= cols, "{} cells", row.cells.len());
+        }
+    }
+    let _ = terminal.snapshot();
+});
@@ -0,0 +1,866 @@
+// This is a copypaste of
+// https://gitlab.com/davidbittner/ansi-parser/-/blob/master/src/parsers.rs?ref_type=heads slightly
+// modified to work directly in byte buffers
+
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_till};
+use nom::character::complete::{digit0, digit1};
+use nom::combinator::{opt, verify};
+use nom::multi::{separated_list0, separated_list1};
+use nom::sequence::{delimited, preceded};
+use nom::{IResult, Parser};
+
+macro_rules! tag_parser {
+    ($sig:ident, $tag:expr, $ret:expr) => {
+        fn $sig(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+            tag($tag)(input).map(|(s, _)| (s, $ret))
+        }
+    };
+}
+
+#[derive(Debug)]
+pub enum AnsiCode {
+    Escape,
+    CursorPos(u32, u32),
+    CursorUp(u32),
+    CursorDown(u32),
+    CursorForward(u32),
+    CursorBackward(u32),
+    /// CHT, `CSI Ps I`, the cursor to the `Ps`th tab stop to the right
+    CursorForwardTab(u32),
+    /// CBT, `CSI Ps Z`, the cursor to the `Ps`th tab stop to the left
+    CursorBackwardTab(u32),
+    /// HTS, `ESC H`, a tab stop at the cursor column
+    SetTabStop,
+    /// TBC, `CSI Ps g`. 0 clears the tab stop at the cursor column and 3 all of them
+    ClearTabStop(u32),
+    /// SCOSC, `CSI s`, saves the cursor like DECSC unless left and right margins are enabled
+    CursorSave,
+    /// SCORC, `CSI u`
+    CursorRestore,
+    /// DECSC, `ESC 7`, saves the cursor with the style it paints in, the charsets and the
+    /// origin mode
+    SaveCursor,
+    /// DECRC, `ESC 8`, puts back what DECSC saved
+    RestoreCursor,
+    EnableCursorBlink,
+    DisableCursorBlink,
+    /// ED, `CSI Ps J`. 0 erases from the cursor to the end of the screen, 1 from the start of
+    /// the screen to the cursor, 2 the whole screen and 3 drops the scrollback
+    EraseDisplay(u32),
+    /// EL, `CSI Ps K`. 0 erases from the cursor to the end of the line, 1 from the start of
+    /// the line to the cursor and 2 the whole line
+    EraseLine(u32),
+    /// SGR, each parameter with the sub-parameters that came after it with `:`. Left out ones
+    /// are 0, so a bare `CSI m` is a reset
+    SetGraphicsMode(Vec<Vec<u32>>),
+    SetMode(u8),
+    ResetMode(u8),
+    HideCursor,
+    ShowCursor,
+    CursorToApp,
+    SetNewLineMode,
+    SetCol132,
+    SetSmoothScroll,
+    SetReverseVideo,
+    SetOriginRelative,
+    SetAutoWrap,
+    SetAutoRepeat,
+    SetInterlacing,
+    SetLineFeedMode,
+    SetCursorKeyToCursor,
+    SetVT52,
+    SetCol80,
+    SetJumpScrolling,
+    SetNormalVideo,
+    SetOriginAbsolute,
+    ResetAutoWrap,
+    ResetAutoRepeat,
+    ResetInterlacing,
+    SetAlternateKeypad,
+    SetNumericKeypad,
+    SetUKG0,
+    SetUKG1,
+    SetUSG0,
+    SetUSG1,
+    SetG0SpecialChars,
+    SetG1SpecialChars,
+    SetG0AlternateChar,
+    SetG1AlternateChar,
+    SetG0AltAndSpecialGraph,
+    SetG1AltAndSpecialGraph,
+    SetSingleShift2,
+    SetSingleShift3,
+    /// IND, `ESC D`, a line feed that never returns the carriage
+    Index,
+    /// NEL, `ESC E`, a carriage return and a line feed
+    NextLine,
+    /// RI, `ESC M`, a line up, scrolling the margins down when on the top one
+    ReverseIndex,
+    SetTopAndBottom(u32, u32),
+    SetLeftAndRight(u32, u32),
+    EnableLeftRightMargins,
+    DisableLeftRightMargins,
+    EnableBracketedPaste,
+    DisableBracketedPaste,
+    CopyRectArea(Rect, u32, u32),
+    FillRectArea(char, Rect),
+    EraseRectArea(Rect),
+    SetCursorStyle(u8),
+    Osc(u32, String),
+    /// `DCS q`, a sixel image. Carries the data after the `q`, see `sixel::decode`
+    Sixel(Vec<u8>),
+    /// APC, the kitty graphics protocol being the one that starts with `G`, see `kitty::parse`
+    Apc(Vec<u8>),
+    /// DECRQM, asking whether a mode is set. `CSI ? Pd $ p` for a private mode, when the flag
+    /// is set, and `CSI Pd $ p` for an ANSI one
+    RequestMode(u32, bool),
+    /// `DCS + q`, XTGETTCAP asking for the terminfo capabilities named, hex encoded in the
+    /// sequence. Names that aren't valid hex are dropped
+    RequestTermcap(Vec<String>),
+    /// Switch to the alternate screen, carries the private mode used (47, 1047 or 1049)
+    EnableAltScreen(u32),
+    DisableAltScreen(u32),
+    /// Mouse reporting modes, carries the private mode number (1000, 1002, 1003 or 1006)
+    EnableMouseMode(u32),
+    DisableMouseMode(u32),
+    /// DECSET or DECRST with several modes, `CSI ? Pm ; Pm h`. Each mode is applied as if it
+    /// came in a sequence of its own, see [`private_mode`]
+    PrivateModes(Vec<u32>, bool),
+}
+
+/// Rectangle parameters as sent by the application, edges left as 0 were omitted and
+/// should fall back to the screen bounds
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Rect {
+    pub top: u32,
+    pub left: u32,
+    pub bottom: u32,
+    pub right: u32,
+}
+
+impl Rect {
+    fn from_params(params: &[u32]) -> Self {
+        Self {
+            top: param(params, 0),
+            left: param(params, 1),
+            bottom: param(params, 2),
+            right: param(params, 3),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AnsiParser<'a> {
+    slice: &'a [u8],
+}
+
+impl<'a> AnsiParser<'a> {
+    pub fn new(slice: &'a [u8]) -> Self {
+        Self { slice }
+    }
+}
+
+#[derive(Debug)]
+pub enum Output<'a> {
+    Bytes(&'a [u8]),
+    Escape(AnsiCode),
+    /// An escape sequence no parser recognized, up to the next escape
+    Unknown(&'a [u8]),
+}
+
+impl<'a> Iterator for AnsiParser<'a> {
+    type Item = Output<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        fn find_in_slice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+            haystack
+                .windows(needle.len())
+                .position(|window| window == needle)
+        }
+
+        if self.slice.is_empty() {
+            return None;
+        }
+
+        match find_in_slice(self.slice, b"\x1b") {
+            Some(0) => {
+                let res = ansi_parse(self.slice);
+
+                if let Ok((rest, ac)) = res {
+                    self.slice = rest;
+                    Some(Output::Escape(ac))
+                } else {
+                    let pos = find_in_slice(&self.slice[1..], b"\x1b");
+                    let bytes = match pos {
+                        Some(i) => {
+                            let i = i + 1;
+                            let bytes = &self.slice[..i];
+                            self.slice = &self.slice[i..];
+                            bytes
+                        }
+
+                        None => {
+                            let bytes = self.slice;
+                            self.slice = &[];
+
+                            bytes
+                        }
+                    };
+                    tracing::debug!(
+                        sequence = ?String::from_utf8_lossy(bytes),
+                        "unknown escape sequence"
+                    );
+                    Some(Output::Unknown(bytes))
+                }
+            }
+            Some(n) => {
+                let bytes = &self.slice[..n];
+                self.slice = &self.slice[n..];
+                Some(Output::Bytes(bytes))
+            }
+            None => {
+                let bytes = self.slice;
+                self.slice = &[];
+
+                Some(Output::Bytes(bytes))
+            }
+        }
+    }
+}
+
+fn parse_def_cursor_int(input: &[u8]) -> IResult<&[u8], u32> {
+    digit0(input).map(|(s, d)| {
+        (
+            s,
+            std::str::from_utf8(d)
+                .unwrap_or("1")
+                .parse::<u32>()
+                .unwrap_or(1),
+        )
+    })
+}
+
+fn parse_u8(input: &[u8]) -> IResult<&[u8], u8> {
+    digit1(input).map(|(s, d)| {
+        (
+            s,
+            std::str::from_utf8(d)
+                .unwrap_or("1")
+                .parse::<u8>()
+                .unwrap_or(1),
+        )
+    })
+}
+
+fn parse_u32(input: &[u8]) -> IResult<&[u8], u32> {
+    digit1(input).map(|(s, d)| {
+        (
+            s,
+            std::str::from_utf8(d)
+                .unwrap_or("1")
+                .parse::<u32>()
+                .unwrap_or(1),
+        )
+    })
+}
+
+fn parse_def_zero_int(input: &[u8]) -> IResult<&[u8], u32> {
+    digit0(input).map(|(s, d)| {
+        (
+            s,
+            std::str::from_utf8(d)
+                .unwrap_or("0")
+                .parse::<u32>()
+                .unwrap_or(0),
+        )
+    })
+}
+
+fn parse_params(input: &[u8]) -> IResult<&[u8], Vec<u32>> {
+    separated_list0(tag(";"), parse_def_zero_int).parse(input)
+}
+
+fn param(params: &[u32], i: usize) -> u32 {
+    params.get(i).copied().unwrap_or(0)
+}
+
+fn escape(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    tag("\u{1b}")(input).map(|(s, _)| (s, AnsiCode::Escape))
+}
+
+fn cursor_up(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("["), parse_def_cursor_int, tag("A"))
+        .parse(input)
+        .map(|(s, amount)| (s, AnsiCode::CursorUp(amount)))
+}
+
+fn cursor_down(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("["), parse_def_cursor_int, tag("B"))
+        .parse(input)
+        .map(|(s, amount)| (s, AnsiCode::CursorDown(amount)))
+}
+fn cursor_forward(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("["), parse_def_cursor_int, tag("C"))
+        .parse(input)
+        .map(|(s, amount)| (s, AnsiCode::CursorForward(amount)))
+}
+fn cursor_backward(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("["), parse_def_cursor_int, tag("D"))
+        .parse(input)
+        .map(|(s, amount)| (s, AnsiCode::CursorBackward(amount)))
+}
+
+fn cursor_forward_tab(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("["), parse_def_cursor_int, tag("I"))
+        .parse(input)
+        .map(|(s, amount)| (s, AnsiCode::CursorForwardTab(amount)))
+}
+
+fn cursor_backward_tab(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("["), parse_def_cursor_int, tag("Z"))
+        .parse(input)
+        .map(|(s, amount)| (s, AnsiCode::CursorBackwardTab(amount)))
+}
+
+fn clear_tab_stop(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("["), parse_def_zero_int, tag("g"))
+        .parse(input)
+        .map(|(s, mode)| (s, AnsiCode::ClearTabStop(mode)))
+}
+
+fn tab_controls(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    alt((
+        cursor_forward_tab,
+        cursor_backward_tab,
+        clear_tab_stop,
+        set_tab_stop,
+    ))
+    .parse(input)
+}
+
+fn erase_in_display(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("["), parse_def_zero_int, tag("J"))
+        .parse(input)
+        .map(|(s, mode)| (s, AnsiCode::EraseDisplay(mode)))
+}
+
+fn erase_in_line(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("["), parse_def_zero_int, tag("K"))
+        .parse(input)
+        .map(|(s, mode)| (s, AnsiCode::EraseLine(mode)))
+}
+
+fn set_cursor_style(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    (tag("["), opt(parse_u8), tag(" q"))
+        .parse(input)
+        .map(|(s, (_, style, _))| (s, AnsiCode::SetCursorStyle(style.unwrap_or(0))))
+}
+
+fn request_mode(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    (tag("["), opt(tag("?")), parse_u32, tag("$p"))
+        .parse(input)
+        .map(|(s, (_, private, mode, _))| (s, AnsiCode::RequestMode(mode, private.is_some())))
+}
+
+fn osc(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    // terminated by either BEL or ST
+    (
+        tag("]"),
+        parse_u32,
+        opt(preceded(tag(";"), take_till(|b| b == 0x07 || b == 0x1b))),
+        alt((tag("\x07"), tag("\x1b\\"))),
+    )
+        .parse(input)
+        .map(|(s, (_, ps, pt, _))| {
+            let pt = String::from_utf8_lossy(pt.unwrap_or_default()).into_owned();
+            (s, AnsiCode::Osc(ps, pt))
+        })
+}
+
+fn sixel(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    // the parameters are the aspect ratio and background, the raster attributes in the data
+    // take over from them
+    (
+        tag("P"),
+        parse_params,
+        tag("q"),
+        take_till(|b| b == 0x1b),
+        tag("\x1b\\"),
+    )
+        .parse(input)
+        .map(|(s, (_, _, _, data, _))| (s, AnsiCode::Sixel(data.to_vec())))
+}
+
+fn request_termcap(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("P+q"), take_till(|b| b == 0x1b), tag("\x1b\\"))
+        .parse(input)
+        .map(|(s, hex)| {
+            let names = hex.split(|&b| b == b';').filter_map(hex_string).collect();
+            (s, AnsiCode::RequestTermcap(names))
+        })
+}
+
+/// Text encoded as two hex digits per byte
+fn hex_string(hex: &[u8]) -> Option<String> {
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let digit = |b: u8| (b as char).to_digit(16);
+    let bytes = hex
+        .chunks_exact(2)
+        .map(|pair| Some((digit(pair[0])? << 4 | digit(pair[1])?) as u8))
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+fn apc(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("_"), take_till(|b| b == 0x1b), tag("\x1b\\"))
+        .parse(input)
+        .map(|(s, data)| (s, AnsiCode::Apc(data.to_vec())))
+}
+
+/// How much of a DCS, OSC or APC is held while its terminator is yet to come, images can take
+/// megabytes
+pub const SEQUENCE_MAX_BYTES: usize = 16 << 20;
+
+/// An escape sequence at the end of the bytes read that isn't whole yet
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Unterminated {
+    /// Starts here, the rest of it is yet to be read. Readers hold it back rather than have it
+    /// parsed in two halves
+    Held(usize),
+    /// Starts here and is already past `SEQUENCE_MAX_BYTES`. Readers drop it, and what comes
+    /// after it up to [`string_end`]
+    TooLong(usize),
+}
+
+/// Where an escape sequence that isn't whole yet starts: a DCS, OSC or APC still missing its
+/// terminator, or a CSI or escape cut off before its final byte. One cancelled by CAN or SUB
+/// has ended, it's no longer waiting for anything
+pub fn unterminated(bytes: &[u8]) -> Option<Unterminated> {
+    let start = bytes.iter().rposition(|&b| b == 0x1b)?;
+    let rest = &bytes[start + 1..];
+    if rest.iter().any(|&b| b == 0x18 || b == 0x1a) {
+        return None;
+    }
+    let string = match rest {
+        // a lone ESC, or one still missing the charset it designates
+        [] | [b'(' | b')' | b'#' | b'%'] => false,
+        // parameters and intermediates so far, the final byte is yet to come
+        [b'[', params @ ..] if params.iter().all(|b| (0x20..0x40).contains(b)) => false,
+        [b'P' | b'_', ..] => true,
+        [b']', ..] if !rest.contains(&0x07) => true,
+        _ => return None,
+    };
+    match bytes.len() - start < SEQUENCE_MAX_BYTES {
+        true => Some(Unterminated::Held(start)),
+        false if string => Some(Unterminated::TooLong(start)),
+        // no CSI is that long, it's output like any other
+        false => None,
+    }
+}
+
+/// Just past the BEL, ST, CAN or SUB that ends the string sequence `bytes` are the rest of
+pub fn string_end(bytes: &[u8]) -> Option<usize> {
+    bytes.iter().enumerate().find_map(|(i, &b)| match b {
+        0x07 | 0x18 | 0x1a => Some(i + 1),
+        0x1b if bytes.get(i + 1) == Some(&b'\\') => Some(i + 2),
+        _ => None,
+    })
+}
+
+fn set_mode(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    (tag("[="), parse_u8, tag("h"))
+        .parse(input)
+        .map(|(s, (_, m, _))| (s, AnsiCode::SetMode(m)))
+}
+
+fn reset_mode(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    (tag("[="), parse_u8, tag("l"))
+        .parse(input)
+        .map(|(s, (_, m, _))| (s, AnsiCode::ResetMode(m)))
+}
+
+fn graphics_mode(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("["), sgr_params, tag("m"))
+        .parse(input)
+        .map(|(s, params)| (s, AnsiCode::SetGraphicsMode(params)))
+}
+
+fn sgr_params(input: &[u8]) -> IResult<&[u8], Vec<Vec<u32>>> {
+    separated_list1(tag(";"), separated_list1(tag(":"), parse_sgr_int)).parse(input)
+}
+
+/// Empty is 0, and a value too big for a `u32` is one no attribute takes
+fn parse_sgr_int(input: &[u8]) -> IResult<&[u8], u32> {
+    digit0(input).map(|(s, d)| match d.is_empty() {
+        true => (s, 0),
+        false => (
+            s,
+            std::str::from_utf8(d)
+                .ok()
+                .and_then(|d| d.parse().ok())
+                .unwrap_or(u32::MAX),
+        ),
+    })
+}
+
+fn set_top_and_bottom(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("["), parse_params, tag("r"))
+        .parse(input)
+        .map(|(s, p)| (s, AnsiCode::SetTopAndBottom(param(&p, 0), param(&p, 1))))
+}
+
+fn set_left_and_right(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    // a bare `[s` is a cursor save, DECSLRM needs at least the left margin
+    (
+        tag("["),
+        parse_u32,
+        opt(preceded(tag(";"), parse_u32)),
+        tag("s"),
+    )
+        .parse(input)
+        .map(|(s, (_, l, r, _))| (s, AnsiCode::SetLeftAndRight(l, r.unwrap_or(0))))
+}
+
+fn cursor_pos(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    (
+        tag("["),
+        parse_def_cursor_int,
+        opt(tag(";")),
+        parse_def_cursor_int,
+        alt((tag("H"), tag("f"))),
+    )
+        .parse(input)
+        .map(|(s, (_, x, _, y, _))| (s, AnsiCode::CursorPos(x, y)))
+}
+
+fn copy_rect_area(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    // Pts;Pls;Pbs;Prs;Pps;Ptd;Pld;Ppd, pages are ignored since there's a single one
+    delimited(tag("["), parse_params, tag("$v"))
+        .parse(input)
+        .map(|(s, p)| {
+            let src = Rect::from_params(&p);
+            (s, AnsiCode::CopyRectArea(src, param(&p, 5), param(&p, 6)))
+        })
+}
+
+fn fill_rect_area(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("["), parse_params, tag("$x"))
+        .parse(input)
+        .map(|(s, p)| {
+            let char = char::from_u32(param(&p, 0)).unwrap_or(' ');
+            let rect = Rect::from_params(p.get(1..).unwrap_or_default());
+            (s, AnsiCode::FillRectArea(char, rect))
+        })
+}
+
+fn erase_rect_area(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    delimited(tag("["), parse_params, tag("$z"))
+        .parse(input)
+        .map(|(s, p)| (s, AnsiCode::EraseRectArea(Rect::from_params(&p))))
+}
+
+fn rect_area(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    alt((copy_rect_area, fill_rect_area, erase_rect_area)).parse(input)
+}
+
+fn alt_screen(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    alt((
+        enable_alt_screen_47,
+        disable_alt_screen_47,
+        enable_alt_screen_1047,
+        disable_alt_screen_1047,
+        enable_alt_screen_1049,
+        disable_alt_screen_1049,
+    ))
+    .parse(input)
+}
+
+fn mouse_mode(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    alt((
+        enable_mouse_1000,
+        disable_mouse_1000,
+        enable_mouse_1002,
+        disable_mouse_1002,
+        enable_mouse_1003,
+        disable_mouse_1003,
+        enable_mouse_1006,
+        disable_mouse_1006,
+    ))
+    .parse(input)
+}
+
+fn private_modes(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    let modes = verify(
+        separated_list1(tag(";"), parse_def_zero_int),
+        |modes: &Vec<u32>| modes.len() > 1,
+    );
+    (preceded(tag("[?"), modes), alt((tag("h"), tag("l"))))
+        .parse(input)
+        .map(|(s, (modes, end))| (s, AnsiCode::PrivateModes(modes, end == b"h")))
+}
+
+/// The code a DECSET (`set`) or DECRST of the single private `mode` parses to, `None` for a
+/// mode no parser knows
+pub fn private_mode(mode: u32, set: bool) -> Option<AnsiCode> {
+    let seq = format!("[?{}{}", mode, if set { 'h' } else { 'l' });
+    match body(seq.as_bytes()) {
+        Ok((&[], ac)) => Some(ac),
+        _ => None,
+    }
+}
+
+fn line_controls(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    alt((index, next_line, reverse_index, save_cursor, restore_cursor)).parse(input)
+}
+
+tag_parser!(cursor_save, "[s", AnsiCode::CursorSave);
+tag_parser!(cursor_restore, "[u", AnsiCode::CursorRestore);
+tag_parser!(enable_bracketed_paste, "[?2004h", AnsiCode::EnableBracketedPaste);
+tag_parser!(disable_bracketed_paste, "[?2004l", AnsiCode::DisableBracketedPaste);
+tag_parser!(enable_cursor_blink, "[?12h", AnsiCode::EnableCursorBlink);
+tag_parser!(disable_cursor_blink, "[?12l", AnsiCode::DisableCursorBlink);
+tag_parser!(enable_lr_margins, "[?69h", AnsiCode::EnableLeftRightMargins);
+tag_parser!(disable_lr_margins, "[?69l", AnsiCode::DisableLeftRightMargins);
+tag_parser!(enable_alt_screen_47, "[?47h", AnsiCode::EnableAltScreen(47));
+tag_parser!(disable_alt_screen_47, "[?47l", AnsiCode::DisableAltScreen(47));
+tag_parser!(enable_alt_screen_1047, "[?1047h", AnsiCode::EnableAltScreen(1047));
+tag_parser!(disable_alt_screen_1047, "[?1047l", AnsiCode::DisableAltScreen(1047));
+tag_parser!(enable_alt_screen_1049, "[?1049h", AnsiCode::EnableAltScreen(1049));
+tag_parser!(disable_alt_screen_1049, "[?1049l", AnsiCode::DisableAltScreen(1049));
+tag_parser!(enable_mouse_1000, "[?1000h", AnsiCode::EnableMouseMode(1000));
+tag_parser!(disable_mouse_1000, "[?1000l", AnsiCode::DisableMouseMode(1000));
+tag_parser!(enable_mouse_1002, "[?1002h", AnsiCode::EnableMouseMode(1002));
+tag_parser!(disable_mouse_1002, "[?1002l", AnsiCode::DisableMouseMode(1002));
+tag_parser!(enable_mouse_1003, "[?1003h", AnsiCode::EnableMouseMode(1003));
+tag_parser!(disable_mouse_1003, "[?1003l", AnsiCode::DisableMouseMode(1003));
+tag_parser!(enable_mouse_1006, "[?1006h", AnsiCode::EnableMouseMode(1006));
+tag_parser!(disable_mouse_1006, "[?1006l", AnsiCode::DisableMouseMode(1006));
+tag_parser!(hide_cursor, "[?25l", AnsiCode::HideCursor);
+tag_parser!(show_cursor, "[?25h", AnsiCode::ShowCursor);
+tag_parser!(cursor_to_app, "[?1h", AnsiCode::CursorToApp);
+tag_parser!(set_new_line_mode, "[20h", AnsiCode::SetNewLineMode);
+tag_parser!(set_col_132, "[?3h", AnsiCode::SetCol132);
+tag_parser!(set_smooth_scroll, "[?4h", AnsiCode::SetSmoothScroll);
+tag_parser!(set_reverse_video, "[?5h", AnsiCode::SetReverseVideo);
+tag_parser!(set_origin_rel, "[?6h", AnsiCode::SetOriginRelative);
+tag_parser!(set_auto_wrap, "[?7h", AnsiCode::SetAutoWrap);
+tag_parser!(set_auto_repeat, "[?8h", AnsiCode::SetAutoRepeat);
+tag_parser!(set_interlacing, "[?9h", AnsiCode::SetInterlacing);
+tag_parser!(set_linefeed, "[20l", AnsiCode::SetLineFeedMode);
+tag_parser!(set_cursorkey, "[?1l", AnsiCode::SetCursorKeyToCursor);
+tag_parser!(set_vt52, "[?2l", AnsiCode::SetVT52);
+tag_parser!(set_col80, "[?3l", AnsiCode::SetCol80);
+tag_parser!(set_jump_scroll, "[?4l", AnsiCode::SetJumpScrolling);
+tag_parser!(set_normal_video, "[?5l", AnsiCode::SetNormalVideo);
+tag_parser!(set_origin_abs, "[?6l", AnsiCode::SetOriginAbsolute);
+tag_parser!(reset_auto_wrap, "[?7l", AnsiCode::ResetAutoWrap);
+tag_parser!(reset_auto_repeat, "[?8l", AnsiCode::ResetAutoRepeat);
+tag_parser!(reset_interlacing, "[?9l", AnsiCode::ResetInterlacing);
+
+tag_parser!(set_alternate_keypad, "=", AnsiCode::SetAlternateKeypad);
+tag_parser!(set_numeric_keypad, ">", AnsiCode::SetNumericKeypad);
+tag_parser!(set_uk_g0, "(A", AnsiCode::SetUKG0);
+tag_parser!(set_uk_g1, ")A", AnsiCode::SetUKG1);
+tag_parser!(set_us_g0, "(B", AnsiCode::SetUSG0);
+tag_parser!(set_us_g1, ")B", AnsiCode::SetUSG1);
+tag_parser!(set_g0_special, "(0", AnsiCode::SetG0SpecialChars);
+tag_parser!(set_g1_special, ")0", AnsiCode::SetG1SpecialChars);
+tag_parser!(set_g0_alternate, "(1", AnsiCode::SetG0AlternateChar);
+tag_parser!(set_g1_alternate, ")1", AnsiCode::SetG1AlternateChar);
+tag_parser!(set_g0_graph, "(2", AnsiCode::SetG0AltAndSpecialGraph);
+tag_parser!(set_g1_graph, ")2", AnsiCode::SetG1AltAndSpecialGraph);
+tag_parser!(set_single_shift2, "N", AnsiCode::SetSingleShift2);
+tag_parser!(set_single_shift3, "O", AnsiCode::SetSingleShift3);
+tag_parser!(index, "D", AnsiCode::Index);
+tag_parser!(next_line, "E", AnsiCode::NextLine);
+tag_parser!(reverse_index, "M", AnsiCode::ReverseIndex);
+tag_parser!(save_cursor, "7", AnsiCode::SaveCursor);
+tag_parser!(restore_cursor, "8", AnsiCode::RestoreCursor);
+tag_parser!(set_tab_stop, "H", AnsiCode::SetTabStop);
+
+pub fn body(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    // `alt` only supports up to 21 parsers, and nom doesn't seem to
+    // have an alternative with higher variability.
+    // So we simply nest them.
+    alt((
+        alt((
+            escape,
+            cursor_pos,
+            cursor_up,
+            cursor_down,
+            cursor_forward,
+            cursor_backward,
+            cursor_save,
+            cursor_restore,
+            erase_in_display,
+            erase_in_line,
+            graphics_mode,
+            set_mode,
+            reset_mode,
+            hide_cursor,
+            show_cursor,
+            cursor_to_app,
+            set_new_line_mode,
+            set_col_132,
+            set_smooth_scroll,
+            set_reverse_video,
+        )),
+        alt((
+            set_auto_wrap,
+            set_origin_rel,
+            set_auto_repeat,
+            set_interlacing,
+            set_linefeed,
+            set_cursorkey,
+            set_vt52,
+            set_col80,
+            set_jump_scroll,
+            set_normal_video,
+            set_origin_abs,
+            reset_auto_wrap,
+            reset_auto_repeat,
+            reset_interlacing,
+            set_top_and_bottom,
+            set_alternate_keypad,
+            set_numeric_keypad,
+            set_uk_g0,
+            set_uk_g1,
+            set_us_g0,
+            set_us_g1,
+        )),
+        set_g0_special,
+        set_g1_special,
+        set_g0_alternate,
+        set_g1_alternate,
+        set_g0_graph,
+        set_g1_graph,
+        set_single_shift2,
+        set_single_shift3,
+        enable_bracketed_paste,
+        disable_bracketed_paste,
+        enable_cursor_blink,
+        disable_cursor_blink,
+        alt((
+            rect_area,
+            alt_screen,
+            mouse_mode,
+            line_controls,
+            request_mode,
+            private_modes,
+            tab_controls,
+        )),
+        set_left_and_right,
+        enable_lr_margins,
+        disable_lr_margins,
+        set_cursor_style,
+        alt((osc, sixel, request_termcap, apc)),
+    ))
+    .parse(input)
+}
+
+pub fn ansi_parse(input: &[u8]) -> IResult<&[u8], AnsiCode> {
+    preceded(tag("\u{1b}"), body).parse(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(sequence: &str) -> AnsiCode {
+        let (rest, code) = ansi_parse(sequence.as_bytes()).expect("should parse");
+        assert!(rest.is_empty(), "{:?} left over", rest);
+        code
+    }
+
+    #[test]
+    fn sgr_parameters() {
+        let long = parse("\x1b[0;1;38;2;1;2;3m");
+        assert!(
+            matches!(long, AnsiCode::SetGraphicsMode(p) if p == [[0], [1], [38], [2], [1], [2], [3]])
+        );
+        let colon = parse("\x1b[38:2::4:5:6;4m");
+        assert!(
+            matches!(colon, AnsiCode::SetGraphicsMode(p) if p == [vec![38, 2, 0, 4, 5, 6], vec![4]])
+        );
+        assert!(matches!(parse("\x1b[m"), AnsiCode::SetGraphicsMode(p) if p == [[0]]));
+        // too big for any parameter, it's ignored rather than wrapped around
+        assert!(
+            matches!(parse("\x1b[99999999999m"), AnsiCode::SetGraphicsMode(p) if p == [[u32::MAX]])
+        );
+    }
+
+    #[test]
+    fn private_mode_lists() {
+        let modes = parse("\x1b[?1000;1006h");
+        assert!(matches!(modes, AnsiCode::PrivateModes(m, true) if m == [1000, 1006]));
+        assert!(matches!(
+            parse("\x1b[?1006l"),
+            AnsiCode::DisableMouseMode(1006)
+        ));
+        assert!(matches!(
+            private_mode(1049, true),
+            Some(AnsiCode::EnableAltScreen(1049))
+        ));
+        assert!(private_mode(9999, true).is_none());
+    }
+
+    #[test]
+    fn unknown_sequences_are_passed_along() {
+        let outputs: Vec<Output> = AnsiParser::new(b"a\x1b[?9999hb").collect();
+        assert!(matches!(
+            outputs.as_slice(),
+            [Output::Bytes(b"a"), Output::Unknown(b"\x1b[?9999hb")]
+        ));
+    }
+
+    #[test]
+    fn erase_parameters() {
+        assert!(matches!(parse("\x1b[J"), AnsiCode::EraseDisplay(0)));
+        assert!(matches!(parse("\x1b[3J"), AnsiCode::EraseDisplay(3)));
+        assert!(matches!(parse("\x1b[K"), AnsiCode::EraseLine(0)));
+        assert!(matches!(parse("\x1b[1K"), AnsiCode::EraseLine(1)));
+    }
+
+    #[test]
+    fn tab_parameters() {
+        assert!(matches!(parse("\x1bH"), AnsiCode::SetTabStop));
+        assert!(matches!(parse("\x1b[g"), AnsiCode::ClearTabStop(0)));
+        assert!(matches!(parse("\x1b[3g"), AnsiCode::ClearTabStop(3)));
+        assert!(matches!(parse("\x1b[I"), AnsiCode::CursorForwardTab(1)));
+        assert!(matches!(parse("\x1b[2Z"), AnsiCode::CursorBackwardTab(2)));
+    }
+
+    #[test]
+    fn unterminated_sequences() {
+        let held = |bytes: &[u8]| match unterminated(bytes) {
+            Some(Unterminated::Held(start)) => Some(start),
+            _ => None,
+        };
+        assert_eq!(held(b"ab\x1b"), Some(2));
+        assert_eq!(held(b"ab\x1b[3"), Some(2));
+        assert_eq!(held(b"ab\x1b[?10"), Some(2));
+        assert_eq!(held(b"ab\x1b("), Some(2));
+        assert_eq!(held(b"ab\x1b]0;title"), Some(2));
+        assert_eq!(held(b"ab\x1bPq#0"), Some(2));
+        assert_eq!(held(b"ab\x1b[31m"), None);
+        assert_eq!(held(b"ab\x1b7"), None);
+        assert_eq!(held(b"ab\x1b]0;title\x07"), None);
+        // cancelled
+        assert_eq!(held(b"ab\x1b]0;title\x18"), None);
+
+        let mut long = b"\x1b_G".to_vec();
+        long.resize(SEQUENCE_MAX_BYTES, b'A');
+        assert_eq!(unterminated(&long), Some(Unterminated::TooLong(0)));
+    }
+
+    #[test]
+    fn string_ends() {
+        assert_eq!(string_end(b"AAAA\x1b\\rest"), Some(6));
+        assert_eq!(string_end(b"AAAA\x07rest"), Some(5));
+        assert_eq!(string_end(b"AA\x1aA"), Some(3));
+        assert_eq!(string_end(b"AAAA\x1b"), None);
+    }
+}
@@ -0,0 +1,81 @@
+//! The character sets an application designates as G0 and G1 and shifts between, which change
+//! what the printable ASCII range draws
+
+/// What a designated set draws in place of ASCII
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Charset {
+    #[default]
+    Ascii,
+    /// The British set, `#` is a pound sign
+    Uk,
+    /// DEC special graphics, the line drawing characters curses borders are made of
+    DecSpecial,
+}
+
+impl Charset {
+    fn map(self, char: char) -> char {
+        match self {
+            Charset::Ascii => char,
+            Charset::Uk if char == '#' => '£',
+            Charset::Uk => char,
+            Charset::DecSpecial => dec_special(char),
+        }
+    }
+}
+
+/// G0 and G1 and which of the two is drawn with, SO and SI switch between them
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Charsets {
+    pub g0: Charset,
+    pub g1: Charset,
+    /// SO was the last of the two sent, G1 is in use
+    pub shifted: bool,
+}
+
+impl Charsets {
+    /// `char` as the set in use draws it
+    pub fn map(&self, char: char) -> char {
+        match self.shifted {
+            true => self.g1.map(char),
+            false => self.g0.map(char),
+        }
+    }
+}
+
+fn dec_special(char: char) -> char {
+    match char {
+        '_' => ' ',
+        '`' => '◆',
+        'a' => '▒',
+        'b' => '␉',
+        'c' => '␌',
+        'd' => '␍',
+        'e' => '␊',
+        'f' => '°',
+        'g' => '±',
+        'h' => '␤',
+        'i' => '␋',
+        'j' => '┘',
+        'k' => '┐',
+        'l' => '┌',
+        'm' => '└',
+        'n' => '┼',
+        'o' => '⎺',
+        'p' => '⎻',
+        'q' => '─',
+        'r' => '⎼',
+        's' => '⎽',
+        't' => '├',
+        'u' => '┤',
+        'v' => '┴',
+        'w' => '┬',
+        'x' => '│',
+        'y' => '≤',
+        'z' => '≥',
+        '{' => 'π',
+        '|' => '≠',
+        '}' => '£',
+        '~' => '·',
+        char => char,
+    }
+}
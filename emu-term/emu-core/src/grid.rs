@@ -0,0 +1,858 @@
+use serde::Deserialize;
+use std::any::Any;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt::Display;
+use std::ops::{Range, RangeInclusive};
+use std::sync::Arc;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TermColor {
+    Rgb(u8, u8, u8),
+    Ansi(u8),
+    Foreground,
+    Background,
+}
+
+impl TermColor {
+    pub fn default_fg() -> Self {
+        Self::Foreground
+    }
+
+    pub fn default_bg() -> Self {
+        Self::Background
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorShape {
+    Block,
+    Underline,
+    Bar,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CursorStyle {
+    pub shape: CursorShape,
+    pub blinking: bool,
+}
+
+impl Display for CursorShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CursorShape::Block => "block",
+            CursorShape::Underline => "underline",
+            CursorShape::Bar => "bar",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Default for CursorStyle {
+    fn default() -> Self {
+        Self {
+            shape: CursorShape::Block,
+            blinking: true,
+        }
+    }
+}
+
+impl CursorStyle {
+    /// Maps a DECSCUSR parameter, 0 has no fixed meaning and is left to the caller
+    pub fn from_decscusr(ps: u8) -> Option<Self> {
+        let shape = match ps {
+            1 | 2 => CursorShape::Block,
+            3 | 4 => CursorShape::Underline,
+            5 | 6 => CursorShape::Bar,
+            _ => return None,
+        };
+
+        Some(Self {
+            shape,
+            blinking: ps % 2 == 1,
+        })
+    }
+}
+
+/// SGR attributes other than the colors, carried by the brush and stamped on every cell it
+/// paints
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Attributes {
+    /// SGR 7, foreground and background swapped when drawn
+    pub reverse: bool,
+    /// SGR 5 and 6, both blink at the same rate
+    pub blink: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Brush {
+    pub fg_color: TermColor,
+    pub bg_color: TermColor,
+    pub attrs: Attributes,
+    pub pos: Cursor,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            pos: Cursor::default(),
+            bg_color: TermColor::default_bg(),
+            fg_color: TermColor::default_fg(),
+            attrs: Attributes::default(),
+        }
+    }
+}
+
+/// Where the brush paints next, 1-based. The row is a grid row, so the cursor stays on its line
+/// as the screen scrolls, and a column right past the right margin means a wrap is pending.
+/// Movements are clamped to the bounds they're given
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub col: usize,
+    pub row: usize,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self { col: 1, row: 1 }
+    }
+}
+
+impl Cursor {
+    /// `n` rows up, stopping at the top of `bounds`
+    pub fn move_up(&mut self, n: usize, bounds: Region) {
+        self.row = self.row.saturating_sub(n).max(bounds.top);
+    }
+
+    /// `n` rows down, stopping at the bottom of `bounds`
+    pub fn move_down(&mut self, n: usize, bounds: Region) {
+        self.row = self.row.saturating_add(n).min(bounds.bottom);
+    }
+
+    /// `n` columns left, stopping at the left of `bounds`. A pending wrap is dropped, the
+    /// cursor goes back from the right edge
+    pub fn move_left(&mut self, n: usize, bounds: Region) {
+        self.col = self
+            .col
+            .min(bounds.right)
+            .saturating_sub(n)
+            .max(bounds.left);
+    }
+
+    /// `n` columns right, stopping at the right of `bounds`
+    pub fn move_right(&mut self, n: usize, bounds: Region) {
+        self.col = self.col.saturating_add(n).min(bounds.right);
+    }
+
+    /// To `row` and `col`, clamped into `bounds`
+    pub fn move_to(&mut self, row: usize, col: usize, bounds: Region) {
+        self.row = row.max(bounds.top).min(bounds.bottom);
+        self.col = col.max(bounds.left).min(bounds.right);
+    }
+}
+
+impl Brush {
+    /// What the cells it paints look like
+    pub fn style(&self) -> Style {
+        Style {
+            fg_color: self.fg_color,
+            bg_color: self.bg_color,
+            attrs: self.attrs,
+        }
+    }
+
+    /// Back to the default colors with no attributes, like SGR 0
+    pub fn reset_style(&mut self) {
+        self.fg_color = TermColor::default_fg();
+        self.bg_color = TermColor::default_bg();
+        self.attrs = Attributes::default();
+    }
+
+    /// Applies the parameters of a SGR sequence in order, each with the sub-parameters that
+    /// came after it with `:`. Unsupported attributes and values out of range are skipped
+    pub fn apply_sgr(&mut self, params: &[Vec<u32>]) {
+        let mut params = params.iter();
+        while let Some(param) = params.next() {
+            let Some((&p, sub)) = param.split_first() else {
+                continue;
+            };
+            match p {
+                0 => self.reset_style(),
+                5 | 6 => self.attrs.blink = true,
+                7 => self.attrs.reverse = true,
+                25 => self.attrs.blink = false,
+                27 => self.attrs.reverse = false,
+                30..=37 => self.fg_color = TermColor::Ansi(p as u8 - 30),
+                38 | 48 => {
+                    // `38;2;r;g;b` takes the parameters after it, `38:2::r:g:b` has them along
+                    let color = match sub.is_empty() {
+                        true => extended_color(&mut params.by_ref().map(|next| next[0])),
+                        false => sub_color(sub),
+                    };
+                    match (p, color) {
+                        (38, Some(color)) => self.fg_color = color,
+                        (_, Some(color)) => self.bg_color = color,
+                        (_, None) => {}
+                    }
+                }
+                39 => self.fg_color = TermColor::default_fg(),
+                40..=47 => self.bg_color = TermColor::Ansi(p as u8 - 40),
+                49 => self.bg_color = TermColor::default_bg(),
+                90..=97 => self.fg_color = TermColor::Ansi(p as u8 - 90 + 8),
+                100..=107 => self.bg_color = TermColor::Ansi(p as u8 - 100 + 8),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Reads the `5;n` or `2;r;g;b` tail of a 38/48 SGR attribute
+fn extended_color(params: &mut impl Iterator<Item = u32>) -> Option<TermColor> {
+    let mut next = || u8::try_from(params.next()?).ok();
+    match next()? {
+        5 => Some(TermColor::Ansi(next()?)),
+        2 => Some(TermColor::Rgb(next()?, next()?, next()?)),
+        _ => None,
+    }
+}
+
+/// Same for the sub-parameters of `38:5:n` and `38:2:r:g:b`, which may have a color space
+/// id before the red, `38:2:id:r:g:b`
+fn sub_color(sub: &[u32]) -> Option<TermColor> {
+    match sub {
+        [2, _, r, g, b, ..] | [2, r, g, b] => extended_color(&mut [2, *r, *g, *b].into_iter()),
+        sub => extended_color(&mut sub.iter().copied()),
+    }
+}
+
+/// Rows of scrollback packed together, see `Chunk`
+const CHUNK_ROWS: usize = 256;
+
+#[derive(Default, Debug)]
+pub struct Grid {
+    /// The oldest rows, packed a chunk at a time once they're far enough up the scrollback
+    chunks: VecDeque<Chunk>,
+    /// Rows at the start of the first chunk that were dropped already, the chunk goes once
+    /// all of them are
+    dropped: usize,
+    /// Every row after the packed ones, the screen among them
+    rows: Vec<GridRow>,
+    /// Drawn over the cells, in the order they were placed
+    pub images: Vec<ImagePlacement>,
+    /// The commands the shell marked with OSC 133, oldest first
+    pub commands: Vec<CommandMark>,
+    /// What the `StyleId`s of the cells stand for
+    styles: Styles,
+    /// 1-based rows written to since the last `take_damage`
+    damage: BTreeSet<usize>,
+}
+
+/// A decoded image pinned to the grid, it scrolls along with the cell its top-left corner
+/// sits on and is scaled to cover exactly `rows` by `cols` cells
+#[derive(Debug, Clone)]
+pub struct ImagePlacement {
+    /// 1-based grid row and column of the top-left cell
+    pub row: usize,
+    pub col: usize,
+    pub rows: usize,
+    pub cols: usize,
+    pub handle: ImageHandle,
+    /// What the application calls it, to replace or delete it later
+    pub id: Option<ImageId>,
+}
+
+/// A command the shell ran, by where it marked its prompt, command line and output. Positions
+/// are 1-based grid rows and columns of the cursor when each mark came
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct CommandMark {
+    /// `A`, the row the prompt starts on
+    pub prompt: usize,
+    /// `B`, where the command line starts once the prompt is printed
+    pub input: Option<(usize, usize)>,
+    /// `C`, where the output starts once the command was entered
+    pub output: Option<(usize, usize)>,
+    /// `D`, where the output ended once the command finished
+    pub end: Option<(usize, usize)>,
+    /// Given with `D`, when the shell knows it
+    pub exit_status: Option<i32>,
+}
+
+impl CommandMark {
+    fn up(&mut self, n: usize) {
+        let up = |(row, col): (usize, usize)| (row - n, col);
+        self.prompt -= n;
+        self.input = self.input.map(up);
+        self.output = self.output.map(up);
+        self.end = self.end.map(up);
+    }
+}
+
+/// Ids of an image and of one of its placements, as given by the kitty graphics protocol
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ImageId {
+    pub image: u32,
+    /// 0 when the application didn't give the placement an id of its own
+    pub placement: u32,
+}
+
+/// Whatever the frontend draws the pixels from, the grid only keeps it where it was placed
+pub type ImageHandle = Arc<dyn Any + Send + Sync>;
+
+#[derive(Default, Debug, Clone)]
+pub struct GridRow {
+    pub cells: Vec<Cell>,
+    /// The text goes on in the next row, it was only cut here because it reached the right
+    /// edge. Rows joined this way make up one logical line
+    pub wrapped: bool,
+}
+
+/// `CHUNK_ROWS` rows in a fraction of the room their cells took: the chars of all of them in
+/// one string and the styles as runs. The blanks at the end of each row are dropped, they
+/// look the same as no cells at all
+#[derive(Debug)]
+struct Chunk {
+    text: String,
+    /// Style of each run of cells and how many cells it covers, across rows
+    runs: Vec<(StyleId, u32)>,
+    /// Cells kept of each row and whether it's wrapped
+    rows: Vec<(u32, bool)>,
+}
+
+impl Chunk {
+    fn pack(rows: impl Iterator<Item = GridRow>) -> Self {
+        let mut chunk = Chunk {
+            text: String::new(),
+            runs: vec![],
+            rows: Vec::with_capacity(CHUNK_ROWS),
+        };
+        let blank = Cell::default();
+        for row in rows {
+            let len = row.cells.iter().rposition(|cell| *cell != blank);
+            let cells = &row.cells[..len.map_or(0, |len| len + 1)];
+            for cell in cells {
+                chunk.text.push(cell.c);
+                match chunk.runs.last_mut() {
+                    Some((style, n)) if *style == cell.style => *n += 1,
+                    _ => chunk.runs.push((cell.style, 1)),
+                }
+            }
+            chunk.rows.push((cells.len() as u32, row.wrapped));
+        }
+        chunk.text.shrink_to_fit();
+        chunk.runs.shrink_to_fit();
+        chunk
+    }
+
+    /// Rows `range` of the chunk
+    fn unpack(&self, range: Range<usize>) -> Vec<GridRow> {
+        let skip: usize = self.rows[..range.start]
+            .iter()
+            .map(|&(len, _)| len as usize)
+            .sum();
+        let mut chars = self.text.chars().skip(skip);
+        let mut styles = self
+            .runs
+            .iter()
+            .flat_map(|&(style, n)| std::iter::repeat_n(style, n as usize))
+            .skip(skip);
+        self.rows[range]
+            .iter()
+            .map(|&(len, wrapped)| GridRow {
+                cells: (&mut chars)
+                    .zip(&mut styles)
+                    .take(len as usize)
+                    .map(|(c, style)| Cell { c, style })
+                    .collect(),
+                wrapped,
+            })
+            .collect()
+    }
+}
+
+/// Eight bytes, the colors and attributes are kept once per grid and only referred to, see
+/// `Grid::style`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Cell {
+    pub c: char,
+    pub style: StyleId,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Cell {
+    fn empty() -> Self {
+        Self {
+            c: ' ',
+            style: StyleId::default(),
+        }
+    }
+}
+
+/// Colors and attributes of a cell
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Style {
+    pub fg_color: TermColor,
+    pub bg_color: TermColor,
+    pub attrs: Attributes,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fg_color: TermColor::default_fg(),
+            bg_color: TermColor::default_bg(),
+            attrs: Attributes::default(),
+        }
+    }
+}
+
+/// A style interned by a grid, only means something to that grid. The default one is the
+/// style of a blank cell
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct StyleId(u32);
+
+/// Every style painted on a grid, each stored once however many cells use it
+#[derive(Debug)]
+pub struct Styles {
+    styles: Vec<Style>,
+    ids: HashMap<Style, StyleId>,
+    /// The brush paints runs of cells in one style, this saves hashing it for each of them
+    last: (Style, StyleId),
+    /// Styles no cell uses anymore are dropped once there are this many, see `Grid::intern`
+    limit: usize,
+}
+
+/// Smallest table worth compacting, a full screen of colored text rarely needs more
+const MIN_STYLES: usize = 4096;
+
+impl Default for Styles {
+    fn default() -> Self {
+        let style = Style::default();
+        Self {
+            styles: vec![style],
+            ids: HashMap::from([(style, StyleId::default())]),
+            last: (style, StyleId::default()),
+            limit: MIN_STYLES,
+        }
+    }
+}
+
+impl Styles {
+    pub fn get(&self, id: StyleId) -> Style {
+        // ids of another grid are no use here, they get the default rather than a panic
+        self.styles.get(id.0 as usize).copied().unwrap_or_default()
+    }
+
+    fn len(&self) -> usize {
+        self.styles.len()
+    }
+
+    fn intern(&mut self, style: Style) -> StyleId {
+        if self.last.0 == style {
+            return self.last.1;
+        }
+
+        let next = StyleId(self.styles.len() as u32);
+        let id = *self.ids.entry(style).or_insert(next);
+        if id == next {
+            self.styles.push(style);
+        }
+        self.last = (style, id);
+        id
+    }
+}
+
+/// A rectangle of cells in grid coordinates, 1-based and inclusive on every edge
+#[derive(Debug, Copy, Clone)]
+pub struct Region {
+    pub top: usize,
+    pub left: usize,
+    pub bottom: usize,
+    pub right: usize,
+}
+
+impl Region {
+    pub fn new(top: usize, left: usize, bottom: usize, right: usize) -> Self {
+        Self {
+            top,
+            left,
+            bottom,
+            right,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.top == 0 || self.left == 0 || self.top > self.bottom || self.left > self.right
+    }
+}
+
+impl Grid {
+    /// Number of rows, the packed ones included
+    pub fn len(&self) -> usize {
+        self.packed() + self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Row `i`, 0-based. A packed one is unpacked on its own
+    pub fn get(&self, i: usize) -> Option<Cow<'_, GridRow>> {
+        let packed = self.packed();
+        if i >= packed {
+            return self.rows.get(i - packed).map(Cow::Borrowed);
+        }
+
+        let i = i + self.dropped;
+        let row = i % CHUNK_ROWS;
+        let unpacked = self.chunks[i / CHUNK_ROWS].unpack(row..row + 1);
+        unpacked.into_iter().next().map(Cow::Owned)
+    }
+
+    /// Rows `range`, 0-based and cut to the rows there are. Each chunk involved is unpacked
+    /// once
+    pub fn rows(&self, range: Range<usize>) -> Vec<Cow<'_, GridRow>> {
+        let end = range.end.min(self.len());
+        let mut i = range.start.min(end);
+        let mut rows = Vec::with_capacity(end - i);
+        while i < end.min(self.packed()) {
+            let at = i + self.dropped;
+            let (chunk, row) = (at / CHUNK_ROWS, at % CHUNK_ROWS);
+            let last = CHUNK_ROWS.min(row + end - i);
+            let unpacked = self.chunks[chunk].unpack(row..last);
+            i += unpacked.len();
+            rows.extend(unpacked.into_iter().map(Cow::Owned));
+        }
+
+        let packed = self.packed();
+        let hot = i.max(packed) - packed..end.max(packed) - packed;
+        rows.extend(self.rows[hot].iter().map(Cow::Borrowed));
+        rows
+    }
+
+    /// Every row from the first, packed ones unpacked a chunk at a time
+    pub fn iter(&self) -> impl Iterator<Item = Cow<'_, GridRow>> {
+        let packed = self.chunks.iter().enumerate().flat_map(|(k, chunk)| {
+            let first = if k == 0 { self.dropped } else { 0 };
+            chunk.unpack(first..CHUNK_ROWS).into_iter().map(Cow::Owned)
+        });
+        packed.chain(self.rows.iter().map(Cow::Borrowed))
+    }
+
+    /// Packs the oldest rows a chunk at a time, leaving the last `keep` and at least a chunk
+    /// above them as they are. Writes only ever go to the screen, the rest of the scrollback is
+    /// only read and packing it saves most of the room it takes
+    pub fn pack(&mut self, keep: usize) {
+        while self.rows.len() >= keep + 2 * CHUNK_ROWS {
+            let chunk = Chunk::pack(self.rows.drain(..CHUNK_ROWS));
+            self.chunks.push_back(chunk);
+        }
+    }
+
+    fn packed(&self) -> usize {
+        self.chunks.len() * CHUNK_ROWS - self.dropped
+    }
+
+    /// Unpacks the chunk row `i` is in and every one after it, so it can be written to
+    fn unpack_from(&mut self, i: usize) {
+        if i >= self.packed() {
+            return;
+        }
+
+        let first = (i + self.dropped) / CHUNK_ROWS;
+        let mut rows = vec![];
+        for (k, chunk) in self.chunks.drain(first..).enumerate() {
+            let start = if first + k == 0 { self.dropped } else { 0 };
+            rows.extend(chunk.unpack(start..CHUNK_ROWS));
+        }
+        if first == 0 {
+            self.dropped = 0;
+        }
+        rows.append(&mut self.rows);
+        self.rows = rows;
+    }
+
+    fn is_wrapped(&self, i: usize) -> bool {
+        let packed = self.packed();
+        if i >= packed {
+            return self.rows.get(i - packed).is_some_and(|row| row.wrapped);
+        }
+
+        let i = i + self.dropped;
+        self.chunks[i / CHUNK_ROWS].rows[i % CHUNK_ROWS].1
+    }
+
+    /// Blanks row `y` from column `from` to its end, it no longer goes on in the next one
+    pub fn erase_line(&mut self, y: usize, from: usize) {
+        let row = self.get_or_insert(y);
+        row.cells.truncate(from - 1);
+        row.wrapped = false;
+    }
+
+    /// Marks row `y` as continued on the next one
+    pub fn set_wrapped(&mut self, y: usize) {
+        self.get_or_insert(y).wrapped = true;
+    }
+
+    /// First and last rows of the logical line row `y` is part of
+    pub fn logical_line(&self, y: usize) -> (usize, usize) {
+        let wrapped = |y: usize| self.is_wrapped(y - 1);
+
+        let mut first = y;
+        while first > 1 && wrapped(first - 1) {
+            first -= 1;
+        }
+        let mut last = y;
+        while last < self.len() && wrapped(last) {
+            last += 1;
+        }
+        (first, last)
+    }
+
+    pub fn paint(&mut self, brush: &Brush, char: char) {
+        let Cursor { col: x, row: y } = brush.pos;
+        let style = self.intern(brush.style());
+        let cell = self.get_or_insert(y).get_or_insert(x);
+        cell.style = style;
+        cell.c = char;
+    }
+
+    /// Colors and attributes of `cell`, one of the cells of this grid
+    pub fn style(&self, cell: &Cell) -> Style {
+        self.styles.get(cell.style)
+    }
+
+    /// The styles the cells refer to, for lines that are handed out on their own
+    pub fn styles(&self) -> &Styles {
+        &self.styles
+    }
+
+    /// Id of `style` in this grid. The table is compacted once it reaches its limit, and
+    /// the limit is then doubled from what's left so the compactions get rarer as it grows
+    fn intern(&mut self, style: Style) -> StyleId {
+        if self.styles.len() >= self.styles.limit && !self.styles.ids.contains_key(&style) {
+            self.compact_styles();
+        }
+        self.styles.intern(style)
+    }
+
+    /// Drops the styles no cell uses anymore and renumbers the rest
+    fn compact_styles(&mut self) {
+        let old = std::mem::take(&mut self.styles);
+        for (style, _) in self
+            .chunks
+            .iter_mut()
+            .flat_map(|chunk| chunk.runs.iter_mut())
+        {
+            *style = self.styles.intern(old.get(*style));
+        }
+        for cell in self.rows.iter_mut().flat_map(|row| row.cells.iter_mut()) {
+            cell.style = self.styles.intern(old.get(cell.style));
+        }
+        self.styles.limit = MIN_STYLES.max(self.styles.len() * 2);
+    }
+
+    /// Adds an image on top of the cells it covers, creating the rows it spans
+    pub fn place_image(&mut self, placement: ImagePlacement) {
+        for y in placement.row..placement.row + placement.rows {
+            self.get_or_insert(y);
+        }
+        self.images.push(placement);
+    }
+
+    /// Drops the images `remove` picks and hands them back
+    pub fn remove_images(
+        &mut self,
+        mut remove: impl FnMut(&ImagePlacement) -> bool,
+    ) -> Vec<ImagePlacement> {
+        let (removed, kept) = std::mem::take(&mut self.images)
+            .into_iter()
+            .partition(|image| remove(image));
+        self.images = kept;
+        removed
+    }
+
+    /// Drops the rows past the first `len` along with the images and commands anchored on them
+    pub fn truncate(&mut self, len: usize) {
+        self.unpack_from(len);
+        self.rows.truncate(len - self.packed());
+        self.images.retain(|image| image.row <= len);
+        self.commands.retain(|mark| mark.prompt <= len);
+    }
+
+    /// Drops the first `n` rows, the images and commands anchored on them go too and the rest
+    /// move up
+    pub fn drop_top(&mut self, n: usize) {
+        let n = n.min(self.len());
+        // the first chunk goes once all of its rows are dropped, the rows past the packed
+        // ones are dropped right away
+        self.dropped += n;
+        let chunks = (self.dropped / CHUNK_ROWS).min(self.chunks.len());
+        self.chunks.drain(..chunks);
+        self.dropped -= chunks * CHUNK_ROWS;
+        if self.chunks.is_empty() {
+            self.rows.drain(..self.dropped);
+            self.dropped = 0;
+        }
+        self.images.retain(|image| image.row > n);
+        self.images.iter_mut().for_each(|image| image.row -= n);
+        self.commands.retain(|mark| mark.prompt > n);
+        self.commands.iter_mut().for_each(|mark| mark.up(n));
+        self.damage = self
+            .damage
+            .iter()
+            .filter(|&&y| y > n)
+            .map(|y| y - n)
+            .collect();
+    }
+
+    /// Adds blank rows at the end until there are at least `len` of them
+    pub fn grow(&mut self, len: usize) {
+        if len > self.len() {
+            self.get_or_insert(len);
+        }
+    }
+
+    /// Returns the rows that were modified since the last call
+    pub fn take_damage(&mut self) -> BTreeSet<usize> {
+        std::mem::take(&mut self.damage)
+    }
+
+    fn get_or_insert(&mut self, y: usize) -> &mut GridRow {
+        // every write goes through here, so this is where rows get marked as damaged
+        self.damage.insert(y);
+        self.unpack_from(y - 1);
+        let y = y - 1 - self.packed();
+        while y >= self.rows.len() {
+            self.rows.push(GridRow::default());
+        }
+
+        &mut self.rows[y]
+    }
+
+    /// Blanks the rows `rows` whole, they no longer wrap into the next one either
+    pub fn erase_rows(&mut self, rows: RangeInclusive<usize>) {
+        for y in rows {
+            let row = self.get_or_insert(y);
+            for cell in row.cells.iter_mut() {
+                *cell = Cell::empty();
+            }
+            row.wrapped = false;
+        }
+    }
+
+    /// Paints every cell of `region` with `char` using the brush colors (DECFRA)
+    pub fn fill_region(&mut self, region: Region, brush: &Brush, char: char) {
+        if region.is_empty() {
+            return;
+        }
+
+        let style = self.intern(brush.style());
+        for y in region.top..=region.bottom {
+            let row = self.get_or_insert(y);
+            for x in region.left..=region.right {
+                let cell = row.get_or_insert(x);
+                cell.style = style;
+                cell.c = char;
+            }
+        }
+    }
+
+    /// Resets every cell of `region` to an empty cell (DECERA)
+    pub fn erase_region(&mut self, region: Region) {
+        if region.is_empty() {
+            return;
+        }
+
+        for y in region.top..=region.bottom {
+            let row = self.get_or_insert(y);
+            for x in region.left..=region.right {
+                *row.get_or_insert(x) = Cell::empty();
+            }
+        }
+    }
+
+    /// Copies the contents of `src` so that its top-left corner lands on `(top, left)` (DECCRA).
+    /// Overlapping source and destination are fine since the source is snapshotted first
+    pub fn copy_region(&mut self, src: Region, top: usize, left: usize) {
+        if src.is_empty() || top == 0 || left == 0 {
+            return;
+        }
+
+        let snapshot: Vec<Vec<Cell>> = (src.top..=src.bottom)
+            .map(|y| {
+                let row = self.get(y - 1);
+                (src.left..=src.right)
+                    .map(|x| {
+                        row.as_ref()
+                            .and_then(|r| r.cells.get(x - 1))
+                            .copied()
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for (dy, cells) in snapshot.into_iter().enumerate() {
+            let row = self.get_or_insert(top + dy);
+            for (dx, cell) in cells.into_iter().enumerate() {
+                *row.get_or_insert(left + dx) = cell;
+            }
+        }
+    }
+
+    /// Shifts the contents of `region` one line up, dropping its first line and blanking the last
+    pub fn scroll_up(&mut self, region: Region) {
+        let Region {
+            top,
+            left,
+            bottom,
+            right,
+        } = region;
+
+        if region.is_empty() {
+            return;
+        }
+
+        if top < bottom {
+            self.copy_region(Region::new(top + 1, left, bottom, right), top, left);
+        }
+        self.erase_region(Region::new(bottom, left, bottom, right));
+    }
+
+    /// Shifts the contents of `region` one line down, dropping its last line and blanking the
+    /// first
+    pub fn scroll_down(&mut self, region: Region) {
+        let Region {
+            top,
+            left,
+            bottom,
+            right,
+        } = region;
+
+        if region.is_empty() {
+            return;
+        }
+
+        if top < bottom {
+            self.copy_region(Region::new(top, left, bottom - 1, right), top + 1, left);
+        }
+        self.erase_region(Region::new(top, left, top, right));
+    }
+}
+
+impl GridRow {
+    fn get_or_insert(&mut self, x: usize) -> &mut Cell {
+        let x = x - 1;
+        while x >= self.cells.len() {
+            self.cells.push(Cell::default());
+        }
+
+        &mut self.cells[x]
+    }
+}
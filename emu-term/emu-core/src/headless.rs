@@ -0,0 +1,195 @@
+use crate::{Event, Terminal};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::pty::{forkpty, ForkptyResult, Winsize};
+use nix::sys::signal::{kill, Signal};
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::Pid;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsFd, AsRawFd};
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// What the child gets in `TERM` unless the command sets it
+const TERM: &str = "xterm-256color";
+
+/// A terminal without a window: a command on a pty of its own, whose output goes through the
+/// emulation whenever it's read. Meant for tests and for scripting against TUIs, the child is
+/// killed when it's dropped
+#[derive(Debug)]
+pub struct Headless {
+    terminal: Terminal,
+    master: File,
+    child: Pid,
+    /// Raised by the output read so far, until `take_events`
+    events: Vec<Event>,
+    /// The child closed its end of the pty
+    eof: bool,
+    /// Exit code once the child was waited for
+    status: Option<i32>,
+    /// Everything read since `keep_output`
+    output: Option<Vec<u8>>,
+}
+
+impl Headless {
+    /// Starts `command` on a pty of `rows` by `cols`
+    pub fn spawn(mut command: Command, rows: usize, cols: usize) -> io::Result<Self> {
+        if !command.get_envs().any(|(name, _)| name == "TERM") {
+            command.env("TERM", TERM);
+        }
+
+        let (master, child) = match unsafe { forkpty(&winsize(rows, cols), None) }? {
+            ForkptyResult::Parent { master, child } => (master, child),
+            ForkptyResult::Child => {
+                let error = command.exec();
+                tracing::error!("failed to start {:?}: {}", command.get_program(), error);
+                std::process::exit(127);
+            }
+        };
+
+        Ok(Self {
+            terminal: Terminal::new(rows, cols),
+            master: master.into(),
+            child,
+            events: vec![],
+            eof: false,
+            status: None,
+            output: None,
+        })
+    }
+
+    pub fn terminal(&self) -> &Terminal {
+        &self.terminal
+    }
+
+    pub fn screen_text(&self) -> String {
+        self.terminal.screen_text()
+    }
+
+    /// Events of the output read since the last call, bells and titles among them
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Keeps a copy of the output read from now on, to replay it later
+    pub fn keep_output(&mut self) {
+        self.output.get_or_insert_with(Vec::new);
+    }
+
+    pub fn output(&self) -> &[u8] {
+        self.output.as_deref().unwrap_or_default()
+    }
+
+    /// Sends input to the child as if it was typed
+    pub fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.master.write_all(bytes)
+    }
+
+    /// Changes the size of the terminal, the child gets SIGWINCH
+    pub fn resize(&mut self, rows: usize, cols: usize) -> io::Result<()> {
+        self.terminal.resize(rows, cols);
+        let winsize = winsize(rows, cols);
+        if unsafe { libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ, &winsize) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Waits up to `timeout` for output and applies it, returning as soon as some came. Returns
+    /// how many bytes were read, 0 when none came in time or the child is gone
+    pub fn read(&mut self, timeout: Duration) -> io::Result<usize> {
+        if self.eof {
+            return Ok(0);
+        }
+
+        let timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX);
+        let mut fds = [PollFd::new(self.master.as_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, timeout) {
+            Ok(0) | Err(Errno::EINTR) => return Ok(0),
+            Ok(_) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut buf = [0u8; 4096];
+        let n = match self.master.read(&mut buf) {
+            Ok(n) => n,
+            // Linux reports a pty whose other end was closed as EIO rather than EOF
+            Err(e) if e.raw_os_error() == Some(Errno::EIO as i32) => 0,
+            Err(e) => return Err(e),
+        };
+        if n == 0 {
+            self.eof = true;
+        }
+        if let Some(output) = self.output.as_mut() {
+            output.extend_from_slice(&buf[..n]);
+        }
+        let events = self.terminal.process_bytes(&buf[..n]);
+        for event in &events {
+            if let Event::Reply(reply) = event {
+                self.master.write_all(reply)?;
+            }
+        }
+        self.events.extend(events);
+        Ok(n)
+    }
+
+    /// Reads until `text` is on the screen, giving up after `timeout` or once the child is
+    /// gone. Returns whether it showed up
+    pub fn wait_for(&mut self, text: &str, timeout: Duration) -> io::Result<bool> {
+        let deadline = Instant::now() + timeout;
+        while !self.screen_text().contains(text) {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() || self.eof {
+                return Ok(false);
+            }
+            self.read(left)?;
+        }
+        Ok(true)
+    }
+
+    /// Reads until the child has been quiet for `idle`, for screens that are redrawn in more
+    /// than one go
+    pub fn settle(&mut self, idle: Duration) -> io::Result<()> {
+        while self.read(idle)? > 0 {}
+        Ok(())
+    }
+
+    /// Reads everything the child prints until it exits, and returns its exit code. A child
+    /// killed by a signal gives the negated signal number
+    pub fn wait(&mut self) -> io::Result<i32> {
+        if let Some(status) = self.status {
+            return Ok(status);
+        }
+
+        while !self.eof {
+            self.read(Duration::MAX)?;
+        }
+        let status = match waitpid(self.child, None)? {
+            WaitStatus::Exited(_, code) => code,
+            WaitStatus::Signaled(_, signal, _) => -(signal as i32),
+            _ => 0,
+        };
+        self.status = Some(status);
+        Ok(status)
+    }
+}
+
+impl Drop for Headless {
+    fn drop(&mut self) {
+        if self.status.is_none() {
+            let _ = kill(self.child, Signal::SIGKILL);
+            let _ = waitpid(self.child, None);
+        }
+    }
+}
+
+fn winsize(rows: usize, cols: usize) -> Winsize {
+    Winsize {
+        ws_row: rows as u16,
+        ws_col: cols as u16,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
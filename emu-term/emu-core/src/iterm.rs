@@ -0,0 +1,64 @@
+//! Inline images of iTerm2's OSC 1337, `File=` followed by `key=value` arguments separated by
+//! `;`, then a `:` and the file in base64. The frontend decodes the file, this only says how
+//! big the application wants it shown
+
+use crate::osc;
+
+/// A width or height asked for
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Size {
+    /// `auto` or left out, what the image itself is or what keeps its aspect ratio
+    #[default]
+    Auto,
+    /// `N`, in cells
+    Cells(u32),
+    /// `Npx`
+    Pixels(u32),
+    /// `N%`, of the width or height of the screen
+    Percent(u32),
+}
+
+#[derive(Debug, Clone)]
+pub struct InlineImage {
+    pub width: Size,
+    pub height: Size,
+    /// `preserveAspectRatio=0` stretches the image over both sizes, otherwise it fits in them
+    pub preserve_aspect_ratio: bool,
+    /// `inline=1`, it's to be shown. Without it the file is for downloading, which isn't
+    /// supported
+    pub inline: bool,
+    /// The file, in whatever format it's in
+    pub data: Vec<u8>,
+}
+
+/// The image of an OSC 1337, `None` for the other iTerm2 commands or a file that isn't base64
+pub fn parse(pt: &str) -> Option<InlineImage> {
+    let (args, data) = pt.strip_prefix("File=")?.split_once(':')?;
+
+    let mut image = InlineImage {
+        width: Size::Auto,
+        height: Size::Auto,
+        preserve_aspect_ratio: true,
+        inline: false,
+        data: osc::base64_decode(data)?,
+    };
+    for (key, value) in args.split(';').filter_map(|arg| arg.split_once('=')) {
+        match key {
+            "width" => image.width = size(value),
+            "height" => image.height = size(value),
+            "preserveAspectRatio" => image.preserve_aspect_ratio = value != "0",
+            "inline" => image.inline = value == "1",
+            _ => {}
+        }
+    }
+    Some(image)
+}
+
+fn size(value: &str) -> Size {
+    let size = match (value.strip_suffix("px"), value.strip_suffix('%')) {
+        (Some(pixels), _) => pixels.parse().map(Size::Pixels),
+        (_, Some(percent)) => percent.parse().map(Size::Percent),
+        _ => value.parse().map(Size::Cells),
+    };
+    size.unwrap_or(Size::Auto)
+}
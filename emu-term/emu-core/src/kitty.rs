@@ -0,0 +1,168 @@
+//! Commands of the kitty graphics protocol, the APCs that start with `G`. The control data is
+//! a list of `key=value` pairs and the payload what follows the `;`, base64 encoded. Images
+//! are kept by the frontend, this only says what the application asked for
+
+use crate::osc;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// `a=t`, keep the image for later
+    #[default]
+    Transmit,
+    /// `a=T`, keep it and put it at the cursor
+    TransmitAndPut,
+    /// `a=p`, put an image sent before at the cursor
+    Put,
+    /// `a=d`
+    Delete,
+    /// `a=q`, whether an image would load, without keeping it
+    Query,
+}
+
+/// How the image data gets to the terminal
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Medium {
+    /// `t=d`, in the payload
+    #[default]
+    Direct,
+    /// `t=f`, in the file the payload names
+    File,
+    /// `t=t`, in a temporary file the terminal deletes once it's read
+    TempFile,
+    /// `t=s`, in a shared memory object
+    SharedMemory,
+}
+
+/// Which placements `a=d` deletes. The image data goes too when the key is in uppercase
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Delete {
+    /// `a`, every placement on screen
+    All,
+    /// `i`, the placements of the image `i`, or only placement `p` of it
+    Id,
+    /// `n`, the same for the image numbered `I`
+    Number,
+    /// `c`, the ones under the cursor
+    Cursor,
+    /// `p`, the ones over the cell at `x` and `y`
+    Cell,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Command {
+    pub action: Action,
+    /// `f`, 24 for RGB, 32 for RGBA and 100 for PNG
+    pub format: u32,
+    pub medium: Medium,
+    /// `o=z`, the data is zlib compressed
+    pub compressed: bool,
+    /// `s` and `v`, size in pixels of RGB and RGBA data
+    pub width: u32,
+    pub height: u32,
+    /// `i`, the id the application picked
+    pub id: u32,
+    /// `I`, a number for the terminal to pick an id for and answer with
+    pub number: u32,
+    /// `p`
+    pub placement: u32,
+    /// `c` and `r`, cells to cover, 0 to go by the size of the image
+    pub cols: u32,
+    pub rows: u32,
+    /// `C=1`, the cursor stays where it is
+    pub keep_cursor: bool,
+    /// `q`, 1 leaves out the OK answers and 2 the errors too
+    pub quiet: u32,
+    /// `d`, what a delete applies to and whether the data goes too. `None` for the kinds of
+    /// deletes that aren't supported
+    pub delete: Option<(Delete, bool)>,
+    /// `x` and `y`, the cell of `d=p`, 1-based
+    pub x: u32,
+    pub y: u32,
+    /// `m=1`, more chunks of the payload follow
+    pub more: bool,
+    /// The payload decoded, `None` when it isn't valid base64
+    pub payload: Option<Vec<u8>>,
+}
+
+/// The command of an APC, what comes after its `_`. `None` for APCs of other protocols
+pub fn parse(apc: &[u8]) -> Option<Command> {
+    let apc = std::str::from_utf8(apc.strip_prefix(b"G")?).ok()?;
+    let (control, payload) = apc.split_once(';').unwrap_or((apc, ""));
+
+    let mut command = Command {
+        format: 32,
+        delete: Some((Delete::All, false)),
+        payload: osc::base64_decode(payload),
+        ..Command::default()
+    };
+    for (key, value) in control.split(',').filter_map(|pair| pair.split_once('=')) {
+        let number = || value.parse::<u32>().unwrap_or(0);
+        match key {
+            "a" => {
+                command.action = match value {
+                    "T" => Action::TransmitAndPut,
+                    "p" => Action::Put,
+                    "d" => Action::Delete,
+                    "q" => Action::Query,
+                    _ => Action::Transmit,
+                }
+            }
+            "f" => command.format = number(),
+            "t" => {
+                command.medium = match value {
+                    "f" => Medium::File,
+                    "t" => Medium::TempFile,
+                    "s" => Medium::SharedMemory,
+                    _ => Medium::Direct,
+                }
+            }
+            "o" => command.compressed = value == "z",
+            "s" => command.width = number(),
+            "v" => command.height = number(),
+            "i" => command.id = number(),
+            "I" => command.number = number(),
+            "p" => command.placement = number(),
+            "c" => command.cols = number(),
+            "r" => command.rows = number(),
+            "C" => command.keep_cursor = value == "1",
+            "q" => command.quiet = number(),
+            "x" => command.x = number(),
+            "y" => command.y = number(),
+            "m" => command.more = value == "1",
+            "d" => {
+                let upper = value.chars().all(|c| c.is_ascii_uppercase());
+                command.delete = match value.to_ascii_lowercase().as_str() {
+                    "a" => Some((Delete::All, upper)),
+                    "i" => Some((Delete::Id, upper)),
+                    "n" => Some((Delete::Number, upper)),
+                    "c" => Some((Delete::Cursor, upper)),
+                    "p" => Some((Delete::Cell, upper)),
+                    _ => None,
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(command)
+}
+
+/// The answer to `command`, `message` being `OK` or an error like `ENOENT:no such image`.
+/// `None` when the command asked for quiet, or has no id to answer with
+pub fn reply(command: &Command, id: u32, message: &str) -> Option<Vec<u8>> {
+    let quiet = match message {
+        "OK" => command.quiet >= 1,
+        _ => command.quiet >= 2,
+    };
+    if quiet || (id == 0 && command.number == 0) {
+        return None;
+    }
+
+    let mut keys = format!("i={}", id);
+    if command.number != 0 {
+        keys.push_str(&format!(",I={}", command.number));
+    }
+    if command.placement != 0 {
+        keys.push_str(&format!(",p={}", command.placement));
+    }
+    Some(format!("\x1b_G{};{}\x1b\\", keys, message).into_bytes())
+}
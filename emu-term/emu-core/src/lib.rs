@@ -0,0 +1,18 @@
+//! Terminal emulation without a frontend: the escape sequence parser, the grid of cells and
+//! the state machine applying one to the other. Whatever draws the grid feeds it the output
+//! of the child and reacts to the events it hands back, or `Headless` runs a command without
+//! any frontend at all
+
+pub mod ansi;
+mod charset;
+pub mod grid;
+pub mod headless;
+pub mod iterm;
+pub mod kitty;
+pub mod modes;
+pub mod osc;
+pub mod sixel;
+mod terminal;
+
+pub use headless::Headless;
+pub use terminal::{Event, ImageCursor, Listener, Output, Terminal};
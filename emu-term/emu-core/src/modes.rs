@@ -0,0 +1,30 @@
+/// Modes set by the application that change what keys send
+#[derive(Debug, Default, Copy, Clone)]
+pub struct KeyModes {
+    /// DECCKM, cursor keys send SS3 instead of CSI sequences
+    pub app_cursor: bool,
+    /// DECKPAM, the numeric keypad sends SS3 sequences instead of its characters
+    pub app_keypad: bool,
+}
+
+/// Mouse events the application asked to be told about, each level reports everything the
+/// previous one does
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MouseTracking {
+    #[default]
+    Off,
+    /// 1000, presses and releases
+    Click,
+    /// 1002, also motion while a button is held
+    Drag,
+    /// 1003, all motion
+    Motion,
+}
+
+/// Modes set by the application that turn mouse input into reports
+#[derive(Debug, Default, Copy, Clone)]
+pub struct MouseModes {
+    pub tracking: MouseTracking,
+    /// 1006, reports in the `CSI < b ; x ; y M` form that has no limit on coordinates
+    pub sgr: bool,
+}
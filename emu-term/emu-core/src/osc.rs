@@ -0,0 +1,140 @@
+//! What the operating system commands carry, decoded into something an embedder can use
+//! without knowing the sequences
+
+use std::path::PathBuf;
+
+/// Base64 text OSC 52 may carry for the clipboard, anything longer is dropped. A megabyte of
+/// text is more than a selection copied over ssh takes
+pub const CLIPBOARD_MAX_BYTES: usize = 1 << 20;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Which selection OSC 52 is about
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClipboardKind {
+    Clipboard,
+    /// The primary selection of X11, the one the middle button pastes
+    Primary,
+}
+
+impl ClipboardKind {
+    /// From the targets of OSC 52, `c` for the clipboard and `p` or `s` for the primary
+    /// selection. No targets at all means the clipboard, like xterm's default
+    pub fn from_targets(targets: &str) -> Self {
+        let primary = targets.contains(['p', 's']) && !targets.contains('c');
+        match primary {
+            true => ClipboardKind::Primary,
+            false => ClipboardKind::Clipboard,
+        }
+    }
+
+    fn target(self) -> char {
+        match self {
+            ClipboardKind::Clipboard => 'c',
+            ClipboardKind::Primary => 'p',
+        }
+    }
+}
+
+/// Progress reported with ConEmu's OSC 9;4, with the percentage for the states that have one
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Progress {
+    /// `0`, over, nothing to show any more
+    Hidden,
+    /// `1`
+    Normal(u8),
+    /// `2`, it failed
+    Error(u8),
+    /// `3`, busy without knowing how far along
+    Indeterminate,
+    /// `4`
+    Paused(u8),
+}
+
+/// The progress of an OSC 9, `4;state;percent`. `None` for the other ConEmu commands
+pub fn progress(pt: &str) -> Option<Progress> {
+    let mut args = pt.strip_prefix("4;")?.split(';');
+    let state = args.next()?;
+    let percent = args.next().and_then(|p| p.parse::<u32>().ok()).unwrap_or(0);
+    let percent = percent.min(100) as u8;
+    let progress = match state {
+        "0" => Progress::Hidden,
+        "1" => Progress::Normal(percent),
+        "2" => Progress::Error(percent),
+        "3" => Progress::Indeterminate,
+        "4" => Progress::Paused(percent),
+        _ => return None,
+    };
+    Some(progress)
+}
+
+/// OSC 52 answering a query for `kind` with `text`
+pub fn clipboard_reply(kind: ClipboardKind, text: &str) -> Vec<u8> {
+    format!(
+        "\x1b]52;{};{}\x07",
+        kind.target(),
+        base64_encode(text.as_bytes())
+    )
+    .into_bytes()
+}
+
+/// The directory in an OSC 7 `file://host/path` URL. The host is ignored, shells report the
+/// local one
+pub fn cwd_from_url(url: &str) -> Option<PathBuf> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    Some(PathBuf::from(percent_decode(path)?))
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+
+        let hex = std::str::from_utf8(rest.get(..2)?).ok()?;
+        bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        rest = &rest[2..];
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+pub(crate) fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+    let (mut bits, mut n) = (0u32, 0);
+    for b in encoded
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace() && *b != b'=')
+    {
+        let value = BASE64.iter().position(|&c| c == b)?;
+        bits = bits << 6 | value as u32;
+        n += 6;
+        if n >= 8 {
+            n -= 8;
+            decoded.push((bits >> n) as u8);
+        }
+    }
+    Some(decoded)
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, &b)| bits | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(BASE64[(bits >> (18 - 6 * i) & 0x3f) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,183 @@
+//! Sixel images, the payload of `DCS q` decoded into RGBA pixels for the frontend to draw.
+//! Each data character paints a column of six pixels in the current color, `$` goes back to
+//! the start of the band and `-` down to the next one
+
+/// Sides are cut off past this many pixels, a bogus image can't ask for gigabytes
+pub const MAX_SIDE: usize = 4096;
+
+/// What the color registers hold until the image defines them, the VT340 defaults in percent
+const VT340: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [20, 20, 80],
+    [80, 13, 13],
+    [20, 80, 20],
+    [80, 20, 80],
+    [20, 80, 80],
+    [80, 80, 20],
+    [53, 53, 53],
+    [26, 26, 26],
+    [33, 33, 60],
+    [60, 26, 26],
+    [33, 60, 33],
+    [60, 33, 60],
+    [33, 60, 60],
+    [60, 60, 33],
+    [80, 80, 80],
+];
+
+/// Images may use up to this many color registers, the higher ones wrap around
+const REGISTERS: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub struct SixelImage {
+    pub width: usize,
+    pub height: usize,
+    /// `height` rows of `width` RGBA pixels. The ones never painted are transparent and show
+    /// the cells under them, which is what the background would be anyway
+    pub rgba: Vec<u8>,
+}
+
+/// Decodes the data of a sixel sequence, what comes after the `q`. `None` if it paints nothing
+pub fn decode(data: &[u8]) -> Option<SixelImage> {
+    let mut palette: Vec<[u8; 4]> = VT340
+        .iter()
+        .map(|&[r, g, b]| [percent(r as u32), percent(g as u32), percent(b as u32), 255])
+        .collect();
+    palette.resize(REGISTERS, [0, 0, 0, 255]);
+
+    let mut rows: Vec<Vec<[u8; 4]>> = vec![];
+    let mut raster = (0, 0);
+    let (mut x, mut y) = (0, 0);
+    let mut color = palette[0];
+    let mut bytes = data.iter().copied().peekable();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'"' => {
+                // Pan;Pad;Ph;Pv, the aspect ratio is taken as square like most terminals do
+                let params = numbers(&mut bytes);
+                if let [_, _, width, height, ..] = params[..] {
+                    raster = (
+                        (width as usize).min(MAX_SIDE),
+                        (height as usize).min(MAX_SIDE),
+                    );
+                }
+            }
+            b'#' => match numbers(&mut bytes)[..] {
+                [register] => color = palette[register as usize % REGISTERS],
+                [register, space, a, b, c, ..] => {
+                    let rgb = match space {
+                        1 => hls(a, b, c),
+                        2 => [percent(a), percent(b), percent(c)],
+                        _ => continue,
+                    };
+                    color = [rgb[0], rgb[1], rgb[2], 255];
+                    palette[register as usize % REGISTERS] = color;
+                }
+                _ => {}
+            },
+            b'!' => {
+                let count = numbers(&mut bytes).first().copied().unwrap_or(1).max(1);
+                if let Some(sixel @ 0x3f..=0x7e) = bytes.next() {
+                    paint(&mut rows, (x, y), sixel, count as usize, color);
+                    x = (x + count as usize).min(MAX_SIDE);
+                }
+            }
+            b'$' => x = 0,
+            b'-' => {
+                x = 0;
+                y = (y + 6).min(MAX_SIDE);
+            }
+            0x3f..=0x7e => {
+                paint(&mut rows, (x, y), b, 1, color);
+                x = (x + 1).min(MAX_SIDE);
+            }
+            _ => {}
+        }
+    }
+
+    let painted = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let width = painted.max(raster.0);
+    let height = rows.len().max(raster.1);
+    if painted == 0 || width == 0 || height == 0 {
+        return None;
+    }
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        let row = rows.get(y).map_or(&[][..], Vec::as_slice);
+        rgba.extend(row.iter().flatten());
+        rgba.resize((y + 1) * width * 4, 0);
+    }
+    Some(SixelImage {
+        width,
+        height,
+        rgba,
+    })
+}
+
+/// Paints the pixels set in `sixel` at `(x, y)` and the `count - 1` columns after it
+fn paint(
+    rows: &mut Vec<Vec<[u8; 4]>>,
+    (x, y): (usize, usize),
+    sixel: u8,
+    count: usize,
+    color: [u8; 4],
+) {
+    let end = (x + count).min(MAX_SIDE);
+    let bits = sixel - 0x3f;
+    for i in (0..6).filter(|i| bits & 1 << i != 0) {
+        let y = y + i;
+        if y >= MAX_SIDE || x >= end {
+            break;
+        }
+        if rows.len() <= y {
+            rows.resize_with(y + 1, Vec::new);
+        }
+        let row = &mut rows[y];
+        if row.len() < end {
+            row.resize(end, [0; 4]);
+        }
+        row[x..end].fill(color);
+    }
+}
+
+/// Numeric parameters separated by `;`, up to the first byte that's neither
+fn numbers(bytes: &mut std::iter::Peekable<impl Iterator<Item = u8>>) -> Vec<u32> {
+    let mut numbers: Vec<u32> = vec![0];
+    while let Some(&b) = bytes.peek() {
+        match b {
+            b'0'..=b'9' => {
+                let n = numbers.last_mut().expect("there's always one");
+                *n = n.saturating_mul(10).saturating_add((b - b'0') as u32);
+            }
+            b';' => numbers.push(0),
+            _ => break,
+        }
+        bytes.next();
+    }
+    numbers
+}
+
+fn percent(value: u32) -> u8 {
+    (value.min(100) * 255 / 100) as u8
+}
+
+/// Sixel HLS, where hue 0 is blue instead of red, to RGB
+fn hls(hue: u32, lightness: u32, saturation: u32) -> [u8; 3] {
+    let h = ((hue % 360 + 240) % 360) as f32 / 60.0;
+    let l = lightness.min(100) as f32 / 100.0;
+    let s = saturation.min(100) as f32 / 100.0;
+
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = l - c / 2.0;
+    [r, g, b].map(|v| ((v + m) * 255.0).round() as u8)
+}
@@ -0,0 +1,1337 @@
+use crate::ansi::{self, AnsiCode, AnsiParser, Unterminated};
+use crate::charset::{Charset, Charsets};
+use crate::grid::{
+    Brush, Cell, CommandMark, Cursor, CursorStyle, Grid, GridRow, ImageHandle, ImageId,
+    ImagePlacement, Region,
+};
+use crate::modes::{KeyModes, MouseModes, MouseTracking};
+use crate::osc::{self, ClipboardKind, Progress};
+use bytes::Bytes;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt::{Debug, Display, Write as _};
+use std::path::PathBuf;
+
+/// A piece of output as `AnsiParser` splits it, owned so it can be parsed on another thread
+#[derive(Debug)]
+pub enum Output {
+    Ansi(AnsiCode),
+    Bytes(Bytes),
+    Unknown(Bytes),
+}
+
+impl Display for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bytes(b) => write!(f, "{}", String::from_utf8_lossy(b)),
+            Self::Ansi(ac) => write!(f, "{:?}", ac),
+            Self::Unknown(b) => write!(f, "Unknown({:?})", String::from_utf8_lossy(b)),
+        }
+    }
+}
+
+impl Output {
+    /// Splits `bytes` like `AnsiParser` does. The pieces share the buffer instead of copying
+    /// out of it
+    pub fn parse(bytes: &Bytes) -> Vec<Output> {
+        AnsiParser::new(bytes)
+            .map(|piece| match piece {
+                ansi::Output::Bytes(b) => Self::Bytes(bytes.slice_ref(b)),
+                ansi::Output::Escape(ac) => Self::Ansi(ac),
+                ansi::Output::Unknown(b) => Self::Unknown(bytes.slice_ref(b)),
+            })
+            .collect()
+    }
+}
+
+impl From<ansi::Output<'_>> for Output {
+    fn from(value: ansi::Output<'_>) -> Self {
+        match value {
+            ansi::Output::Bytes(b) => Self::Bytes(Bytes::copy_from_slice(b)),
+            ansi::Output::Escape(ac) => Self::Ansi(ac),
+            ansi::Output::Unknown(b) => Self::Unknown(Bytes::copy_from_slice(b)),
+        }
+    }
+}
+
+/// What the state alone doesn't cover, left to whoever drives the terminal
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Bell,
+    /// OSC 0 or 2 named the window. The icon name of OSC 1 has nowhere to go
+    TitleChanged(String),
+    /// OSC 7, the shell moved to this directory
+    CwdChanged(PathBuf),
+    /// OSC 52 with text for the clipboard, at most `osc::CLIPBOARD_MAX_BYTES` of it encoded
+    ClipboardStore(ClipboardKind, String),
+    /// OSC 52 asking for the clipboard back, see `osc::clipboard_reply`. Any program on the
+    /// pty can ask, ssh'd ones included, so it's fine to leave it unanswered
+    ClipboardLoad(ClipboardKind),
+    /// OSC 9 or OSC 777 `notify` asking for a desktop notification, with a title, empty for
+    /// OSC 9, and a body
+    Notification(String, String),
+    /// ConEmu's OSC 9;4 reported how far along a long job is
+    Progress(Progress),
+    /// `CSI 3J` dropped the whole scrollback, this many rows
+    ScrollbackCleared(usize),
+    /// The alternate screen came up or went away
+    ScreenSwitched,
+    /// DECSCNM changed, every line looks different
+    ReverseVideo,
+    /// An answer for the child, like a DECRQM report, to be written to the pty as it is
+    Reply(Vec<u8>),
+    /// XTGETTCAP asking for these terminfo capabilities, which only the frontend knows
+    TermcapQuery(Vec<String>),
+    /// Any other OSC, like the colors the terminal doesn't keep
+    Osc(u32, String),
+}
+
+/// Gets every event as it's raised
+pub type Listener = Box<dyn FnMut(&Event) + Send>;
+
+#[derive(Default)]
+struct Listeners(Vec<Listener>);
+
+impl Debug for Listeners {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Listeners({})", self.0.len())
+    }
+}
+
+/// The screen and scrollback of a terminal along with every mode the application can set.
+/// Output of the child goes in through `process_bytes`, which applies it and hands back what
+/// the frontend has to deal with
+#[derive(Debug)]
+pub struct Terminal {
+    grid: Grid,
+    brush: Brush,
+    /// Where the last char printed left the cursor when that was past the last column, with the
+    /// column the next one wraps to. The cursor getting there any other way doesn't wrap
+    pending_wrap: Option<(Cursor, usize)>,
+    margins: Margins,
+    lr_margins_enabled: bool,
+    rows: usize,
+    cols: usize,
+    /// Whether each column, from the first, has a tab stop. Every 8th one has one to start with
+    tab_stops: Vec<bool>,
+    cursor_visible: bool,
+    cursor_style: CursorStyle,
+    /// DECSCNM, the whole screen drawn with the default colors swapped
+    reverse_video: bool,
+    key_modes: KeyModes,
+    mouse_modes: MouseModes,
+    /// Mode 2004, pastes are wrapped in markers
+    bracketed_paste: bool,
+    /// G0 and G1 as designated, and which one SO and SI left in use
+    charsets: Charsets,
+    /// DECOM, cursor addressing is relative to the margins and can't leave them
+    origin_mode: bool,
+    /// LNM, line feeds return the carriage too, for programs that only send `\n`
+    new_line_mode: bool,
+    /// What DECSC saved on the screen that's up
+    saved_cursor: Option<SavedCursor>,
+    /// Primary screen, its cursor and what DECSC saved on it, put aside while the alternate
+    /// screen is up
+    primary: Option<(Grid, Cursor, Option<SavedCursor>)>,
+    /// What `CSI 0 q` goes back to
+    default_cursor_style: CursorStyle,
+    /// Start of a character the last output cut in two, see `decode`
+    partial: Vec<u8>,
+    /// Start of an escape sequence the last `process_bytes` cut in two
+    held: Vec<u8>,
+    /// A sequence grew too long to hold, what's left of it is dropped up to its terminator
+    dropping: bool,
+    /// Raised while processing, handed out once it's done
+    events: Vec<Event>,
+    listeners: Listeners,
+}
+
+/// What DECSC saves, the cursor as a screen line so it stays put as the screen scrolls
+#[derive(Debug, Clone)]
+struct SavedCursor {
+    brush: Brush,
+    line: usize,
+    /// The column a wrap was pending to
+    wrap_to: Option<usize>,
+    charsets: Charsets,
+    origin_mode: bool,
+}
+
+impl Default for SavedCursor {
+    /// What DECRC puts back when nothing was saved, the cursor home in the default style
+    fn default() -> Self {
+        Self {
+            brush: Brush::default(),
+            line: 1,
+            wrap_to: None,
+            charsets: Charsets::default(),
+            origin_mode: false,
+        }
+    }
+}
+
+/// Where the cursor goes once an image is placed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageCursor {
+    /// To the line below the image, in the column it started from, like sixel does
+    Below,
+    /// Right of the last row of the image, like kitty does
+    After,
+    Stay,
+}
+
+/// Scrolling margins in screen coordinates, 1-based and inclusive
+#[derive(Debug, Copy, Clone)]
+pub struct Margins {
+    top: usize,
+    bottom: usize,
+    left: usize,
+    right: usize,
+}
+
+impl Margins {
+    fn full(rows: usize, cols: usize) -> Self {
+        Self {
+            top: 1,
+            bottom: rows,
+            left: 1,
+            right: cols,
+        }
+    }
+
+    fn is_full_screen(&self, rows: usize, cols: usize) -> bool {
+        self.top == 1 && self.bottom == rows && self.left == 1 && self.right == cols
+    }
+}
+
+impl Terminal {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self {
+            grid: Grid::default(),
+            brush: Brush::default(),
+            pending_wrap: None,
+            margins: Margins::full(rows, cols),
+            lr_margins_enabled: false,
+            rows,
+            cols,
+            tab_stops: (1..=cols).map(default_tab_stop).collect(),
+            cursor_visible: true,
+            cursor_style: CursorStyle::default(),
+            reverse_video: false,
+            key_modes: KeyModes::default(),
+            mouse_modes: MouseModes::default(),
+            bracketed_paste: false,
+            new_line_mode: false,
+            origin_mode: false,
+            charsets: Charsets::default(),
+            saved_cursor: None,
+            primary: None,
+            default_cursor_style: CursorStyle::default(),
+            partial: vec![],
+            held: vec![],
+            dropping: false,
+            events: vec![],
+            listeners: Listeners::default(),
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The grid on view, the alternate one while it's up
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// Rows written to since the last call, 1-based grid rows
+    pub fn take_damage(&mut self) -> BTreeSet<usize> {
+        self.grid.take_damage()
+    }
+
+    /// The rows of the screen, without the scrollback above them
+    pub fn screen(&self) -> Vec<Cow<'_, GridRow>> {
+        self.window(self.screen_origin(), self.rows)
+    }
+
+    /// The screen as text, one line per row with the blanks at the end dropped
+    pub fn screen_text(&self) -> String {
+        let mut lines: Vec<String> = self
+            .screen()
+            .iter()
+            .map(|row| {
+                let line: String = row.cells.iter().map(|cell| cell.c).collect();
+                line.trim_end().to_owned()
+            })
+            .collect();
+        lines.resize(self.rows, String::new());
+        lines.join("\n")
+    }
+
+    /// The screen, the cursor and the modes as plain text, stable enough to compare against
+    /// golden files. Cells that aren't in the default style get a line each run of them
+    pub fn snapshot(&self) -> String {
+        let (col, line) = self.cursor();
+        let modes: Vec<&str> = [
+            (self.is_alt_screen(), "alt-screen"),
+            (self.reverse_video, "reverse-video"),
+            (self.lr_margins_enabled, "lr-margins"),
+            (self.bracketed_paste, "bracketed-paste"),
+            (self.new_line_mode, "new-line"),
+            (self.origin_mode, "origin"),
+            (self.key_modes.app_cursor, "app-cursor"),
+            (self.key_modes.app_keypad, "app-keypad"),
+            (self.mouse_modes.tracking != MouseTracking::Off, "mouse"),
+            (self.mouse_modes.sgr, "mouse-sgr"),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect();
+        let cursor = match (self.cursor_visible, self.cursor_style.blinking) {
+            (false, _) => "hidden".to_owned(),
+            (true, false) => self.cursor_style.shape.to_string(),
+            (true, true) => format!("{} blinking", self.cursor_style.shape),
+        };
+
+        let mut snapshot = format!("size {}x{}\n", self.rows, self.cols);
+        let _ = writeln!(snapshot, "cursor {},{} {}", line, col, cursor);
+        let modes = match modes.is_empty() {
+            true => "none".to_owned(),
+            false => modes.join(" "),
+        };
+        let _ = writeln!(snapshot, "modes {}", modes);
+        let _ = writeln!(snapshot, "scrollback {}", self.screen_origin());
+        let Margins {
+            top,
+            bottom,
+            left,
+            right,
+        } = self.margins;
+        let _ = writeln!(snapshot, "margins {}-{} {}-{}", top, bottom, left, right);
+        snapshot.push_str("screen\n");
+        for (i, line) in self.screen_text().split('\n').enumerate() {
+            let _ = writeln!(snapshot, "{:4}|{}", i + 1, line);
+        }
+
+        snapshot.push_str("styles\n");
+        let default = Cell::default().style;
+        for (i, row) in self.screen().iter().enumerate() {
+            let mut col = 1;
+            // a grid has one id per style, the same id is the same style
+            for run in row.cells.chunk_by(|a, b| a.style == b.style) {
+                if run[0].style != default {
+                    let style = self.grid.style(&run[0]);
+                    let _ = write!(
+                        snapshot,
+                        "{:4}:{}-{} {:?} {:?}",
+                        i + 1,
+                        col,
+                        col + run.len() - 1,
+                        style.fg_color,
+                        style.bg_color,
+                    );
+                    for (on, name) in [
+                        (style.attrs.reverse, " reverse"),
+                        (style.attrs.blink, " blink"),
+                    ] {
+                        if on {
+                            snapshot.push_str(name);
+                        }
+                    }
+                    snapshot.push('\n');
+                }
+                col += run.len();
+            }
+        }
+        snapshot
+    }
+
+    /// Column and screen line of the cursor, 1-based. The column is one past the right edge
+    /// while a wrap is pending
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.brush.pos.col, self.cursor_line())
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    pub fn cursor_style(&self) -> CursorStyle {
+        self.cursor_style
+    }
+
+    /// Sets what `CSI 0 q` goes back to, the cursor takes it right away unless the
+    /// application picked a style of its own
+    pub fn set_default_cursor_style(&mut self, style: CursorStyle) {
+        if self.cursor_style == self.default_cursor_style {
+            self.cursor_style = style;
+        }
+        self.default_cursor_style = style;
+    }
+
+    pub fn reverse_video(&self) -> bool {
+        self.reverse_video
+    }
+
+    pub fn key_modes(&self) -> KeyModes {
+        self.key_modes
+    }
+
+    pub fn mouse_modes(&self) -> MouseModes {
+        self.mouse_modes
+    }
+
+    pub fn bracketed_paste(&self) -> bool {
+        self.bracketed_paste
+    }
+
+    pub fn lr_margins_enabled(&self) -> bool {
+        self.lr_margins_enabled
+    }
+
+    /// Changes the screen dimensions. Lines below the cursor are dropped first when shrinking
+    /// so the cursor stays on screen, and the margins go back to covering the whole screen
+    pub fn resize(&mut self, rows: usize, cols: usize) {
+        self.grid.truncate(self.brush.pos.row - 1 + rows);
+        self.rows = rows;
+        self.cols = cols;
+        // columns the screen gains get the default stops, the ones it keeps the ones set
+        let kept = self.tab_stops.len().min(cols);
+        self.tab_stops.truncate(kept);
+        self.tab_stops
+            .extend((kept + 1..=cols).map(default_tab_stop));
+        self.margins = Margins::full(rows, cols);
+        let Cursor { row, col } = self.brush.pos;
+        self.brush.pos.move_to(row, col, self.cursor_bounds());
+    }
+
+    /// `height` rows starting at grid row `origin + 1`
+    pub fn window(&self, origin: usize, height: usize) -> Vec<Cow<'_, GridRow>> {
+        self.grid.rows(origin..origin + height)
+    }
+
+    /// The grid with the scrollback, which is put aside while the alternate screen is up
+    pub fn scrollback(&self) -> &Grid {
+        self.primary.as_ref().map_or(&self.grid, |(grid, ..)| grid)
+    }
+
+    pub fn is_alt_screen(&self) -> bool {
+        self.primary.is_some()
+    }
+
+    /// Switches to a blank alternate screen, which has no scrollback. The cursor keeps its
+    /// place on screen
+    fn enter_alt_screen(&mut self) {
+        if self.is_alt_screen() {
+            return;
+        }
+
+        // the primary grid only has the rows written to, the cursor can be past its end
+        let line = self.cursor_line().min(self.rows);
+        let primary = std::mem::take(&mut self.grid);
+        let saved_cursor = self.saved_cursor.take();
+        self.primary = Some((primary, self.brush.pos, saved_cursor));
+        self.brush.pos.row = line;
+    }
+
+    /// Goes back to the primary screen, 1049 also restores the cursor it had when leaving it
+    fn exit_alt_screen(&mut self, restore_cursor: bool) {
+        let Some((primary, pos, saved_cursor)) = self.primary.take() else {
+            return;
+        };
+
+        let line = self.cursor_line();
+        self.grid = primary;
+        self.saved_cursor = saved_cursor;
+        if restore_cursor {
+            self.brush.pos = pos;
+        } else {
+            self.brush.pos.row = self.screen_origin() + line;
+        }
+    }
+
+    /// Drops the oldest lines of scrollback past `limit` and returns how many went. The cursor
+    /// moves up with the rest of the grid. What's left well above the screen is packed
+    pub fn trim_scrollback(&mut self, limit: usize) -> usize {
+        let dropped = self.drop_scrollback(self.screen_origin().saturating_sub(limit));
+        self.grid.pack(self.rows);
+        dropped
+    }
+
+    pub fn clear_scrollback(&mut self) -> usize {
+        self.drop_scrollback(self.screen_origin())
+    }
+
+    /// Drops the first `n` rows of the scrollback, the alternate screen has none. Returns how
+    /// many went
+    fn drop_scrollback(&mut self, n: usize) -> usize {
+        if n == 0 || self.is_alt_screen() {
+            return 0;
+        }
+
+        self.grid.drop_top(n);
+        // the rows move up under the cursor, it keeps its place on screen
+        self.brush.pos.row = self.brush.pos.row.saturating_sub(n).max(1);
+        n
+    }
+
+    /// Pushes the lines above the cursor into the scrollback, the cursor line ends up at the
+    /// top of an otherwise blank screen. The alternate screen has no scrollback and is blanked
+    pub fn clear_screen(&mut self) {
+        if self.is_alt_screen() {
+            let origin = self.screen_origin();
+            let screen = Region::new(origin + 1, 1, origin + self.rows, self.cols);
+            self.grid.erase_region(screen);
+            return;
+        }
+
+        let y = self.brush.pos.row;
+        self.grid.truncate(y);
+        let below = Region::new(y + 1, 1, y + self.rows - 1, self.cols);
+        self.grid.erase_region(below);
+    }
+
+    /// Number of grid rows sitting above the first line of the screen
+    pub fn screen_origin(&self) -> usize {
+        self.grid.len().saturating_sub(self.rows)
+    }
+
+    /// Maps a rectangle in screen coordinates to grid coordinates, applying the VT defaults
+    /// for omitted edges and clipping it to the screen
+    fn screen_region(&self, rect: ansi::Rect) -> Region {
+        let (rows, cols) = (self.rows, self.cols);
+        let origin = self.screen_origin();
+
+        Region::new(
+            origin + clamp_param(rect.top, 1, rows),
+            clamp_param(rect.left, 1, cols),
+            origin + clamp_param(rect.bottom, rows, rows),
+            clamp_param(rect.right, cols, cols),
+        )
+    }
+
+    /// Screen line the cursor is on
+    fn cursor_line(&self) -> usize {
+        self.brush.pos.row.saturating_sub(self.screen_origin())
+    }
+
+    /// Where cursor movements stop, in grid coordinates: the margins while the cursor is
+    /// within them and the screen edges past them
+    fn cursor_bounds(&self) -> Region {
+        let margins = self.margins;
+        let line = self.cursor_line();
+        let col = self.brush.pos.col;
+
+        let top = if line >= margins.top { margins.top } else { 1 };
+        let bottom = if line <= margins.bottom {
+            margins.bottom
+        } else {
+            self.rows
+        };
+        let left = if col >= margins.left { margins.left } else { 1 };
+        // right past the right margin is the column a pending wrap sits on
+        let pending = self
+            .pending_wrap
+            .is_some_and(|(pos, _)| pos == self.brush.pos);
+        let right = if col <= margins.right || (col == margins.right + 1 && pending) {
+            margins.right
+        } else {
+            self.cols
+        };
+
+        let origin = self.screen_origin();
+        Region::new(origin + top, left, origin + bottom, right)
+    }
+
+    /// `n` tab stops right, the cursor stops at the right margin when there are no more
+    fn tab_forward(&mut self, n: usize) {
+        let bounds = self.cursor_bounds();
+        for _ in 0..n {
+            let col = self.brush.pos.col.min(bounds.right);
+            self.brush.pos.col = (col + 1..bounds.right)
+                .find(|&col| self.tab_stops[col - 1])
+                .unwrap_or(bounds.right);
+        }
+    }
+
+    /// `n` tab stops left, the cursor stops at the left margin when there are no more
+    fn tab_backward(&mut self, n: usize) {
+        let bounds = self.cursor_bounds();
+        for _ in 0..n {
+            let col = self.brush.pos.col.min(bounds.right);
+            self.brush.pos.col = (bounds.left + 1..col)
+                .rev()
+                .find(|&col| self.tab_stops[col - 1])
+                .unwrap_or(bounds.left);
+        }
+    }
+
+    /// Moves the cursor to the top-left corner of the screen, of the margins in origin mode
+    fn home_cursor(&mut self) {
+        self.cursor_to(1, 1);
+    }
+
+    /// DECSC, see `SavedCursor`
+    fn save_cursor(&mut self) {
+        let wrap_to = self.pending_wrap.filter(|(pos, _)| *pos == self.brush.pos);
+        self.saved_cursor = Some(SavedCursor {
+            brush: self.brush.clone(),
+            line: self.cursor_line(),
+            wrap_to: wrap_to.map(|(_, col)| col),
+            charsets: self.charsets,
+            origin_mode: self.origin_mode,
+        });
+    }
+
+    /// DECRC, the cursor is kept on screen when it shrank since
+    fn restore_cursor(&mut self) {
+        let saved = self.saved_cursor.clone().unwrap_or_default();
+        let col = saved.brush.pos.col;
+        self.brush = saved.brush;
+        self.charsets = saved.charsets;
+        self.origin_mode = saved.origin_mode;
+
+        let origin = self.screen_origin();
+        // a pending wrap sits right past the last column
+        let right = self.cols + usize::from(saved.wrap_to.is_some());
+        let screen = Region::new(origin + 1, 1, origin + self.rows, right);
+        self.brush.pos.move_to(origin + saved.line, col, screen);
+        self.pending_wrap = saved.wrap_to.map(|col| (self.brush.pos, col));
+    }
+
+    /// Moves the cursor to `row` and `col` of the screen (CUP), counted from the margins in
+    /// origin mode. Zero or missing parameters are the first row and column
+    fn cursor_to(&mut self, row: u32, col: u32) {
+        let margins = self.margins;
+        let origin = self.screen_origin();
+        let bounds = match self.origin_mode {
+            true => Region::new(
+                origin + margins.top,
+                margins.left,
+                origin + margins.bottom,
+                margins.right,
+            ),
+            false => Region::new(origin + 1, 1, origin + self.rows, self.cols),
+        };
+
+        let row = bounds.top + clamp_param(row, 1, self.rows) - 1;
+        let col = bounds.left + clamp_param(col, 1, self.cols) - 1;
+        self.brush.pos.move_to(row, col, bounds);
+        self.pending_wrap = None;
+    }
+
+    fn set_top_and_bottom(&mut self, top: u32, bottom: u32) {
+        let top = clamp_param(top, 1, self.rows);
+        let bottom = clamp_param(bottom, self.rows, self.rows);
+        if top >= bottom {
+            return;
+        }
+
+        self.margins.top = top;
+        self.margins.bottom = bottom;
+        self.home_cursor();
+    }
+
+    fn set_left_and_right(&mut self, left: u32, right: u32) {
+        if !self.lr_margins_enabled {
+            return;
+        }
+
+        let left = clamp_param(left, 1, self.cols);
+        let right = clamp_param(right, self.cols, self.cols);
+        if left >= right {
+            return;
+        }
+
+        self.margins.left = left;
+        self.margins.right = right;
+        self.home_cursor();
+    }
+
+    fn set_lr_margins_enabled(&mut self, enabled: bool) {
+        self.lr_margins_enabled = enabled;
+        if !enabled {
+            let Margins { left, right, .. } = Margins::full(self.rows, self.cols);
+            self.margins.left = left;
+            self.margins.right = right;
+        }
+    }
+
+    /// Moves the cursor one line down, scrolling the margins instead when sitting on the bottom
+    /// one. Without margins the grid just grows and the old top line goes to scrollback, except
+    /// on the alternate screen which has none. Below the margins it stops at the last line
+    fn line_feed(&mut self) {
+        let margins = self.margins;
+        let grows = margins.is_full_screen(self.rows, self.cols) && !self.is_alt_screen();
+        let line = self.cursor_line();
+        if grows && line >= self.rows {
+            // the new line has to exist for the screen to move down with the cursor, blank
+            // lines would otherwise leave it below the screen
+            self.grid.grow(self.brush.pos.row + 1);
+        }
+        if grows || (line != margins.bottom && line < self.rows) {
+            self.brush.pos.move_down(1, self.cursor_bounds());
+            return;
+        }
+        if line != margins.bottom {
+            return;
+        }
+
+        let origin = self.screen_origin();
+        let region = Region::new(
+            origin + margins.top,
+            margins.left,
+            origin + margins.bottom,
+            margins.right,
+        );
+        self.grid.scroll_up(region);
+    }
+
+    /// Moves the cursor one line up, scrolling the margins down instead when sitting on the top
+    /// one. Above the margins it stops at the first line
+    fn reverse_index(&mut self) {
+        let margins = self.margins;
+        let line = self.cursor_line();
+        if line != margins.top {
+            self.brush.pos.move_up(1, self.cursor_bounds());
+            return;
+        }
+
+        let origin = self.screen_origin();
+        let region = Region::new(
+            origin + margins.top,
+            margins.left,
+            origin + margins.bottom,
+            margins.right,
+        );
+        self.grid.scroll_down(region);
+    }
+
+    fn carriage_return(&mut self) {
+        self.brush.pos.col = self.cursor_bounds().left;
+    }
+
+    /// Paints `char` at the cursor and moves it right. A char printed in the right margin, or
+    /// at the right edge when the cursor is outside the margins, leaves a wrap pending for the
+    /// next one
+    fn print(&mut self, char: char) {
+        let wrap_to = match self.pending_wrap.take() {
+            Some((pos, col)) if pos == self.brush.pos => Some(col),
+            _ if self.brush.pos.col > self.cols => Some(1),
+            _ => None,
+        };
+        if let Some(col) = wrap_to {
+            self.grid.set_wrapped(self.brush.pos.row);
+            self.brush.pos.col = col;
+            self.line_feed();
+        }
+
+        self.grid.paint(&self.brush, char);
+        let Margins { left, right, .. } = self.margins;
+        let col = self.brush.pos.col;
+        // right past the last column is where a wrap waits for the next char
+        self.brush.pos.col += 1;
+        if col == right && left <= col {
+            self.pending_wrap = Some((self.brush.pos, left));
+        } else if col == self.cols {
+            self.pending_wrap = Some((self.brush.pos, 1));
+        }
+    }
+
+    /// EL, the cells erased are blank in the default colors
+    fn erase_line(&mut self, mode: u32) {
+        let Cursor { row, col } = self.brush.pos;
+        match mode {
+            0 => self.grid.erase_line(row, col),
+            1 => {
+                let region = Region::new(row, 1, row, col.min(self.cols));
+                self.grid.erase_region(region);
+            }
+            2 => self.grid.erase_line(row, 1),
+            _ => {}
+        }
+    }
+
+    /// ED 0 and 1, the lines the cursor isn't on go whole. Rows past the end of the grid are
+    /// blank already and aren't added
+    fn erase_display(&mut self, mode: u32) {
+        let origin = self.screen_origin();
+        let row = self.brush.pos.row;
+        let rows = match mode {
+            0 => row + 1..=(origin + self.rows).min(self.grid.len()),
+            1 => origin + 1..=row - 1,
+            _ => return,
+        };
+        for y in rows {
+            self.grid.erase_line(y, 1);
+        }
+        self.erase_line(mode);
+    }
+
+    fn fill_rect(&mut self, char: char, rect: ansi::Rect) {
+        let region = self.screen_region(rect);
+        self.grid.fill_region(region, &self.brush, char);
+    }
+
+    fn erase_rect(&mut self, rect: ansi::Rect) {
+        let region = self.screen_region(rect);
+        self.grid.erase_region(region);
+    }
+
+    fn copy_rect(&mut self, src: ansi::Rect, top: u32, left: u32) {
+        let origin = self.screen_origin();
+        let top = origin + clamp_param(top, 1, self.rows);
+        let left = clamp_param(left, 1, self.cols);
+
+        // whatever doesn't fit at the destination is dropped
+        let mut src = self.screen_region(src);
+        if src.is_empty() {
+            return;
+        }
+        src.bottom = src.bottom.min(src.top + origin + self.rows - top);
+        src.right = src.right.min(src.left + self.cols - left);
+
+        self.grid.copy_region(src, top, left);
+    }
+
+    /// Puts an image at the cursor covering `rows` by `cols` cells and moves the cursor past
+    /// it. A placement with the same ids as one already there takes its place
+    pub fn place_image(
+        &mut self,
+        handle: ImageHandle,
+        (rows, cols): (usize, usize),
+        id: Option<ImageId>,
+        cursor: ImageCursor,
+    ) {
+        if let Some(id) = id.filter(|id| id.placement != 0) {
+            self.grid.remove_images(|image| image.id == Some(id));
+        }
+
+        let Cursor { col, row } = self.brush.pos;
+        self.grid.place_image(ImagePlacement {
+            row,
+            col: col.min(self.cols),
+            rows,
+            cols,
+            handle,
+            id,
+        });
+        match cursor {
+            ImageCursor::Below => {
+                for _ in 0..rows {
+                    self.line_feed();
+                }
+                self.brush.pos.col = col;
+            }
+            ImageCursor::After => {
+                for _ in 1..rows {
+                    self.line_feed();
+                }
+                self.brush.pos.move_right(cols, self.cursor_bounds());
+            }
+            ImageCursor::Stay => {}
+        }
+    }
+
+    /// Drops the images of the grid on view that `remove` picks and hands them back
+    pub fn remove_images(
+        &mut self,
+        remove: impl FnMut(&ImagePlacement) -> bool,
+    ) -> Vec<ImagePlacement> {
+        self.grid.remove_images(remove)
+    }
+
+    /// Parses and applies output of the child. Characters and escape sequences split across
+    /// two calls are put back together, a sequence longer than `ansi::SEQUENCE_MAX_BYTES` is
+    /// dropped instead, up to its terminator
+    pub fn process_bytes(&mut self, mut bytes: &[u8]) -> Vec<Event> {
+        if self.dropping {
+            let Some(end) = ansi::string_end(bytes) else {
+                return vec![];
+            };
+            self.dropping = false;
+            bytes = &bytes[end..];
+        }
+
+        self.held.extend_from_slice(bytes);
+        let rest = match ansi::unterminated(&self.held) {
+            Some(Unterminated::Held(start)) => self.held.split_off(start),
+            Some(Unterminated::TooLong(start)) => {
+                let len = self.held.len() - start;
+                tracing::warn!(bytes = len, "dropped a sequence too long to hold");
+                self.held.truncate(start);
+                self.dropping = true;
+                vec![]
+            }
+            None => vec![],
+        };
+        let bytes = Bytes::from(std::mem::replace(&mut self.held, rest));
+        self.handle_output(Output::parse(&bytes))
+    }
+
+    /// Calls `listener` with every event as it's raised, before `handle_output` hands them
+    /// back. For embedders that would rather react in place than go through the returned ones
+    pub fn subscribe(&mut self, listener: impl FnMut(&Event) + Send + 'static) {
+        self.listeners.0.push(Box::new(listener));
+    }
+
+    /// Applies output that was already parsed, the parser can run away from the frontend
+    pub fn handle_output(&mut self, outputs: Vec<Output>) -> Vec<Event> {
+        let _span = tracing::trace_span!("handle_output", outputs = outputs.len()).entered();
+        for output in outputs {
+            tracing::trace!("{}", output);
+            match output {
+                Output::Bytes(b) | Output::Unknown(b) => self.handle_bytes(b),
+                Output::Ansi(ac) => self.handle_ansi(ac),
+            }
+        }
+        std::mem::take(&mut self.events)
+    }
+
+    fn handle_bytes(&mut self, bytes: Bytes) {
+        let parsed = self.decode(&bytes);
+        for char in parsed.chars() {
+            match char {
+                '\n' | '\u{b}' | '\u{c}' => {
+                    self.line_feed();
+                    if self.new_line_mode {
+                        self.carriage_return();
+                    }
+                }
+                '\r' => {
+                    self.carriage_return();
+                }
+                '\t' => self.tab_forward(1),
+                '\u{7}' => self.emit(Event::Bell),
+                '\u{e}' => self.charsets.shifted = true,
+                '\u{f}' => self.charsets.shifted = false,
+                '\u{8}' => {
+                    self.brush.pos.move_left(1, self.cursor_bounds());
+                }
+                // NUL, DEL, ESC and the other controls don't draw anything
+                char if char.is_control() => {}
+                _ => {
+                    self.print(self.charsets.map(char));
+                }
+            }
+        }
+    }
+
+    /// `bytes` as text after whatever the last output left of a character. An incomplete one at
+    /// the very end waits for the rest of it, bytes that can't be UTF-8 turn into U+FFFD
+    fn decode<'a>(&mut self, bytes: &'a [u8]) -> Cow<'a, str> {
+        if self.partial.is_empty() {
+            if let Ok(text) = std::str::from_utf8(bytes) {
+                return Cow::Borrowed(text);
+            }
+        }
+
+        self.partial.extend_from_slice(bytes);
+        let incomplete = match self.partial.utf8_chunks().last() {
+            Some(chunk)
+                if std::str::from_utf8(chunk.invalid()).is_err_and(|e| e.error_len().is_none()) =>
+            {
+                chunk.invalid().len()
+            }
+            _ => 0,
+        };
+        let rest = self.partial.split_off(self.partial.len() - incomplete);
+        let text = match String::from_utf8_lossy(&self.partial) {
+            Cow::Borrowed(text) => text.to_owned(),
+            Cow::Owned(text) => {
+                let len = self.partial.len();
+                tracing::warn!(len, "replaced output that isn't valid UTF-8");
+                text
+            }
+        };
+        self.partial = rest;
+        Cow::Owned(text)
+    }
+
+    fn handle_ansi(&mut self, ac: AnsiCode) {
+        use AnsiCode::*;
+
+        match ac {
+            EraseLine(mode) => self.erase_line(mode),
+            EraseDisplay(2) => {
+                // the whole screen is blanked where it is, like xterm does, and the cursor
+                // stays put. Rows past the end of the grid are blank already
+                let origin = self.screen_origin();
+                let bottom = (origin + self.rows).min(self.grid.len());
+                self.grid.erase_rows(origin + 1..=bottom);
+            }
+            EraseDisplay(3) => {
+                let dropped = self.clear_scrollback();
+                self.emit(Event::ScrollbackCleared(dropped));
+            }
+            EraseDisplay(mode) => self.erase_display(mode),
+            HideCursor => self.cursor_visible = false,
+            ShowCursor => self.cursor_visible = true,
+            EnableCursorBlink => self.cursor_style.blinking = true,
+            DisableCursorBlink => self.cursor_style.blinking = false,
+            SetCursorStyle(ps) => {
+                self.cursor_style =
+                    CursorStyle::from_decscusr(ps).unwrap_or(self.default_cursor_style);
+            }
+            CursorSave if self.lr_margins_enabled => {
+                // with DECLRMM set a bare `CSI s` is a DECSLRM resetting the margins
+                self.set_left_and_right(0, 0);
+            }
+            CursorSave | SaveCursor => self.save_cursor(),
+            CursorRestore | RestoreCursor => self.restore_cursor(),
+            SetTopAndBottom(top, bottom) => {
+                self.set_top_and_bottom(top, bottom);
+            }
+            SetLeftAndRight(left, right) => {
+                self.set_left_and_right(left, right);
+            }
+            EnableLeftRightMargins => {
+                self.set_lr_margins_enabled(true);
+            }
+            DisableLeftRightMargins => {
+                self.set_lr_margins_enabled(false);
+            }
+            CopyRectArea(src, top, left) => {
+                self.copy_rect(src, top, left);
+            }
+            FillRectArea(char, rect) => {
+                self.fill_rect(char, rect);
+            }
+            EraseRectArea(rect) => {
+                self.erase_rect(rect);
+            }
+            EnableAltScreen(_) => {
+                self.enter_alt_screen();
+                self.emit(Event::ScreenSwitched);
+            }
+            DisableAltScreen(mode) => {
+                self.exit_alt_screen(mode == 1049);
+                self.emit(Event::ScreenSwitched);
+            }
+            EnableBracketedPaste => self.bracketed_paste = true,
+            DisableBracketedPaste => self.bracketed_paste = false,
+            CursorToApp => self.key_modes.app_cursor = true,
+            SetCursorKeyToCursor => self.key_modes.app_cursor = false,
+            SetAlternateKeypad => self.key_modes.app_keypad = true,
+            SetNumericKeypad => self.key_modes.app_keypad = false,
+            EnableMouseMode(1006) => self.mouse_modes.sgr = true,
+            DisableMouseMode(1006) => self.mouse_modes.sgr = false,
+            EnableMouseMode(mode) => {
+                self.mouse_modes.tracking = match mode {
+                    1000 => MouseTracking::Click,
+                    1002 => MouseTracking::Drag,
+                    _ => MouseTracking::Motion,
+                }
+            }
+            DisableMouseMode(_) => self.mouse_modes.tracking = MouseTracking::Off,
+            PrivateModes(modes, set) => {
+                // modes no parser knows are skipped like the sequences they'd come in alone
+                for mode in modes {
+                    if let Some(ac) = ansi::private_mode(mode, set) {
+                        self.handle_ansi(ac);
+                    }
+                }
+            }
+            CursorPos(row, col) => self.cursor_to(row, col),
+            CursorUp(n) => {
+                let n = clamp_param(n, 1, self.rows);
+                self.brush.pos.move_up(n, self.cursor_bounds());
+            }
+            CursorDown(n) => {
+                let n = clamp_param(n, 1, self.rows);
+                self.brush.pos.move_down(n, self.cursor_bounds());
+            }
+            CursorForward(n) => {
+                let n = clamp_param(n, 1, self.cols);
+                self.brush.pos.move_right(n, self.cursor_bounds());
+            }
+            CursorBackward(n) => {
+                let n = clamp_param(n, 1, self.cols);
+                self.brush.pos.move_left(n, self.cursor_bounds());
+            }
+            CursorForwardTab(n) => self.tab_forward(clamp_param(n, 1, self.cols)),
+            CursorBackwardTab(n) => self.tab_backward(clamp_param(n, 1, self.cols)),
+            SetTabStop => {
+                if let Some(stop) = self.tab_stops.get_mut(self.brush.pos.col - 1) {
+                    *stop = true;
+                }
+            }
+            ClearTabStop(0) => {
+                if let Some(stop) = self.tab_stops.get_mut(self.brush.pos.col - 1) {
+                    *stop = false;
+                }
+            }
+            ClearTabStop(3) => self.tab_stops.fill(false),
+            SetOriginRelative => {
+                self.origin_mode = true;
+                self.home_cursor();
+            }
+            SetOriginAbsolute => {
+                self.origin_mode = false;
+                self.home_cursor();
+            }
+            // there's no alternate character ROM, its sets draw as ASCII
+            SetUSG0 | SetG0AlternateChar | SetG0AltAndSpecialGraph => {
+                self.charsets.g0 = Charset::Ascii;
+            }
+            SetUSG1 | SetG1AlternateChar | SetG1AltAndSpecialGraph => {
+                self.charsets.g1 = Charset::Ascii;
+            }
+            SetUKG0 => self.charsets.g0 = Charset::Uk,
+            SetUKG1 => self.charsets.g1 = Charset::Uk,
+            SetG0SpecialChars => self.charsets.g0 = Charset::DecSpecial,
+            SetG1SpecialChars => self.charsets.g1 = Charset::DecSpecial,
+            Index => self.line_feed(),
+            NextLine => {
+                self.carriage_return();
+                self.line_feed();
+            }
+            ReverseIndex => self.reverse_index(),
+            SetNewLineMode => self.new_line_mode = true,
+            SetLineFeedMode => self.new_line_mode = false,
+            SetReverseVideo => self.set_reverse_video(true),
+            SetNormalVideo => self.set_reverse_video(false),
+            SetGraphicsMode(params) => self.brush.apply_sgr(&params),
+            Osc(ps, pt) => self.osc(ps, pt),
+            RequestMode(mode, private) => self.report_mode(mode, private),
+            RequestTermcap(names) => self.emit(Event::TermcapQuery(names)),
+            ac => tracing::trace!(?ac, "not applied"),
+        }
+    }
+
+    fn osc(&mut self, ps: u32, pt: String) {
+        let event = match ps {
+            0 | 2 => Event::TitleChanged(pt),
+            1 => return,
+            7 => match osc::cwd_from_url(&pt) {
+                Some(cwd) => Event::CwdChanged(cwd),
+                None => return,
+            },
+            52 => {
+                let (targets, data) = pt.split_once(';').unwrap_or(("", &pt));
+                let kind = ClipboardKind::from_targets(targets);
+                if data == "?" {
+                    Event::ClipboardLoad(kind)
+                } else if data.len() > osc::CLIPBOARD_MAX_BYTES {
+                    tracing::debug!(bytes = data.len(), "dropped OSC 52, too long");
+                    return;
+                } else {
+                    let Some(text) = osc::base64_decode(data) else {
+                        return;
+                    };
+                    Event::ClipboardStore(kind, String::from_utf8_lossy(&text).into_owned())
+                }
+            }
+            // ConEmu uses OSC 9 with a number first for its progress bar and the like
+            9 if is_conemu(&pt) => match osc::progress(&pt) {
+                Some(progress) => Event::Progress(progress),
+                None => return,
+            },
+            9 => Event::Notification(String::new(), pt),
+            133 => return self.mark_command(&pt),
+            777 => {
+                let Some(notification) = pt.strip_prefix("notify;") else {
+                    return;
+                };
+                let (title, body) = notification.split_once(';').unwrap_or((notification, ""));
+                Event::Notification(title.to_owned(), body.to_owned())
+            }
+            _ => Event::Osc(ps, pt),
+        };
+        self.emit(event);
+    }
+
+    /// OSC 133 of shell integration, `A` as the prompt starts, `B` where the command line does,
+    /// `C` as the command runs and `D;status` once it's done. Marks are kept on the primary
+    /// screen only, the alternate one has no scrollback to go through
+    fn mark_command(&mut self, pt: &str) {
+        if self.is_alt_screen() {
+            return;
+        }
+        let at = (self.brush.pos.row, self.brush.pos.col);
+        let mut fields = pt.split(';');
+        let commands = &mut self.grid.commands;
+        let kind = fields.next().unwrap_or("");
+        if kind == "A" {
+            // a prompt drawn again before anything ran, after Ctrl+C or an empty line, takes
+            // the place of the one before
+            if commands.last().is_some_and(|mark| mark.output.is_none()) {
+                commands.pop();
+            }
+            commands.push(CommandMark {
+                prompt: at.0,
+                ..CommandMark::default()
+            });
+            return;
+        }
+
+        let Some(mark) = commands.last_mut().filter(|mark| mark.end.is_none()) else {
+            return;
+        };
+        match kind {
+            "B" => mark.input = Some(at),
+            "C" => mark.output = Some(at),
+            "D" => {
+                mark.end = Some(at);
+                mark.exit_status = fields.next().and_then(|status| status.parse().ok());
+            }
+            _ => {}
+        }
+    }
+
+    /// Answers DECRQM: 1 for a mode that's set, 2 for one that's reset, 3 for autowrap, which
+    /// can't be turned off, and 0 for the modes not known
+    fn report_mode(&mut self, mode: u32, private: bool) {
+        let tracking = self.mouse_modes.tracking;
+        let set = match (private, mode) {
+            (false, 20) => Some(self.new_line_mode),
+            (true, 1) => Some(self.key_modes.app_cursor),
+            (true, 5) => Some(self.reverse_video),
+            (true, 6) => Some(self.origin_mode),
+            (true, 12) => Some(self.cursor_style.blinking),
+            (true, 25) => Some(self.cursor_visible),
+            (true, 47 | 1047 | 1049) => Some(self.is_alt_screen()),
+            (true, 69) => Some(self.lr_margins_enabled),
+            (true, 1000) => Some(tracking == MouseTracking::Click),
+            (true, 1002) => Some(tracking == MouseTracking::Drag),
+            (true, 1003) => Some(tracking == MouseTracking::Motion),
+            (true, 1006) => Some(self.mouse_modes.sgr),
+            (true, 2004) => Some(self.bracketed_paste),
+            _ => None,
+        };
+        let value = match (private, mode, set) {
+            (true, 7, _) => 3,
+            (_, _, Some(true)) => 1,
+            (_, _, Some(false)) => 2,
+            (_, _, None) => 0,
+        };
+
+        let prefix = if private { "?" } else { "" };
+        let reply = format!("\x1b[{}{};{}$y", prefix, mode, value);
+        self.emit(Event::Reply(reply.into_bytes()));
+    }
+
+    fn emit(&mut self, event: Event) {
+        for listener in &mut self.listeners.0 {
+            listener(&event);
+        }
+        self.events.push(event);
+    }
+
+    fn set_reverse_video(&mut self, enabled: bool) {
+        if self.reverse_video != enabled {
+            self.reverse_video = enabled;
+            self.emit(Event::ReverseVideo);
+        }
+    }
+}
+
+/// Zero means "use the default" for VT numeric parameters
+/// Columns 9, 17, 25... like a VT100 sets them on power up
+fn default_tab_stop(col: usize) -> bool {
+    col % 8 == 1 && col > 1
+}
+
+fn clamp_param(value: u32, default: usize, max: usize) -> usize {
+    match value {
+        0 => default,
+        v => (v as usize).min(max),
+    }
+}
+
+/// Whether an OSC 9 is one of ConEmu's commands, `N;...`, rather than a notification
+fn is_conemu(pt: &str) -> bool {
+    pt.split_once(';')
+        .is_some_and(|(n, _)| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::TermColor;
+
+    fn first_line(terminal: &Terminal) -> String {
+        let text = terminal.screen_text();
+        text.split('\n').next().unwrap_or_default().to_owned()
+    }
+
+    #[test]
+    fn decode_split_characters() {
+        let mut terminal = Terminal::new(4, 10);
+        assert!(matches!(terminal.decode(b"plain"), Cow::Borrowed("plain")));
+        assert_eq!(terminal.decode(b"a\xc3"), "a");
+        assert_eq!(terminal.decode(b"\xa9b"), "\u{e9}b");
+        assert_eq!(terminal.decode(b"\xe2\x82"), "");
+        assert_eq!(terminal.decode(b"\xac"), "\u{20ac}");
+        assert!(terminal.partial.is_empty());
+    }
+
+    #[test]
+    fn decode_invalid_bytes() {
+        let mut terminal = Terminal::new(4, 10);
+        assert_eq!(terminal.decode(b"a\xffb"), "a\u{fffd}b");
+        // only the incomplete character at the very end waits
+        assert_eq!(terminal.decode(b"\xc3a\xe2"), "\u{fffd}a");
+        assert_eq!(terminal.decode(b"\x82\xac"), "\u{20ac}");
+    }
+
+    #[test]
+    fn split_sequences_wait_for_the_rest() {
+        let mut terminal = Terminal::new(4, 10);
+        terminal.process_bytes(b"a\x1b[3");
+        terminal.process_bytes(b"1mb\x1b");
+        terminal.process_bytes(b"[0mc");
+        assert_eq!(first_line(&terminal), "abc");
+        let red = terminal.grid().style(&terminal.screen()[0].cells[1]);
+        assert_eq!(red.fg_color, TermColor::Ansi(1));
+        assert!(terminal.held.is_empty());
+    }
+
+    #[test]
+    fn sequences_too_long_are_dropped() {
+        let mut terminal = Terminal::new(4, 10);
+        terminal.process_bytes(b"a\x1b]0;");
+        terminal.process_bytes(&vec![b'x'; ansi::SEQUENCE_MAX_BYTES]);
+        assert!(terminal.dropping);
+        assert!(terminal.held.is_empty());
+        terminal.process_bytes(b"xxxx");
+        terminal.process_bytes(b"xx\x07b");
+        assert_eq!(first_line(&terminal), "ab");
+        assert!(!terminal.dropping);
+    }
+
+    #[test]
+    fn split_escapes_wait_for_the_rest() {
+        let mut terminal = Terminal::new(4, 10);
+        terminal.process_bytes(b"abc\x1b");
+        terminal.process_bytes(b"[2Kd");
+        assert_eq!(first_line(&terminal), "   d");
+        terminal.process_bytes(b"\x1b]0;ti");
+        terminal.process_bytes(b"tle\x07e");
+        assert_eq!(first_line(&terminal), "   de");
+    }
+
+    #[test]
+    fn erase_lines_and_the_display() {
+        let mut terminal = Terminal::new(5, 6);
+        terminal.process_bytes(b"aaaaaa\r\nbbbbbb\r\ncccccc\r\ndddddd\r\neeeeee");
+        // put the cursor on column 3 of a line without moving it through sequences
+        let erase = |terminal: &mut Terminal, line: usize, sequence: &[u8]| {
+            terminal.brush.pos = Cursor { row: line, col: 3 };
+            terminal.process_bytes(sequence);
+        };
+        erase(&mut terminal, 2, b"\x1b[1K");
+        erase(&mut terminal, 3, b"\x1b[K");
+        erase(&mut terminal, 4, b"\x1b[2K");
+        erase(&mut terminal, 1, b"\x1b[1J");
+        erase(&mut terminal, 5, b"\x1b[J");
+        assert_eq!(terminal.screen_text(), "   aaa\n   bbb\ncc\n\nee");
+        erase(&mut terminal, 2, b"\x1b[2J");
+        assert_eq!(terminal.screen_text(), "\n\n\n\n");
+        assert_eq!(terminal.brush.pos, Cursor { row: 2, col: 3 });
+    }
+}
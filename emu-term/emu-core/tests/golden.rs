@@ -0,0 +1,80 @@
+//! Conformance cases: each `golden/<case>.bytes` is output captured from a program, which is
+//! replayed through the terminal and compared against the snapshot in `<case>.golden`.
+//! `UPDATE_GOLDEN=1 cargo test` writes the snapshots anew after a deliberate change, and
+//! `cargo run --example golden` records new cases
+
+use emu_core::Terminal;
+use std::path::{Path, PathBuf};
+
+/// Size of the cases recorded without a snapshot to read it from
+const DEFAULT_SIZE: (usize, usize) = (24, 80);
+
+#[test]
+fn golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut cases: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .expect("the golden directory should be readable")
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "bytes"))
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no cases in {}", dir.display());
+
+    let mut failed = vec![];
+    for case in &cases {
+        let name = case.file_stem().unwrap_or_default().to_string_lossy();
+        let golden_path = case.with_extension("golden");
+        let golden = std::fs::read_to_string(&golden_path).ok();
+        let (rows, cols) = golden.as_deref().and_then(size).unwrap_or(DEFAULT_SIZE);
+
+        let mut terminal = Terminal::new(rows, cols);
+        let bytes = std::fs::read(case).expect("the case should be readable");
+        terminal.process_bytes(&bytes);
+        let snapshot = terminal.snapshot();
+
+        if update {
+            std::fs::write(&golden_path, &snapshot).expect("the snapshot should be writable");
+            continue;
+        }
+        match golden {
+            Some(golden) if golden == snapshot => {}
+            Some(golden) => {
+                eprintln!("{} differs:\n{}", name, diff(&golden, &snapshot));
+                failed.push(name);
+            }
+            None => {
+                eprintln!("{} has no snapshot, run with UPDATE_GOLDEN=1", name);
+                failed.push(name);
+            }
+        }
+    }
+    assert!(failed.is_empty(), "failed: {}", failed.join(", "));
+}
+
+/// `rows` by `cols` from the first line of a snapshot
+fn size(snapshot: &str) -> Option<(usize, usize)> {
+    let size = snapshot.lines().next()?.strip_prefix("size ")?;
+    let (rows, cols) = size.split_once('x')?;
+    Some((rows.parse().ok()?, cols.parse().ok()?))
+}
+
+/// The lines that changed, the expected one first
+fn diff(expected: &str, actual: &str) -> String {
+    let (expected, actual): (Vec<&str>, Vec<&str>) =
+        (expected.lines().collect(), actual.lines().collect());
+    let mut diff = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        let (old, new) = (expected.get(i), actual.get(i));
+        if old == new {
+            continue;
+        }
+        if let Some(old) = old {
+            diff.push_str(&format!("-{}\n", old));
+        }
+        if let Some(new) = new {
+            diff.push_str(&format!("+{}\n", new));
+        }
+    }
+    diff
+}
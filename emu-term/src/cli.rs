@@ -0,0 +1,254 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::ptylog::{LogFormat, PtyLog};
+use crate::session::Session;
+use crate::shell::Shell;
+use crate::ssh::SshTarget;
+
+pub const USAGE: &str = "\
+usage: emu-term [options] [-e command [args...] | ssh://[user@]host[:port]]
+
+options:
+    -e, --command <command>...     run a command instead of the shell, takes the rest of the line
+        --working-directory <dir>  start in this directory
+        --title <title>            initial window title
+        --profile <name>           run the first tab with a profile from the config
+        --config <file>            read the config from this file, not ~/.config/emu-term/config.toml
+        --login                    start the shell as a login shell
+        --env <name=value>         set a variable for the shell, can be repeated
+        --unset-env <name>         don't pass a variable down to the shell, can be repeated
+        --connect <address>        attach to a socket instead, unix:<path> or host:port
+        --serial <device> [baud]   attach to a serial port instead, at 115200 baud by default
+        --record <file>            record the first tab to an asciicast file
+        --play <file>              play back an asciicast or a typescript instead
+        --timing <file>            timing of the typescript given to --play, from script -t
+        --stdin                    page through what was piped in instead
+        --attach <session>         take a detached session from the daemon instead
+        --sessions                 list the sessions the daemon holds and exit
+        --daemon                   hold the ptys of detachable tabs, started by the first one
+        --log-pty <file>           copy everything read from the ptys to a file, for debugging
+        --log-format <format>      raw, escaped or hex, raw by default and plays with --play
+    -h, --help                     print this message
+
+while playing back, space pauses, + and - change the speed, the left and right arrows seek,
+0 starts over and q quits
+
+while paging, j and k or the arrows move a line, space and b a page, d and u half a page,
+g and G go to the start and the end, / searches and q quits
+
+diagnostics go to standard error, EMU_TERM_LOG takes a filter like debug or emu_core=trace";
+
+const DEFAULT_BAUD: u32 = 115200;
+
+/// Command line options
+#[derive(Debug, Default, Clone)]
+pub struct Args {
+    /// Program and arguments given to `-e`
+    pub command: Option<Vec<String>>,
+    pub working_directory: Option<PathBuf>,
+    pub title: Option<String>,
+    pub config: Option<PathBuf>,
+    /// Name of a profile in the config, for the first tab
+    pub profile: Option<String>,
+    pub login: bool,
+    pub env: Vec<(String, String)>,
+    pub unset_env: Vec<String>,
+    /// `--connect` address, see `Session::connect_to`
+    pub connect: Option<String>,
+    pub serial: Option<(PathBuf, u32)>,
+    /// An `ssh://` URL in place of a command
+    pub ssh: Option<SshTarget>,
+    /// asciicast file the first tab is recorded to
+    pub record: Option<PathBuf>,
+    /// Recording played back in the first tab
+    pub play: Option<PathBuf>,
+    pub timing: Option<PathBuf>,
+    /// Show standard input rather than running anything
+    pub stdin: bool,
+    /// A session of the daemon for the first tab
+    pub attach: Option<u32>,
+    /// Print the sessions of the daemon instead of opening a window
+    pub sessions: bool,
+    /// Run as the daemon, see `daemon`
+    pub daemon: bool,
+    /// Debug log of the output of every tab
+    pub pty_log: Option<PtyLog>,
+    pub help: bool,
+}
+
+#[derive(Debug)]
+pub enum ArgsError {
+    MissingValue(String),
+    /// `--env` without an `=`
+    BadVariable(String),
+    BadUrl(String),
+    BadLogFormat(String),
+    BadSession(String),
+    Unknown(String),
+}
+
+impl Display for ArgsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgsError::MissingValue(flag) => write!(f, "{} needs a value", flag),
+            ArgsError::BadVariable(var) => write!(f, "expected name=value, got {}", var),
+            ArgsError::BadUrl(url) => write!(f, "expected ssh://[user@]host[:port], got {}", url),
+            ArgsError::BadLogFormat(format) => {
+                write!(f, "expected raw, escaped or hex, got {}", format)
+            }
+            ArgsError::BadSession(session) => {
+                write!(f, "expected the number of a session, got {}", session)
+            }
+            ArgsError::Unknown(arg) => write!(f, "unknown argument {}", arg),
+        }
+    }
+}
+
+impl Args {
+    /// Parses the arguments after the program name. Options take their value either as the
+    /// next argument or after an `=`
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ArgsError> {
+        let mut parsed = Args::default();
+        let (mut log_path, mut log_format) = (None, LogFormat::default());
+        let mut args = args.into_iter().peekable();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_owned())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| ArgsError::MissingValue(flag.to_owned()))
+            };
+
+            match flag {
+                "-e" | "--command" => {
+                    let command: Vec<String> =
+                        inline.iter().cloned().chain(args.by_ref()).collect();
+                    if command.is_empty() {
+                        return Err(ArgsError::MissingValue(flag.to_owned()));
+                    }
+                    parsed.command = Some(command);
+                    break;
+                }
+                "--working-directory" => parsed.working_directory = Some(value()?.into()),
+                "--title" => parsed.title = Some(value()?),
+                "--config" => parsed.config = Some(value()?.into()),
+                "--profile" => parsed.profile = Some(value()?),
+                "--login" => parsed.login = true,
+                "--env" => {
+                    let var = value()?;
+                    let Some((name, value)) = var.split_once('=') else {
+                        return Err(ArgsError::BadVariable(var));
+                    };
+                    parsed.env.push((name.to_owned(), value.to_owned()));
+                }
+                "--unset-env" => parsed.unset_env.push(value()?),
+                "--connect" => parsed.connect = Some(value()?),
+                "--serial" => {
+                    let device = value()?.into();
+                    let baud = match args.peek().and_then(|baud| baud.parse().ok()) {
+                        Some(baud) => {
+                            args.next();
+                            baud
+                        }
+                        None => DEFAULT_BAUD,
+                    };
+                    parsed.serial = Some((device, baud));
+                }
+                "--record" => parsed.record = Some(value()?.into()),
+                "--play" => parsed.play = Some(value()?.into()),
+                "--timing" => parsed.timing = Some(value()?.into()),
+                "--stdin" => parsed.stdin = true,
+                "--attach" => {
+                    let session = value()?;
+                    let number = session.parse().map_err(|_| ArgsError::BadSession(session));
+                    parsed.attach = Some(number?);
+                }
+                "--sessions" => parsed.sessions = true,
+                "--daemon" => parsed.daemon = true,
+                "--log-pty" => log_path = Some(PathBuf::from(value()?)),
+                "--log-format" => {
+                    let format = value()?;
+                    log_format =
+                        LogFormat::parse(&format).ok_or(ArgsError::BadLogFormat(format))?;
+                }
+                "-h" | "--help" => parsed.help = true,
+                url if url.starts_with("ssh://") => {
+                    let target = SshTarget::parse(url);
+                    parsed.ssh = Some(target.ok_or_else(|| ArgsError::BadUrl(arg.clone()))?);
+                }
+                _ => return Err(ArgsError::Unknown(arg)),
+            }
+        }
+
+        parsed.pty_log = log_path.map(|path| PtyLog {
+            path,
+            format: log_format,
+        });
+        Ok(parsed)
+    }
+
+    /// What the first tab talks to, standard input or a socket or a serial port or a recording
+    /// when asked for and a pty otherwise
+    pub fn session(&self, config: &Config) -> Session {
+        if self.stdin {
+            return Session::Stdin;
+        }
+        if let Some(session) = self.attach {
+            return Session::Attach(session);
+        }
+        if let Some(recording) = &self.play {
+            return Session::Replay {
+                recording: recording.clone(),
+                timing: self.timing.clone(),
+            };
+        }
+        if let Some((device, baud)) = &self.serial {
+            return Session::Serial {
+                device: device.clone(),
+                baud: *baud,
+            };
+        }
+        if let Some(target) = &self.ssh {
+            let shell = self.shell(config).run(&target.command());
+            return Session::Ssh(target.clone(), shell);
+        }
+        match &self.connect {
+            Some(address) => Session::connect_to(address),
+            None => Session::Pty(self.shell(config)),
+        }
+    }
+
+    /// What to run on the pty. Variables given here win over the ones from the config, and
+    /// `-e` and `--working-directory` over the profile
+    pub fn shell(&self, config: &Config) -> Shell {
+        let mut shell = match (&self.command, &config.shell) {
+            (Some(command), _) | (None, Some(command)) => Shell::command(command),
+            (None, None) => Shell::resolve(self.login),
+        };
+        shell.working_directory = self.working_directory.clone();
+        shell.env = [&config.env[..], &self.env[..]].concat();
+        shell.unset_env = [&config.unset_env[..], &self.unset_env[..]].concat();
+
+        if let Some(profile) = self
+            .profile
+            .as_deref()
+            .and_then(|name| config.profile(name))
+        {
+            let command = profile.command.as_ref().filter(|_| self.command.is_none());
+            if let Some(command) = command {
+                shell = shell.run(command);
+            }
+            if self.working_directory.is_none() {
+                shell.working_directory = profile.working_directory.clone();
+            }
+        }
+        shell
+    }
+}
@@ -0,0 +1,933 @@
+use crate::palette::{parse_color_spec, AlacrittyColors, Palette, Rgb};
+use crate::shell::Shell;
+use crate::MONO;
+use emu_core::grid::{CursorShape, CursorStyle};
+use iced::font::Family;
+use iced::keyboard::Modifiers;
+use iced::Font;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// User settings, every field has a sane default so a partial config is always usable
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub font: FontConfig,
+    /// Cursor used until the application asks for a different one, and restored by DECSCUSR 0
+    pub cursor: CursorStyle,
+    /// Alacritty (TOML/YAML) or base16 theme file to load the palette from
+    pub theme: Option<PathBuf>,
+    /// Colors set in the config itself, on top of the theme
+    pub colors: Option<AlacrittyColors>,
+    /// Alpha of the default background, cells with an explicit background stay opaque. What
+    /// shows through isn't blurred, there's no blur option: iced doesn't give access to the
+    /// window to ask the compositor for it
+    pub background_opacity: f32,
+    /// How new windows start out, each can be toggled at runtime
+    pub always_on_top: bool,
+    pub fullscreen: bool,
+    /// The title bar and borders the window manager draws, off for a borderless window
+    pub decorations: bool,
+    /// Slim scrollbar on the right edge, only shown while there's scrollback
+    pub scrollbar: bool,
+    /// Lines kept above the screen, the oldest ones are dropped past this
+    pub scrollback: usize,
+    /// Space kept around the grid, in logical pixels
+    pub padding: PaddingConfig,
+    /// Spread what's left of the window after fitting whole cells evenly around the grid,
+    /// rather than leaving it all on the right and bottom
+    pub center: bool,
+    pub visual_bell: VisualBell,
+    /// How long the visual bell stays up
+    pub visual_bell_duration: Duration,
+    /// How much to darken the content while the window isn't focused, 0 leaves it as is and
+    /// 1 blacks it out
+    pub unfocused_dim: f32,
+    /// The mouse coming over a window raises and focuses it, and over a pane of a split tab
+    /// gives that pane the keyboard. Tabs are still switched by clicking them in the tab bar
+    pub focus_follows_mouse: bool,
+    /// macOS only, Option works as Alt and sends ESC-prefixed keys instead of composing
+    /// characters. Alt always does elsewhere
+    pub option_as_alt: bool,
+    /// Drop control characters other than tab and newline from bracketed pastes too, they're
+    /// always dropped from the others
+    pub paste_strip_controls: bool,
+    /// Put the selection on the clipboard as soon as the mouse button is released
+    pub copy_on_select: bool,
+    /// Show the notifications applications ask for with OSC 9 and OSC 777 on the desktop. Off
+    /// by default, they'd let a remote host pass its messages off as the desktop's
+    pub notifications: bool,
+    /// Let applications put text on the clipboard with OSC 52. Off by default, what's pasted
+    /// next could be a command they slipped in. Reading the clipboard is never allowed
+    pub clipboard_write: bool,
+    /// Characters that a double-click takes as part of a word, besides letters and digits
+    pub word_chars: String,
+    /// Underline paths to existing files on hover and open them on Ctrl+click, like URLs. The
+    /// `path:line:column` compilers print too, they open in the editor at that line
+    pub detect_paths: bool,
+    /// Ctrl+click opens paths in `$EDITOR`, in a new tab, instead of the desktop's default
+    /// handler
+    pub open_paths_in_editor: bool,
+    /// Program and arguments that open the `path:line:column` a compiler printed, with
+    /// `{path}`, `{line}` and `{column}` in them. It's started without a terminal, so it has
+    /// to be a GUI editor. `$EDITOR +line path` in a new tab without it
+    pub editor_command: Option<Vec<String>>,
+    /// Shells are handed to the daemon, which keeps them running once their tab or window is
+    /// closed. `--attach` brings one back
+    pub detachable: bool,
+    /// Save the tabs of the last window closed, with their directories and scrollback, and
+    /// offer them back on the next launch
+    pub restore_session: bool,
+    /// Ask before closing a window while its shells have commands running
+    pub confirm_close: bool,
+    /// What happens to the window once the shell exits
+    pub on_child_exit: ChildExit,
+    /// Program and arguments started in place of the user's shell, `-e` still wins
+    pub shell: Option<Vec<String>>,
+    /// Checked before the built-in shortcuts, so they can be taken over
+    pub keybindings: Vec<KeyBinding>,
+    /// Variables set for the child on top of what it inherits
+    pub env: Vec<(String, String)>,
+    /// Inherited variables the child shouldn't see
+    pub unset_env: Vec<String>,
+    /// Colors and font of the tabs that `ssh` into particular hosts
+    pub host_profiles: Vec<HostProfile>,
+    /// Opened from the tab bar or with `--profile`
+    pub profiles: Vec<Profile>,
+    /// Text drawn in colors of its own wherever it's printed, the first rule wins where
+    /// matches overlap
+    pub highlights: Vec<HighlightRule>,
+    /// Window title, with `{title}` replaced by what the application set (or `--title`),
+    /// `{process}` by the program in the foreground and `{cwd}` by its working directory
+    pub title_template: String,
+}
+
+/// Colors and font some tabs get in place of the configured ones
+#[derive(Debug, Default, Clone)]
+pub struct Look {
+    pub theme: Option<PathBuf>,
+    pub font: Option<FontConfig>,
+}
+
+/// Overrides for the tabs connected to `host` from the quick-connect dialog or `ssh://`
+#[derive(Debug, Clone)]
+pub struct HostProfile {
+    /// As it's typed, without the user and port
+    pub host: String,
+    pub look: Look,
+}
+
+/// A named kind of tab, with what it runs, where and how it looks
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    /// Program and arguments, the shell when `None`
+    pub command: Option<Vec<String>>,
+    pub working_directory: Option<PathBuf>,
+    pub look: Look,
+}
+
+impl Profile {
+    /// `base` with the command and directory of the profile, when it has them
+    pub fn shell(&self, base: &Shell) -> Shell {
+        let mut shell = match &self.command {
+            Some(command) => base.run(command),
+            None => base.clone(),
+        };
+        if let Some(dir) = &self.working_directory {
+            shell.working_directory = Some(dir.clone());
+        }
+        shell
+    }
+}
+
+/// A regex, how its matches look and what printing one does, see `highlight::Highlights`
+#[derive(Debug, Clone)]
+pub struct HighlightRule {
+    pub regex: Regex,
+    pub style: HighlightStyle,
+    pub actions: TriggerActions,
+}
+
+/// What a highlight changes about the cells it covers, the rest stays as the application
+/// left it
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct HighlightStyle {
+    pub foreground: Option<Rgb>,
+    pub background: Option<Rgb>,
+    pub underline: bool,
+}
+
+/// What a highlight rule does once a match is printed
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TriggerActions {
+    pub bell: bool,
+    /// Shows a desktop notification with the match, `notifications` doesn't need to be on
+    pub notify: bool,
+    /// Capture group put on the clipboard, 0 for the whole match
+    pub copy: Option<usize>,
+    /// Program and arguments to run, see `highlight::Fired::command`
+    pub command: Option<Vec<String>>,
+    /// Only while the window isn't focused
+    pub unfocused: bool,
+}
+
+impl TriggerActions {
+    pub fn any(&self) -> bool {
+        self.bell || self.notify || self.copy.is_some() || self.command.is_some()
+    }
+}
+
+/// A key combination and what it does
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBinding {
+    pub mods: Modifiers,
+    /// A character, matched without the layout's shift applied, or the name of a key like
+    /// `PageUp` or `F5`. Lowercase either way
+    pub key: String,
+    pub action: Action,
+}
+
+/// What a key binding can do
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Copy,
+    CopyLastOutput,
+    Paste,
+    NewTab,
+    CloseTab,
+    NextTab,
+    PreviousTab,
+    MoveTabLeft,
+    MoveTabRight,
+    RestartTab,
+    NewWindow,
+    QuickConnect,
+    PreviousPrompt,
+    NextPrompt,
+    CommandHistory,
+    /// Splits the focused pane, the new one to the right of it or below it
+    SplitRight,
+    SplitDown,
+    ClosePane,
+    NextPane,
+    PreviousPane,
+    /// Around the focused pane, see `layout::Change`
+    ZoomPane,
+    SwapPanePrevious,
+    SwapPaneNext,
+    RotatePanes,
+    Settings,
+    Search,
+    ClearScreen,
+    ClearScrollback,
+    SaveScrollback,
+    PipeScrollback,
+    CopyHtml,
+    SaveHtml,
+    Screenshot,
+    ToggleRecording,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    ToggleHints,
+    ToggleReadOnly,
+    ToggleTransparency,
+    ToggleAlwaysOnTop,
+    ToggleFullscreen,
+    /// Hides the title bar and borders of the window, or shows them again
+    ToggleDecorations,
+    ToggleDebug,
+    /// Sends the text to the child as if it was typed, from `chars` rather than `action`
+    #[serde(skip)]
+    Chars(String),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChildExit {
+    Close,
+    /// Keeps the window open with the exit status printed, until a key is pressed
+    Hold,
+}
+
+/// What BEL does to the screen
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VisualBell {
+    None,
+    /// Flashes a translucent layer over the grid
+    Flash,
+    /// Swaps the default colors of the whole screen, like DECSCNM
+    Invert,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct PaddingConfig {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            font: FontConfig::default(),
+            cursor: CursorStyle::default(),
+            theme: default_theme(),
+            colors: None,
+            background_opacity: 1.0,
+            always_on_top: false,
+            fullscreen: false,
+            decorations: true,
+            scrollbar: true,
+            scrollback: 10_000,
+            padding: PaddingConfig::default(),
+            center: false,
+            visual_bell: VisualBell::Flash,
+            visual_bell_duration: Duration::from_millis(150),
+            unfocused_dim: 0.0,
+            focus_follows_mouse: false,
+            option_as_alt: false,
+            paste_strip_controls: false,
+            copy_on_select: false,
+            notifications: false,
+            clipboard_write: false,
+            word_chars: "-_./~:@%+=?&#".to_owned(),
+            detect_paths: true,
+            open_paths_in_editor: false,
+            editor_command: None,
+            detachable: false,
+            restore_session: false,
+            confirm_close: true,
+            on_child_exit: ChildExit::Close,
+            shell: None,
+            keybindings: vec![],
+            env: vec![],
+            unset_env: vec![],
+            host_profiles: vec![],
+            profiles: vec![],
+            highlights: vec![],
+            title_template: "{title}".to_owned(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads a TOML config. Whatever the file leaves out keeps its default, and unknown keys
+    /// are rejected so typos don't go unnoticed
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let content =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        let file: ConfigFile = toml::from_str(&content).map_err(|e| {
+            ConfigError::Parse(path.to_owned(), e.to_string().trim_end().to_owned())
+        })?;
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        file.apply(Self::default(), dir)
+            .map_err(|e| ConfigError::Invalid(path.to_owned(), e))
+    }
+
+    pub fn host_profile(&self, host: &str) -> Option<&HostProfile> {
+        self.host_profiles
+            .iter()
+            .find(|profile| profile.host.eq_ignore_ascii_case(host))
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/emu-term`, falling back to `~/.config/emu-term`
+pub fn config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(base.join("emu-term"))
+}
+
+/// `config.toml` in the config dir, read when `--config` isn't given
+pub fn default_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("config.toml"))
+}
+
+/// A `theme.toml`, `theme.yaml` or `theme.yml` dropped in the config dir
+fn default_theme() -> Option<PathBuf> {
+    let dir = config_dir()?;
+    ["theme.toml", "theme.yaml", "theme.yml"]
+        .into_iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontConfig {
+    /// Family name, `None` uses whatever the system resolves as its monospace font
+    pub family: Option<String>,
+    pub size: f32,
+    /// Multiplier applied to the font size to get the height of a row
+    pub line_height: f32,
+}
+
+impl Default for FontConfig {
+    fn default() -> Self {
+        Self {
+            family: None,
+            size: 16.0,
+            line_height: 1.3,
+        }
+    }
+}
+
+/// iced wants font names to live forever. Each name is leaked once, the fonts switched between
+/// at runtime reuse it
+fn intern(name: &str) -> &'static str {
+    static NAMES: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut names = NAMES
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    match names.get(name) {
+        Some(interned) => interned,
+        None => {
+            let interned: &'static str = Box::leak(name.to_owned().into_boxed_str());
+            names.insert(interned);
+            interned
+        }
+    }
+}
+
+impl FontConfig {
+    pub fn font(&self) -> Font {
+        let family = match &self.family {
+            Some(name) => Family::Name(intern(name)),
+            None => Family::Monospace,
+        };
+
+        Font { family, ..MONO }
+    }
+
+    pub fn validate(&self) -> Result<(), FontError> {
+        if !self.size.is_finite() || self.size <= 0.0 {
+            return Err(FontError::InvalidSize(self.size));
+        }
+        if !self.line_height.is_finite() || self.line_height <= 0.0 {
+            return Err(FontError::InvalidLineHeight(self.line_height));
+        }
+
+        Ok(())
+    }
+
+    pub fn family_name(&self) -> &str {
+        self.family.as_deref().unwrap_or("monospace")
+    }
+}
+
+#[derive(Debug)]
+pub enum FontError {
+    InvalidSize(f32),
+    InvalidLineHeight(f32),
+    NotMonospace(String),
+}
+
+impl Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidSize(size) => write!(f, "invalid font size {}", size),
+            Self::InvalidLineHeight(lh) => write!(f, "invalid line height {}", lh),
+            Self::NotMonospace(family) => write!(f, "font family {:?} is not monospaced", family),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    /// What toml had to say, it points at the line and the key
+    Parse(PathBuf, String),
+    /// A value that parsed but makes no sense
+    Invalid(PathBuf, String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            Self::Parse(path, e) => write!(f, "failed to parse {}: {}", path.display(), e),
+            Self::Invalid(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+/// The config as it's written, everything optional
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    font: Option<FontFile>,
+    cursor: Option<CursorFile>,
+    theme: Option<PathBuf>,
+    colors: Option<AlacrittyColors>,
+    background_opacity: Option<f32>,
+    always_on_top: Option<bool>,
+    fullscreen: Option<bool>,
+    decorations: Option<bool>,
+    scrollbar: Option<bool>,
+    scrollback: Option<usize>,
+    padding: Option<PaddingFile>,
+    center: Option<bool>,
+    bell: Option<BellFile>,
+    unfocused_dim: Option<f32>,
+    focus_follows_mouse: Option<bool>,
+    option_as_alt: Option<bool>,
+    paste_strip_controls: Option<bool>,
+    copy_on_select: Option<bool>,
+    notifications: Option<bool>,
+    clipboard_write: Option<bool>,
+    word_chars: Option<String>,
+    detect_paths: Option<bool>,
+    open_paths_in_editor: Option<bool>,
+    editor_command: Option<Vec<String>>,
+    detachable: Option<bool>,
+    restore_session: Option<bool>,
+    confirm_close: Option<bool>,
+    on_child_exit: Option<ChildExit>,
+    shell: Option<ShellFile>,
+    #[serde(default)]
+    keybindings: Vec<KeyBindingFile>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    #[serde(default)]
+    unset_env: Vec<String>,
+    #[serde(default)]
+    hosts: Vec<HostFile>,
+    #[serde(default)]
+    profiles: Vec<ProfileFile>,
+    #[serde(default)]
+    highlights: Vec<HighlightFile>,
+    title_template: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FontFile {
+    family: Option<String>,
+    size: Option<f32>,
+    line_height: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CursorFile {
+    shape: Option<CursorShape>,
+    blinking: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PaddingFile {
+    x: Option<f32>,
+    y: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BellFile {
+    visual: Option<VisualBell>,
+    duration_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ShellFile {
+    program: String,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+/// `key = "ctrl+shift+t"` with either an `action` or the `chars` to send
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyBindingFile {
+    key: String,
+    action: Option<Action>,
+    chars: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HostFile {
+    host: String,
+    theme: Option<PathBuf>,
+    font: Option<FontFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileFile {
+    name: String,
+    command: Option<Vec<String>>,
+    working_directory: Option<PathBuf>,
+    theme: Option<PathBuf>,
+    font: Option<FontFile>,
+}
+
+/// `regex = "\\bERROR\\b"` with a `foreground`, `background` or `underline`, colors written
+/// like xterm takes them, and whatever actions a match sets off
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HighlightFile {
+    regex: String,
+    foreground: Option<String>,
+    background: Option<String>,
+    #[serde(default)]
+    underline: bool,
+    #[serde(default)]
+    bell: bool,
+    #[serde(default)]
+    notify: bool,
+    copy: Option<usize>,
+    command: Option<Vec<String>>,
+    #[serde(default)]
+    unfocused: bool,
+}
+
+impl ConfigFile {
+    /// Sets what the file has on top of `config`. Relative paths are taken from `dir`, where
+    /// the file is
+    fn apply(self, mut config: Config, dir: &Path) -> Result<Config, String> {
+        if let Some(font) = self.font {
+            config.font = font.apply(config.font)?;
+        }
+        if let Some(cursor) = self.cursor {
+            config.cursor.shape = cursor.shape.unwrap_or(config.cursor.shape);
+            config.cursor.blinking = cursor.blinking.unwrap_or(config.cursor.blinking);
+        }
+        if let Some(theme) = self.theme {
+            config.theme = Some(resolve_path(dir, theme));
+        }
+        if let Some(colors) = self.colors {
+            // checked here rather than whenever a window opens
+            Palette::default()
+                .with_colors(colors.clone())
+                .map_err(|e| e.to_string())?;
+            config.colors = Some(colors);
+        }
+        if let Some(opacity) = self.background_opacity {
+            config.background_opacity = fraction("background_opacity", opacity)?;
+        }
+        if let Some(padding) = self.padding {
+            config.padding.x = length("padding.x", padding.x.unwrap_or(config.padding.x))?;
+            config.padding.y = length("padding.y", padding.y.unwrap_or(config.padding.y))?;
+        }
+        if let Some(bell) = self.bell {
+            config.visual_bell = bell.visual.unwrap_or(config.visual_bell);
+            if let Some(ms) = bell.duration_ms {
+                config.visual_bell_duration = Duration::from_millis(ms);
+            }
+        }
+        if let Some(dim) = self.unfocused_dim {
+            config.unfocused_dim = fraction("unfocused_dim", dim)?;
+        }
+        if let Some(shell) = self.shell {
+            config.shell = Some([vec![shell.program], shell.args].concat());
+        }
+        for binding in self.keybindings {
+            config.keybindings.push(binding.parse()?);
+        }
+        config.env.extend(self.env);
+        config.unset_env.extend(self.unset_env);
+        for host in self.hosts {
+            let font = host.font.map(|font| font.apply(config.font.clone()));
+            config.host_profiles.push(HostProfile {
+                host: host.host,
+                look: Look {
+                    theme: host.theme.map(|theme| resolve_path(dir, theme)),
+                    font: font.transpose()?,
+                },
+            });
+        }
+        for profile in self.profiles {
+            if config.profile(&profile.name).is_some() {
+                return Err(format!("profile {:?} is defined twice", profile.name));
+            }
+            if profile.command.as_ref().is_some_and(Vec::is_empty) {
+                return Err(format!("profile {:?} has an empty command", profile.name));
+            }
+            let font = profile.font.map(|font| font.apply(config.font.clone()));
+            config.profiles.push(Profile {
+                name: profile.name,
+                command: profile.command,
+                working_directory: profile
+                    .working_directory
+                    .map(|working_directory| resolve_path(dir, working_directory)),
+                look: Look {
+                    theme: profile.theme.map(|theme| resolve_path(dir, theme)),
+                    font: font.transpose()?,
+                },
+            });
+        }
+        for highlight in self.highlights {
+            config.highlights.push(highlight.parse()?);
+        }
+        if self.editor_command.as_ref().is_some_and(Vec::is_empty) {
+            return Err("editor_command is empty".to_owned());
+        }
+        config.editor_command = self.editor_command.or(config.editor_command);
+
+        let set = |value: Option<bool>, field: &mut bool| *field = value.unwrap_or(*field);
+        set(self.scrollbar, &mut config.scrollbar);
+        set(self.center, &mut config.center);
+        set(self.focus_follows_mouse, &mut config.focus_follows_mouse);
+        set(self.option_as_alt, &mut config.option_as_alt);
+        set(self.paste_strip_controls, &mut config.paste_strip_controls);
+        set(self.copy_on_select, &mut config.copy_on_select);
+        set(self.notifications, &mut config.notifications);
+        set(self.clipboard_write, &mut config.clipboard_write);
+        set(self.detect_paths, &mut config.detect_paths);
+        set(self.open_paths_in_editor, &mut config.open_paths_in_editor);
+        set(self.detachable, &mut config.detachable);
+        set(self.restore_session, &mut config.restore_session);
+        set(self.confirm_close, &mut config.confirm_close);
+        set(self.always_on_top, &mut config.always_on_top);
+        set(self.fullscreen, &mut config.fullscreen);
+        set(self.decorations, &mut config.decorations);
+        config.scrollback = self.scrollback.unwrap_or(config.scrollback);
+        config.word_chars = self.word_chars.unwrap_or(config.word_chars);
+        config.on_child_exit = self.on_child_exit.unwrap_or(config.on_child_exit);
+        config.title_template = self.title_template.unwrap_or(config.title_template);
+
+        Ok(config)
+    }
+}
+
+impl FontFile {
+    fn apply(self, font: FontConfig) -> Result<FontConfig, String> {
+        let font = FontConfig {
+            family: self.family.or(font.family),
+            size: self.size.unwrap_or(font.size),
+            line_height: self.line_height.unwrap_or(font.line_height),
+        };
+        font.validate().map_err(|e| e.to_string())?;
+        Ok(font)
+    }
+}
+
+impl HighlightFile {
+    fn parse(self) -> Result<HighlightRule, String> {
+        let regex = Regex::new(&self.regex)
+            .map_err(|e| format!("bad highlight regex {:?}: {}", self.regex, e))?;
+        let color = |color: Option<String>| {
+            let parse = |color: String| {
+                parse_color_spec(&color)
+                    .ok_or_else(|| format!("bad color {:?} in highlight {:?}", color, self.regex))
+            };
+            color.map(parse).transpose()
+        };
+
+        let style = HighlightStyle {
+            foreground: color(self.foreground)?,
+            background: color(self.background)?,
+            underline: self.underline,
+        };
+        let actions = TriggerActions {
+            bell: self.bell,
+            notify: self.notify,
+            copy: self.copy,
+            command: self.command,
+            unfocused: self.unfocused,
+        };
+        if style == HighlightStyle::default() && !actions.any() {
+            return Err(format!(
+                "highlight {:?} needs a foreground, background, underline or an action",
+                self.regex
+            ));
+        }
+        if actions
+            .copy
+            .is_some_and(|group| group >= regex.captures_len())
+        {
+            return Err(format!(
+                "highlight {:?} has no such group to copy",
+                self.regex
+            ));
+        }
+        if actions.command.as_ref().is_some_and(Vec::is_empty) {
+            return Err(format!("highlight {:?} has an empty command", self.regex));
+        }
+        Ok(HighlightRule {
+            regex,
+            style,
+            actions,
+        })
+    }
+}
+
+impl KeyBindingFile {
+    /// Splits `ctrl+shift+t` into the modifiers and the key, the last `+` is the key itself
+    /// in `ctrl++`
+    fn parse(self) -> Result<KeyBinding, String> {
+        let (mods, key) = match self.key.strip_suffix("++") {
+            Some(mods) => (mods, "+"),
+            None => self.key.rsplit_once('+').unwrap_or(("", &self.key)),
+        };
+        if key.is_empty() {
+            return Err(format!("no key in keybinding {:?}", self.key));
+        }
+
+        let mut modifiers = Modifiers::empty();
+        for name in mods.split('+').filter(|name| !name.is_empty()) {
+            modifiers |= match name.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "shift" => Modifiers::SHIFT,
+                "alt" | "option" => Modifiers::ALT,
+                "super" | "cmd" | "logo" => Modifiers::LOGO,
+                _ => {
+                    return Err(format!(
+                        "unknown modifier {:?} in keybinding {:?}",
+                        name, self.key
+                    ))
+                }
+            };
+        }
+
+        let action = match (self.action, self.chars) {
+            (Some(action), None) => action,
+            (None, Some(chars)) => Action::Chars(chars),
+            _ => {
+                return Err(format!(
+                    "keybinding {:?} needs either an action or chars",
+                    self.key
+                ))
+            }
+        };
+
+        Ok(KeyBinding {
+            mods: modifiers,
+            key: key.to_lowercase(),
+            action,
+        })
+    }
+}
+
+fn fraction(name: &str, value: f32) -> Result<f32, String> {
+    match (0.0..=1.0).contains(&value) {
+        true => Ok(value),
+        false => Err(format!("{} must be between 0 and 1, got {}", name, value)),
+    }
+}
+
+fn length(name: &str, value: f32) -> Result<f32, String> {
+    match value.is_finite() && value >= 0.0 {
+        true => Ok(value),
+        false => Err(format!("{} can't be negative, got {}", name, value)),
+    }
+}
+
+/// `~/` is the home directory and anything else relative starts from `dir`
+fn resolve_path(dir: &Path, path: PathBuf) -> PathBuf {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    match (path.strip_prefix("~"), home) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => dir.join(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> Result<Config, String> {
+        let file: ConfigFile = toml::from_str(toml).map_err(|e| e.to_string())?;
+        file.apply(Config::default(), Path::new("/etc/emu-term"))
+    }
+
+    #[test]
+    fn empty_file_keeps_the_defaults() {
+        let config = parse("").unwrap();
+        let default = Config::default();
+        assert_eq!(config.font, default.font);
+        assert_eq!(config.scrollback, default.scrollback);
+        assert!(!config.copy_on_select);
+    }
+
+    #[test]
+    fn values_override_the_defaults() {
+        let config = parse(
+            r#"
+            scrollback = 500
+            copy_on_select = true
+            focus_follows_mouse = true
+            theme = "themes/dark.toml"
+
+            [font]
+            family = "Iosevka"
+            size = 13.5
+
+            [cursor]
+            shape = "bar"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.scrollback, 500);
+        assert!(config.copy_on_select);
+        assert!(config.focus_follows_mouse);
+        assert_eq!(
+            config.theme,
+            Some(PathBuf::from("/etc/emu-term/themes/dark.toml"))
+        );
+        assert_eq!(config.font.family.as_deref(), Some("Iosevka"));
+        assert_eq!(config.font.size, 13.5);
+        assert_eq!(config.font.line_height, FontConfig::default().line_height);
+        assert_eq!(config.cursor.shape, CursorShape::Bar);
+    }
+
+    #[test]
+    fn keybindings() {
+        let config = parse(
+            r#"
+            [[keybindings]]
+            key = "Ctrl+Shift+T"
+            action = "new_tab"
+
+            [[keybindings]]
+            key = "alt++"
+            chars = "plus"
+            "#,
+        )
+        .unwrap();
+        let bindings = &config.keybindings[config.keybindings.len() - 2..];
+        assert_eq!(
+            bindings[0],
+            KeyBinding {
+                mods: Modifiers::CTRL | Modifiers::SHIFT,
+                key: "t".to_owned(),
+                action: Action::NewTab,
+            }
+        );
+        assert_eq!(bindings[1].mods, Modifiers::ALT);
+        assert_eq!(bindings[1].key, "+");
+        assert_eq!(bindings[1].action, Action::Chars("plus".to_owned()));
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let bad = [
+            "unknown = 1",
+            "[font]\nsize = -1.0",
+            "unfocused_dim = 2.0",
+            "[[keybindings]]\nkey = \"hyper+a\"\naction = \"copy\"",
+            "[[keybindings]]\nkey = \"a\"",
+            "[[highlights]]\nregex = \"(\"",
+            "editor_command = []",
+        ];
+        for toml in bad {
+            assert!(parse(toml).is_err(), "{:?}", toml);
+        }
+    }
+}
@@ -0,0 +1,410 @@
+//! Detachable sessions. With `detachable` set, a tab hands a copy of its pty to the daemon,
+//! `emu-term --daemon`, which holds on to it while no window has it. Closing the tab or the
+//! window, or the emulator going down, then only detaches: the shell keeps running and the
+//! daemon reads what it prints, keeping the tail of it for `--attach` to show in a new tab
+//!
+//! Requests are a line on the daemon's socket, a pty goes along with one as `SCM_RIGHTS`. The
+//! connection a session was kept or attached over stays open for as long as the tab has it.
+//! A tab keeping its session holds a shared lock on `clients.lock` until it's answered, the
+//! daemon only exits once it gets that lock to itself and finds it has no session still
+
+use crate::{links, process};
+use nix::errno::Errno;
+use nix::fcntl::{Flock, FlockArg};
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::socket::{recvmsg, sendmsg, ControlMessage, ControlMessageOwned, MsgFlags};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Output of a detached session kept for the window that attaches next, older output is
+/// dropped
+const TAIL_BYTES: usize = 256 << 10;
+/// Requests and the answers to them are a line of at most this much
+const MAX_LINE: usize = 4096;
+/// The daemon started for the first detachable tab has this long to come up
+const START_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A session the daemon holds
+#[derive(Debug)]
+struct Held {
+    master: File,
+    /// What the tab ran, to tell the sessions apart
+    title: String,
+    /// A tab has the pty, the daemon leaves its output alone
+    attached: Mutex<bool>,
+    /// Signalled when the tab lets go of the pty
+    detached: Condvar,
+    /// Output read while detached
+    tail: Mutex<VecDeque<u8>>,
+}
+
+#[derive(Debug)]
+struct Daemon {
+    sessions: Mutex<BTreeMap<u32, Arc<Held>>>,
+    next_id: AtomicU32,
+    path: PathBuf,
+}
+
+/// Where the daemon's socket and lock are, in the runtime directory when there's one. Only the
+/// user can get into it, so the socket is never open to anyone else, not even between being
+/// bound and having its permissions set
+fn runtime_dir() -> io::Result<PathBuf> {
+    let uid = nix::unistd::getuid();
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("emu-term"),
+        None => std::env::temp_dir().join(format!("emu-term-{}", uid)),
+    };
+    match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+        _ => {}
+    }
+
+    // in a shared directory like /tmp, someone else could have made it first
+    let metadata = std::fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid.as_raw() || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is open to other users", dir.display()),
+        ));
+    }
+    Ok(dir)
+}
+
+/// Where the daemon listens
+pub fn socket_path() -> io::Result<PathBuf> {
+    Ok(runtime_dir()?.join("daemon.sock"))
+}
+
+/// Locks `clients.lock`, shared for a tab keeping its session and exclusive for the daemon
+/// about to exit. Blocks until it gets it
+fn lock_clients(arg: FlockArg) -> io::Result<Flock<File>> {
+    let file = File::create(runtime_dir()?.join("clients.lock"))?;
+    Flock::lock(file, arg).map_err(|(_, e)| io::Error::from(e))
+}
+
+/// Runs the daemon until the last session it holds ends. It holds the lock next to its socket
+/// all along, a second one started meanwhile finds it taken and leaves the socket alone
+pub fn run() -> io::Result<()> {
+    let dir = runtime_dir()?;
+    let lock = File::create(dir.join("daemon.lock"))?;
+    let _lock = Flock::lock(lock, FlockArg::LockExclusiveNonblock).map_err(|(_, e)| match e {
+        Errno::EWOULDBLOCK => io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("a daemon is already running in {}", dir.display()),
+        ),
+        e => io::Error::from(e),
+    })?;
+
+    let path = socket_path()?;
+    // left behind by a daemon that didn't get to clean up
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    // out of the session of whatever started it, its hangup isn't for the daemon
+    let _ = nix::unistd::setsid();
+
+    let daemon = Arc::new(Daemon {
+        sessions: Mutex::default(),
+        next_id: AtomicU32::new(1),
+        path,
+    });
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::warn!("failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        let daemon = daemon.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = daemon.serve(stream) {
+                tracing::debug!("a request failed: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+impl Daemon {
+    fn serve(self: &Arc<Self>, mut stream: UnixStream) -> io::Result<()> {
+        let (request, _, fd) = receive(&stream)?;
+        let (verb, arg) = request.split_once(' ').unwrap_or((&request, ""));
+        match (verb, fd) {
+            ("keep", Some(fd)) => {
+                let held = Arc::new(Held {
+                    master: File::from(fd),
+                    title: arg.to_owned(),
+                    attached: Mutex::new(true),
+                    detached: Condvar::new(),
+                    tail: Mutex::default(),
+                });
+                let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                self.sessions.lock().unwrap().insert(id, held.clone());
+                let daemon = self.clone();
+                let followed = held.clone();
+                std::thread::spawn(move || daemon.follow(id, &followed));
+
+                let answered = stream.write_all(format!("ok {}\n", id).as_bytes());
+                self.hold(stream, &held, answered)
+            }
+            ("attach", _) => {
+                let sessions = self.sessions.lock().unwrap();
+                let held = arg.parse().ok().and_then(|id| sessions.get(&id).cloned());
+                drop(sessions);
+                let Some(held) = held else {
+                    return stream.write_all(b"error no such session\n");
+                };
+                if std::mem::replace(&mut *held.attached.lock().unwrap(), true) {
+                    return stream.write_all(b"error the session is attached to another tab\n");
+                }
+
+                // taking the tail waits for a read in progress, and none start after it
+                let tail = Vec::from(std::mem::take(&mut *held.tail.lock().unwrap()));
+                let header = format!("ok {}\n", tail.len());
+                let answered = send(&stream, header.as_bytes(), Some(held.master.as_fd()))
+                    .and_then(|()| stream.write_all(&tail));
+                self.hold(stream, &held, answered)
+            }
+            ("list", _) => {
+                let sessions = self.sessions.lock().unwrap().clone();
+                let mut list = String::new();
+                for (id, held) in sessions {
+                    let state = match *held.attached.lock().unwrap() {
+                        true => "attached",
+                        false => "detached",
+                    };
+                    let running = process::foreground(&held.master).map_or(String::new(), |fg| {
+                        let cwd = fg.cwd.as_deref().map(process::tilde);
+                        format!("{} in {}", fg.name, cwd.unwrap_or_default())
+                    });
+                    list.push_str(&format!("{}\t{}\t{}\t{}\n", id, state, held.title, running));
+                }
+                stream.write_all(list.as_bytes())
+            }
+            _ => stream.write_all(b"error unknown request\n"),
+        }
+    }
+
+    /// Leaves the session to the tab until it closes the connection
+    fn hold(
+        &self,
+        mut stream: UnixStream,
+        held: &Held,
+        answered: io::Result<()>,
+    ) -> io::Result<()> {
+        if answered.is_ok() {
+            let mut buf = [0u8; 64];
+            while matches!(stream.read(&mut buf), Ok(n) if n > 0) {}
+        }
+        *held.attached.lock().unwrap() = false;
+        held.detached.notify_all();
+        answered
+    }
+
+    /// Reads the output of session `id` while it's detached, until its shell is gone
+    fn follow(&self, id: u32, held: &Held) {
+        let mut buf = [0u8; 4096];
+        loop {
+            let attached = held.attached.lock().unwrap();
+            drop(
+                held.detached
+                    .wait_while(attached, |attached| *attached)
+                    .unwrap(),
+            );
+            let mut fds = [PollFd::new(held.master.as_fd(), PollFlags::POLLIN)];
+            match poll(&mut fds, PollTimeout::NONE) {
+                Ok(0) | Err(Errno::EINTR) => continue,
+                Ok(_) => {}
+                Err(_) => break,
+            }
+
+            let mut tail = held.tail.lock().unwrap();
+            // a tab may have attached while this waited, it reads the output now
+            if *held.attached.lock().unwrap() {
+                continue;
+            }
+            match (&held.master).read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    tail.extend(&buf[..n]);
+                    let dropped = tail.len().saturating_sub(TAIL_BYTES);
+                    tail.drain(..dropped);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                // EIO once the shell and everything it started are gone
+                Err(_) => break,
+            }
+        }
+
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.remove(&id);
+        if sessions.is_empty() {
+            drop(sessions);
+            self.exit_if_idle();
+        }
+    }
+
+    /// Exits unless a session came in meanwhile. A tab that's connected keeps its lock until
+    /// its session is in, one that isn't yet finds the socket gone and starts a new daemon
+    fn exit_if_idle(&self) {
+        let _clients = match lock_clients(FlockArg::LockExclusive) {
+            Ok(lock) => lock,
+            Err(e) => {
+                tracing::warn!("failed to lock out new sessions, staying up: {}", e);
+                return;
+            }
+        };
+        if self.sessions.lock().unwrap().is_empty() {
+            let _ = std::fs::remove_file(&self.path);
+            std::process::exit(0);
+        }
+    }
+}
+
+/// Hands a copy of `master` to the daemon, starting it if it isn't running. The session stays
+/// attached to the tab for as long as the connection returned is open
+pub fn keep(master: &File, title: &str) -> io::Result<UnixStream> {
+    // the daemon doesn't exit under a session being kept
+    let _clients = lock_clients(FlockArg::LockShared)?;
+    let stream = connect()?;
+    let request = format!("keep {}\n", title.replace('\n', " "));
+    send(&stream, request.as_bytes(), Some(master.as_fd()))?;
+    let (answer, _, _) = receive(&stream)?;
+    ok(&answer)?;
+    Ok(stream)
+}
+
+/// Takes the pty of detached session `session` for a tab, along with what it printed while it
+/// was detached. It stays attached for as long as the connection returned is open
+pub fn attach(session: u32) -> io::Result<(File, Vec<u8>, UnixStream)> {
+    let stream = UnixStream::connect(socket_path()?)?;
+    send(&stream, format!("attach {}\n", session).as_bytes(), None)?;
+    let (answer, mut tail, fd) = receive(&stream)?;
+    let len: usize = ok(&answer)?
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad answer from the daemon"))?;
+    let master =
+        fd.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "the daemon sent no pty"))?;
+
+    let read = tail.len();
+    tail.resize(len.max(read), 0);
+    (&stream).read_exact(&mut tail[read..])?;
+    Ok((File::from(master), tail, stream))
+}
+
+/// The sessions the daemon holds, a line each, for `--sessions`. Nothing when it isn't
+/// running
+pub fn list() -> io::Result<String> {
+    let mut stream = match UnixStream::connect(socket_path()?) {
+        Ok(stream) => stream,
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(String::new())
+        }
+        Err(e) => return Err(e),
+    };
+    stream.write_all(b"list\n")?;
+    let mut list = String::new();
+    stream.read_to_string(&mut list)?;
+    Ok(list)
+}
+
+/// Connects to the daemon, starting it first if it isn't running
+fn connect() -> io::Result<UnixStream> {
+    let path = socket_path()?;
+    if let Ok(stream) = UnixStream::connect(&path) {
+        return Ok(stream);
+    }
+
+    let mut daemon = Command::new(std::env::current_exe()?);
+    daemon
+        .arg("--daemon")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    links::spawn(&mut daemon)?;
+    let started = Instant::now();
+    loop {
+        std::thread::sleep(Duration::from_millis(20));
+        match UnixStream::connect(&path) {
+            Ok(stream) => return Ok(stream),
+            Err(e) if started.elapsed() > START_TIMEOUT => return Err(e),
+            Err(_) => {}
+        }
+    }
+}
+
+/// The rest of an `ok` answer, an `error` one as the error
+fn ok(answer: &str) -> io::Result<&str> {
+    match answer.split_once(' ').unwrap_or((answer, "")) {
+        ("ok", rest) => Ok(rest),
+        (_, reason) => Err(io::Error::other(reason.to_owned())),
+    }
+}
+
+/// Writes `bytes`, with `fd` along with the first of them
+fn send(stream: &UnixStream, bytes: &[u8], fd: Option<BorrowedFd<'_>>) -> io::Result<()> {
+    let fds = fd.map(|fd| [fd.as_raw_fd()]);
+    let messages: Vec<ControlMessage<'_>> = fds
+        .iter()
+        .map(|fds| ControlMessage::ScmRights(fds))
+        .collect();
+    let iov = [IoSlice::new(bytes)];
+    let sent = sendmsg::<()>(stream.as_raw_fd(), &iov, &messages, MsgFlags::empty(), None)?;
+    (&*stream).write_all(&bytes[sent..])
+}
+
+/// Reads a line and the fd that came along with it, if any. What was read past the line is
+/// returned too
+fn receive(stream: &UnixStream) -> io::Result<(String, Vec<u8>, Option<OwnedFd>)> {
+    let mut buf = vec![0u8; MAX_LINE];
+    let mut space = nix::cmsg_space!([RawFd; 1]);
+    let (n, fd) = {
+        let mut iov = [IoSliceMut::new(&mut buf)];
+        let message = recvmsg::<()>(
+            stream.as_raw_fd(),
+            &mut iov,
+            Some(&mut space),
+            MsgFlags::MSG_CMSG_CLOEXEC,
+        )?;
+        let fd = message.cmsgs()?.find_map(|message| match message {
+            ControlMessageOwned::ScmRights(fds) => fds.first().copied(),
+            _ => None,
+        });
+        (message.bytes, fd)
+    };
+    // received with the line, so it's ours to close
+    let fd = fd.map(|fd| unsafe { OwnedFd::from_raw_fd(fd) });
+
+    let mut received = buf[..n].to_vec();
+    let mut n = n;
+    loop {
+        if let Some(end) = received.iter().position(|&b| b == b'\n') {
+            let rest = received.split_off(end + 1);
+            received.pop();
+            return Ok((String::from_utf8_lossy(&received).into_owned(), rest, fd));
+        }
+        if n == 0 || received.len() > MAX_LINE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "expected a line",
+            ));
+        }
+        n = (&*stream).read(&mut buf)?;
+        received.extend_from_slice(&buf[..n]);
+    }
+}
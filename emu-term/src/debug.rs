@@ -0,0 +1,83 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Rates are averaged over this much time
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Counters behind the debug overlay. They're cheap enough to keep updated all the time, only
+/// the frame clock stops while the overlay is hidden
+#[derive(Debug, Default)]
+pub struct DebugStats {
+    frames: VecDeque<Instant>,
+    reads: VecDeque<(Instant, usize)>,
+    /// Cells of the lines invalidated since the last frame
+    cells_pending: usize,
+    cells_last_frame: usize,
+    unknown_sequences: usize,
+}
+
+impl DebugStats {
+    pub fn frame(&mut self, now: Instant) {
+        self.frames.push_back(now);
+        prune(&mut self.frames, now, |at| *at);
+        self.cells_last_frame = std::mem::take(&mut self.cells_pending);
+    }
+
+    pub fn read(&mut self, bytes: usize) {
+        let now = Instant::now();
+        self.reads.push_back((now, bytes));
+        prune(&mut self.reads, now, |(at, _)| *at);
+    }
+
+    pub fn redrawn(&mut self, cells: usize) {
+        self.cells_pending += cells;
+    }
+
+    pub fn unknown_sequence(&mut self) {
+        self.unknown_sequences += 1;
+    }
+
+    pub fn fps(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Bytes read from the pty over the last second
+    pub fn throughput(&self) -> usize {
+        self.reads.iter().map(|(_, bytes)| bytes).sum()
+    }
+
+    pub fn cells_last_frame(&self) -> usize {
+        self.cells_last_frame
+    }
+
+    pub fn unknown_sequences(&self) -> usize {
+        self.unknown_sequences
+    }
+}
+
+/// `1536` as `1.5 KiB`
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = bytes as f32;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn prune<T>(samples: &mut VecDeque<T>, now: Instant, at: impl Fn(&T) -> Instant) {
+    while samples
+        .front()
+        .is_some_and(|sample| now.duration_since(at(sample)) > WINDOW)
+    {
+        samples.pop_front();
+    }
+}
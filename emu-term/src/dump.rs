@@ -0,0 +1,182 @@
+use crate::palette::{Palette, Rgb};
+use emu_core::grid::{Cell, Grid, Style, Styles, TermColor};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Every row of `grid` as text. Wrapped rows are joined back into their logical line and the
+/// blanks at the end of each line are dropped. With `colors` each change of style is written
+/// as an SGR sequence, for `less -R` or `cat` to show it as it was
+pub fn text(grid: &Grid, colors: bool) -> String {
+    let mut text = String::new();
+    let mut line: Vec<(char, Style)> = vec![];
+    for row in grid.iter() {
+        line.extend(row.cells.iter().map(|cell| (cell.c, grid.style(cell))));
+        if row.wrapped {
+            continue;
+        }
+
+        while line
+            .last()
+            .is_some_and(|&(c, style)| is_blank(c, style, colors))
+        {
+            line.pop();
+        }
+        match colors {
+            true => push_styled(&mut text, &line),
+            false => text.extend(line.iter().map(|&(c, _)| c)),
+        }
+        text.push('\n');
+        line.clear();
+    }
+
+    // the screen below the last line written is all blank rows
+    let len = text.trim_end_matches('\n').len();
+    text.truncate(len);
+    text.push('\n');
+    text
+}
+
+/// Writes `text` to `path`, only readable by the user since the scrollback may have anything
+pub fn save(path: &Path, text: &str) -> io::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(text.as_bytes())
+}
+
+/// Puts `text` in a temporary file and returns the argv of a shell running `command` with it as
+/// standard input. The file is removed as soon as the shell opens it
+pub fn pipe(text: &str, command: &str) -> io::Result<Vec<String>> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+    let name = format!("emu-term-{}-{}.txt", std::process::id(), nanos);
+    let path: PathBuf = std::env::temp_dir().join(name);
+    save(&path, text)?;
+
+    Ok(vec![
+        "/bin/sh".into(),
+        "-c".into(),
+        format!("exec < \"$1\"; rm -f \"$1\"; {}", command),
+        "sh".into(),
+        path.to_string_lossy().into_owned(),
+    ])
+}
+
+/// A blank that's only there because the line was shorter, colored ones are kept along with
+/// the colors
+fn is_blank(c: char, style: Style, colors: bool) -> bool {
+    c == ' ' && (!colors || (style.bg_color == TermColor::Background && !style.attrs.reverse))
+}
+
+fn push_styled(text: &mut String, cells: &[(char, Style)]) {
+    let mut style = None;
+    for &(c, current) in cells {
+        if style != Some(current) {
+            text.push_str(&sgr(current));
+            style = Some(current);
+        }
+        text.push(c);
+    }
+    if style.is_some() {
+        text.push_str("\x1b[0m");
+    }
+}
+
+/// Resets the style and sets `style`
+fn sgr(style: Style) -> String {
+    let mut params = vec!["0".to_owned()];
+    if style.attrs.blink {
+        params.push("5".to_owned());
+    }
+    if style.attrs.reverse {
+        params.push("7".to_owned());
+    }
+    params.extend(color_param(style.fg_color, 30));
+    params.extend(color_param(style.bg_color, 40));
+    format!("\x1b[{}m", params.join(";"))
+}
+
+/// `base` is 30 for the foreground and 40 for the background, the defaults need nothing after
+/// a reset
+fn color_param(color: TermColor, base: u8) -> Option<String> {
+    match color {
+        TermColor::Foreground | TermColor::Background => None,
+        TermColor::Ansi(n @ 0..=7) => Some((base + n).to_string()),
+        TermColor::Ansi(n @ 8..=15) => Some((base + 60 + n - 8).to_string()),
+        TermColor::Ansi(n) => Some(format!("{};5;{}", base + 8, n)),
+        TermColor::Rgb(r, g, b) => Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+    }
+}
+
+/// `lines` of cells as a standalone `<pre>` for pasting into documents, with the colors of
+/// `palette` inlined. Cells in the default colors are left to the `<pre>` itself
+pub fn html(lines: &[&[Cell]], styles: &Styles, palette: &Palette, reverse_video: bool) -> String {
+    let (fg, bg) = (palette.foreground, palette.background);
+    let (fg, bg) = if reverse_video { (bg, fg) } else { (fg, bg) };
+    let mut html = format!(
+        "<pre style=\"color: {}; background-color: {}; padding: 8px; font-family: monospace\">",
+        format_rgb(fg),
+        format_rgb(bg),
+    );
+
+    for (i, line) in lines.iter().enumerate() {
+        let end = line
+            .iter()
+            .rposition(|cell| !is_blank(cell.c, styles.get(cell.style), true));
+        let cells: Vec<((Rgb, Rgb), char)> = line[..end.map_or(0, |end| end + 1)]
+            .iter()
+            .map(|cell| {
+                (
+                    colors(styles.get(cell.style), palette, reverse_video),
+                    cell.c,
+                )
+            })
+            .collect();
+        for run in cells.chunk_by(|a, b| a.0 == b.0) {
+            let text = escape_html(&run.iter().map(|(_, c)| c).collect::<String>());
+            let (run_fg, run_bg) = run[0].0;
+            if (run_fg, run_bg) == (fg, bg) {
+                html.push_str(&text);
+                continue;
+            }
+            html.push_str(&format!(
+                "<span style=\"color: {}; background-color: {}\">{}</span>",
+                format_rgb(run_fg),
+                format_rgb(run_bg),
+                text
+            ));
+        }
+        if i + 1 < lines.len() {
+            html.push('\n');
+        }
+    }
+
+    html.push_str("</pre>\n");
+    html
+}
+
+/// Colors a cell in `style` is drawn with, after SGR 7 and DECSCNM
+fn colors(style: Style, palette: &Palette, reverse_video: bool) -> (Rgb, Rgb) {
+    let (fg, bg) = (palette.rgb(style.fg_color), palette.rgb(style.bg_color));
+    match style.attrs.reverse != reverse_video {
+        true => (bg, fg),
+        false => (fg, bg),
+    }
+}
+
+fn format_rgb((r, g, b): Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
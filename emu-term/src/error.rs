@@ -0,0 +1,55 @@
+use crate::config::FontError;
+use crate::replay::LoadError;
+use crate::writer::WriteError;
+use std::fmt::Display;
+use std::io;
+use std::path::PathBuf;
+
+/// What can go wrong while running a session. These end up in a notice on top of the window
+/// rather than on standard error, which nobody sees when started from a launcher
+#[derive(Debug)]
+pub enum EmuError {
+    /// The program couldn't be started, most likely it doesn't exist
+    Spawn(PathBuf, io::Error),
+    /// No pty could be allocated
+    Pty(io::Error),
+    /// A socket, a serial port or the standard input couldn't be opened, named like the session
+    Open(String, io::Error),
+    /// Reading what the session prints failed halfway through
+    Read(io::Error),
+    /// The recording given to `--play` couldn't be read
+    Load(LoadError),
+    Record(PathBuf, io::Error),
+    /// Typed input was refused, see `PtyWriter::send`
+    Write(WriteError),
+    /// A paste found no text on the clipboard
+    Clipboard,
+    /// The default font can't lay out a grid, there's nothing to fall back to
+    Font(FontError),
+}
+
+impl Display for EmuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmuError::Spawn(program, e) => {
+                write!(f, "failed to start {}: {}", program.display(), e)
+            }
+            EmuError::Pty(e) => write!(f, "failed to allocate a pty: {}", e),
+            EmuError::Open(name, e) => write!(f, "failed to open {}: {}", name, e),
+            EmuError::Read(e) => write!(f, "failed to read from the session: {}", e),
+            EmuError::Load(e) => e.fmt(f),
+            EmuError::Record(path, e) => write!(f, "failed to record to {}: {}", path.display(), e),
+            EmuError::Write(e) => write!(f, "dropped the input: {}", e),
+            EmuError::Clipboard => write!(f, "nothing to paste, the clipboard holds no text"),
+            EmuError::Font(e) => write!(f, "no usable default font: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for EmuError {}
+
+impl From<LoadError> for EmuError {
+    fn from(e: LoadError) -> Self {
+        EmuError::Load(e)
+    }
+}
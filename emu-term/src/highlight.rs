@@ -0,0 +1,199 @@
+//! Highlight rules from the config, regexes whose matches are drawn in colors of their own
+//! and may set off actions. Only the rows written to are matched again, along with the rest of
+//! their logical line, so a log scrolling by costs the lines that come in and nothing more
+
+use crate::config::HighlightRule;
+use crate::search::{self, Match};
+use emu_core::grid::GridRow;
+use emu_core::Terminal as Emulator;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::process::Command;
+
+/// Rows of a logical line matched on either side of a row written to, a line wrapped over
+/// thousands of rows isn't matched whole on every write
+const MAX_LINE_ROWS: usize = 100;
+
+/// A match on a grid row, inclusive 1-based columns
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Highlight {
+    pub left: usize,
+    pub right: usize,
+    /// Index of the rule in the config
+    pub rule: usize,
+}
+
+/// A match of a rule with actions that wasn't there before the last update
+#[derive(Debug, Clone)]
+pub struct Fired {
+    pub rule: usize,
+    /// The match and then its capture groups, empty for the ones that didn't take part
+    pub groups: Vec<String>,
+}
+
+impl Fired {
+    /// `command` of the rule, with `{0}` in its arguments replaced by the match and `{1}` and
+    /// on by its groups
+    pub fn command(&self, command: &[String]) -> Option<Command> {
+        let (program, args) = command.split_first()?;
+        let mut command = Command::new(program);
+        command.args(args.iter().map(|arg| self.expand(arg)));
+        Some(command)
+    }
+
+    fn expand(&self, arg: &str) -> String {
+        let mut expanded = String::new();
+        let mut rest = arg;
+        while let Some(open) = rest.find('{') {
+            expanded.push_str(&rest[..open]);
+            rest = &rest[open + 1..];
+            let number = rest.split_once('}').map_or("", |(number, _)| number);
+            match number
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| self.groups.get(n))
+            {
+                Some(group) => {
+                    expanded.push_str(group);
+                    rest = &rest[number.len() + 1..];
+                }
+                None => expanded.push('{'),
+            }
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+}
+
+/// Matches of the rules on the primary screen and its scrollback. The alternate screen is
+/// left alone, what's there is redrawn by the application rather than printed
+#[derive(Debug, Default)]
+pub struct Highlights {
+    /// The matches on each grid row
+    rows: Vec<Vec<Highlight>>,
+    /// Every row is matched again on the next update
+    stale: bool,
+    /// Taken by the tab once the output is in
+    fired: Vec<Fired>,
+}
+
+impl Highlights {
+    /// Matches `rules` on the logical lines of the rows in `damage`, or on the whole grid
+    /// after a `reset`. Returns the rows matched again, which the damage may not cover whole
+    pub fn update(
+        &mut self,
+        state: &Emulator,
+        rules: &[HighlightRule],
+        damage: &BTreeSet<usize>,
+    ) -> Vec<usize> {
+        let mut matched = vec![];
+        if rules.is_empty() {
+            self.rows.clear();
+            return matched;
+        }
+        if state.is_alt_screen() {
+            return matched;
+        }
+
+        let grid = state.grid();
+        self.rows.resize_with(grid.len(), Vec::new);
+        // what's already on the grid doesn't set anything off
+        if std::mem::take(&mut self.stale) {
+            self.rows.iter_mut().for_each(Vec::clear);
+            for (i, rule) in rules.iter().enumerate() {
+                let matches = search::find_lines(grid.iter(), 1, &rule.regex);
+                self.add(&matches, i, state.cols());
+            }
+            return (1..=grid.len()).collect();
+        }
+
+        for &y in damage {
+            // the rest of the line was matched along with an earlier row
+            if matched.last().is_some_and(|&last| y <= last) || y > grid.len() {
+                continue;
+            }
+            let (first, last) = grid.logical_line(y);
+            let first = first.max(y.saturating_sub(MAX_LINE_ROWS));
+            let last = last.min(y + MAX_LINE_ROWS);
+            matched.extend(first..=last);
+
+            let rows = grid.rows(first - 1..last);
+            let before: Vec<Vec<Highlight>> = self.rows[first - 1..last]
+                .iter_mut()
+                .map(std::mem::take)
+                .collect();
+            for (i, rule) in rules.iter().enumerate() {
+                let borrowed = rows.iter().map(|row| Cow::Borrowed(row.as_ref()));
+                let matches = search::find_lines(borrowed, first, &rule.regex);
+                self.add(&matches, i, state.cols());
+                if !rule.actions.any() {
+                    continue;
+                }
+
+                let new = matches.iter().filter(|found| {
+                    let before = &before[found.start.row - first];
+                    !before
+                        .iter()
+                        .any(|h| h.rule == i && h.left == found.start.col)
+                });
+                for found in new {
+                    let text = text(&rows, first, found, state.cols());
+                    let groups = match rule.regex.captures(&text) {
+                        Some(captures) => captures
+                            .iter()
+                            .map(|group| group.map_or("", |group| group.as_str()).to_owned())
+                            .collect(),
+                        None => vec![text.clone()],
+                    };
+                    self.fired.push(Fired { rule: i, groups });
+                }
+            }
+        }
+        matched
+    }
+
+    fn add(&mut self, matches: &[Match], rule: usize, cols: usize) {
+        for found in matches {
+            for row in found.start.row..=found.end.row {
+                let columns = found.columns(row, cols);
+                if let (Some(ranges), Some((left, right))) = (self.rows.get_mut(row - 1), columns) {
+                    ranges.push(Highlight { left, right, rule });
+                }
+            }
+        }
+    }
+
+    /// Forgets every match, for when the rules changed or a resize rewrapped the lines
+    pub fn reset(&mut self) {
+        self.rows.clear();
+        self.stale = true;
+    }
+
+    /// Moves the matches up along with the grid once its first `rows` are dropped
+    pub fn scroll_off(&mut self, rows: usize) {
+        self.rows.drain(..rows.min(self.rows.len()));
+    }
+
+    /// The matches on grid row `row`, 1-based, in the order of the rules
+    pub fn row(&self, row: usize) -> &[Highlight] {
+        self.rows.get(row - 1).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn take_fired(&mut self) -> Vec<Fired> {
+        std::mem::take(&mut self.fired)
+    }
+}
+
+/// What `found` covers of `rows`, the first of them being grid row `first`
+fn text(rows: &[Cow<'_, GridRow>], first: usize, found: &Match, cols: usize) -> String {
+    let mut text = String::new();
+    for row in found.start.row..=found.end.row {
+        let (Some(cells), Some((left, right))) = (rows.get(row - first), found.columns(row, cols))
+        else {
+            continue;
+        };
+        let cells = cells.cells.iter().skip(left - 1).take(right + 1 - left);
+        text.extend(cells.map(|cell| cell.c));
+    }
+    text
+}
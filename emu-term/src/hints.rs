@@ -0,0 +1,107 @@
+use crate::links::LinkKind;
+
+/// Keys labels are made of, home row first so the common case needs no reaching
+const ALPHABET: &[u8] = b"asdfghjklqwertyuiopzxcvbnm";
+
+/// Something on screen that can be picked from the keyboard
+#[derive(Debug, Clone)]
+pub struct Hint {
+    pub label: String,
+    pub kind: LinkKind,
+    /// Grid row and first column of the target
+    pub row: usize,
+    pub left: usize,
+    pub text: String,
+}
+
+/// What typing a label does, Shift copies instead of opening
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HintAction {
+    Open,
+    Copy,
+}
+
+/// Labels over every link on screen, narrowed down as keys are typed
+#[derive(Debug, Clone)]
+pub struct Hints {
+    hints: Vec<Hint>,
+    typed: String,
+    action: HintAction,
+}
+
+/// The outcome of a key typed while hints are shown
+pub enum HintInput {
+    /// Still more than one label left
+    Pending,
+    Picked(Hint, HintAction),
+    /// No label starts with what was typed
+    Cancelled,
+}
+
+impl Hints {
+    /// Labels `targets` in order. Every label has the same length so none is a prefix of
+    /// another
+    pub fn new(targets: Vec<(LinkKind, usize, usize, String)>) -> Self {
+        let labels = labels(targets.len());
+        let hints = targets
+            .into_iter()
+            .zip(labels)
+            .map(|((kind, row, left, text), label)| Hint {
+                label,
+                kind,
+                row,
+                left,
+                text,
+            })
+            .collect();
+
+        Self {
+            hints,
+            typed: String::new(),
+            action: HintAction::Open,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hints.is_empty()
+    }
+
+    /// Hints whose label still matches, along with the part left to type
+    pub fn visible(&self) -> impl Iterator<Item = (&Hint, &str)> {
+        self.hints
+            .iter()
+            .filter_map(|hint| Some((hint, hint.label.strip_prefix(&self.typed)?)))
+    }
+
+    pub fn type_char(&mut self, c: char) -> HintInput {
+        if c.is_uppercase() {
+            self.action = HintAction::Copy;
+        }
+        self.typed.extend(c.to_lowercase());
+
+        let mut visible = self.visible();
+        match (visible.next(), visible.next()) {
+            (None, _) => HintInput::Cancelled,
+            (Some((hint, "")), None) => HintInput::Picked(hint.clone(), self.action),
+            _ => HintInput::Pending,
+        }
+    }
+}
+
+/// `count` labels of one letter, or of two when there are more targets than letters
+fn labels(count: usize) -> Vec<String> {
+    let letters = ALPHABET.iter().map(|b| *b as char);
+    if count <= ALPHABET.len() {
+        return letters.take(count).map(String::from).collect();
+    }
+
+    letters
+        .clone()
+        .flat_map(|first| {
+            letters
+                .clone()
+                .map(move |second| format!("{}{}", first, second))
+        })
+        .take(count)
+        .collect()
+}
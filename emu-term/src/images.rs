@@ -0,0 +1,321 @@
+//! Images the application sent with the kitty graphics protocol, decoded and kept by id until
+//! it puts them on screen or deletes them. Commands come parsed from `emu_core::kitty`
+
+use emu_core::kitty::{Command, Medium};
+use iced::widget::image::Handle;
+use std::collections::{HashMap, VecDeque};
+use std::fs::OpenOptions;
+use std::io::Read as _;
+use std::os::unix::fs::{MetadataExt as _, OpenOptionsExt as _};
+use std::path::{Path, PathBuf};
+
+/// Decoded image data kept at most, the oldest images go first past it
+const MAX_STORED_BYTES: usize = 320 << 20;
+/// Sides of raw RGB and RGBA data, and of decoded files, past this are refused
+pub const MAX_SIDE: u32 = 10_000;
+/// Files bigger than this aren't read, nor compressed PNG data inflating past it
+const MAX_FILE_BYTES: u64 = 128 << 20;
+/// Where a file is never read from, whatever the path the application sent
+const SYSTEM_DIRS: [&str; 3] = ["/proc", "/sys", "/dev"];
+/// The answer to any file that can't be read, which doesn't tell whether it's there
+const UNREADABLE: &str = "EBADF:can't read the file";
+
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub handle: Handle,
+    pub width: u32,
+    pub height: u32,
+    /// Bytes of the data it was decoded from, what counts towards `MAX_STORED_BYTES`
+    size: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Images {
+    images: HashMap<u32, Image>,
+    /// Ids in the order they were stored, the oldest is dropped first
+    order: VecDeque<u32>,
+    stored: usize,
+    /// `I` numbers to the ids picked for them
+    numbers: HashMap<u32, u32>,
+    /// Ids picked for numbered images count down from the top, away from the ones
+    /// applications pick
+    next_id: u32,
+    /// A transmission whose last chunk hasn't come yet, its first command and the data so far
+    loading: Option<(Command, Vec<u8>)>,
+}
+
+impl Images {
+    /// Takes one chunk of a transmission. Returns the command with the whole payload once the
+    /// last one is in, chunks without `m=1` are whole on their own
+    pub fn receive(&mut self, command: Command) -> Option<Command> {
+        let payload = command.payload.clone().unwrap_or_default();
+        let (mut first, data) = match self.loading.take() {
+            Some((first, mut data)) => {
+                data.extend_from_slice(&payload);
+                (first, data)
+            }
+            None => (command.clone(), payload),
+        };
+
+        if command.more {
+            self.loading = Some((first, data));
+            return None;
+        }
+        first.payload = Some(data);
+        first.more = false;
+        Some(first)
+    }
+
+    /// The image of `id`, or of the one picked for `number` when there's no id
+    pub fn get(&self, id: u32, number: u32) -> Option<(u32, &Image)> {
+        let id = self.id(id, number)?;
+        Some((id, self.images.get(&id)?))
+    }
+
+    pub fn id(&self, id: u32, number: u32) -> Option<u32> {
+        match id {
+            0 => self.numbers.get(&number).copied(),
+            id => Some(id),
+        }
+    }
+
+    /// Keeps `image` under the id of the command, picking one for a numbered image. Returns the
+    /// id, or `None` when the command has neither
+    pub fn store(&mut self, command: &Command, image: Image) -> Option<u32> {
+        let id = match (command.id, command.number) {
+            (0, 0) => return None,
+            (0, number) => {
+                self.next_id = self.next_id.wrapping_sub(1).max(1 << 31);
+                self.numbers.insert(number, self.next_id);
+                self.next_id
+            }
+            (id, _) => id,
+        };
+
+        self.remove(id);
+        self.stored += image.size;
+        self.images.insert(id, image);
+        self.order.push_back(id);
+        while self.stored > MAX_STORED_BYTES && self.order.len() > 1 {
+            let oldest = self.order[0];
+            self.remove(oldest);
+        }
+        Some(id)
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        if let Some(image) = self.images.remove(&id) {
+            self.stored -= image.size;
+            self.order.retain(|&i| i != id);
+            self.numbers.retain(|_, &mut i| i != id);
+        }
+    }
+}
+
+/// Decodes the image a command carries, or an error to answer it with
+pub fn load(command: &Command) -> Result<Image, String> {
+    let payload = command
+        .payload
+        .as_deref()
+        .ok_or("EINVAL:the payload isn't base64")?;
+    let data = match command.medium {
+        Medium::Direct => payload.to_vec(),
+        Medium::File | Medium::TempFile => read_file(payload, command.medium)?,
+        Medium::SharedMemory => return Err("EINVAL:shared memory isn't supported".to_owned()),
+    };
+    let (width, height) = (command.width, command.height);
+    let data = match command.compressed {
+        true => {
+            // raw pixels can't inflate past their size, a PNG past the size of a file
+            let limit = match command.format {
+                24 => width as u64 * height as u64 * 3,
+                32 => width as u64 * height as u64 * 4,
+                _ => MAX_FILE_BYTES,
+            };
+            inflate(&data, limit)?
+        }
+        false => data,
+    };
+    let size = data.len();
+
+    let raw = |bytes_per_pixel: usize| {
+        if width == 0 || height == 0 || width > MAX_SIDE || height > MAX_SIDE {
+            return Err(format!("EINVAL:bad size {}x{}", width, height));
+        }
+        match data.len() == width as usize * height as usize * bytes_per_pixel {
+            true => Ok(()),
+            false => Err(format!(
+                "ENODATA:{} bytes for {}x{}",
+                data.len(),
+                width,
+                height
+            )),
+        }
+    };
+    let handle = match command.format {
+        24 => {
+            raw(3)?;
+            let rgba: Vec<u8> = data
+                .chunks_exact(3)
+                .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                .collect();
+            Handle::from_rgba(width, height, rgba)
+        }
+        32 => {
+            raw(4)?;
+            Handle::from_rgba(width, height, data)
+        }
+        100 => {
+            let info = png::Decoder::new(data.as_slice())
+                .read_info()
+                .map_err(|e| format!("EBADPNG:{}", e))?
+                .info()
+                .clone();
+            return Ok(Image {
+                width: info.width,
+                height: info.height,
+                handle: Handle::from_bytes(data),
+                size,
+            });
+        }
+        format => return Err(format!("EINVAL:unknown format {}", format)),
+    };
+    Ok(Image {
+        handle,
+        width,
+        height,
+        size,
+    })
+}
+
+/// Decodes a whole image file, PNG, JPEG or GIF, into RGBA
+pub fn decode(data: &[u8]) -> Result<Image, String> {
+    let reader = image::io::Reader::new(std::io::Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?;
+    let (width, height) = reader.into_dimensions().map_err(|e| e.to_string())?;
+    if width == 0 || height == 0 || width > MAX_SIDE || height > MAX_SIDE {
+        return Err(format!("bad size {}x{}", width, height));
+    }
+
+    let rgba = image::load_from_memory(data)
+        .map_err(|e| e.to_string())?
+        .into_rgba8();
+    Ok(Image {
+        handle: Handle::from_rgba(width, height, rgba.into_raw()),
+        width,
+        height,
+        size: data.len(),
+    })
+}
+
+/// Zlib data inflated, refused once it goes past `limit` bytes
+fn inflate(data: &[u8], limit: u64) -> Result<Vec<u8>, String> {
+    let mut inflated = vec![];
+    flate2::read::ZlibDecoder::new(data)
+        .take(limit + 1)
+        .read_to_end(&mut inflated)
+        .map_err(|e| format!("EINVAL:failed to inflate the data: {}", e))?;
+    match inflated.len() as u64 > limit {
+        true => Err(format!("EINVAL:the data inflates past {} bytes", limit)),
+        false => Ok(inflated),
+    }
+}
+
+/// The data of `t=f` and `t=t`, the payload being the path. Every failure gets the same
+/// answer, the application doesn't learn what's there. Temporary files are only taken from
+/// right in the temporary directory, with the protocol in their name and owned by the user,
+/// and they're deleted once read
+fn read_file(path: &[u8], medium: Medium) -> Result<Vec<u8>, String> {
+    let path = Path::new(std::str::from_utf8(path).map_err(|_| "EINVAL:bad path")?);
+    let path = std::fs::canonicalize(path).map_err(|_| UNREADABLE)?;
+    if SYSTEM_DIRS.iter().any(|dir| path.starts_with(dir)) {
+        return Err(UNREADABLE.to_owned());
+    }
+    if medium == Medium::TempFile && !is_temporary(&path) {
+        return Err(UNREADABLE.to_owned());
+    }
+
+    // a fifo would block the open, a symlink swapped in since would be followed
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_NOFOLLOW)
+        .open(&path)
+        .map_err(|_| UNREADABLE)?;
+    let metadata = file.metadata().map_err(|_| UNREADABLE)?;
+    let owned = metadata.uid() == nix::unistd::getuid().as_raw();
+    if !metadata.is_file() || metadata.len() > MAX_FILE_BYTES {
+        return Err(UNREADABLE.to_owned());
+    }
+    if medium == Medium::TempFile && !owned {
+        return Err(UNREADABLE.to_owned());
+    }
+    let mut data = vec![];
+    file.take(MAX_FILE_BYTES)
+        .read_to_end(&mut data)
+        .map_err(|_| UNREADABLE)?;
+
+    if medium == Medium::TempFile {
+        let _ = std::fs::remove_file(&path);
+    }
+    Ok(data)
+}
+
+/// Whether a canonical path is a file right in the temporary directory made for the protocol
+fn is_temporary(path: &Path) -> bool {
+    let temp_dir =
+        std::fs::canonicalize(std::env::temp_dir()).unwrap_or_else(|_| PathBuf::from("/tmp"));
+    let named = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().contains("tty-graphics-protocol"));
+    path.parent() == Some(temp_dir.as_path()) && named
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+
+    fn compressed(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn inflating_stops_at_the_limit() {
+        let data = compressed(&[0; 4096]);
+        assert_eq!(inflate(&data, 4096).map(|d| d.len()), Ok(4096));
+        assert!(inflate(&data, 4095).is_err());
+    }
+
+    #[test]
+    fn temporary_files_come_from_the_temporary_directory() {
+        let dir = std::env::temp_dir().join(format!("emu-term-images-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let outside = dir.join("kept");
+        std::fs::write(&outside, b"data").unwrap();
+
+        // climbing out of a directory with the protocol in its name
+        std::fs::create_dir_all(dir.join("tty-graphics-protocol")).unwrap();
+        let escape = dir.join("tty-graphics-protocol").join("..").join("kept");
+        let result = read_file(escape.to_str().unwrap().as_bytes(), Medium::TempFile);
+        assert_eq!(result, Err(UNREADABLE.to_owned()));
+        assert!(outside.exists());
+
+        let missing = read_file(b"/nonexistent/file", Medium::File);
+        assert_eq!(missing, Err(UNREADABLE.to_owned()));
+        let system = read_file(b"/proc/self/environ", Medium::File);
+        assert_eq!(system, Err(UNREADABLE.to_owned()));
+
+        let temporary = std::env::temp_dir().join(format!(
+            "tty-graphics-protocol-emu-term-{}",
+            std::process::id()
+        ));
+        std::fs::write(&temporary, b"data").unwrap();
+        let read = read_file(temporary.to_str().unwrap().as_bytes(), Medium::TempFile);
+        assert_eq!(read, Ok(b"data".to_vec()));
+        assert!(!temporary.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
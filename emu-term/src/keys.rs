@@ -0,0 +1,373 @@
+use emu_core::modes::{KeyModes, MouseModes, MouseTracking};
+use iced::keyboard::key::Named;
+use iced::keyboard::{Key, Modifiers};
+
+/// The xterm modifier parameter, 1 plus a bit per modifier held
+fn modifier_param(mods: Modifiers) -> u8 {
+    1 + mods.shift() as u8 + 2 * mods.alt() as u8 + 4 * mods.control() as u8
+}
+
+/// Keys that end in a letter: `CSI x`, `SS3 x` or with modifiers `CSI 1 ; m x`
+fn letter(final_byte: char, mods: Modifiers, ss3: bool) -> Vec<u8> {
+    match modifier_param(mods) {
+        1 if ss3 => format!("\x1bO{}", final_byte),
+        1 => format!("\x1b[{}", final_byte),
+        m => format!("\x1b[1;{}{}", m, final_byte),
+    }
+    .into_bytes()
+}
+
+/// Keys that end in a tilde: `CSI n ~` or with modifiers `CSI n ; m ~`
+fn tilde(code: u8, mods: Modifiers) -> Vec<u8> {
+    match modifier_param(mods) {
+        1 => format!("\x1b[{}~", code),
+        m => format!("\x1b[{};{}~", code, m),
+    }
+    .into_bytes()
+}
+
+/// The C0 control byte Ctrl plus `c` sends, following the ASCII layout where Ctrl clears the
+/// upper bits of the character. The digit row doubles for the symbols that are awkward to reach
+pub fn control_byte(c: &str) -> Option<u8> {
+    let &[byte] = c.as_bytes() else {
+        return None;
+    };
+
+    match byte.to_ascii_uppercase() {
+        upper @ (b'@'..=b'_') => Some(upper & 0x1f),
+        b' ' | b'2' => Some(0x00),
+        b'3' => Some(0x1b),
+        b'4' => Some(0x1c),
+        b'5' => Some(0x1d),
+        b'6' => Some(0x1e),
+        b'/' | b'7' => Some(0x1f),
+        b'?' | b'8' => Some(0x7f),
+        _ => None,
+    }
+}
+
+/// Prefixes ESC while Alt is held, for keys that have no modifier encoding of their own
+fn meta(mods: Modifiers, bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(bytes.len() + 1);
+    if mods.alt() {
+        encoded.push(0x1b);
+    }
+    encoded.extend_from_slice(bytes);
+    encoded
+}
+
+/// Encodes a named key the way xterm does, `None` for keys that don't send anything
+pub fn encode_named(named: Named, mods: Modifiers, modes: KeyModes) -> Option<Vec<u8>> {
+    let cursor = |final_byte| Some(letter(final_byte, mods, modes.app_cursor));
+
+    match named {
+        Named::Enter => Some(meta(mods, b"\r")),
+        Named::Tab if mods.shift() => Some(b"\x1b[Z".to_vec()),
+        Named::Tab => Some(meta(mods, b"\t")),
+        Named::Space if mods.control() => Some(meta(mods, b"\0")),
+        Named::Space => Some(meta(mods, b" ")),
+        Named::Backspace if mods.control() => Some(meta(mods, b"\x08")),
+        Named::Backspace => Some(meta(mods, b"\x7f")),
+        Named::Escape => Some(meta(mods, b"\x1b")),
+        Named::ArrowUp => cursor('A'),
+        Named::ArrowDown => cursor('B'),
+        Named::ArrowRight => cursor('C'),
+        Named::ArrowLeft => cursor('D'),
+        Named::Home => cursor('H'),
+        Named::End => cursor('F'),
+        Named::Insert => Some(tilde(2, mods)),
+        Named::Delete => Some(tilde(3, mods)),
+        Named::PageUp => Some(tilde(5, mods)),
+        Named::PageDown => Some(tilde(6, mods)),
+        Named::F1 => Some(letter('P', mods, true)),
+        Named::F2 => Some(letter('Q', mods, true)),
+        Named::F3 => Some(letter('R', mods, true)),
+        Named::F4 => Some(letter('S', mods, true)),
+        Named::F5 => Some(tilde(15, mods)),
+        Named::F6 => Some(tilde(17, mods)),
+        Named::F7 => Some(tilde(18, mods)),
+        Named::F8 => Some(tilde(19, mods)),
+        Named::F9 => Some(tilde(20, mods)),
+        Named::F10 => Some(tilde(21, mods)),
+        Named::F11 => Some(tilde(23, mods)),
+        Named::F12 => Some(tilde(24, mods)),
+        _ => None,
+    }
+}
+
+/// Encodes a key of the numeric keypad. Outside of application keypad mode it sends the
+/// same thing as its counterpart on the main keyboard
+pub fn encode_keypad(key: &Key, mods: Modifiers, modes: KeyModes) -> Option<Vec<u8>> {
+    if modes.app_keypad {
+        let final_byte = match key {
+            Key::Named(Named::Enter) => Some('M'),
+            Key::Character(c) => match c.as_str() {
+                "*" => Some('j'),
+                "+" => Some('k'),
+                "," => Some('l'),
+                "-" => Some('m'),
+                "." => Some('n'),
+                "/" => Some('o'),
+                "=" => Some('X'),
+                // digits go from `SS3 p` for 0 to `SS3 y` for 9
+                c => c
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|digit| *digit < 10)
+                    .map(|digit| (b'p' + digit) as char),
+            },
+            _ => None,
+        };
+        if let Some(final_byte) = final_byte {
+            return Some(format!("\x1bO{}", final_byte).into_bytes());
+        }
+    }
+
+    match key {
+        Key::Named(named) => encode_named(*named, mods, modes),
+        Key::Character(c) => Some(c.as_bytes().to_vec()),
+        _ => None,
+    }
+}
+
+/// Turns clipboard text into what gets written to the pty. Line endings become the CR Enter
+/// sends, and in bracketed paste mode the text is wrapped in the markers that let the
+/// application tell it apart from typing. Without them control characters other than tab and
+/// newline are always dropped, an ESC in the text would be taken as a key otherwise
+pub fn encode_paste(text: &str, bracketed: bool, strip_controls: bool) -> Vec<u8> {
+    let text = text.replace("\r\n", "\r").replace('\n', "\r");
+    let mut text: String = if strip_controls || !bracketed {
+        text.chars()
+            .filter(|c| !c.is_control() || matches!(c, '\t' | '\r'))
+            .collect()
+    } else {
+        text
+    };
+
+    if !bracketed {
+        return text.into_bytes();
+    }
+
+    // an end marker inside the text would let the rest of it through as typed input
+    while text.contains(PASTE_END) {
+        text = text.replace(PASTE_END, "");
+    }
+    [PASTE_START, &text, PASTE_END].concat().into_bytes()
+}
+
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
+    /// Motion with no button held
+    None,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MouseAction {
+    Press,
+    Release,
+    Motion,
+}
+
+/// A mouse event on a screen cell, to be sent to the application
+#[derive(Debug, Copy, Clone)]
+pub struct MouseReport {
+    pub button: MouseButton,
+    pub action: MouseAction,
+    /// Screen line and column
+    pub cell: (usize, usize),
+    pub modifiers: Modifiers,
+}
+
+/// Encodes a mouse report, `None` when the modes don't ask for it or, in the legacy
+/// encoding, when the cell is too far to be represented
+pub fn encode_mouse(report: MouseReport, modes: MouseModes) -> Option<Vec<u8>> {
+    let MouseReport {
+        button,
+        action,
+        cell: (line, col),
+        modifiers,
+    } = report;
+
+    let wanted = match action {
+        MouseAction::Press | MouseAction::Release => MouseTracking::Click,
+        MouseAction::Motion if button == MouseButton::None => MouseTracking::Motion,
+        MouseAction::Motion => MouseTracking::Drag,
+    };
+    if modes.tracking < wanted || (action == MouseAction::Release && is_wheel(button)) {
+        return None;
+    }
+
+    let mut code = match button {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+        MouseButton::None => 3,
+        MouseButton::WheelUp => 64,
+        MouseButton::WheelDown => 65,
+    };
+    if action == MouseAction::Motion {
+        code += 32;
+    }
+    code += 4 * modifiers.shift() as usize
+        + 8 * modifiers.alt() as usize
+        + 16 * modifiers.control() as usize;
+
+    if modes.sgr {
+        let last = if action == MouseAction::Release {
+            'm'
+        } else {
+            'M'
+        };
+        return Some(format!("\x1b[<{};{};{}{}", code, col, line, last).into_bytes());
+    }
+
+    // the legacy encoding can't tell which button was released
+    if action == MouseAction::Release {
+        code = code & !3 | 3;
+    }
+    let byte = |value: usize| u8::try_from(value + 32).ok();
+    Some(vec![0x1b, b'[', b'M', byte(code)?, byte(col)?, byte(line)?])
+}
+
+fn is_wheel(button: MouseButton) -> bool {
+    matches!(button, MouseButton::WheelUp | MouseButton::WheelDown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(button: MouseButton, action: MouseAction, cell: (usize, usize)) -> MouseReport {
+        MouseReport {
+            button,
+            action,
+            cell,
+            modifiers: Modifiers::empty(),
+        }
+    }
+
+    #[test]
+    fn control_bytes() {
+        assert_eq!(control_byte("c"), Some(0x03));
+        assert_eq!(control_byte("C"), Some(0x03));
+        assert_eq!(control_byte("["), Some(0x1b));
+        assert_eq!(control_byte(" "), Some(0x00));
+        assert_eq!(control_byte("2"), Some(0x00));
+        assert_eq!(control_byte("/"), Some(0x1f));
+        assert_eq!(control_byte("8"), Some(0x7f));
+        assert_eq!(control_byte("1"), None);
+        assert_eq!(control_byte("\u{e9}"), None);
+    }
+
+    #[test]
+    fn named_keys_follow_the_cursor_key_mode() {
+        let normal = KeyModes::default();
+        let app = KeyModes {
+            app_cursor: true,
+            ..normal
+        };
+        let none = Modifiers::empty();
+        assert_eq!(
+            encode_named(Named::ArrowUp, none, normal).unwrap(),
+            b"\x1b[A"
+        );
+        assert_eq!(encode_named(Named::ArrowUp, none, app).unwrap(), b"\x1bOA");
+        // a modifier always goes in the CSI form
+        let ctrl = Modifiers::CTRL;
+        assert_eq!(
+            encode_named(Named::ArrowLeft, ctrl, app).unwrap(),
+            b"\x1b[1;5D"
+        );
+    }
+
+    #[test]
+    fn named_keys_with_modifiers() {
+        let modes = KeyModes::default();
+        let shift_alt = Modifiers::SHIFT | Modifiers::ALT;
+        assert_eq!(
+            encode_named(Named::Delete, Modifiers::empty(), modes).unwrap(),
+            b"\x1b[3~"
+        );
+        assert_eq!(
+            encode_named(Named::PageUp, shift_alt, modes).unwrap(),
+            b"\x1b[5;4~"
+        );
+        assert_eq!(
+            encode_named(Named::F1, Modifiers::empty(), modes).unwrap(),
+            b"\x1bOP"
+        );
+        assert_eq!(
+            encode_named(Named::F12, Modifiers::CTRL, modes).unwrap(),
+            b"\x1b[24;5~"
+        );
+        assert_eq!(
+            encode_named(Named::Tab, Modifiers::SHIFT, modes).unwrap(),
+            b"\x1b[Z"
+        );
+        assert_eq!(
+            encode_named(Named::Enter, Modifiers::ALT, modes).unwrap(),
+            b"\x1b\r"
+        );
+        assert_eq!(
+            encode_named(Named::CapsLock, Modifiers::empty(), modes),
+            None
+        );
+    }
+
+    #[test]
+    fn paste_line_endings_and_controls() {
+        assert_eq!(encode_paste("a\r\nb\nc", false, false), b"a\rb\rc");
+        assert_eq!(encode_paste("a\x1b[31mb\tc", false, false), b"a[31mb\tc");
+    }
+
+    #[test]
+    fn bracketed_paste_drops_end_markers_inside() {
+        let pasted = encode_paste("a\x1b[201~b\x1b[201\x1b[201~~~c", true, false);
+        assert_eq!(pasted, b"\x1b[200~ab~c\x1b[201~");
+        let stripped = encode_paste("a\x1bb", true, true);
+        assert_eq!(stripped, b"\x1b[200~ab\x1b[201~");
+    }
+
+    #[test]
+    fn mouse_reports_depend_on_the_tracking_mode() {
+        let press = report(MouseButton::Left, MouseAction::Press, (2, 3));
+        let off = MouseModes::default();
+        assert_eq!(encode_mouse(press, off), None);
+
+        let click = MouseModes {
+            tracking: MouseTracking::Click,
+            sgr: false,
+        };
+        assert_eq!(encode_mouse(press, click).unwrap(), b"\x1b[M #\"");
+        let drag = report(MouseButton::Left, MouseAction::Motion, (2, 3));
+        assert_eq!(encode_mouse(drag, click), None);
+        // the legacy encoding can't go past column 223
+        let far = report(MouseButton::Left, MouseAction::Press, (1, 300));
+        assert_eq!(encode_mouse(far, click), None);
+    }
+
+    #[test]
+    fn sgr_mouse_reports() {
+        let modes = MouseModes {
+            tracking: MouseTracking::Drag,
+            sgr: true,
+        };
+        let release = report(MouseButton::Right, MouseAction::Release, (10, 300));
+        assert_eq!(encode_mouse(release, modes).unwrap(), b"\x1b[<2;300;10m");
+        let drag = MouseReport {
+            modifiers: Modifiers::CTRL,
+            ..report(MouseButton::Left, MouseAction::Motion, (1, 1))
+        };
+        assert_eq!(encode_mouse(drag, modes).unwrap(), b"\x1b[<48;1;1M");
+        let wheel = report(MouseButton::WheelUp, MouseAction::Release, (1, 1));
+        assert_eq!(encode_mouse(wheel, modes), None);
+    }
+}
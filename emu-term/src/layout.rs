@@ -0,0 +1,382 @@
+//! The panes of a tab, laid out as a tree of splits. A split shares its room among its
+//! children by weight, the way `Length::FillPortion` does when the tab is drawn, so the sizes
+//! worked out here for the ptys are the ones the panes end up with
+
+use iced::Size;
+
+use crate::TabId;
+
+/// Room between two panes of a split
+pub const GAP: f32 = 2.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Axis {
+    /// Side by side
+    Horizontal,
+    /// One above the other
+    Vertical,
+}
+
+/// Changes to the layout of a tab around its focused pane
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Makes the pane take the whole tab, or puts the layout back once it does
+    Zoom,
+    /// Trades places with the pane before it
+    SwapPrevious,
+    SwapNext,
+    /// Moves every pane of the tab one place back, the first one to the end
+    Rotate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layout<P> {
+    Pane(P),
+    /// Children with their weights, none of them 0
+    Split(Axis, Vec<(u16, Layout<P>)>),
+}
+
+impl<P: Copy + PartialEq> Layout<P> {
+    /// Left to right and top to bottom
+    pub fn panes(&self) -> Vec<P> {
+        match self {
+            Self::Pane(pane) => vec![*pane],
+            Self::Split(_, children) => children
+                .iter()
+                .flat_map(|(_, child)| child.panes())
+                .collect(),
+        }
+    }
+
+    pub fn contains(&self, pane: P) -> bool {
+        self.panes().contains(&pane)
+    }
+
+    /// The same splits with other panes, `None` if `f` has none for one of them
+    pub fn map<Q>(&self, f: &impl Fn(P) -> Option<Q>) -> Option<Layout<Q>> {
+        Some(match self {
+            Self::Pane(pane) => Layout::Pane(f(*pane)?),
+            Self::Split(axis, children) => Layout::Split(
+                *axis,
+                children
+                    .iter()
+                    .map(|(weight, child)| Some((*weight, child.map(f)?)))
+                    .collect::<Option<_>>()?,
+            ),
+        })
+    }
+
+    /// Puts `new` where `old` is
+    pub fn replace(&mut self, old: P, new: P) {
+        match self {
+            Self::Pane(pane) if *pane == old => *pane = new,
+            Self::Pane(_) => {}
+            Self::Split(_, children) => {
+                for (_, child) in children {
+                    child.replace(old, new);
+                }
+            }
+        }
+    }
+
+    /// Puts `new` next to `pane`, the two share the room `pane` had
+    pub fn split(&mut self, pane: P, new: P, axis: Axis) {
+        match self {
+            Self::Pane(p) if *p == pane => {
+                *self = Self::Split(axis, vec![(1, Self::Pane(pane)), (1, Self::Pane(new))]);
+            }
+            Self::Pane(_) => {}
+            Self::Split(_, children) => {
+                for (_, child) in children {
+                    child.split(pane, new, axis);
+                }
+            }
+        }
+    }
+
+    /// Puts the panes in the places of the ones `panes` had, in order
+    fn assign(&mut self, panes: &mut impl Iterator<Item = P>) {
+        match self {
+            Self::Pane(pane) => *pane = panes.next().unwrap_or(*pane),
+            Self::Split(_, children) => {
+                for (_, child) in children {
+                    child.assign(panes);
+                }
+            }
+        }
+    }
+
+    /// The layout without `pane`, `None` once there's nothing left. A split left with a single
+    /// child is replaced by it
+    pub fn without(self, pane: P) -> Option<Self> {
+        match self {
+            Self::Pane(p) if p == pane => None,
+            Self::Pane(p) => Some(Self::Pane(p)),
+            Self::Split(axis, children) => {
+                let mut children: Vec<(u16, Self)> = children
+                    .into_iter()
+                    .filter_map(|(weight, child)| Some((weight, child.without(pane)?)))
+                    .collect();
+                match children.len() {
+                    0 => None,
+                    1 => children.pop().map(|(_, child)| child),
+                    _ => Some(Self::Split(axis, children)),
+                }
+            }
+        }
+    }
+
+    /// How big each pane is in `size`
+    pub fn sizes(&self, size: Size) -> Vec<(P, Size)> {
+        match self {
+            Self::Pane(pane) => vec![(*pane, size)],
+            Self::Split(axis, children) => {
+                let gaps = GAP * children.len().saturating_sub(1) as f32;
+                let total: f32 = children.iter().map(|(weight, _)| *weight as f32).sum();
+                children
+                    .iter()
+                    .flat_map(|(weight, child)| {
+                        let share = *weight as f32 / total;
+                        let size = match axis {
+                            Axis::Horizontal => {
+                                Size::new((size.width - gaps).max(0.0) * share, size.height)
+                            }
+                            Axis::Vertical => {
+                                Size::new(size.width, (size.height - gaps).max(0.0) * share)
+                            }
+                        };
+                        child.sizes(size)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// A tab of the window with its panes, one of which has the keyboard
+#[derive(Debug, Clone)]
+pub struct Tab {
+    pub layout: Layout<TabId>,
+    pub focus: TabId,
+    /// Only the focused pane is shown, over the whole tab
+    pub zoomed: bool,
+    /// The tmux window the tab shows, along with the tab tmux runs in
+    pub window: Option<(TabId, u32)>,
+}
+
+impl Tab {
+    pub fn new(pane: TabId) -> Self {
+        Self {
+            layout: Layout::Pane(pane),
+            focus: pane,
+            zoomed: false,
+            window: None,
+        }
+    }
+
+    pub fn panes(&self) -> Vec<TabId> {
+        self.layout.panes()
+    }
+
+    pub fn contains(&self, pane: TabId) -> bool {
+        self.layout.contains(pane)
+    }
+
+    pub fn replace(&mut self, old: TabId, new: TabId) {
+        self.layout.replace(old, new);
+        if self.focus == old {
+            self.focus = new;
+        }
+    }
+
+    /// Puts `new` next to the focused pane and gives it the keyboard
+    pub fn split(&mut self, new: TabId, axis: Axis) {
+        self.layout.split(self.focus, new, axis);
+        self.focus = new;
+        self.zoomed = false;
+    }
+
+    /// The pane `step` places after the focused one, wrapping around
+    pub fn next(&self, step: isize) -> TabId {
+        let panes = self.panes();
+        let index = panes
+            .iter()
+            .position(|&pane| pane == self.focus)
+            .unwrap_or(0);
+        panes[(index as isize + step).rem_euclid(panes.len() as isize) as usize]
+    }
+
+    /// The focused pane keeps the keyboard wherever it moves to
+    pub fn change(&mut self, change: Change) {
+        let mut panes = self.panes();
+        if panes.len() < 2 {
+            return;
+        }
+        match change {
+            Change::Zoom => {
+                self.zoomed = !self.zoomed;
+                return;
+            }
+            Change::SwapPrevious | Change::SwapNext => {
+                let step = if change == Change::SwapNext { 1 } else { -1 };
+                let (a, b) = (self.focus, self.next(step));
+                for pane in &mut panes {
+                    *pane = if *pane == a {
+                        b
+                    } else if *pane == b {
+                        a
+                    } else {
+                        *pane
+                    };
+                }
+            }
+            Change::Rotate => panes.rotate_left(1),
+        }
+        self.layout.assign(&mut panes.into_iter());
+        self.zoomed = false;
+    }
+
+    /// The tab without `pane`, `None` once it has no panes left. The focus goes to the pane
+    /// before it when it had it
+    pub fn without(self, pane: TabId) -> Option<Self> {
+        let panes = self.panes();
+        let layout = self.layout.without(pane)?;
+        if self.focus != pane {
+            return Some(Self { layout, ..self });
+        }
+
+        let index = panes.iter().position(|&p| p == pane).unwrap_or(0);
+        let focus = match index {
+            0 => panes[1],
+            _ => panes[index - 1],
+        };
+        Some(Self {
+            layout,
+            focus,
+            zoomed: false,
+            ..self
+        })
+    }
+
+    /// How big each pane is in `size`, the focused one takes all of it while zoomed and the
+    /// others keep theirs
+    pub fn sizes(&self, size: Size) -> Vec<(TabId, Size)> {
+        let mut sizes = self.layout.sizes(size);
+        if self.zoomed {
+            for (pane, pane_size) in &mut sizes {
+                if *pane == self.focus {
+                    *pane_size = size;
+                }
+            }
+        }
+        sizes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(axis: Axis, panes: &[(u16, usize)]) -> Layout<usize> {
+        let children = panes
+            .iter()
+            .map(|&(weight, pane)| (weight, Layout::Pane(pane)))
+            .collect();
+        Layout::Split(axis, children)
+    }
+
+    #[test]
+    fn splits_share_their_room_by_weight() {
+        let layout = Layout::Split(
+            Axis::Horizontal,
+            vec![
+                (1, Layout::Pane(0)),
+                (1, split(Axis::Vertical, &[(3, 1), (1, 2)])),
+            ],
+        );
+        let sizes = layout.sizes(Size::new(202.0, 102.0));
+        assert_eq!(
+            sizes,
+            vec![
+                (0, Size::new(100.0, 102.0)),
+                (1, Size::new(100.0, 75.0)),
+                (2, Size::new(100.0, 25.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_split_left_with_one_pane_goes() {
+        let layout = Layout::Split(
+            Axis::Horizontal,
+            vec![
+                (1, Layout::Pane(0)),
+                (1, split(Axis::Vertical, &[(1, 1), (1, 2)])),
+            ],
+        );
+        let layout = layout.without(1).unwrap();
+        assert_eq!(layout, split(Axis::Horizontal, &[(1, 0), (1, 2)]));
+        assert_eq!(layout.without(0).unwrap(), Layout::Pane(2));
+        assert_eq!(Layout::Pane(2).without(2), None);
+    }
+
+    #[test]
+    fn splitting_halves_the_pane() {
+        let mut layout = split(Axis::Horizontal, &[(2, 0), (1, 1)]);
+        layout.split(0, 2, Axis::Vertical);
+        let expected = Layout::Split(
+            Axis::Horizontal,
+            vec![
+                (2, split(Axis::Vertical, &[(1, 0), (1, 2)])),
+                (1, Layout::Pane(1)),
+            ],
+        );
+        assert_eq!(layout, expected);
+    }
+
+    #[test]
+    fn panes_trade_places_and_keep_the_focus() {
+        let (a, b, c) = (TabId(0), TabId(1), TabId(2));
+        let mut tab = Tab::new(a);
+        tab.split(b, Axis::Horizontal);
+        tab.split(c, Axis::Vertical);
+        assert_eq!((tab.panes(), tab.focus), (vec![a, b, c], c));
+
+        tab.change(Change::SwapPrevious);
+        assert_eq!(tab.panes(), vec![a, c, b]);
+        tab.change(Change::SwapNext);
+        assert_eq!(tab.panes(), vec![a, b, c]);
+        tab.change(Change::SwapNext);
+        assert_eq!(tab.panes(), vec![c, b, a]);
+        tab.change(Change::Rotate);
+        assert_eq!(tab.panes(), vec![b, a, c]);
+        assert_eq!(tab.focus, c);
+
+        tab.change(Change::Zoom);
+        assert!(tab.zoomed);
+        let sizes = tab.sizes(Size::new(100.0, 50.0));
+        assert!(sizes.contains(&(c, Size::new(100.0, 50.0))));
+        tab.change(Change::Zoom);
+        assert!(!tab.zoomed);
+    }
+
+    #[test]
+    fn the_focus_goes_to_the_pane_before() {
+        let (a, b, c) = (TabId(0), TabId(1), TabId(2));
+        let mut tab = Tab::new(a);
+        tab.layout = Layout::Split(
+            Axis::Horizontal,
+            vec![
+                (1, Layout::Pane(a)),
+                (1, Layout::Pane(b)),
+                (1, Layout::Pane(c)),
+            ],
+        );
+        tab.focus = b;
+        let tab = tab.without(b).unwrap();
+        assert_eq!(tab.focus, a);
+        let tab = tab.without(a).unwrap();
+        assert_eq!(tab.focus, c);
+        assert!(tab.without(c).is_none());
+    }
+}
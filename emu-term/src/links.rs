@@ -0,0 +1,252 @@
+use emu_core::grid::Cell;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SCHEMES: [&str; 5] = ["https://", "http://", "file://", "ftp://", "mailto:"];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LinkKind {
+    Url,
+    /// A path to a file or directory that exists
+    Path,
+    /// `path:line` or `path:line:column` of a file that exists, as compilers print them
+    Diagnostic,
+    /// Something that looks like a commit hash, only ever copied
+    Hash,
+}
+
+/// Characters that can't be part of a URL as printed on a terminal, they end the word
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '"' | '\'' | '`' | '<' | '>' | '|' | '{' | '}')
+}
+
+fn is_trailing_punctuation(c: char) -> bool {
+    matches!(c, '.' | ',' | ';' | ':' | '!' | '?' | ')' | ']')
+}
+
+/// Bounds of the run of non-delimiters covering index `at`, end exclusive
+fn word_at(chars: &[char], at: usize) -> Option<(usize, usize)> {
+    if chars.get(at).is_none_or(|c| is_delimiter(*c)) {
+        return None;
+    }
+
+    let start = chars[..at]
+        .iter()
+        .rposition(|c| is_delimiter(*c))
+        .map_or(0, |i| i + 1);
+    let end = chars[at..]
+        .iter()
+        .position(|c| is_delimiter(*c))
+        .map_or(chars.len(), |i| at + i);
+    Some((start, end))
+}
+
+/// Moves `end` back over punctuation closing a sentence or a parenthesis, it isn't part of
+/// the link
+fn trim_punctuation(chars: &[char], start: usize, mut end: usize) -> usize {
+    while end > start && is_trailing_punctuation(chars[end - 1]) {
+        end -= 1;
+    }
+    end
+}
+
+/// The URL in the word at `start..end` of a line, as its first and last columns
+fn url(chars: &[char], start: usize, end: usize) -> Option<(usize, usize)> {
+    let word: String = chars[start..end].iter().collect();
+
+    // the URL may be wrapped in something like `(see https://...)`, skip to its scheme
+    let offset = SCHEMES
+        .iter()
+        .filter_map(|scheme| word.find(scheme))
+        .min()?;
+    let url_start = start + word[..offset].chars().count();
+    let url_end = trim_punctuation(chars, url_start, end);
+    (url_start < url_end).then_some((url_start + 1, url_end))
+}
+
+/// The word at `start..end` without the brackets or punctuation around it, as the word and
+/// its first and last columns
+fn bare_word(chars: &[char], mut start: usize, end: usize) -> Option<(String, usize, usize)> {
+    while start < end && matches!(chars[start], '(' | '[') {
+        start += 1;
+    }
+    let end = trim_punctuation(chars, start, end);
+    (start < end).then(|| (chars[start..end].iter().collect(), start + 1, end))
+}
+
+/// The kind of path `word` is, relative ones being looked up in `dir`. A diagnostic's line
+/// number makes it likely enough to be one that paths without a slash count too, other paths
+/// need one or they'd match every file name in the working directory
+fn path_kind(word: &str, dir: Option<&Path>) -> Option<LinkKind> {
+    if let Some((path, _, _)) = diagnostic(word) {
+        return resolve(path, dir).is_file().then_some(LinkKind::Diagnostic);
+    }
+    (word.contains('/') && resolve(word, dir).exists()).then_some(LinkKind::Path)
+}
+
+/// The link the word at `start..end` of a line is, with its first and last columns. Paths and
+/// diagnostics only when `paths` is set, the relative ones in `dir`. Each takes one lookup of
+/// the file at most
+fn word_link(
+    chars: &[char],
+    start: usize,
+    end: usize,
+    paths: bool,
+    dir: Option<&Path>,
+) -> Option<(LinkKind, usize, usize)> {
+    if let Some((left, right)) = url(chars, start, end) {
+        return Some((LinkKind::Url, left, right));
+    }
+    if !paths {
+        return None;
+    }
+    let (word, left, right) = bare_word(chars, start, end)?;
+    path_kind(&word, dir).map(|kind| (kind, left, right))
+}
+
+/// Finds any kind of link covering column `col`. Paths and diagnostics only when `paths` is
+/// set, the relative ones in `dir`
+pub fn link_at(
+    cells: &[Cell],
+    col: usize,
+    paths: bool,
+    dir: Option<&Path>,
+) -> Option<(LinkKind, usize, usize)> {
+    let chars: Vec<char> = cells.iter().map(|cell| cell.c).collect();
+    let (start, end) = word_at(&chars, col.checked_sub(1)?)?;
+    let link = word_link(&chars, start, end, paths, dir)?;
+    (link.1..=link.2).contains(&col).then_some(link)
+}
+
+/// `path:line` or `path:line:column` as the path, the line and the column
+pub fn diagnostic(word: &str) -> Option<(&str, u32, Option<u32>)> {
+    let (rest, last) = word.rsplit_once(':')?;
+    let last = last.parse().ok()?;
+    let (path, line, column) = match rest.rsplit_once(':') {
+        Some((path, line)) if line.parse::<u32>().is_ok() => (path, line.parse().ok()?, Some(last)),
+        _ => (rest, last, None),
+    };
+    (!path.is_empty()).then_some((path, line, column))
+}
+
+/// The word at `start..end` when it's a hex string long enough to be an abbreviated commit
+/// hash, as its first and last columns
+fn hash(chars: &[char], start: usize, end: usize) -> Option<(usize, usize)> {
+    let end = trim_punctuation(chars, start, end);
+    let word = &chars[start..end];
+
+    let is_hex = |c: &char| c.is_ascii_digit() || ('a'..='f').contains(c);
+    let is_hash = (7..=64).contains(&word.len())
+        && word.iter().all(is_hex)
+        && word.iter().any(char::is_ascii_digit);
+    is_hash.then_some((start + 1, end))
+}
+
+/// Every link on a line, hashes included, as kind and inclusive columns. The line is split in
+/// words once and each of them is looked up once
+pub fn find_all(cells: &[Cell], paths: bool, dir: Option<&Path>) -> Vec<(LinkKind, usize, usize)> {
+    let chars: Vec<char> = cells.iter().map(|cell| cell.c).collect();
+    let mut found = vec![];
+    let mut start = 0;
+    while start < chars.len() {
+        if is_delimiter(chars[start]) {
+            start += 1;
+            continue;
+        }
+
+        let end = chars[start..]
+            .iter()
+            .position(|c| is_delimiter(*c))
+            .map_or(chars.len(), |i| start + i);
+        let commit = || hash(&chars, start, end).map(|(left, right)| (LinkKind::Hash, left, right));
+        found.extend(word_link(&chars, start, end, paths, dir).or_else(commit));
+        start = end;
+    }
+    found
+}
+
+/// Text of columns `left` to `right`, both inclusive
+pub fn text(cells: &[Cell], left: usize, right: usize) -> String {
+    cells
+        .iter()
+        .take(right)
+        .skip(left - 1)
+        .map(|cell| cell.c)
+        .collect()
+}
+
+/// `~/src` as `$HOME/src`
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// `path` from `dir`, or from the home directory when it starts with `~/`
+pub fn resolve(path: &str, dir: Option<&Path>) -> PathBuf {
+    let path = expand_home(path);
+    match dir {
+        Some(dir) => dir.join(path),
+        None => path,
+    }
+}
+
+/// Hands `target` to the desktop's default handler
+pub fn open(target: &str) -> io::Result<()> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    spawn(Command::new(opener).arg(target))
+}
+
+/// `$EDITOR path`, or `$EDITOR +line path` for a line. `$EDITOR` may carry arguments of its
+/// own. It's run in a tab, editors set there usually need a terminal
+pub fn editor(path: &Path, line: Option<u32>) -> io::Result<Vec<String>> {
+    let editor = std::env::var("EDITOR").unwrap_or_default();
+    let mut argv: Vec<String> = editor.split_whitespace().map(str::to_owned).collect();
+    if argv.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "$EDITOR is not set",
+        ));
+    }
+    argv.extend(line.map(|line| format!("+{}", line)));
+    argv.push(path.to_string_lossy().into_owned());
+    Ok(argv)
+}
+
+/// Opens the file of a diagnostic with `template`, whose arguments have `{path}`, `{line}` and
+/// `{column}` replaced. It's started on its own without a terminal, like a GUI editor is
+pub fn open_diagnostic(target: &str, dir: Option<&Path>, template: &[String]) -> io::Result<()> {
+    let Some((path, line, column)) = diagnostic(target) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "not a path with a line number",
+        ));
+    };
+    let Some((program, args)) = template.split_first() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "editor_command is empty",
+        ));
+    };
+
+    let path = resolve(path, dir);
+    let args = args.iter().map(|arg| {
+        arg.replace("{path}", &path.to_string_lossy())
+            .replace("{line}", &line.to_string())
+            .replace("{column}", &column.unwrap_or(1).to_string())
+    });
+    spawn(Command::new(program).args(args))
+}
+
+/// Starts `command` without blocking, a thread reaps it once it exits
+pub fn spawn(command: &mut Command) -> io::Result<()> {
+    let mut child = command.spawn()?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use tracing_subscriber::EnvFilter;
+
+/// Takes a filter in the `RUST_LOG` syntax, `debug` or `emu_core=trace` and the like
+pub const ENV: &str = "EMU_TERM_LOG";
+
+/// Warnings of the emulator itself. The windowing and GPU crates log plenty on their own, they
+/// only show when the filter names them
+const DEFAULT: &str = "emu_term=warn,emu_core=warn";
+
+/// Sends the diagnostics to standard error
+pub fn init() {
+    let filter = match std::env::var(ENV) {
+        Ok(filter) => EnvFilter::try_new(&filter).unwrap_or_else(|e| {
+            eprintln!("bad {} filter {:?}: {}", ENV, filter, e);
+            EnvFilter::new(DEFAULT)
+        }),
+        Err(_) => EnvFilter::new(DEFAULT),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+}
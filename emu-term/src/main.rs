@@ -1,33 +1,139 @@
-mod ansi;
+mod cli;
+mod config;
+mod daemon;
+mod debug;
+mod dump;
+mod error;
+mod highlight;
+mod hints;
+mod images;
+mod keys;
+mod layout;
+mod links;
+mod logging;
+mod notify;
+mod palette;
+mod process;
+mod ptylog;
+mod record;
+mod render;
+mod replay;
+mod restore;
+mod screenshot;
+mod search;
+mod selection;
+mod session;
+mod settings;
+mod shell;
+mod ssh;
+mod terminfo;
+mod tmux;
+mod writer;
 
-use ansi::{AnsiCode, AnsiParser};
-use ansi_colours;
-use async_std::io::{stdout, WriteExt};
-use futures::{SinkExt, StreamExt};
+use bytes::{Bytes, BytesMut};
+use cli::Args;
+use config::{
+    Action, ChildExit, Config, FontConfig, FontError, KeyBinding, Look, PaddingConfig, VisualBell,
+};
+use debug::{format_bytes, DebugStats};
+use emu_core::ansi::{self, AnsiCode, Unterminated};
+use emu_core::grid::{Cell, GridRow, ImageId, TermColor};
+use emu_core::iterm::{self, InlineImage};
+use emu_core::kitty::{self, Command, Delete};
+use emu_core::osc::{ClipboardKind, Progress};
+use emu_core::{ImageCursor, Output, Terminal as Emulator};
+use error::EmuError;
+use futures::SinkExt;
+use highlight::Highlights;
+use hints::{HintAction, HintInput, Hints};
 use iced::futures::Stream;
-use iced::widget::{button, column, container, text, Column};
+use iced::widget::canvas::{Cache, Canvas};
+use iced::widget::{container, image};
 use iced::{self, *};
+use images::{Image, Images};
 use keyboard::key::Named;
-use keyboard::{on_key_press, Key, Modifiers};
+use keyboard::{Key, Location, Modifiers};
+use keys::{MouseAction, MouseButton, MouseReport};
+use layout::{Axis, Change, Layout, Tab};
 use libc::winsize;
+use links::LinkKind;
 use mouse::ScrollDelta;
-use nix::pty::{forkpty, ForkptyResult};
-use nix::sys::termios::Termios;
-use std::fmt::Display;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use palette::{format_color_spec, parse_color_spec, Palette};
+use process::Foreground;
+use ptylog::PtyLog;
+use record::Recorder;
+use render::{CellMetrics, CursorView, HintLabel, LineDecorations, ScrollbarView, TermCanvas};
+use replay::{Player, Recording, Step};
+use search::Search;
+use selection::{Point, Selection, SelectionKind, Span};
+use session::{Connection, Session};
+use settings::{Settings, SettingsEvent, CURSOR_SHAPES};
+use shell::{ExitStatus, Shell};
+use ssh::SshTarget;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::File;
-use std::io::{Read, Write};
-use std::os::unix::process::CommandExt;
-use std::process::Command;
-use std::str::FromStr;
-use std::thread::sleep;
-use std::time::Duration;
+use std::os::fd::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
 use tokio::io::AsyncReadExt as _;
-use tokio::sync::mpsc::channel;
-use widget::container::{background, dark, Style};
-use widget::{row, scrollable, Row, Scrollable};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{channel, Sender};
+use widget::button;
+use widget::container::Style;
+use writer::{PtyWriter, WriteError};
+
+/// Logical size the window opens with, the grid dimensions are derived from it
+const WINDOW_SIZE: Size = Size::new(1024.0, 768.0);
+
+/// Output of a busy child is gathered for this long before it's applied, the screen can't be
+/// drawn any more often anyway
+const OUTPUT_FRAME: std::time::Duration = std::time::Duration::from_millis(16);
+/// Sent early past this much, so a fast child doesn't stall the parser for a whole frame
+const OUTPUT_MAX_BYTES: usize = 64 * 1024;
+/// Held back while the window is busy, see `send_output`
+const OUTPUT_BACKLOG: usize = 1 << 20;
+/// Messages on their way from a tab to the window. A few frames' worth, output any further
+/// behind is merged rather than queued
+const OUTPUT_QUEUE: usize = 4;
+/// Room for the first read from a pty. Doubled while reads fill it, up to `OUTPUT_MAX_BYTES`,
+/// and halved back once they come back small
+const READ_SIZE: usize = 4 * 1024;
+
+/// How long the child gets to exit after SIGHUP when the window is closed, before it's killed
+const CLOSE_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Height of the tab bar, which only shows once there's a second tab
+const TAB_BAR_HEIGHT: f32 = 28.0;
+
+/// Window title unless one is given with `--title`
+const DEFAULT_TITLE: &str = "A toy terminal emulator";
+
+const ZOOM_STEP: f32 = 1.0;
+const MIN_FONT_SIZE: f32 = 6.0;
+const MAX_FONT_SIZE: f32 = 72.0;
+
+const CURSOR_BLINK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+/// Lines scrolled back per notch of the mouse wheel
+const SCROLL_LINES: f32 = 3.0;
 
-const ROWS: u16 = 37;
-const COLS: u16 = 100;
+/// How often the foreground process of every tab is looked up
+const FOREGROUND_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How often the config file is checked for changes
+const CONFIG_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// Desktop notifications a tab raises closer together than this are dropped
+const NOTIFICATION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3);
+/// How long a notice stays up, like a config that failed to reload
+const NOTICE_DURATION: std::time::Duration = std::time::Duration::from_secs(5);
+/// Commands listed in the history overlay at most, the latest ones
+const COMMAND_HISTORY: usize = 50;
+
+/// SGR 5 asks for less than 150 blinks per minute
+const TEXT_BLINK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(800);
 
 const MONO: Font = Font {
     family: font::Family::Monospace,
@@ -45,16 +151,183 @@ pub enum Event {
 pub enum Content {
     Text(String),
     Bytes(Vec<u8>),
-    Key(Named),
-    Sigint,
+    /// Clipboard text encoded for the pty, which goes through however big it is
+    Clipboard(Vec<u8>),
+    /// A named key and the modifiers held, encoded against the current key modes
+    Key(Named, Modifiers),
+    /// Any key of the numeric keypad, which application keypad mode changes
+    Keypad(Key, Modifiers),
+    /// A character typed with Alt: the bytes to send after ESC when Alt is Meta, and the text
+    /// the layout composed for when it isn't (Option on macOS)
+    Meta(Vec<u8>, String),
+}
+
+/// A key going down, turned into a message once the key bindings have been looked at
+#[derive(Debug)]
+pub struct KeyPress {
+    /// With the layout and every modifier but Ctrl applied
+    key: Key,
+    /// With none of them
+    base_key: Key,
+    text: Option<String>,
+    mods: Modifiers,
+    location: Location,
 }
 
 #[derive(Debug)]
 pub enum Message {
-    Init(File),
+    /// What the canvas or the search bar of a pane emitted, see `Screen::pane_message`
+    Pane(TabId, Box<Message>),
+    /// The session is up and all input goes through the writer. Only a pty has a file kept
+    /// for resizing it and a child
+    Init(TabId, Option<File>, PtyWriter, Option<Pid>),
+    /// The socket a session connected to, see `Terminal::connection`
+    Connected(TabId, Connection),
+    /// The window's close button, the children are hung up on before exiting
+    CloseRequested,
+    /// Some child didn't exit in time after being hung up on
+    CloseTimedOut,
+    /// The close dialog was accepted
+    ConfirmClose,
+    CancelClose,
+    /// Puts the tabs saved on the last exit in place of the ones the window opened with
+    RestoreSession,
+    DismissRestore,
+    KeyPressed(KeyPress),
     Write(Content),
-    Output(Vec<Output>),
+    /// Parsed output and the bytes read to get it
+    Output(TabId, Vec<Output>, Bytes),
+    /// What tmux said in control mode on the tab, see `tmux::Control`
+    Tmux(TabId, Bytes),
+    /// Input typed in a tmux pane, for the tab tmux runs in to send it on
+    TmuxInput(TabId, u32, Vec<u8>),
+    /// Changes the layout of the active tab around its focused pane
+    Layout(Change),
+    /// Splits the focused pane of the active tab
+    Split(Axis),
+    /// Gives the keyboard to the pane this many places after the focused one, wrapping around
+    FocusPane(isize),
+    /// The child is gone, `None` when it couldn't be waited on
+    ChildExited(TabId, Option<ExitStatus>),
+    /// Something the user should hear about, shown as a notice
+    Failed(EmuError),
+    NewTab,
+    /// Runs a program in a new tab in this directory, the editor a link opens in
+    OpenInTab(Vec<String>, Option<PathBuf>),
+    /// Opens a tab with the profile at this index in the config
+    NewProfileTab(usize),
+    ToggleProfileMenu,
+    SelectTab(TabId),
+    /// The close button on a tab
+    CloseTab(TabId),
+    CloseActiveTab,
+    ClosePane(TabId),
+    CloseActivePane,
+    /// Starts the child of the active tab over on a new pty
+    RestartTab,
+    OpenQuickConnect,
+    QuickConnect(QuickConnectEvent),
+    /// Lists the commands the shell marked in the active tab
+    OpenCommands,
+    Commands(CommandsEvent),
+    OpenSettings,
+    Settings(SettingsEvent),
+    /// Clears the screen of the active tab and its scrollback, whatever the child thinks
+    ClearScreen,
+    ClearScrollback,
+    /// Opens the dialog writing the scrollback of the active tab to a file
+    SaveScrollback,
+    /// Same, for a command run in a new tab with the scrollback as its input
+    PipeScrollback,
+    /// Puts the selection of the active tab on the clipboard as HTML, or its screen when
+    /// nothing is selected
+    CopyHtml,
+    /// Same, to a file
+    SaveHtml,
+    /// Saves what the window shows as a PNG, once it's drawn without the overlays
+    TakeScreenshot,
+    /// A frame of the window was drawn
+    Drawn,
+    Screenshot(window::Screenshot),
+    ScreenshotSaved(PathBuf, std::io::Result<()>),
+    Dump(DumpEvent),
+    /// Opens the search of the active tab, or goes back to typing the query
+    OpenSearch,
+    Search(SearchEvent),
+    /// Activates the tab this many places to the right, wrapping around
+    SwitchTab(isize),
+    /// Moves the active tab this many places to the right, wrapping around
+    MoveTab(isize),
+    NewWindow,
+    /// Anything meant for the window `Id`, which handles it like a single window would
+    Window(window::Id, Box<Message>),
+    WindowClosed(window::Id),
     WindowResized(Size),
+    WindowFocused(bool),
+    /// The mouse came over the window
+    MouseEntered,
+    FontZoom(Zoom),
+    CursorBlink,
+    TextBlink,
+    PollForeground,
+    /// Reloads the config if the file changed
+    PollConfig,
+    /// Takes down the notice with this number, unless another one replaced it
+    DismissNotice(usize),
+    BellDone,
+    /// Puts the selection on the clipboard
+    Copy,
+    /// Selects the output of the last command that finished and puts it on the clipboard
+    CopyLastOutput,
+    /// Reads the clipboard and pastes it
+    Paste,
+    /// Same as `Paste` from the primary selection, for the middle button on Linux
+    PastePrimary,
+    Pasted(Option<String>),
+    ToggleDebug,
+    /// Frame clock, only running while the debug overlay is shown
+    Frame(std::time::Instant),
+    ToggleTransparency,
+    ToggleAlwaysOnTop,
+    ToggleFullscreen,
+    ToggleDecorations,
+    /// Labels every link on screen so it can be picked from the keyboard
+    ToggleHints,
+    ToggleReadOnly,
+    /// Starts recording the active tab to a new asciicast file, or stops it
+    ToggleRecording,
+    /// Screen line and column the left button went down on
+    SelectionStart((usize, usize), SelectionKind),
+    SelectionExtend((usize, usize)),
+    /// The left button was released, the selection is complete
+    SelectionEnd,
+    /// Ctrl+click on a screen line and column
+    OpenLink((usize, usize)),
+    /// Cell under the mouse, `None` once it leaves the window
+    Hover(Option<(usize, usize)>),
+    Scroll(ScrollDelta),
+    /// Mouse input the application asked to receive
+    Mouse(MouseReport),
+    /// Scrollbar drag, to this many lines back from the bottom
+    ScrollTo(usize),
+    /// Scrolls the prompt this many commands down from the top of the view to the top, up
+    /// when negative
+    JumpToPrompt(isize),
+}
+
+/// Where a screenshot of the window is at, the overlays are hidden until it's taken
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Capture {
+    /// Waiting for a frame without them
+    Drawing,
+    Taking,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum Zoom {
+    In,
+    Out,
+    Reset,
 }
 
 impl From<&str> for Content {
@@ -63,526 +336,4961 @@ impl From<&str> for Content {
     }
 }
 
-impl From<Named> for Content {
-    fn from(named: Named) -> Self {
-        Self::Key(named)
-    }
-}
-
 impl Message {
     fn write<C: Into<Content>>(c: C) -> Self {
         Self::Write(c.into())
     }
-
-    fn bytes<V: Into<Vec<u8>>>(v: V) -> Self {
-        Self::Write(Content::Bytes(v.into()))
-    }
-
-    fn named(named: Named) -> Self {
-        Self::Write(named.into())
-    }
 }
 
-#[derive(Debug)]
-pub enum Output {
-    Ansi(AnsiCode),
-    Bytes(Vec<u8>),
+fn is_word_char(c: char, word_chars: &str) -> bool {
+    c.is_alphanumeric() || word_chars.contains(c)
 }
 
-impl Display for Output {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Bytes(b) => write!(f, "{}", String::from_utf8_lossy(b)),
-            Self::Ansi(ac) => write!(f, "{:?}", ac),
-        }
-    }
+/// Room left for the grid in a window of `size` once the padding is taken out
+fn grid_area(config: &Config, size: Size) -> Size {
+    let PaddingConfig { x, y } = config.padding;
+    Size::new(
+        (size.width - 2.0 * x.max(0.0)).max(0.0),
+        (size.height - 2.0 * y.max(0.0)).max(0.0),
+    )
 }
 
-impl From<ansi::Output<'_>> for Output {
-    fn from(value: ansi::Output<'_>) -> Self {
-        match value {
-            ansi::Output::Bytes(b) => Self::Bytes(b.to_vec()),
-            ansi::Output::Escape(ac) => Self::Ansi(ac),
+/// Colors of the configured theme, the defaults when there's none or it can't be loaded
+fn load_theme(config: &Config) -> Palette {
+    let theme = match config.theme.as_deref().map(Palette::load) {
+        Some(Ok(theme)) => theme,
+        Some(Err(e)) => {
+            tracing::warn!("{}, using the default colors", e);
+            Palette::default()
         }
+        None => Palette::default(),
+    };
+
+    // checked when the config was loaded
+    match config.colors.clone() {
+        Some(colors) => theme.clone().with_colors(colors).unwrap_or(theme),
+        None => theme,
     }
 }
 
-#[derive(Default, Debug)]
-pub struct State {
-    grid: Grid,
-    brush: Brush,
-}
+/// Identifies a terminal, a tab or one of the panes it's split into, wherever it's moved to.
+/// The messages from its pty carry it
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TabId(usize);
 
+/// What the tab bar emits, `Message` can't be cloned the way buttons need it to
 #[derive(Debug, Copy, Clone)]
-pub enum TermColor {
-    Rgb(u8, u8, u8),
-    Ansi(u8),
+enum TabClick {
+    Select(TabId),
+    Close(TabId),
+    New,
+    ProfileMenu,
+    /// An entry of the profile menu, by index in the config
+    Profile(usize),
 }
 
-impl TermColor {
-    pub fn default_fg() -> Self {
-        Self::white()
-    }
-
-    pub fn default_bg() -> Self {
-        Self::dark()
-    }
+/// What the search overlay emits
+#[derive(Debug, Clone)]
+pub enum SearchEvent {
+    Input(String),
+    Regex(bool),
+    /// Enter in the field, `n` and `N` step through the matches from then on
+    Submit,
+}
 
-    pub fn white() -> Self {
-        Self::Rgb(255, 255, 255)
-    }
+/// What the quick-connect dialog emits
+#[derive(Debug, Clone)]
+pub enum QuickConnectEvent {
+    Input(String),
+    Submit,
+    /// One of the recent hosts
+    Pick(String),
+}
 
-    pub fn black() -> Self {
-        Self::Rgb(0, 0, 0)
-    }
+/// What the command history overlay emits, each command by the grid row of its prompt
+#[derive(Debug, Clone)]
+pub enum CommandsEvent {
+    /// Runs the command again
+    Run(String),
+    CopyOutput(usize),
+    /// Scrolls back to where it ran
+    Show(usize),
+}
 
-    pub fn dark() -> Self {
-        Self::Rgb(30, 30, 30)
-    }
+/// What the scrollback dialog emits
+#[derive(Debug, Clone)]
+pub enum DumpEvent {
+    Input(String),
+    Colors(bool),
+    Submit,
+}
 
-    pub fn red() -> Self {
-        Self::Rgb(255, 0, 0)
-    }
+/// What the dump dialog writes out, and where
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+enum DumpKind {
+    /// The scrollback to a file
+    #[default]
+    File,
+    /// The scrollback to a command
+    Command,
+    /// The selection or the screen to a file, as HTML
+    Html,
 }
 
-#[derive(Debug)]
-pub struct Brush {
-    fg_color: TermColor,
-    bg_color: TermColor,
-    pos: (usize, usize),
+/// The dialog asking where the scrollback or the HTML goes
+#[derive(Debug, Default)]
+struct Dump {
+    kind: DumpKind,
+    input: String,
+    /// Keep the colors of the scrollback as SGR sequences
+    colors: bool,
+    /// Why the last attempt failed
+    error: Option<String>,
 }
 
-impl Default for Brush {
-    fn default() -> Self {
-        Self {
-            pos: (1, 1),
-            bg_color: TermColor::default_bg(),
-            fg_color: TermColor::default_fg(),
-        }
-    }
+/// The quick-connect dialog, which starts `ssh` in a new tab
+#[derive(Debug, Default)]
+struct QuickConnect {
+    input: String,
+    recent: Vec<String>,
 }
 
-impl Brush {
-    pub fn reset_color(&mut self) {
-        self.fg_color = TermColor::default_fg();
-        self.bg_color = TermColor::default_bg();
-    }
+/// A command the shell ran, as listed in the history overlay
+#[derive(Debug, Clone)]
+struct CommandEntry {
+    /// Grid row of its prompt
+    prompt: usize,
+    command: String,
+    /// `None` while it's running, or when the shell didn't say
+    exit_status: Option<i32>,
+    finished: bool,
 }
 
-#[derive(Default, Debug)]
-pub struct Grid {
-    rows: Vec<GridRow>,
+/// Same for the buttons of the close dialog
+#[derive(Debug, Copy, Clone)]
+enum CloseChoice {
+    Close,
+    Cancel,
 }
 
-#[derive(Default, Debug)]
-pub struct GridRow {
-    cells: Vec<Cell>,
+#[derive(Debug, Copy, Clone)]
+enum RestoreChoice {
+    Restore,
+    Dismiss,
 }
 
+/// What runs in a tab: a child on its own pty and the screen it draws on
 #[derive(Debug)]
-pub struct Cell {
-    pub fg_color: TermColor,
-    pub bg_color: TermColor,
-    pub c: char,
+pub struct Terminal {
+    id: TabId,
+    /// Started once the subscription runs
+    session: Session,
+    /// The profile from the config the tab was opened with
+    profile: Option<String>,
+    /// Set by the application with OSC 0 or 2, shown on the tab and as the window title
+    title: String,
+    /// Reported by the shell with OSC 7
+    cwd: Option<PathBuf>,
+    /// Stored by the application with OSC 52, written to the clipboard once the output is in
+    clipboard: Option<(ClipboardKind, String)>,
+    /// Title and body of a notification asked for with OSC 9 or 777, shown once the output
+    /// is in
+    notification: Option<(String, String)>,
+    /// When the last one was, see `NOTIFICATION_INTERVAL`
+    notified: Option<std::time::Instant>,
+    /// Reported by the application with OSC 9;4, shown on the tab and in the window title
+    progress: Option<Progress>,
+    /// Sent with the kitty graphics protocol, kept until the application deletes them
+    images: Images,
+    /// Matches of the highlight rules in the config
+    highlights: Highlights,
+    /// tmux is attached in control mode, its windows are shown in tabs of their own and keys
+    /// typed in this one are dropped
+    tmux: Option<tmux::Control>,
+    /// Polled on a timer, and right before the window closes
+    foreground: Option<Foreground>,
+    /// The pty master, only used to resize it
+    handle: Option<File>,
+    writer: Option<PtyWriter>,
+    /// Process group of the child, until it exits
+    child: Option<Pid>,
+    /// Of a socket session, dropped along with the tab so the connection doesn't outlive it
+    connection: Option<Connection>,
+    /// Lines and columns tmux has the pane at, the grid sticks to them whatever room it gets
+    tmux_size: Option<(usize, usize)>,
+    /// Terminal emulation of the tab, the screen and the modes
+    state: Emulator,
+    curr_size: Size,
+    config: Config,
+    metrics: CellMetrics,
+    /// Colors in use, starts as `theme` and is changed by the application through OSC 4/10/11
+    palette: Palette,
+    /// Colors loaded from the configured theme, what the OSC resets go back to
+    theme: Palette,
+    /// One canvas layer per screen line, see `render::TermCanvas`
+    line_caches: Vec<Cache>,
+    /// View origin the caches were drawn against
+    cached_origin: usize,
+    /// Lines the view is scrolled back into the scrollback, 0 follows the screen
+    scroll_offset: usize,
+    /// What the wheel moved short of a whole line, touchpads scroll in small steps
+    scroll_fraction: f32,
+    /// Phase of the cursor blink, flipped by the blink subscription
+    blink_on: bool,
+    /// Phase of SGR 5 text, which blinks on its own slower timer
+    text_blink_on: bool,
+    /// Visual bell showing, cleared by the bell subscription once it times out
+    bell_on: bool,
+    focused: bool,
+    stats: DebugStats,
+    /// Keys, pastes and mouse reports are dropped instead of reaching the pty
+    read_only: bool,
+    /// Output and input are written out as asciicast while `Some`
+    recorder: Option<Recorder>,
+    selection: Option<Selection>,
+    /// URL under the mouse, underlined while hovered
+    hover: Option<Link>,
+    /// Keyboard hints mode, on while `Some`
+    hints: Option<Hints>,
+    search: Option<Search>,
+    /// The child exited and the tab is held open, the next key closes it
+    exited: bool,
+    /// Held open once the child exits whatever the config says, so the output of a command
+    /// the scrollback was piped into can still be read
+    hold: bool,
+    /// Printed something while in the background, marked on its tab until it's looked at
+    activity: bool,
+    /// Same for the bell
+    rang: bool,
 }
 
-impl Default for Cell {
-    fn default() -> Self {
-        Self::empty()
-    }
+/// A detected URL or path, grid row and inclusive columns
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Link {
+    kind: LinkKind,
+    row: usize,
+    left: usize,
+    right: usize,
 }
 
-impl Cell {
-    fn empty() -> Self {
-        Self {
-            c: ' ',
-            fg_color: TermColor::default_fg(),
-            bg_color: TermColor::default_bg(),
+impl Terminal {
+    /// Starts out with the default font, measured as `metrics`, so there's always something to
+    /// fall back to
+    pub fn new(
+        id: TabId,
+        mut config: Config,
+        theme: Palette,
+        metrics: CellMetrics,
+        session: Session,
+        title: String,
+    ) -> Self {
+        let font = std::mem::take(&mut config.font);
+        let (rows, cols) = metrics.grid_size(grid_area(&config, WINDOW_SIZE));
+
+        let mut screen = Self {
+            id,
+            profile: None,
+            handle: None,
+            writer: None,
+            child: None,
+            connection: None,
+            tmux_size: None,
+            state: Emulator::new(rows, cols),
+            curr_size: WINDOW_SIZE,
+            config,
+            metrics,
+            palette: theme.clone(),
+            theme,
+            line_caches: vec![],
+            cached_origin: 0,
+            scroll_offset: 0,
+            scroll_fraction: 0.0,
+            blink_on: true,
+            text_blink_on: true,
+            bell_on: false,
+            focused: true,
+            stats: DebugStats::default(),
+            read_only: false,
+            recorder: None,
+            selection: None,
+            hover: None,
+            hints: None,
+            search: None,
+            exited: false,
+            hold: false,
+            activity: false,
+            clipboard: None,
+            notification: None,
+            notified: None,
+            progress: None,
+            images: Images::default(),
+            highlights: Highlights::default(),
+            tmux: None,
+            rang: false,
+            session,
+            title,
+            cwd: None,
+            foreground: None,
+        };
+        screen.state.set_default_cursor_style(screen.config.cursor);
+
+        if let Err(e) = screen.set_font(font) {
+            tracing::warn!("{}, falling back to the default font", e);
         }
+
+        screen
     }
-}
 
-impl Grid {
-    pub fn erase_line(&mut self, brush: &Brush) {
-        let row = self.get_or_insert(brush.pos.1);
-        let x = brush.pos.0 - 1;
+    /// Switches to a different font at runtime. The font is rejected as a whole if any of its
+    /// settings is invalid, in which case the current one stays in place
+    pub fn set_font(&mut self, font: FontConfig) -> std::result::Result<(), FontError> {
+        self.apply_font(&font)?;
+        self.config.font = font;
+        Ok(())
+    }
 
-        while row.cells.len() > x {
-            row.cells.pop();
-        }
+    fn apply_font(&mut self, font: &FontConfig) -> std::result::Result<(), FontError> {
+        self.metrics = CellMetrics::measure(font)?;
+        self.resize(self.curr_size);
+        self.invalidate_all_lines();
+        Ok(())
     }
 
-    pub fn paint(&mut self, brush: &Brush, char: char) {
-        let Brush {
-            pos: (x, y),
-            bg_color,
-            fg_color,
-        } = brush;
+    /// Changes the font size without touching the configured font, so `Zoom::Reset` can go
+    /// back to it
+    fn zoom(&mut self, zoom: Zoom) {
+        let size = match zoom {
+            Zoom::In => self.metrics.font_size + ZOOM_STEP,
+            Zoom::Out => self.metrics.font_size - ZOOM_STEP,
+            Zoom::Reset => self.config.font.size,
+        };
+        self.set_font_size(size);
+    }
 
-        let cell = self.get_or_insert(*y).get_or_insert(*x);
-        cell.fg_color = *fg_color;
-        cell.bg_color = *bg_color;
-        cell.c = char;
+    fn set_font_size(&mut self, size: f32) {
+        let font = FontConfig {
+            size: size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE),
+            ..self.config.font.clone()
+        };
+        if let Err(e) = self.apply_font(&font) {
+            tracing::warn!("failed to zoom: {}", e);
+        }
     }
 
-    fn get_or_insert(&mut self, y: usize) -> &mut GridRow {
-        let y = y - 1;
-        while y >= self.rows.len() {
-            self.rows.push(GridRow::default());
+    /// `focus` is for the pane of its tab that has the keyboard, the others are dimmed like
+    /// an unfocused window. `overlays` puts the badges and the search bar over the grid
+    pub fn view(&self, transparent: bool, focus: bool, overlays: bool) -> Element<'_, Message> {
+        let lines = self.state.window(self.view_origin(), self.state.rows());
+        let canvas = Canvas::new(TermCanvas {
+            lines,
+            cols: self.state.cols(),
+            caches: &self.line_caches,
+            metrics: self.metrics,
+            palette: &self.palette,
+            decorations: self.decorations(),
+            images: &self.state.grid().images,
+            styles: self.state.grid().styles(),
+            origin: self.view_origin(),
+            reverse_video: self.reverse_video(),
+            blink_on: self.text_blink_on,
+            cursor: self.cursor(),
+            scrollbar: self.scrollbar(),
+            hints: self.hint_labels(),
+            flash: self.bell_on && self.config.visual_bell == VisualBell::Flash,
+            mouse_tracking: self.state.mouse_modes().tracking,
+            dim: if self.focused && focus {
+                0.0
+            } else {
+                self.config.unfocused_dim.clamp(0.0, 1.0)
+            },
+        })
+        .width(self.state.cols() as f32 * self.metrics.width)
+        .height(self.state.rows() as f32 * self.metrics.height);
+
+        let mut bg_color = self.palette.resolve(if self.reverse_video() {
+            TermColor::default_fg()
+        } else {
+            TermColor::default_bg()
+        });
+        if transparent {
+            bg_color.a = self.config.background_opacity.clamp(0.0, 1.0);
         }
+        let style = Style::default().background(Background::Color(bg_color));
+        let PaddingConfig { x, y } = self.config.padding;
+        let content = container(canvas).padding([y.max(0.0), x.max(0.0)]);
+        let content = if self.config.center {
+            content.center(Length::Fill)
+        } else {
+            content.width(Length::Fill).height(Length::Fill)
+        };
 
-        &mut self.rows[y]
+        let mut layers = vec![content.style(move |_| style).into()];
+        if overlays && (self.read_only || self.recorder.is_some()) {
+            layers.push(self.badges());
+        }
+        if let Some(search) = self.search.as_ref().filter(|_| overlays) {
+            layers.push(search_bar(search));
+        }
+        widget::Stack::with_children(layers).into()
     }
 
-    fn erase_display_from(&mut self, brush: &Brush) {
-        let (x, y) = brush.pos;
-        for i in 0..ROWS as usize {
-            let row = self.get_or_insert(y + i);
-            for cell in row.cells.iter_mut() {
-                cell.c = ' ';
-                cell.fg_color = TermColor::default_fg();
-                cell.bg_color = TermColor::default_bg();
-            }
-        }
+    /// What's on in the bottom right corner, the recording and read-only mode
+    fn badges(&self) -> Element<'_, Message> {
+        let style = Style::default()
+            .background(Color {
+                a: 0.7,
+                ..Color::BLACK
+            })
+            .color(Color::WHITE);
+        let labels = [
+            (self.recorder.is_some(), "REC"),
+            (self.read_only, "READ ONLY"),
+        ];
+        let badges = labels.into_iter().filter(|(on, _)| *on).map(|(_, label)| {
+            container(widget::text(label).font(MONO).size(12))
+                .padding(6)
+                .style(move |_| style)
+                .into()
+        });
+
+        container(widget::Row::with_children(badges).spacing(4))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .align_x(alignment::Horizontal::Right)
+            .align_y(alignment::Vertical::Bottom)
+            .into()
     }
 
-    fn erase_display_preserve_cursor(&mut self, brush: &Brush) {}
-}
+    fn debug_overlay(&self) -> Element<'_, Message> {
+        let (stats, state) = (&self.stats, &self.state);
+        let modes: Vec<&str> = [
+            (state.is_alt_screen(), "alt-screen"),
+            (state.reverse_video(), "reverse-video"),
+            (state.lr_margins_enabled(), "lr-margins"),
+            (state.cursor_visible(), "cursor"),
+            (state.cursor_style().blinking, "cursor-blink"),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect();
 
-impl GridRow {
-    fn get_or_insert(&mut self, x: usize) -> &mut Cell {
-        let x = x - 1;
-        while x >= self.cells.len() {
-            self.cells.push(Cell::default());
+        let lines = [
+            format!("{} fps", stats.fps()),
+            format!("{} cells redrawn", stats.cells_last_frame()),
+            format!("{}/s read", format_bytes(stats.throughput())),
+            format!("{} unknown sequences", stats.unknown_sequences()),
+            format!(
+                "{}x{}, {} lines back",
+                state.cols(),
+                state.rows(),
+                self.scroll_offset
+            ),
+            format!("modes: {}", modes.join(" ")),
+        ];
+
+        let style = Style::default()
+            .background(Color {
+                a: 0.7,
+                ..Color::BLACK
+            })
+            .color(Color::WHITE);
+        let panel = container(widget::text(lines.join("\n")).font(MONO).size(12))
+            .padding(6)
+            .style(move |_| style);
+
+        container(panel)
+            .width(Length::Fill)
+            .align_x(alignment::Horizontal::Right)
+            .into()
+    }
+
+    fn cursor(&self) -> Option<CursorView> {
+        let (cursor_visible, cursor_style) =
+            (self.state.cursor_visible(), self.state.cursor_style());
+        if !cursor_visible || (cursor_style.blinking && !self.blink_on) {
+            return None;
+        }
+
+        let line = self.state.cursor().1 + self.scroll_offset;
+        if line > self.state.rows() {
+            // scrolled out of view
+            return None;
+        }
+
+        Some(CursorView {
+            line: line.clamp(1, self.state.rows()),
+            // the cursor sits past the last column while a wrap is pending
+            col: self.state.cursor().0.clamp(1, self.state.cols()),
+            shape: cursor_style.shape,
+        })
+    }
+
+    fn scrollbar(&self) -> Option<ScrollbarView> {
+        let history = self.state.screen_origin();
+        if !self.config.scrollbar || self.state.is_alt_screen() || history == 0 {
+            return None;
         }
 
-        &mut self.cells[x]
+        Some(ScrollbarView {
+            offset: self.scroll_offset,
+            history,
+            rows: self.state.rows(),
+        })
+    }
+
+    /// Number of grid rows sitting above the first line on view
+    fn view_origin(&self) -> usize {
+        self.state.screen_origin() - self.scroll_offset
     }
-}
 
-impl State {
-    fn window(&self, height: usize) -> &[GridRow] {
-        let l = self.grid.rows.len();
-        if height > l {
-            &self.grid.rows[..]
+    fn scroll_to(&mut self, offset: usize) {
+        let offset = if self.state.is_alt_screen() {
+            0
         } else {
-            &self.grid.rows[l - height..]
+            offset.min(self.state.screen_origin())
+        };
+        if offset == self.scroll_offset {
+            return;
         }
+
+        self.scroll_offset = offset;
+        self.cached_origin = self.view_origin();
+        self.invalidate_all_lines();
     }
 
-    fn text(&self) -> String {
-        let mut text = String::new();
+    /// Mouse wheel, scrolling up goes back into the scrollback
+    fn scroll(&mut self, delta: ScrollDelta) {
+        let lines = self.scroll_fraction
+            + match delta {
+                ScrollDelta::Lines { y, .. } => y * SCROLL_LINES,
+                ScrollDelta::Pixels { y, .. } => y / self.metrics.height,
+            };
+        let whole = lines.trunc();
+        self.scroll_fraction = lines - whole;
 
-        for row in self.grid.rows.iter() {
-            for cell in row.cells.iter() {
-                text.push(cell.c);
+        // the alternate screen has no scrollback, the wheel goes to the application instead as
+        // a cursor key per line, like a pager or an editor would have it. It's input, so not
+        // while input isn't forwarded, the same as typing
+        if self.state.is_alt_screen() {
+            if self.read_only || self.tmux.is_some() {
+                return;
             }
-            text.push('\n');
+            let arrow = if whole > 0.0 {
+                Named::ArrowUp
+            } else {
+                Named::ArrowDown
+            };
+            let key = keys::encode_named(arrow, Modifiers::empty(), self.state.key_modes());
+            if let Some(key) = key.filter(|_| whole != 0.0) {
+                self.reply(&key.repeat(whole.abs() as usize));
+            }
+            return;
         }
 
-        text
+        let offset = self.scroll_offset as f32 + whole;
+        self.scroll_to(offset.max(0.0) as usize);
     }
-}
 
-impl From<&Cell> for Element<'_, Message> {
-    fn from(cell: &Cell) -> Self {
-        let bg_color = Color::from(&cell.bg_color);
-        let fg_color = Color::from(&cell.fg_color);
-        container(text(cell.c.to_string()).font(MONO).color(fg_color))
-            .style(move |_| background(Background::Color(bg_color)))
-            .into()
+    /// Selection, hover, search and highlight ranges of every screen line
+    fn decorations(&self) -> Vec<LineDecorations> {
+        let origin = self.view_origin();
+        let cols = self.state.cols();
+        let matches = self
+            .search
+            .as_ref()
+            .map_or(&[][..], |search| &search.matches);
+        let current = self.search.as_ref().and_then(Search::current);
+        (1..=self.state.rows())
+            .map(|line| {
+                let row = origin + line;
+                LineDecorations {
+                    found: matches
+                        .iter()
+                        .filter_map(|found| found.columns(row, cols))
+                        .collect(),
+                    current: current.and_then(|found| found.columns(row, cols)),
+                    selected: self
+                        .selection
+                        .and_then(|selection| selection.columns(row, self.state.cols())),
+                    underlined: self
+                        .hover
+                        .filter(|link| link.row == row)
+                        .map(|link| (link.left, link.right)),
+                    highlighted: match self.state.is_alt_screen() {
+                        true => vec![],
+                        false => self
+                            .highlights
+                            .row(row)
+                            .iter()
+                            .filter_map(|h| {
+                                let rule = self.config.highlights.get(h.rule)?;
+                                Some((h.left, h.right, rule.style))
+                            })
+                            .collect(),
+                    },
+                }
+            })
+            .collect()
     }
-}
 
-impl From<&TermColor> for Color {
-    fn from(tc: &TermColor) -> Self {
-        match *tc {
-            TermColor::Rgb(r, g, b) => Color {
-                r: r as f32 / 255.0,
-                g: g as f32 / 255.0,
-                b: b as f32 / 255.0,
-                a: 1.0,
-            },
-            TermColor::Ansi(_) => todo!(),
-        }
+    fn open_search(&mut self) -> Task<Message> {
+        self.search.get_or_insert_with(Search::new).editing = true;
+        widget::text_input::focus(search_input())
     }
-}
 
-#[derive(Default, Debug)]
-pub struct Screen {
-    handle: Option<File>,
-    contents: Vec<String>,
-    state: State,
-    curr_size: Size,
-}
+    /// Runs the search as the query is typed, from the bottom of the view up
+    fn search(&mut self, event: SearchEvent) {
+        let bottom = self.view_origin() + self.state.rows();
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
 
-impl Screen {
-    pub fn new() -> Self {
-        Self {
-            ..Default::default()
+        match event {
+            SearchEvent::Input(query) => search.query = query,
+            SearchEvent::Regex(regex) => search.regex = regex,
+            SearchEvent::Submit => {
+                search.editing = false;
+                return;
+            }
         }
+        search.run(self.state.grid(), bottom);
+        self.reveal_match();
+        self.invalidate_all_lines();
     }
 
-    pub fn view(&self) -> Element<'_, Message> {
-        let window = self.state.window(ROWS as usize);
+    /// Once the query is in, `n` goes to an older match and `N` to a newer one, and `/` goes
+    /// back to typing. Escape closes the search either way
+    fn search_input(&mut self, content: Content) -> Task<Message> {
+        let Some(search) = self.search.as_mut() else {
+            return Task::none();
+        };
 
-        let mut lines: Vec<Element<'_, Message>> = vec![];
-        for line in window.iter() {
-            let mut column: Vec<Element<'_, Message>> = vec![];
-            for cell in line.cells.iter() {
-                column.push(Element::from(cell));
-            }
-            let col: Element<'_, Message> = Row::with_children(column).into();
-            lines.push(col);
+        match content {
+            Content::Key(Named::Escape, _) => self.search = None,
+            Content::Text(text) if !search.editing => match text.as_str() {
+                "n" | "N" => {
+                    search.step(text == "n");
+                    self.reveal_match();
+                }
+                "/" => return self.open_search(),
+                _ => return Task::none(),
+            },
+            _ => return Task::none(),
         }
-
-        let rows = Column::with_children(lines);
-        let bg_color = Color::from(&TermColor::dark());
-        let style = Style::default().background(Background::Color(bg_color));
-        container(rows)
-            .height(1024)
-            .width(2048)
-            .style(move |_| style)
-            .into()
+        self.invalidate_all_lines();
+        Task::none()
     }
 
-    pub fn update(&mut self, message: Message) {
-        match message {
-            Message::Init(handle) => self.handle = Some(handle),
-            Message::Output(s) => self.handle_output(s),
-            Message::Write(c) => {
-                let Some(handle) = self.handle.as_mut() else {
-                    return;
-                };
-
-                match c {
-                    Content::Text(s) => handle.write_all(s.as_bytes()).unwrap(),
-                    Content::Bytes(b) => handle.write_all(b.as_slice()).unwrap(),
-                    Content::Sigint => handle.write_all(b"\x03").unwrap(),
-                    Content::Key(named) => match named {
-                        Named::Enter => handle.write_all(b"\n").unwrap(),
-                        Named::Space => handle.write_all(b" ").unwrap(),
-                        Named::Backspace => handle.write_all(b"\x7F").unwrap(),
-                        Named::Escape => handle.write_all(b"\x1b").unwrap(),
-                        _named => {}
-                    },
-                };
-            }
-            Message::WindowResized(size) => {
-                self.curr_size = size;
-            }
+    /// Keys of a `--stdin` tab move through the input like in `less`, there's nothing to send
+    /// them to
+    fn page_input(&mut self, content: Content) -> Task<Message> {
+        let page = self.state.rows() as isize - 1;
+        let lines = match content {
+            Content::Text(text) => match text.as_str() {
+                "q" => return Task::done(Message::ClosePane(self.id)),
+                "/" => return self.open_search(),
+                "j" => -1,
+                "k" => 1,
+                " " | "f" => -page,
+                "b" => page,
+                "d" => -page / 2,
+                "u" => page / 2,
+                "g" => isize::MAX,
+                "G" => isize::MIN,
+                _ => return Task::none(),
+            },
+            Content::Key(named, _) => match named {
+                Named::ArrowDown | Named::Enter => -1,
+                Named::ArrowUp => 1,
+                Named::Space | Named::PageDown => -page,
+                Named::PageUp => page,
+                Named::Home => isize::MAX,
+                Named::End => isize::MIN,
+                _ => return Task::none(),
+            },
+            _ => return Task::none(),
         };
+        self.scroll_to(self.scroll_offset.saturating_add_signed(lines));
+        Task::none()
     }
 
-    pub fn handle_bytes(&mut self, bytes: Vec<u8>) {
-        match bytes.as_slice() {
-            b"\x07" => { // according to chatgpt this is when there is nothing else to backspace
-                 // to, some terminals emit a sound (idk)
-            }
-            b"\x08" => { // according to chatgpt this is to move the cursor to the left after a
-                 // backspace??? not sure about that
-            }
-            b"\x08\x1b\x5b\x4b" => {
-                // backspace
-                let _ = self.contents.last_mut().and_then(|l| l.pop());
-            }
-            _ => {
-                let Ok(parsed) = String::from_utf8(bytes) else {
-                    eprintln!("failed to parse");
-                    return;
-                };
+    /// Scrolls the current match to the middle of the view, unless it's already in it
+    fn reveal_match(&mut self) {
+        let Some(row) = self
+            .search
+            .as_ref()
+            .and_then(Search::current)
+            .map(|m| m.start.row)
+        else {
+            return;
+        };
+        let origin = self.view_origin();
+        if (origin + 1..=origin + self.state.rows()).contains(&row) {
+            return;
+        }
 
-                for char in parsed.chars() {
-                    match char {
-                        '\n' => {
-                            self.state.brush.pos.1 += 1;
-                        }
-                        '\r' => {
-                            self.state.brush.pos.0 = 1;
-                        }
-                        '\t' => {
-                            self.state.brush.pos.0 += 4;
-                        }
-                        '\u{1b}' => {}
-                        '\u{8}' => {
-                            self.state.brush.pos.0 -= 1;
-                        }
-                        _ => {
-                            self.state.grid.paint(&self.state.brush, char);
-                            self.state.brush.pos.0 += 1;
-                        }
-                    }
-                }
-            }
+        let top = row.saturating_sub(self.state.rows() / 2 + 1);
+        self.scroll_to(self.state.screen_origin().saturating_sub(top));
+    }
+
+    /// Goes `step` prompts down from the top of the view, or up when negative. Past the last
+    /// prompt the view goes back to the bottom. Shells mark their prompts with OSC 133
+    fn jump_to_prompt(&mut self, step: isize) {
+        let top = self.view_origin() + 1;
+        let prompts = self
+            .state
+            .scrollback()
+            .commands
+            .iter()
+            .map(|mark| mark.prompt);
+        let n = step.unsigned_abs().saturating_sub(1);
+        let row = match step < 0 {
+            true => prompts.rev().filter(|&row| row < top).nth(n),
+            false => prompts.filter(|&row| row > top).nth(n),
         };
+        match row {
+            Some(row) => self.reveal_prompt(row),
+            None if step > 0 => self.scroll_to(0),
+            None => {}
+        }
     }
 
-    pub fn handle_ansi(&mut self, ac: AnsiCode) {
-        use AnsiCode::*;
+    /// Scrolls grid row `row` up to the top of the view, or as far as it goes
+    fn reveal_prompt(&mut self, row: usize) {
+        self.scroll_to(self.state.screen_origin().saturating_sub(row - 1));
+    }
 
-        match ac {
-            EraseLine => {
-                self.state.grid.erase_line(&self.state.brush);
-            }
-            EraseDisplay => {
-                // deletes all text from the cursor position to the end of the screen
+    /// The latest commands with a command line and output marked, the last one first
+    fn commands(&self) -> Vec<CommandEntry> {
+        self.state
+            .scrollback()
+            .commands
+            .iter()
+            .rev()
+            .filter_map(|mark| {
+                let command = self.marked_text(mark.input?, mark.output?);
+                Some(CommandEntry {
+                    prompt: mark.prompt,
+                    command: command.trim().to_owned(),
+                    exit_status: mark.exit_status,
+                    finished: mark.end.is_some(),
+                })
+            })
+            .filter(|entry| !entry.command.is_empty())
+            .take(COMMAND_HISTORY)
+            .collect()
+    }
 
-                //self.state.grid.erase_display_from(&self.state.brush);
-            }
-            EraseAllDisplay => {
-                // deletes all text in the screen and preserves cursor position
+    /// What the command whose prompt is on `prompt` printed, up to the next prompt while it's
+    /// running
+    fn command_output(&self, prompt: usize) -> Option<String> {
+        let grid = self.state.scrollback();
+        let commands = &grid.commands;
+        let i = commands.iter().position(|mark| mark.prompt == prompt)?;
+        let mark = commands[i];
+        let end = mark
+            .end
+            .or_else(|| Some((commands.get(i + 1)?.prompt, 1)))
+            .unwrap_or((grid.len() + 1, 1));
+        Some(self.marked_text(mark.output?, end))
+    }
 
-                self.state.grid.erase_display_from(&self.state.brush);
-            }
-            CursorSave => {}
-            SetGraphicsMode(1, [0, _, _, _, _]) => {
-                self.state.brush.reset_color();
-            }
-            SetGraphicsMode(1, [39, _, _, _, _]) => {
-                self.state.brush.fg_color = TermColor::default_fg();
-            }
+    /// The text on the primary grid from one mark up to another, the cell of the second left
+    /// out
+    fn marked_text(&self, from: (usize, usize), to: (usize, usize)) -> String {
+        match self.marked(from, to) {
+            Some(selection) => selection.text(self.state.scrollback(), self.state.cols()),
+            None => String::new(),
+        }
+    }
 
-            SetGraphicsMode(1, [49, _, _, _, _]) => {
-                self.state.brush.bg_color = TermColor::default_bg();
-            }
-            SetGraphicsMode(3, [38, 5, id, _, _]) => {
-                let (r, g, b) = ansi_colours::rgb_from_ansi256(id);
-                self.state.brush.fg_color = TermColor::Rgb(r, g, b);
-            }
-            SetGraphicsMode(3, [48, 5, id, _, _]) => {
-                let (r, g, b) = ansi_colours::rgb_from_ansi256(id);
-                self.state.brush.bg_color = TermColor::Rgb(r, g, b);
-            }
-            SetGraphicsMode(5, [38, 2, r, g, b]) => {
-                self.state.brush.fg_color = TermColor::Rgb(r, g, b);
-            }
-            SetGraphicsMode(5, [48, 2, r, g, b]) => {
-                self.state.brush.bg_color = TermColor::Rgb(r, g, b);
-            }
-            _ => {}
+    /// The cells from one mark up to another as a selection, `None` when there are none. Marks
+    /// are where the cursor was, which may be past the last column
+    fn marked(&self, from: (usize, usize), to: (usize, usize)) -> Option<Selection> {
+        let cols = self.state.cols();
+        let start = Point::new(from.0, from.1.min(cols));
+        let end = match to {
+            (row, col) if col > 1 => Point::new(row, (col - 1).min(cols)),
+            (row, _) => Point::new(row.saturating_sub(1), cols),
+        };
+        let end = end.min(Point::new(self.state.scrollback().len(), cols));
+        if end < start || start.row == 0 {
+            return None;
         }
+        // a span of its own, like a word is, so a single cell isn't taken for a click
+        Some(Selection::new(SelectionKind::Word, (start, end)))
     }
 
-    pub fn handle_output(&mut self, outputs: Vec<Output>) {
-        for op in outputs.iter() {
-            print!("{}, ", op);
+    /// Selects what the last command that finished printed and copies it
+    fn copy_last_output(&mut self) -> Task<Message> {
+        // the selection would be on the alternate screen, the marks aren't
+        if self.state.is_alt_screen() {
+            return Task::none();
         }
-        for output in outputs {
-            match output {
-                Output::Bytes(b) => self.handle_bytes(b),
-                Output::Ansi(ac) => self.handle_ansi(ac),
-            }
+        let last = self
+            .state
+            .scrollback()
+            .commands
+            .iter()
+            .rev()
+            .find_map(|mark| {
+                let output = mark.output?;
+                self.marked(output, mark.end?)
+            });
+        let Some(selection) = last else {
+            return Task::none();
+        };
+
+        if let Some((start, end)) = self.selection.take().map(|s| s.bounds()) {
+            self.invalidate_rows(start.row..=end.row);
         }
+        let (start, end) = selection.bounds();
+        self.selection = Some(selection);
+        self.invalidate_rows(start.row..=end.row);
+        self.copy()
     }
-}
 
-fn handle_key(key: Key, mods: Modifiers) -> Option<Message> {
-    use iced::keyboard::Key as IKey;
-    use Content::*;
-    use Message::*;
+    /// Whether any line on screen has blinking text, the blink timer only runs while it does
+    fn has_blinking_text(&self) -> bool {
+        self.state
+            .window(self.view_origin(), self.state.rows())
+            .iter()
+            .any(|line| self.has_blink(line))
+    }
 
-    match key {
-        IKey::Character(c) if mods.control() && c.as_str() == "c" => Some(Write(Sigint)),
-        IKey::Character(c) if mods.shift() && c.as_str() == "7" => Some(Message::write("&")),
-        IKey::Character(c) if mods.shift() && c.as_str() == "\\" => Some(Message::write("|")),
-        IKey::Character(c) if mods.shift() && c.as_str() == "-" => Some(Message::write("_")),
-        IKey::Character(c) if mods.shift() && c.as_str() == ";" => Some(Message::write(":")),
-        IKey::Character(c) if mods.shift() && c.as_str() == "1" => Some(Message::write("!")),
-        IKey::Character(c) => Some(Write(Text(c.to_string()))),
-        IKey::Named(named) => Some(Message::named(named)),
-        _ => None,
+    fn has_blink(&self, line: &GridRow) -> bool {
+        let grid = self.state.grid();
+        line.cells.iter().any(|cell| grid.style(cell).attrs.blink)
     }
-}
 
-fn start_slave_process() {
-    let _ = Command::new("/bin/zsh").exec();
-    std::process::exit(0)
-}
+    fn blink_text(&mut self) {
+        self.text_blink_on = !self.text_blink_on;
+
+        let origin = self.view_origin();
+        let blinking: Vec<usize> = (origin + 1..)
+            .zip(self.state.window(origin, self.state.rows()))
+            .filter(|(_, line)| self.has_blink(line))
+            .map(|(y, _)| y)
+            .collect();
+        self.invalidate_rows(blinking);
+    }
+
+    /// Rows and columns an image of `width` by `height` pixels covers
+    fn image_cells(&self, width: u32, height: u32) -> (usize, usize) {
+        let cols = (width as f32 / self.metrics.width).ceil().max(1.0) as usize;
+        let rows = (height as f32 / self.metrics.height).ceil().max(1.0) as usize;
+        (rows, cols)
+    }
 
-fn pcomms() -> impl Stream<Item = Message> {
-    stream::channel(100, |mut output| async move {
-        let winsize = winsize {
-            ws_row: 50,
-            ws_col: 100,
-            ws_xpixel: 1024,
-            ws_ypixel: 2048,
+    /// Applies a kitty graphics command once all of its chunks are in, and answers it unless
+    /// it asked for quiet
+    fn kitty(&mut self, apc: &[u8]) {
+        let Some(command) = kitty::parse(apc) else {
+            return;
+        };
+        let Some(command) = self.images.receive(command) else {
+            return;
         };
 
-        let result = unsafe { forkpty(&winsize, None).unwrap() };
+        let result = match command.action {
+            kitty::Action::Query => images::load(&command).map(|_| command.id),
+            kitty::Action::Transmit | kitty::Action::TransmitAndPut => match images::load(&command)
+            {
+                Ok(image) => {
+                    let id = self.images.store(&command, image.clone()).unwrap_or(0);
+                    if command.action == kitty::Action::TransmitAndPut {
+                        self.put_image(&command, &image, id);
+                    }
+                    Ok(id)
+                }
+                Err(e) => Err(e),
+            },
+            kitty::Action::Put => match self.images.get(command.id, command.number) {
+                Some((id, image)) => {
+                    let image = image.clone();
+                    self.put_image(&command, &image, id);
+                    Ok(id)
+                }
+                None => Err("ENOENT:no such image".to_owned()),
+            },
+            kitty::Action::Delete => {
+                self.delete_images(&command);
+                return;
+            }
+        };
 
-        let master = match result {
-            ForkptyResult::Parent { master, .. } => master,
-            ForkptyResult::Child => {
-                start_slave_process();
-                std::process::exit(0);
+        let (id, message) = match result {
+            Ok(id) => (id, "OK".to_owned()),
+            Err(e) => {
+                tracing::debug!(id = command.id, "kitty image refused: {}", e);
+                (command.id, e)
             }
         };
+        if let Some(reply) = kitty::reply(&command, id, &message) {
+            self.reply(&reply);
+        }
+    }
 
-        let (tx, mut rx) = channel::<Vec<Output>>(100);
-        let whandle: File = master.into();
-        let mut rhandle = tokio::fs::File::from(whandle.try_clone().unwrap());
+    /// Puts `image` at the cursor, over the cells the command asks for or as many as its size
+    /// needs
+    fn put_image(&mut self, command: &Command, image: &Image, id: u32) {
+        let (rows, cols) = self.image_cells(image.width, image.height);
+        let rows = match command.rows {
+            0 => rows,
+            r => (r as usize).min(images::MAX_SIDE as usize),
+        };
+        let cols = match command.cols {
+            0 => cols,
+            c => (c as usize).min(self.state.cols()),
+        };
+        let cursor = match command.keep_cursor {
+            true => ImageCursor::Stay,
+            false => ImageCursor::After,
+        };
+        let id = ImageId {
+            image: id,
+            placement: command.placement,
+        };
+        let handle = Arc::new(image.handle.clone());
+        self.state
+            .place_image(handle, (rows, cols), Some(id), cursor);
+    }
 
-        output.send(Message::Init(whandle)).await.unwrap();
-        async_std::task::spawn(async move {
-            let mut buf = [0u8; 1024];
-            loop {
-                let n = rhandle.read(&mut buf).await.unwrap();
-                let items = AnsiParser::new(&buf[..n])
-                    .map(Output::from)
-                    .collect::<Vec<Output>>();
+    /// Takes the placements a delete command picks off the grid, and with an uppercase `d` the
+    /// images they show. Sixel images have no id and are left alone
+    fn delete_images(&mut self, command: &Command) {
+        let Some((delete, free)) = command.delete else {
+            return;
+        };
 
-                tx.send(items).await.unwrap();
+        let origin = self.state.screen_origin();
+        let (col, line) = self.state.cursor();
+        let cell = match delete {
+            Delete::Cell => (origin + command.y as usize, command.x as usize),
+            _ => (origin + line, col),
+        };
+        let id = self.images.id(command.id, command.number);
+        let removed = self.state.remove_images(|image| {
+            let Some(image_id) = image.id else {
+                return false;
+            };
+            match delete {
+                Delete::All => image.row + image.rows > origin,
+                Delete::Id | Delete::Number => {
+                    Some(image_id.image) == id
+                        && (command.placement == 0 || image_id.placement == command.placement)
+                }
+                Delete::Cursor | Delete::Cell => {
+                    (image.row..image.row + image.rows).contains(&cell.0)
+                        && (image.col..image.col + image.cols).contains(&cell.1)
+                }
             }
         });
 
-        loop {
-            if let Some(msg) = rx.recv().await {
-                output.send(Message::Output(msg)).await.unwrap();
-                output.flush().await.unwrap();
-            }
+        if !free {
+            return;
         }
-    })
-}
+        let ids = removed
+            .iter()
+            .filter_map(|image| image.id.map(|id| id.image));
+        for id in ids.chain(id.filter(|_| matches!(delete, Delete::Id | Delete::Number))) {
+            self.images.remove(id);
+        }
+    }
 
-fn subscription(_s: &Screen) -> Subscription<Message> {
-    use event::Event as AppEvent;
+    /// DECSCNM, flipped while an inverting visual bell is up
+    fn reverse_video(&self) -> bool {
+        let inverted = self.bell_on && self.config.visual_bell == VisualBell::Invert;
+        self.state.reverse_video() != inverted
+    }
 
-    fn keyboard_sub() -> Subscription<Message> {
-        on_key_press(handle_key)
+    fn bell(&mut self) {
+        self.rang = true;
+        if self.config.visual_bell == VisualBell::None {
+            return;
+        }
+
+        self.bell_on = true;
+        if self.config.visual_bell == VisualBell::Invert {
+            self.invalidate_all_lines();
+        }
     }
 
-    fn process_comm_sub() -> Subscription<Message> {
-        Subscription::run(pcomms)
+    /// Keeps a notification the application asked for, unless they're off in the config
+    fn notification(&mut self, title: String, body: String) {
+        if self.config.notifications {
+            self.queue_notification(title, body);
+        }
     }
 
-    fn window_resize() -> Subscription<Message> {
-        event::listen_with(|event, _status, _id| match event {
-            AppEvent::Window(window::Event::Resized(size)) => Some(Message::WindowResized(size)),
-            _ => None,
-        })
+    /// Keeps a notification for the window to show, unless the last one was too recent. The
+    /// tab is marked like for the bell
+    fn queue_notification(&mut self, title: String, body: String) {
+        if self
+            .notified
+            .is_some_and(|at| at.elapsed() < NOTIFICATION_INTERVAL)
+        {
+            tracing::debug!(%title, "notification dropped, the last one was too recent");
+            return;
+        }
+
+        self.notified = Some(std::time::Instant::now());
+        self.rang = true;
+        let title = match title.is_empty() {
+            true => self.title.clone(),
+            false => title,
+        };
+        self.notification = Some((title, body));
     }
 
-    fn mouse_sub() -> Subscription<Message> {
-        fn handle_delta(delta: ScrollDelta) -> Option<Message> {
-            match delta {
-                ScrollDelta::Lines { x, y } if y < 0.0 => Some(Message::bytes(b"\x1b[S")),
-                ScrollDelta::Lines { x, y } if y > 0.0 => Some(Message::bytes(b"\x1b[T")),
-                ScrollDelta::Pixels { x, y } => Some(Message::bytes(b"\x1b[T")),
-                _ => None,
+    fn bell_done(&mut self) {
+        self.bell_on = false;
+        if self.config.visual_bell == VisualBell::Invert {
+            self.invalidate_all_lines();
+        }
+    }
+
+    /// Maps a screen cell to grid coordinates, clamped to the screen
+    fn grid_point(&self, (line, col): (usize, usize)) -> Point {
+        let line = line.clamp(1, self.state.rows());
+        Point::new(self.view_origin() + line, col.clamp(1, self.state.cols()))
+    }
+
+    /// The cells a selection of `kind` covers when one of its ends is at `point`
+    fn snap(&self, point: Point, kind: SelectionKind) -> Span {
+        match kind {
+            SelectionKind::Simple | SelectionKind::Block => (point, point),
+            SelectionKind::Word => {
+                let row = self.state.grid().get(point.row - 1);
+                let cells = row.as_ref().map_or(&[][..], |row| &row.cells[..]);
+                let is_word = |i: usize| {
+                    cells
+                        .get(i)
+                        .is_some_and(|cell| is_word_char(cell.c, &self.config.word_chars))
+                };
+
+                let at = point.col - 1;
+                if !is_word(at) {
+                    return (point, point);
+                }
+                let mut left = at;
+                while left > 0 && is_word(left - 1) {
+                    left -= 1;
+                }
+                let mut right = at;
+                while is_word(right + 1) {
+                    right += 1;
+                }
+                (
+                    Point::new(point.row, left + 1),
+                    Point::new(point.row, right + 1),
+                )
+            }
+            SelectionKind::Line => {
+                let (first, last) = self.state.grid().logical_line(point.row);
+                (Point::new(first, 1), Point::new(last, self.state.cols()))
             }
         }
+    }
 
-        event::listen_with(|e, _status, _id| match e {
-            AppEvent::Mouse(mouse::Event::WheelScrolled { delta }) => handle_delta(delta),
-            _ => None,
-        })
+    fn start_selection(&mut self, cell: (usize, usize), kind: SelectionKind) {
+        if let Some((start, end)) = self.selection.take().map(|s| s.bounds()) {
+            self.invalidate_rows(start.row..=end.row);
+        }
+        let span = self.snap(self.grid_point(cell), kind);
+        self.selection = Some(Selection::new(kind, span));
+        self.invalidate_rows(span.0.row..=span.1.row);
     }
-    Subscription::batch([process_comm_sub(), keyboard_sub(), mouse_sub()])
-}
 
-#[tokio::main]
-pub async fn main() -> iced::Result {
-    iced::application("A toy terminal emulator", Screen::update, Screen::view)
-        .subscription(subscription)
-        .run()
+    fn extend_selection(&mut self, cell: (usize, usize)) {
+        let Some(kind) = self.selection.map(|selection| selection.kind()) else {
+            return;
+        };
+        let span = self.snap(self.grid_point(cell), kind);
+        let Some(selection) = self.selection.as_mut() else {
+            return;
+        };
+
+        let (before_start, before_end) = selection.bounds();
+        selection.extend(span);
+        let (start, end) = selection.bounds();
+        self.invalidate_rows(before_start.row.min(start.row)..=before_end.row.max(end.row));
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        let selection = self.selection.filter(|selection| !selection.is_empty())?;
+        Some(selection.text(self.state.grid(), self.state.cols()))
+    }
+
+    /// Finishing a selection makes it the primary selection on Linux, and with `copy_on_select`
+    /// puts it on the clipboard too
+    fn end_selection(&self) -> Task<Message> {
+        let Some(text) = self.selected_text() else {
+            return Task::none();
+        };
+
+        let mut tasks = vec![];
+        if cfg!(target_os = "linux") {
+            tasks.push(iced::clipboard::write_primary(text.clone()));
+        }
+        if self.config.copy_on_select {
+            tasks.push(iced::clipboard::write(text));
+        }
+        Task::batch(tasks)
+    }
+
+    fn copy(&self) -> Task<Message> {
+        let Some(text) = self.selected_text() else {
+            return Task::none();
+        };
+
+        if cfg!(target_os = "linux") {
+            Task::batch([
+                iced::clipboard::write_primary(text.clone()),
+                iced::clipboard::write(text),
+            ])
+        } else {
+            iced::clipboard::write(text)
+        }
+    }
+
+    /// The selection as HTML, or the screen as it's shown when nothing is selected
+    fn html(&self) -> String {
+        let cols = self.state.cols();
+        let lines: Vec<Vec<Cell>> = match self.selection.filter(|sel| !sel.is_empty()) {
+            Some(selection) => {
+                let (start, end) = selection.bounds();
+                let rows = self.state.grid().rows(start.row - 1..end.row);
+                (start.row..=end.row)
+                    .zip(rows)
+                    .filter_map(|(row, line)| {
+                        let cells = &line.cells;
+                        let (left, right) = selection.columns(row, cols)?;
+                        let right = right.min(cells.len());
+                        Some(cells[(left - 1).min(right)..right].to_vec())
+                    })
+                    .collect()
+            }
+            None => self
+                .state
+                .window(self.view_origin(), self.state.rows())
+                .into_iter()
+                .map(|row| row.into_owned().cells)
+                .collect(),
+        };
+        let lines: Vec<&[Cell]> = lines.iter().map(Vec::as_slice).collect();
+        let styles = self.state.grid().styles();
+        dump::html(&lines, styles, &self.palette, self.reverse_video())
+    }
+
+    fn hover(&mut self, cell: Option<(usize, usize)>) {
+        let link = cell.and_then(|cell| {
+            let point = self.grid_point(cell);
+            let row = self.state.grid().get(point.row - 1)?;
+            let (kind, left, right) = links::link_at(
+                &row.cells,
+                point.col,
+                self.config.detect_paths,
+                self.current_dir(),
+            )?;
+            Some(Link {
+                kind,
+                row: point.row,
+                left,
+                right,
+            })
+        });
+        if link == self.hover {
+            return;
+        }
+
+        let rows: Vec<usize> = [self.hover, link].iter().flatten().map(|l| l.row).collect();
+        self.hover = link;
+        self.invalidate_rows(rows);
+    }
+
+    /// Ctrl+click on a link
+    fn open_link(&self, cell: (usize, usize)) -> Task<Message> {
+        let point = self.grid_point(cell);
+        let Some(row) = self.state.grid().get(point.row - 1) else {
+            return Task::none();
+        };
+        let Some((kind, left, right)) = links::link_at(
+            &row.cells,
+            point.col,
+            self.config.detect_paths,
+            self.current_dir(),
+        ) else {
+            return Task::none();
+        };
+
+        self.open_target(kind, &links::text(&row.cells, left, right))
+    }
+
+    /// URLs go to the desktop's handler and so do paths unless they're set to open in
+    /// `$EDITOR`. Diagnostics open in `editor_command`, or in `$EDITOR` at their line. `$EDITOR`
+    /// gets a new tab. Relative paths are where the tab is
+    fn open_target(&self, kind: LinkKind, target: &str) -> Task<Message> {
+        let dir = self.current_dir();
+        let editor = |path, line| links::editor(&links::resolve(path, dir), line).map(Some);
+        let opened = match (kind, self.config.editor_command.as_deref()) {
+            (LinkKind::Path, _) if self.config.open_paths_in_editor => editor(target, None),
+            (LinkKind::Path, _) => {
+                links::open(&links::resolve(target, dir).to_string_lossy()).map(|()| None)
+            }
+            (LinkKind::Diagnostic, Some(template)) => {
+                links::open_diagnostic(target, dir, template).map(|()| None)
+            }
+            (LinkKind::Diagnostic, None) => match links::diagnostic(target) {
+                Some((path, line, _)) => editor(path, Some(line)),
+                None => Ok(None),
+            },
+            _ => links::open(target).map(|()| None),
+        };
+        match opened {
+            Ok(Some(argv)) => Task::done(Message::OpenInTab(argv, dir.map(Path::to_owned))),
+            Ok(None) => Task::none(),
+            Err(e) => {
+                tracing::warn!("failed to open {}: {}", target, e);
+                Task::none()
+            }
+        }
+    }
+
+    fn toggle_hints(&mut self) {
+        if self.hints.take().is_some() {
+            return;
+        }
+
+        let origin = self.view_origin();
+        let window = self.state.window(origin, self.state.rows());
+        let targets = (origin + 1..)
+            .zip(window)
+            .flat_map(|(row, line)| {
+                links::find_all(&line.cells, self.config.detect_paths, self.current_dir())
+                    .into_iter()
+                    .map(move |(kind, left, right)| {
+                        let text = links::text(&line.cells, left, right);
+                        (kind, row, left, text)
+                    })
+            })
+            .collect();
+
+        let hints = Hints::new(targets);
+        self.hints = (!hints.is_empty()).then_some(hints);
+    }
+
+    /// Keys typed while hints are shown pick a label, anything but a letter leaves the mode
+    fn hint_input(&mut self, content: Content) -> Task<Message> {
+        let Some(hints) = self.hints.as_mut() else {
+            return Task::none();
+        };
+        let Content::Text(text) = content else {
+            self.hints = None;
+            return Task::none();
+        };
+
+        for c in text.chars() {
+            match hints.type_char(c) {
+                HintInput::Pending => continue,
+                HintInput::Cancelled => self.hints = None,
+                HintInput::Picked(hint, action) => {
+                    self.hints = None;
+                    if action == HintAction::Copy || hint.kind == LinkKind::Hash {
+                        return iced::clipboard::write(hint.text);
+                    }
+                    return self.open_target(hint.kind, &hint.text);
+                }
+            }
+            break;
+        }
+        Task::none()
+    }
+
+    /// Labels of the hints on screen, at the first column of their targets
+    fn hint_labels(&self) -> Vec<HintLabel> {
+        let Some(hints) = self.hints.as_ref() else {
+            return vec![];
+        };
+        let origin = self.view_origin();
+        hints
+            .visible()
+            .filter_map(|(hint, label)| {
+                let line = hint.row.checked_sub(origin)?;
+                (1..=self.state.rows()).contains(&line).then(|| HintLabel {
+                    line,
+                    col: hint.left,
+                    label: label.to_owned(),
+                })
+            })
+            .collect()
+    }
+
+    /// Drops the cached layers of the given grid rows that are on screen
+    fn invalidate_rows(&mut self, rows: impl IntoIterator<Item = usize>) {
+        let origin = self.view_origin();
+        for y in rows {
+            let line = y.checked_sub(origin + 1);
+            if let Some(cache) = line.and_then(|l| self.line_caches.get(l)) {
+                cache.clear();
+                let cells = self.state.grid().get(y - 1).map_or(0, |r| r.cells.len());
+                self.stats.redrawn(cells);
+            }
+        }
+    }
+
+    /// Drops the cached layers of the screen lines touched since the last frame, or all of them
+    /// when the view scrolled since every line moved
+    fn invalidate_damaged_lines(&mut self) {
+        let damage = self.state.take_damage();
+        let highlighted = self
+            .highlights
+            .update(&self.state, &self.config.highlights, &damage);
+        // a view scrolled back stays on the same lines while output comes in
+        if self.scroll_offset > 0 {
+            let screen_origin = self.state.screen_origin();
+            self.scroll_offset = screen_origin.saturating_sub(self.cached_origin);
+        }
+
+        let origin = self.view_origin();
+        if origin != self.cached_origin || self.line_caches.len() != self.state.rows() {
+            self.cached_origin = origin;
+            self.invalidate_all_lines();
+            return;
+        }
+
+        self.invalidate_rows(damage.into_iter().chain(highlighted));
+    }
+
+    /// Matches the highlight rules over the whole grid again, the caller redraws every line
+    fn rehighlight(&mut self) {
+        self.highlights.reset();
+        let rules = &self.config.highlights;
+        self.highlights.update(&self.state, rules, &BTreeSet::new());
+    }
+
+    fn invalidate_all_lines(&mut self) {
+        self.line_caches
+            .resize_with(self.state.rows(), Cache::default);
+        self.line_caches.iter().for_each(Cache::clear);
+        let lines = self.state.window(self.view_origin(), self.state.rows());
+        let cells = lines.iter().map(|line| line.cells.len()).sum();
+        self.stats.redrawn(cells);
+    }
+
+    /// Recomputes the grid dimensions that fit in `size`
+    fn resize(&mut self, size: Size) {
+        self.curr_size = size;
+
+        let (rows, cols) = self
+            .tmux_size
+            .unwrap_or_else(|| self.metrics.grid_size(grid_area(&self.config, size)));
+        if (rows, cols) == (self.state.rows(), self.state.cols()) {
+            return;
+        }
+
+        self.state.resize(rows, cols);
+        self.rehighlight();
+        self.record(|recorder| recorder.resize(cols, rows));
+        self.scroll_offset = self.scroll_offset.min(self.state.screen_origin());
+        self.cached_origin = self.view_origin();
+        self.invalidate_all_lines();
+        self.resize_pty();
+    }
+
+    /// Tells the kernel about the new grid size, which in turn sends SIGWINCH to the
+    /// foreground process group of the child so it can relayout
+    fn resize_pty(&mut self) {
+        if self.tmux.is_some() {
+            let (rows, cols) = (self.state.rows(), self.state.cols());
+            self.tmux_command(|control| control.resize(cols, rows));
+        }
+        let Some(handle) = self.handle.as_ref() else {
+            return;
+        };
+
+        let winsize = self.winsize();
+        if unsafe { libc::ioctl(handle.as_raw_fd(), libc::TIOCSWINSZ, &winsize) } < 0 {
+            tracing::warn!("failed to resize pty: {}", std::io::Error::last_os_error());
+        }
+    }
+
+    /// Sends `signal` to the child's process group and the pty's foreground job, if the
+    /// child is still around
+    fn signal(&self, signal: Signal) {
+        if let Some(child) = self.child {
+            shell::signal(self.handle.as_ref(), child, signal);
+        }
+    }
+
+    fn child_exited(&mut self) {
+        self.handle = None;
+        self.connection = None;
+        self.writer = None;
+        self.child = None;
+        self.progress = None;
+        self.tmux = None;
+    }
+
+    /// Put before the title while the application reports progress: the percentage, `!` if
+    /// it failed, `=` if it's paused and `...` if it didn't say how far along it is
+    fn progress_label(&self) -> String {
+        match self.progress {
+            None | Some(Progress::Hidden) => String::new(),
+            Some(Progress::Normal(percent)) => format!("[{}%] ", percent),
+            Some(Progress::Error(percent)) => format!("[!{}%] ", percent),
+            Some(Progress::Indeterminate) => "[...] ".to_owned(),
+            Some(Progress::Paused(percent)) => format!("[={}%] ", percent),
+        }
+    }
+
+    /// Keeps the tab open after the child exited, showing how it ended
+    fn hold(&mut self, status: Option<ExitStatus>) {
+        let status = status.map_or("exited".to_owned(), |status| status.to_string());
+        self.show(&format!(
+            "\r\n[process {} - press any key to close]",
+            status
+        ));
+        self.exited = true;
+    }
+
+    /// Prints a notice of the emulator's own, as if the child had
+    fn show(&mut self, notice: &str) {
+        for event in self.state.process_bytes(notice.as_bytes()) {
+            self.handle_event(event);
+        }
+        self.invalidate_damaged_lines();
+    }
+
+    /// Takes what tmux said in control mode, writing the commands it takes to answer it. What
+    /// comes back is for the panes of its windows
+    fn tmux_control(&mut self, bytes: &[u8]) -> Vec<tmux::Notification> {
+        self.stats.read(bytes.len());
+        let attached = self.tmux.is_none();
+        let control = self.tmux.get_or_insert_with(tmux::Control::default);
+        let (notifications, commands) = control.feed(bytes);
+        self.send(commands);
+        if attached {
+            let notice =
+                "\r\n[tmux attached - each window has a tab, close this one to detach]\r\n";
+            self.show(notice);
+            self.resize_pty();
+        }
+
+        if notifications
+            .iter()
+            .any(|notification| matches!(notification, tmux::Notification::Exit))
+        {
+            self.tmux = None;
+            self.show("[tmux detached]\r\n");
+        }
+        notifications
+    }
+
+    /// Sends tmux the command `f` makes, while it's attached
+    fn tmux_command(&mut self, f: impl FnOnce(&mut tmux::Control) -> Vec<u8>) {
+        if let Some(control) = self.tmux.as_mut() {
+            let command = f(control);
+            self.send(command);
+        }
+    }
+
+    /// Whether the tab shows `pane` of the tmux attached on `gateway`
+    fn is_tmux_pane(&self, gateway: TabId, pane: Option<u32>) -> bool {
+        match self.session {
+            Session::Tmux {
+                gateway: g,
+                pane: p,
+            } => g == gateway && pane.is_none_or(|pane| pane == p),
+            _ => false,
+        }
+    }
+
+    fn poll_foreground(&mut self) {
+        self.foreground = self.handle.as_ref().and_then(process::foreground);
+    }
+
+    /// What's running in the foreground when it isn't the child itself, usually a command
+    /// started from the shell
+    fn busy(&self) -> Option<&Foreground> {
+        let child = self.child?;
+        self.foreground
+            .as_ref()
+            .filter(|foreground| foreground.pgrp != child)
+    }
+
+    /// The directory the shell reported wins over the one of the foreground process, which is
+    /// stale while it runs something that doesn't `cd`
+    fn current_dir(&self) -> Option<&Path> {
+        let foreground = self.foreground.as_ref();
+        self.cwd
+            .as_deref()
+            .or(foreground.and_then(|process| process.cwd.as_deref()))
+    }
+
+    /// Fills in `Config::title_template`
+    fn format_title(&self, template: &str) -> String {
+        let cwd = self.current_dir().map(process::tilde).unwrap_or_default();
+        let process = self
+            .foreground
+            .as_ref()
+            .map_or("", |process| process.name.as_str());
+
+        template
+            .replace("{title}", &self.title)
+            .replace("{process}", process)
+            .replace("{cwd}", &cwd)
+    }
+
+    /// Takes a reloaded config. The font is only set again when it changed, so a zoomed tab
+    /// stays zoomed, and the same goes for colors the application changed with OSC
+    fn reconfigure(&mut self, mut config: Config, theme: Palette) {
+        let font = std::mem::replace(&mut config.font, self.config.font.clone());
+        self.config = config;
+        self.state.set_default_cursor_style(self.config.cursor);
+        self.rehighlight();
+        if theme != self.theme {
+            self.palette = theme.clone();
+            self.theme = theme;
+        }
+        if font != self.config.font {
+            if let Err(e) = self.set_font(font) {
+                tracing::warn!("{}, keeping the current font", e);
+            }
+        }
+
+        // the padding may have changed without the grid size
+        self.resize(self.curr_size);
+        self.invalidate_all_lines();
+    }
+
+    /// Colors and font of a profile, in place of the ones from the config
+    fn apply_look(&mut self, look: &Look) {
+        if let Some(path) = &look.theme {
+            match Palette::load(path) {
+                Ok(theme) => {
+                    self.palette = theme.clone();
+                    self.theme = theme;
+                }
+                Err(e) => tracing::warn!("{}, keeping the default colors", e),
+            }
+        }
+        if let Some(font) = look.font.clone() {
+            if let Err(e) = self.set_font(font) {
+                tracing::warn!("{}, keeping the default font", e);
+            }
+        }
+    }
+
+    /// Both markers are dropped once the tab has been looked at
+    fn seen(&mut self) {
+        self.activity = false;
+        self.rang = false;
+    }
+
+    /// The window may have been resized while the child was starting
+    fn init(&mut self, handle: Option<File>, writer: PtyWriter, child: Option<Pid>) {
+        self.handle = handle;
+        self.writer = Some(writer);
+        self.child = child;
+        self.resize_pty();
+    }
+
+    fn output(&mut self, outputs: Vec<Output>, bytes: Bytes) {
+        self.stats.read(bytes.len());
+        self.record(|recorder| recorder.output(&bytes));
+        self.handle_output(outputs);
+        self.trim_scrollback();
+        if let Some(search) = self.search.as_mut() {
+            search.refresh(self.state.grid());
+        }
+        self.invalidate_damaged_lines();
+        self.trigger();
+    }
+
+    /// Does what the highlight rules ask for once their matches are printed
+    fn trigger(&mut self) {
+        for fired in self.highlights.take_fired() {
+            let Some(rule) = self.config.highlights.get(fired.rule) else {
+                continue;
+            };
+            let actions = rule.actions.clone();
+            if actions.unfocused && self.focused {
+                continue;
+            }
+
+            if actions.bell {
+                self.bell();
+            }
+            if actions.notify {
+                self.queue_notification(String::new(), fired.groups[0].clone());
+            }
+            if let Some(group) = actions.copy {
+                let text = fired.groups.get(group).cloned().unwrap_or_default();
+                self.clipboard = Some((ClipboardKind::Clipboard, text));
+            }
+            let command = actions.command.as_deref().and_then(|c| fired.command(c));
+            if let Some(mut command) = command {
+                if let Some(dir) = self.current_dir() {
+                    command.current_dir(dir);
+                }
+                if let Err(e) = links::spawn(&mut command) {
+                    tracing::warn!("failed to run {:?}: {}", command.get_program(), e);
+                }
+            }
+        }
+    }
+
+    /// Keeps the scrollback within the configured limit
+    fn trim_scrollback(&mut self) {
+        let dropped = self.state.trim_scrollback(self.config.scrollback);
+        self.scrolled_off(dropped);
+    }
+
+    /// Drops the whole scrollback, for `CSI 3J` and the clear scrollback action
+    fn clear_scrollback(&mut self) {
+        let dropped = self.state.clear_scrollback();
+        self.scrollback_cleared(dropped);
+    }
+
+    fn scrollback_cleared(&mut self, dropped: usize) {
+        self.scrolled_off(dropped);
+        self.scroll_offset = 0;
+    }
+
+    /// Rows are counted from the top of the grid, so once the first `dropped` are gone
+    /// whatever points into it moves up along with them
+    fn scrolled_off(&mut self, dropped: usize) {
+        if dropped == 0 {
+            return;
+        }
+
+        self.cached_origin = self.cached_origin.saturating_sub(dropped);
+        self.highlights.scroll_off(dropped);
+        self.selection = self.selection.and_then(|sel| sel.scrolled_off(dropped));
+        self.hover = None;
+        self.hints = None;
+        if let Some(search) = self.search.as_mut() {
+            search.scroll_off(dropped);
+        }
+    }
+
+    /// The clear screen action, which leaves the line the cursor is on at the top so the
+    /// prompt stays where the shell expects it relative to the cursor
+    fn clear_screen(&mut self) {
+        self.state.clear_screen();
+        self.selection = None;
+        self.hover = None;
+        self.hints = None;
+        if let Some(search) = self.search.as_mut() {
+            search.refresh(self.state.grid());
+        }
+        self.scroll_to(0);
+        self.invalidate_all_lines();
+    }
+
+    /// What restores the tab on the next launch. `None` for the sessions that only make sense
+    /// while this one runs, sockets, recordings, tmux panes and the like
+    fn saved(&self) -> Option<restore::SavedTab> {
+        let ssh = match &self.session {
+            Session::Pty(_) => None,
+            Session::Ssh(target, _) => Some(target.to_string()),
+            _ => return None,
+        };
+        Some(restore::SavedTab {
+            title: self.title.clone(),
+            dir: self.current_dir().map(Path::to_owned),
+            profile: self.profile.clone(),
+            ssh,
+            scrollback: dump::text(self.state.scrollback(), true),
+        })
+    }
+
+    /// Prints the scrollback a restored tab had, the shell starts below it. What's in it
+    /// doesn't set off the highlight rules again
+    fn restore_scrollback(&mut self, text: &str) {
+        if text.trim().is_empty() {
+            return;
+        }
+        let bytes = Bytes::from(text.replace('\n', "\r\n"));
+        self.handle_output(Output::parse(&bytes));
+        self.trim_scrollback();
+        self.state.take_damage();
+        self.rehighlight();
+        self.invalidate_all_lines();
+    }
+
+    /// The grid size in cells and pixels, as the pty reports it
+    fn winsize(&self) -> winsize {
+        let (rows, cols) = (self.state.rows(), self.state.cols());
+        winsize {
+            ws_row: rows as u16,
+            ws_col: cols as u16,
+            ws_xpixel: (cols as f32 * self.metrics.width) as u16,
+            ws_ypixel: (rows as f32 * self.metrics.height) as u16,
+        }
+    }
+
+    pub fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::Paste => return iced::clipboard::read().map(Message::Pasted),
+            Message::PastePrimary => return iced::clipboard::read_primary().map(Message::Pasted),
+            Message::Pasted(Some(text)) => return self.paste(&text),
+            Message::Pasted(None) => return Task::done(Message::Failed(EmuError::Clipboard)),
+            Message::Write(c) if self.hints.is_some() => return self.hint_input(c),
+            Message::Write(c) if self.search.is_some() => return self.search_input(c),
+            Message::Write(c) if matches!(self.session, Session::Stdin) => {
+                return self.page_input(c)
+            }
+            Message::OpenSearch => return self.open_search(),
+            Message::ClearScreen => self.clear_screen(),
+            Message::ClearScrollback => {
+                self.clear_scrollback();
+                self.invalidate_all_lines();
+            }
+            Message::Search(event) => self.search(event),
+            Message::Write(c) => return self.write(c),
+            Message::FontZoom(zoom) => self.zoom(zoom),
+            Message::CursorBlink => self.blink_on = !self.blink_on,
+            Message::TextBlink => self.blink_text(),
+            Message::Frame(now) => self.stats.frame(now),
+            Message::ToggleHints => self.toggle_hints(),
+            Message::ToggleReadOnly => self.read_only = !self.read_only,
+            Message::ToggleRecording => return self.toggle_recording(),
+            Message::SelectionStart(cell, kind) => self.start_selection(cell, kind),
+            Message::SelectionExtend(cell) => self.extend_selection(cell),
+            Message::SelectionEnd => return self.end_selection(),
+            Message::Copy => return self.copy(),
+            Message::CopyLastOutput => return self.copy_last_output(),
+            Message::CopyHtml => return iced::clipboard::write(self.html()),
+            Message::Mouse(_) if self.read_only => {}
+            Message::Mouse(report) => {
+                if let Some(bytes) = keys::encode_mouse(report, self.state.mouse_modes()) {
+                    self.reply(&bytes);
+                }
+            }
+            Message::Hover(cell) => self.hover(cell),
+            Message::OpenLink(cell) => return self.open_link(cell),
+            Message::Scroll(delta) => self.scroll(delta),
+            Message::ScrollTo(offset) => self.scroll_to(offset),
+            Message::JumpToPrompt(step) => self.jump_to_prompt(step),
+            // the window and the tabs, see `Screen::update`
+            _ => {}
+        };
+
+        Task::none()
+    }
+
+    fn write(&mut self, c: Content) -> Task<Message> {
+        // typing at tmux in control mode would be taken as commands
+        if self.read_only || self.tmux.is_some() {
+            return Task::none();
+        }
+
+        // keep the cursor solid while typing, and bring it into view
+        self.blink_on = true;
+        self.scroll_to(0);
+        let modes = self.state.key_modes();
+        let alt_is_meta = !cfg!(target_os = "macos") || self.config.option_as_alt;
+
+        let paste = matches!(c, Content::Clipboard(_));
+        let bytes = match c {
+            Content::Text(s) => Some(s.into_bytes()),
+            Content::Bytes(b) | Content::Clipboard(b) => Some(b),
+            Content::Key(named, mods) => keys::encode_named(named, mods, modes),
+            Content::Meta(meta, _) if alt_is_meta => Some([b"\x1b", meta.as_slice()].concat()),
+            Content::Meta(_, text) => Some(text.into_bytes()),
+            Content::Keypad(key, mods) => keys::encode_keypad(&key, mods, modes),
+        };
+        let Some(bytes) = bytes else {
+            return Task::none();
+        };
+        self.record(|recorder| recorder.input(&bytes));
+        if paste {
+            self.send_paste(bytes);
+            return Task::none();
+        }
+        self.send_input(bytes)
+    }
+
+    fn start_recording(&mut self, path: &Path) -> std::result::Result<(), EmuError> {
+        let (cols, rows) = (self.state.cols(), self.state.rows());
+        let recorder =
+            Recorder::create(path, cols, rows).map_err(|e| EmuError::Record(path.to_owned(), e))?;
+        self.recorder = Some(recorder);
+        Ok(())
+    }
+
+    fn toggle_recording(&mut self) -> Task<Message> {
+        if self.recorder.take().is_some() {
+            return Task::none();
+        }
+        match self.start_recording(&record::path()) {
+            Ok(()) => Task::none(),
+            Err(e) => Task::done(Message::Failed(e)),
+        }
+    }
+
+    /// Passes an event on to the recording, which stops if it can't be written
+    fn record(&mut self, event: impl FnOnce(&mut Recorder) -> std::io::Result<()>) {
+        let Some(recorder) = self.recorder.as_mut() else {
+            return;
+        };
+
+        if let Err(e) = event(recorder) {
+            tracing::error!("failed to record to {}: {}", recorder.path().display(), e);
+            self.recorder = None;
+        }
+    }
+
+    /// Queues input for the child
+    fn send(&self, bytes: Vec<u8>) {
+        let Some(writer) = self.writer.as_ref() else {
+            return;
+        };
+
+        if let Err(e) = writer.send(bytes) {
+            tracing::error!("failed to write to the pty: {}", e);
+        }
+    }
+
+    /// Queues what was typed. Once the child is too far behind on its input to take more, the
+    /// keys are refused and the user is told, rather than have them go missing unseen
+    fn send_input(&self, bytes: Vec<u8>) -> Task<Message> {
+        match self.writer.as_ref().map(|writer| writer.send(bytes)) {
+            Some(Err(e @ WriteError::Full)) => Task::done(Message::Failed(EmuError::Write(e))),
+            Some(Err(e)) => {
+                tracing::error!("failed to write to the pty: {}", e);
+                Task::none()
+            }
+            _ => Task::none(),
+        }
+    }
+
+    /// Queues a paste, which unlike other input isn't refused when the child is behind
+    fn send_paste(&self, bytes: Vec<u8>) {
+        let Some(writer) = self.writer.as_ref() else {
+            return;
+        };
+
+        if let Err(e) = writer.send_paste(bytes) {
+            tracing::error!("failed to write to the pty: {}", e);
+        }
+    }
+
+    fn paste(&mut self, text: &str) -> Task<Message> {
+        let bracketed = self.state.bracketed_paste();
+        let bytes = keys::encode_paste(text, bracketed, self.config.paste_strip_controls);
+        self.write(Content::Clipboard(bytes))
+    }
+
+    fn handle_event(&mut self, event: emu_core::Event) {
+        use emu_core::Event;
+
+        match event {
+            Event::Bell => self.bell(),
+            Event::TitleChanged(title) => self.title = title,
+            Event::CwdChanged(cwd) => self.cwd = Some(cwd),
+            Event::ClipboardStore(kind, text) if self.config.clipboard_write => {
+                self.clipboard = Some((kind, text));
+            }
+            Event::ClipboardStore(..) => tracing::debug!("OSC 52 ignored, clipboard_write is off"),
+            Event::ClipboardLoad(_) => {}
+            Event::Notification(title, body) => self.notification(title, body),
+            Event::Progress(Progress::Hidden) => self.progress = None,
+            Event::Progress(progress) => self.progress = Some(progress),
+            Event::ScrollbackCleared(dropped) => self.scrollback_cleared(dropped),
+            Event::ScreenSwitched => {
+                self.scroll_offset = 0;
+                self.invalidate_all_lines();
+            }
+            Event::ReverseVideo => self.invalidate_all_lines(),
+            Event::Reply(reply) => self.reply(&reply),
+            Event::TermcapQuery(names) => self.reply(&terminfo::xtgettcap_reply(&names)),
+            Event::Osc(ps, pt) => self.handle_osc(ps, &pt),
+        }
+    }
+
+    fn handle_osc(&mut self, ps: u32, pt: &str) {
+        match ps {
+            4 => {
+                let mut args = pt.split(';');
+                while let (Some(index), Some(spec)) = (args.next(), args.next()) {
+                    let Ok(index) = index.parse::<u8>() else {
+                        continue;
+                    };
+
+                    let color = &mut self.palette.colors[index as usize];
+                    if spec == "?" {
+                        let reply = format!("\x1b]4;{};{}\x07", index, format_color_spec(*color));
+                        self.reply(reply.as_bytes());
+                    } else if let Some(rgb) = parse_color_spec(spec) {
+                        *color = rgb;
+                    }
+                }
+            }
+            10 | 11 => {
+                let color = match ps {
+                    10 => &mut self.palette.foreground,
+                    _ => &mut self.palette.background,
+                };
+
+                if pt == "?" {
+                    let reply = format!("\x1b]{};{}\x07", ps, format_color_spec(*color));
+                    self.reply(reply.as_bytes());
+                } else if let Some(rgb) = parse_color_spec(pt) {
+                    *color = rgb;
+                }
+            }
+            104 if pt.is_empty() => self.palette.colors = self.theme.colors,
+            104 => {
+                for index in pt.split(';').filter_map(|i| i.parse::<u8>().ok()) {
+                    self.palette.colors[index as usize] = self.theme.colors[index as usize];
+                }
+            }
+            110 => self.palette.foreground = self.theme.foreground,
+            111 => self.palette.background = self.theme.background,
+            _ => return,
+        }
+
+        // colors are resolved while drawing, every cached line may be stale now
+        self.invalidate_all_lines();
+    }
+
+    /// Answers a query from the application
+    fn reply(&mut self, bytes: &[u8]) {
+        self.send(bytes.to_vec());
+    }
+
+    pub fn handle_output(&mut self, outputs: Vec<Output>) {
+        // images are placed at the cursor, so what comes before them has to be applied first
+        let mut batch = Vec::with_capacity(outputs.len());
+        for op in outputs {
+            match op {
+                Output::Ansi(AnsiCode::Sixel(data)) => {
+                    self.apply(std::mem::take(&mut batch));
+                    self.sixel(&data);
+                }
+                Output::Ansi(AnsiCode::Apc(data)) => {
+                    self.apply(std::mem::take(&mut batch));
+                    self.kitty(&data);
+                }
+                Output::Ansi(AnsiCode::Osc(1337, pt)) if pt.starts_with("File=") => {
+                    self.apply(std::mem::take(&mut batch));
+                    self.inline_image(&pt);
+                }
+                Output::Unknown(_) => {
+                    self.stats.unknown_sequence();
+                    batch.push(op);
+                }
+                op => batch.push(op),
+            }
+        }
+        self.apply(batch);
+    }
+
+    fn apply(&mut self, outputs: Vec<Output>) {
+        if outputs.is_empty() {
+            return;
+        }
+
+        for event in self.state.handle_output(outputs) {
+            self.handle_event(event);
+        }
+    }
+
+    fn sixel(&mut self, data: &[u8]) {
+        let Some(sixel) = emu_core::sixel::decode(data) else {
+            tracing::debug!(bytes = data.len(), "sixel image with nothing in it");
+            return;
+        };
+
+        let (width, height) = (sixel.width as u32, sixel.height as u32);
+        let handle = image::Handle::from_rgba(width, height, sixel.rgba);
+        let cells = self.image_cells(width, height);
+        let cursor = ImageCursor::Below;
+        self.state
+            .place_image(Arc::new(handle), cells, None, cursor);
+    }
+
+    /// Shows the file of an iTerm2 inline image at the cursor, leaving the cursor right of it
+    fn inline_image(&mut self, pt: &str) {
+        let Some(inline) = iterm::parse(pt).filter(|inline| inline.inline) else {
+            tracing::debug!("iTerm2 file that isn't an inline image");
+            return;
+        };
+        let image = match images::decode(&inline.data) {
+            Ok(image) => image,
+            Err(e) => {
+                tracing::debug!(bytes = inline.data.len(), "inline image refused: {}", e);
+                return;
+            }
+        };
+
+        let cells = self.inline_cells(&inline, image.width, image.height);
+        let cursor = ImageCursor::After;
+        self.state
+            .place_image(Arc::new(image.handle), cells, None, cursor);
+    }
+
+    /// Rows and columns an inline image covers, from the size it asked for and its own in
+    /// pixels. One wider than the screen is shrunk to fit it
+    fn inline_cells(&self, inline: &InlineImage, width: u32, height: u32) -> (usize, usize) {
+        let cell = (self.metrics.width, self.metrics.height);
+        let screen = (
+            self.state.cols() as f32 * cell.0,
+            self.state.rows() as f32 * cell.1,
+        );
+        let pixels = |size, screen: f32, cell: f32| match size {
+            iterm::Size::Auto => None,
+            iterm::Size::Cells(n) => Some(n as f32 * cell),
+            iterm::Size::Pixels(n) => Some(n as f32),
+            iterm::Size::Percent(n) => Some(n.min(100) as f32 * screen / 100.0),
+        };
+        let wanted = (
+            pixels(inline.width, screen.0, cell.0),
+            pixels(inline.height, screen.1, cell.1),
+        );
+
+        let (w, h) = (width as f32, height as f32);
+        let keep = inline.preserve_aspect_ratio;
+        let (w, h) = match wanted {
+            (None, None) => (w, h),
+            (Some(ww), None) => (ww, if keep { h * ww / w } else { h }),
+            (None, Some(wh)) => (if keep { w * wh / h } else { w }, wh),
+            (Some(ww), Some(wh)) if keep => {
+                let scale = (ww / w).min(wh / h);
+                (w * scale, h * scale)
+            }
+            (Some(ww), Some(wh)) => (ww, wh),
+        };
+        let (w, h) = match w > screen.0 {
+            true => (screen.0, if keep { h * screen.0 / w } else { h }),
+            false => (w, h),
+        };
+
+        let cols = (w / cell.0).ceil().max(1.0) as usize;
+        let rows = ((h / cell.1).ceil().max(1.0) as usize).min(images::MAX_SIDE as usize);
+        (rows, cols)
+    }
+}
+
+/// The window: a row of tabs, each with its panes split side by side or one above the other.
+/// Only the active tab is drawn and its focused pane gets the keys, the others keep reading
+/// from their pty in the background
+#[derive(Debug)]
+pub struct Screen {
+    window: window::Id,
+    /// Every terminal of the window, the panes of all the tabs
+    panes: Vec<Terminal>,
+    /// In the order of the tab bar
+    tabs: Vec<Tab>,
+    active: usize,
+    /// Pane under the mouse, the wheel scrolls it
+    hovered: Option<TabId>,
+    next_id: usize,
+    /// Started in new tabs, the user's shell even when the first tab runs a command
+    shell: Shell,
+    /// What tabs are called until the application names them
+    title: String,
+    config: Config,
+    theme: Palette,
+    /// The default font measured, see `App::metrics`
+    metrics: CellMetrics,
+    /// Window size, the terminals get what the tab bar leaves
+    size: Size,
+    /// Waiting for the children to exit so the window can close
+    closing: bool,
+    /// Asking whether to close the window over the commands listed, see `close_dialog`
+    confirm_close: Option<Vec<String>>,
+    quick_connect: Option<QuickConnect>,
+    /// The session saved on the last exit, offered back while `Some`
+    restore: Option<restore::Saved>,
+    /// The tabs as they were when the window started closing, saved once it's the last one
+    saved: Option<restore::Saved>,
+    /// The command history overlay, with the commands of the active tab when it was opened
+    commands: Option<Vec<CommandEntry>>,
+    dump: Option<Dump>,
+    /// The list of profiles dropped down from the tab bar
+    profile_menu: bool,
+    settings: Option<Settings>,
+    /// Whether `Config::background_opacity` applies, toggled at runtime
+    transparent: bool,
+    /// Start out as the config says, then flipped by their actions
+    always_on_top: bool,
+    fullscreen: bool,
+    /// Debug overlay, toggled with Ctrl+Shift+F12
+    show_debug: bool,
+    capture: Option<Capture>,
+}
+
+impl Screen {
+    /// The first tab runs `session` with `profile`, the ones opened later the shell of `app`
+    pub fn new(app: &App, window: window::Id, session: Session, profile: Option<String>) -> Self {
+        let config = app.config.clone();
+        let (always_on_top, fullscreen) = (config.always_on_top, config.fullscreen);
+        let mut screen = Self {
+            window,
+            panes: vec![],
+            tabs: vec![],
+            active: 0,
+            hovered: None,
+            next_id: 0,
+            shell: app.shell.clone(),
+            title: app.title.clone(),
+            config,
+            theme: app.theme.clone(),
+            metrics: app.metrics,
+            size: WINDOW_SIZE,
+            closing: false,
+            confirm_close: None,
+            quick_connect: None,
+            restore: None,
+            saved: None,
+            commands: None,
+            dump: None,
+            profile_menu: false,
+            settings: None,
+            transparent: true,
+            always_on_top,
+            fullscreen,
+            show_debug: false,
+            capture: None,
+        };
+        let first = screen.terminal(session, profile);
+        screen.tabs.push(Tab::new(first.id));
+        screen.panes.push(first);
+        screen
+    }
+
+    /// The focused pane of the active tab
+    fn active(&self) -> &Terminal {
+        self.focused(&self.tabs[self.active])
+    }
+
+    fn active_mut(&mut self) -> &mut Terminal {
+        let focus = self.tabs[self.active].focus;
+        self.pane_mut(focus)
+            .expect("tabs only have panes of the window")
+    }
+
+    /// The pane of `tab` that has the keyboard
+    fn focused(&self, tab: &Tab) -> &Terminal {
+        self.pane(tab.focus)
+            .expect("tabs only have panes of the window")
+    }
+
+    fn pane(&self, id: TabId) -> Option<&Terminal> {
+        self.panes.iter().find(|pane| pane.id == id)
+    }
+
+    fn pane_mut(&mut self, id: TabId) -> Option<&mut Terminal> {
+        self.panes.iter_mut().find(|pane| pane.id == id)
+    }
+
+    fn position(&self, id: TabId) -> Option<usize> {
+        self.panes.iter().position(|pane| pane.id == id)
+    }
+
+    /// The tab the pane `id` is in
+    fn tab_of(&self, id: TabId) -> Option<usize> {
+        self.tabs.iter().position(|tab| tab.contains(id))
+    }
+
+    fn tab_panes<'a>(&'a self, tab: &'a Tab) -> impl Iterator<Item = &'a Terminal> {
+        self.panes.iter().filter(|pane| tab.contains(pane.id))
+    }
+
+    pub fn title(&self) -> String {
+        let active = self.active();
+        let title = active.format_title(&self.config.title_template);
+        format!("{}{}", active.progress_label(), title)
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let mut layers = vec![self.tab_view(&self.tabs[self.active])];
+        if self.capture.is_none() {
+            layers.extend(self.overlays());
+        }
+        let content = widget::Stack::with_children(layers).into();
+
+        if !self.shows_tab_bar() {
+            return content;
+        }
+        widget::Column::with_children([self.tab_bar(), content]).into()
+    }
+
+    /// The dialogs and menus open over the active tab
+    fn overlays(&self) -> Vec<Element<'_, Message>> {
+        let mut layers = vec![];
+        if self.show_debug {
+            layers.push(self.active().debug_overlay());
+        }
+        if let Some(running) = &self.confirm_close {
+            layers.push(close_dialog(running));
+        }
+        if let Some(saved) = &self.restore {
+            layers.push(restore_dialog(saved));
+        }
+        if self.profile_menu {
+            layers.push(self.profile_menu());
+        }
+        if let Some(dialog) = &self.quick_connect {
+            layers.push(quick_connect_dialog(dialog));
+        }
+        if let Some(commands) = &self.commands {
+            layers.push(commands_dialog(commands));
+        }
+        if let Some(settings) = &self.settings {
+            layers.push(settings_dialog(settings));
+        }
+        if let Some(dump) = &self.dump {
+            layers.push(dump_dialog(dump));
+        }
+        layers
+    }
+
+    /// The panes of `tab` where its layout puts them, or only the focused one while zoomed
+    fn tab_view(&self, tab: &Tab) -> Element<'_, Message> {
+        if tab.zoomed {
+            return self.pane_view(tab.focus, true, false);
+        }
+        let split = matches!(tab.layout, Layout::Split(..));
+        self.layout_view(&tab.layout, tab.focus, split)
+    }
+
+    fn layout_view(
+        &self,
+        layout: &Layout<TabId>,
+        focus: TabId,
+        split: bool,
+    ) -> Element<'_, Message> {
+        let (axis, children) = match layout {
+            Layout::Pane(id) => return self.pane_view(*id, *id == focus, split),
+            Layout::Split(axis, children) => (*axis, children),
+        };
+        let children = children.iter().map(|(weight, child)| {
+            let portion = Length::FillPortion(*weight);
+            let (width, height) = match axis {
+                Axis::Horizontal => (portion, Length::Fill),
+                Axis::Vertical => (Length::Fill, portion),
+            };
+            container(self.layout_view(child, focus, split))
+                .width(width)
+                .height(height)
+                .clip(true)
+                .into()
+        });
+        match axis {
+            Axis::Horizontal => widget::Row::with_children(children)
+                .spacing(layout::GAP)
+                .into(),
+            Axis::Vertical => widget::Column::with_children(children)
+                .spacing(layout::GAP)
+                .into(),
+        }
+    }
+
+    /// A pane with what it emits tagged with its id. The focused one is outlined while the
+    /// tab is split
+    fn pane_view(&self, id: TabId, focus: bool, split: bool) -> Element<'_, Message> {
+        let Some(pane) = self.pane(id) else {
+            return widget::Space::new(Length::Fill, Length::Fill).into();
+        };
+        let view = pane
+            .view(self.transparent, focus, self.capture.is_none())
+            .map(move |message| Message::Pane(id, Box::new(message)));
+        if !(focus && split) {
+            return view;
+        }
+
+        let fg = pane.palette.resolve(TermColor::default_fg());
+        let outline = Border::default().width(1).color(Color { a: 0.5, ..fg });
+        container(view)
+            .style(move |_| Style::default().border(outline))
+            .into()
+    }
+
+    /// With a single tab the bar only stays for the profile menu
+    fn shows_tab_bar(&self) -> bool {
+        self.tabs.len() > 1 || !self.config.profiles.is_empty()
+    }
+
+    /// Hangs from the right end of the tab bar
+    fn profile_menu(&self) -> Element<'_, Message> {
+        let style = Style::default()
+            .background(Color {
+                a: 0.9,
+                ..Color::BLACK
+            })
+            .color(Color::WHITE)
+            .border(Border::default().rounded(4));
+        let entries = self.config.profiles.iter().enumerate().map(|(i, profile)| {
+            widget::button(widget::text(&profile.name).font(MONO).size(12))
+                .width(Length::Fill)
+                .style(button::text)
+                .on_press(TabClick::Profile(i))
+                .into()
+        });
+        let menu: Element<'_, TabClick> = container(
+            container(widget::Column::with_children(entries))
+                .padding(4)
+                .width(200)
+                .style(move |_| style),
+        )
+        .width(Length::Fill)
+        .align_x(alignment::Horizontal::Right)
+        .into();
+
+        menu.map(tab_message)
+    }
+
+    /// A button per tab, named after its focused pane. The ones in the background are marked
+    /// once a pane of theirs rings the bell or prints something
+    fn tab_bar(&self) -> Element<'_, Message> {
+        let palette = &self.active().palette;
+        let fg = palette.resolve(TermColor::default_fg());
+        let mut bg = palette.resolve(TermColor::default_bg());
+        if self.transparent {
+            bg.a = self.config.background_opacity.clamp(0.0, 1.0);
+        }
+
+        let tabs = self.tabs.iter().enumerate().map(|(i, tab)| {
+            let marker = if self.tab_panes(tab).any(|pane| pane.rang) {
+                "! "
+            } else if self.tab_panes(tab).any(|pane| pane.activity) {
+                "* "
+            } else {
+                ""
+            };
+            let tab = self.focused(tab);
+            let progress = tab.progress_label();
+            let label = match tab.busy() {
+                Some(process) => format!("{}{}{}: {}", marker, progress, process.name, tab.title),
+                None => format!("{}{}{}", marker, progress, tab.title),
+            };
+            let label = widget::text(label)
+                .font(MONO)
+                .size(12)
+                .wrapping(widget::text::Wrapping::None);
+            let close = widget::button(widget::text("x").font(MONO).size(12))
+                .padding([0, 4])
+                .style(move |_, status| button::Style {
+                    text_color: match status {
+                        button::Status::Hovered => fg,
+                        _ => Color { a: 0.5, ..fg },
+                    },
+                    ..button::Style::default()
+                })
+                .on_press(TabClick::Close(tab.id));
+            let content = widget::row![container(label).width(Length::Fill).clip(true), close]
+                .align_y(Alignment::Center);
+
+            let active = i == self.active;
+            widget::button(content)
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .padding([0, 8])
+                .style(move |_, status| button::Style {
+                    background: (active || status == button::Status::Hovered)
+                        .then_some(Background::Color(Color { a: 0.15, ..fg })),
+                    text_color: if active { fg } else { Color { a: 0.7, ..fg } },
+                    ..button::Style::default()
+                })
+                .on_press(TabClick::Select(tab.id))
+                .into()
+        });
+
+        let bar_button = move |label, click| {
+            widget::button(widget::text(label).font(MONO).size(12))
+                .height(Length::Fill)
+                .padding([0, 8])
+                .style(move |_, status| button::Style {
+                    background: (status == button::Status::Hovered)
+                        .then_some(Background::Color(Color { a: 0.15, ..fg })),
+                    text_color: fg,
+                    ..button::Style::default()
+                })
+                .on_press(click)
+                .into()
+        };
+        let mut buttons = vec![bar_button("+", TabClick::New)];
+        if !self.config.profiles.is_empty() {
+            buttons.push(bar_button("v", TabClick::ProfileMenu));
+        }
+
+        let row = widget::Row::with_children(tabs.chain(buttons)).spacing(1);
+        let bar: Element<'_, TabClick> = container(row)
+            .width(Length::Fill)
+            .height(TAB_BAR_HEIGHT)
+            .style(move |_| Style::default().background(bg))
+            .into();
+        bar.map(tab_message)
+    }
+
+    /// Room left for the terminals once the tab bar is taken out
+    fn terminal_size(&self) -> Size {
+        if !self.shows_tab_bar() {
+            return self.size;
+        }
+        Size::new(
+            self.size.width,
+            (self.size.height - TAB_BAR_HEIGHT).max(0.0),
+        )
+    }
+
+    /// Every tab is kept at the window size so switching to one doesn't have to relayout it,
+    /// its panes get their share of it
+    fn resize(&mut self, size: Size) {
+        self.size = size;
+        let size = self.terminal_size();
+        let sizes: Vec<(TabId, Size)> = self.tabs.iter().flat_map(|tab| tab.sizes(size)).collect();
+        for (id, size) in sizes {
+            if let Some(pane) = self.pane_mut(id) {
+                pane.resize(size);
+            }
+        }
+    }
+
+    /// Passes a reloaded config on to every tab, along with their profiles
+    fn reconfigure(&mut self, config: &Config, theme: &Palette) {
+        self.config = config.clone();
+        self.theme = theme.clone();
+        for pane in &mut self.panes {
+            pane.reconfigure(config.clone(), theme.clone());
+            if let Some(look) = tab_look(config, pane) {
+                pane.apply_look(look);
+            }
+        }
+        // profiles may have come or gone, and the tab bar with them
+        self.resize(self.size);
+    }
+
+    /// Opens a tab running the command of a profile, in its directory and with its look
+    fn new_profile_tab(&mut self, index: usize) {
+        self.profile_menu = false;
+        let Some(profile) = self.config.profiles.get(index) else {
+            return;
+        };
+
+        let session = Session::Pty(profile.shell(&self.shell));
+        let name = profile.name.clone();
+        self.new_tab(session, Some(name));
+    }
+
+    /// Opens a tab running `session` next to the active one and switches to it
+    fn new_tab(&mut self, session: Session, profile: Option<String>) {
+        let pane = self.terminal(session, profile);
+        self.tabs.insert(self.active + 1, Tab::new(pane.id));
+        self.panes.push(pane);
+        self.select(self.active + 1);
+        // the tab bar shows up with the second tab
+        self.resize(self.size);
+    }
+
+    /// A terminal for `session` with an id of its own, which is what starts it. It gets the
+    /// font size of the active tab, unless its profile has a font
+    fn terminal(&mut self, session: Session, profile: Option<String>) -> Terminal {
+        let id = TabId(self.next_id);
+        self.next_id += 1;
+
+        let title = session.name().unwrap_or(self.title.clone());
+        let mut tab = Terminal::new(
+            id,
+            self.config.clone(),
+            self.theme.clone(),
+            self.metrics,
+            session,
+            title,
+        );
+        tab.profile = profile;
+        if let Some(active) = self.tabs.get(self.active) {
+            tab.set_font_size(self.focused(active).metrics.font_size);
+        }
+        if let Some(look) = tab_look(&self.config, &tab).cloned() {
+            tab.apply_look(&look);
+        }
+        tab
+    }
+
+    fn open_quick_connect(&mut self) -> Task<Message> {
+        self.quick_connect = Some(QuickConnect {
+            input: String::new(),
+            recent: ssh::recent_hosts(),
+        });
+        widget::text_input::focus(quick_connect_input())
+    }
+
+    fn quick_connect(&mut self, event: QuickConnectEvent) {
+        let Some(dialog) = self.quick_connect.as_mut() else {
+            return;
+        };
+
+        let host = match event {
+            QuickConnectEvent::Input(input) => {
+                dialog.input = input;
+                return;
+            }
+            QuickConnectEvent::Submit => dialog.input.clone(),
+            QuickConnectEvent::Pick(host) => host,
+        };
+        // an unparseable host leaves the dialog up to be fixed
+        let Some(target) = SshTarget::parse(&host) else {
+            return;
+        };
+
+        self.quick_connect = None;
+        ssh::remember(&target);
+        let shell = self.shell.run(&target.command());
+        self.new_tab(Session::Ssh(target, shell), None);
+    }
+
+    fn commands(&mut self, event: CommandsEvent) -> Task<Message> {
+        self.commands = None;
+        let active = self.active_mut();
+        match event {
+            CommandsEvent::Run(command) => {
+                active.update(Message::Write(Content::Text(format!("{}\r", command))))
+            }
+            CommandsEvent::CopyOutput(prompt) => match active.command_output(prompt) {
+                Some(output) => iced::clipboard::write(output),
+                None => Task::none(),
+            },
+            CommandsEvent::Show(prompt) => {
+                active.reveal_prompt(prompt);
+                Task::none()
+            }
+        }
+    }
+
+    fn open_dump(&mut self, kind: DumpKind) -> Task<Message> {
+        self.dump = Some(Dump {
+            kind,
+            ..Dump::default()
+        });
+        widget::text_input::focus(dump_input())
+    }
+
+    fn dump(&mut self, event: DumpEvent) {
+        let Some(dialog) = self.dump.as_mut() else {
+            return;
+        };
+
+        match event {
+            DumpEvent::Input(input) => dialog.input = input,
+            DumpEvent::Colors(colors) => dialog.colors = colors,
+            DumpEvent::Submit => {
+                let (kind, colors) = (dialog.kind, dialog.colors);
+                let target = dialog.input.trim().to_owned();
+                if target.is_empty() {
+                    return;
+                }
+
+                let scrollback = || dump::text(self.active().state.scrollback(), colors);
+                let dumped = match kind {
+                    DumpKind::File => self.save(&scrollback(), &target),
+                    DumpKind::Command => self.pipe_scrollback(&scrollback(), target),
+                    DumpKind::Html => self.save(&self.active().html(), &target),
+                };
+                match dumped {
+                    Ok(()) => self.dump = None,
+                    Err(e) => self.dump.as_mut().expect("the dialog is up").error = Some(e),
+                }
+            }
+        }
+    }
+
+    /// `~` is the home directory, and relative paths start from where the active tab is
+    fn save(&self, text: &str, target: &str) -> std::result::Result<(), String> {
+        let path = links::expand_home(target);
+        let path = match self.active().current_dir() {
+            Some(dir) => dir.join(path),
+            None => path,
+        };
+        dump::save(&path, text).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    }
+
+    /// Runs `command` in a new tab named after it, the tab stays once it's done
+    fn pipe_scrollback(&mut self, text: &str, command: String) -> std::result::Result<(), String> {
+        let argv = dump::pipe(text, &command)
+            .map_err(|e| format!("failed to write the scrollback: {}", e))?;
+        let mut shell = self.shell.run(&argv);
+        shell.working_directory = self.active().current_dir().map(Path::to_owned);
+
+        self.new_tab(Session::Pty(shell), None);
+        let tab = self.active_mut();
+        tab.title = command;
+        tab.hold = true;
+        Ok(())
+    }
+
+    /// Hangs up on the child of the active pane and puts a fresh terminal in its place, running
+    /// the same program in the directory the old one was last in. The new id drops the
+    /// subscription of the old pty and starts another
+    fn restart_tab(&mut self) {
+        let old = self.active_mut();
+        old.signal(Signal::SIGHUP);
+        old.poll_foreground();
+        let mut session = old.session.clone();
+        if let (Session::Pty(shell) | Session::Ssh(_, shell), Some(dir)) =
+            (&mut session, old.current_dir())
+        {
+            shell.working_directory = Some(dir.to_owned());
+        }
+
+        let (id, profile, size) = (old.id, old.profile.clone(), old.curr_size);
+        let mut pane = self.terminal(session, profile);
+        pane.resize(size);
+        self.tabs[self.active].replace(id, pane.id);
+        if let Some(index) = self.position(id) {
+            self.panes[index] = pane;
+        }
+    }
+
+    /// What brings the tabs back on the next launch, the ones with nothing to restore are left
+    /// out. The panes of a split come back as tabs of their own
+    fn saved(&self) -> restore::Saved {
+        let mut saved = restore::Saved::default();
+        for (i, tab) in self.tabs.iter().enumerate() {
+            if i == self.active {
+                saved.active = saved.tabs.len();
+            }
+            for id in tab.panes() {
+                saved.tabs.extend(self.pane(id).and_then(Terminal::saved));
+            }
+        }
+        saved
+    }
+
+    /// Puts the saved tabs in place of the ones the window opened with, nothing was typed in
+    /// those while the dialog was up
+    fn restore_session(&mut self) {
+        let Some(saved) = self.restore.take() else {
+            return;
+        };
+        let (panes, scrollbacks): (Vec<Terminal>, Vec<String>) = saved
+            .tabs
+            .into_iter()
+            .filter_map(|saved| {
+                let scrollback = saved.scrollback.clone();
+                Some((self.restored_tab(saved)?, scrollback))
+            })
+            .unzip();
+        if panes.is_empty() {
+            return;
+        }
+
+        for pane in std::mem::replace(&mut self.panes, panes) {
+            pane.signal(Signal::SIGHUP);
+        }
+        self.tabs = self.panes.iter().map(|pane| Tab::new(pane.id)).collect();
+        self.active = saved.active.min(self.tabs.len() - 1);
+        self.hovered = None;
+        self.resize(self.size);
+        // printed once the tabs have their size, so the lines wrap where they did
+        for (pane, scrollback) in self.panes.iter_mut().zip(scrollbacks) {
+            pane.restore_scrollback(&scrollback);
+        }
+    }
+
+    /// A saved tab as a fresh shell in the directory it was in, or connecting to its host
+    /// again. `None` for a host that doesn't parse
+    fn restored_tab(&mut self, saved: restore::SavedTab) -> Option<Terminal> {
+        let profile = saved
+            .profile
+            .filter(|name| self.config.profile(name).is_some());
+        let session = match saved.ssh {
+            Some(host) => {
+                let target = SshTarget::parse(&host)?;
+                let shell = self.shell.run(&target.command());
+                Session::Ssh(target, shell)
+            }
+            None => {
+                let mut shell = match profile.as_deref().and_then(|n| self.config.profile(n)) {
+                    Some(profile) => profile.shell(&self.shell),
+                    None => self.shell.clone(),
+                };
+                if let Some(dir) = saved.dir.filter(|dir| dir.is_dir()) {
+                    shell.working_directory = Some(dir);
+                }
+                Session::Pty(shell)
+            }
+        };
+
+        let mut tab = self.terminal(session, profile);
+        tab.title = saved.title;
+        Some(tab)
+    }
+
+    fn select(&mut self, index: usize) {
+        self.seen();
+        self.active = index;
+        self.seen();
+    }
+
+    /// Clears the marks on the panes of the active tab
+    fn seen(&mut self) {
+        let tab = &self.tabs[self.active];
+        for pane in self.panes.iter_mut().filter(|pane| tab.contains(pane.id)) {
+            pane.seen();
+        }
+    }
+
+    /// Gives the pane `id` of the active tab the keyboard, tmux is told when it's one of its
+    fn focus(&mut self, id: TabId) {
+        let tab = &mut self.tabs[self.active];
+        if tab.focus == id || !tab.contains(id) {
+            return;
+        }
+        tab.focus = id;
+        tab.zoomed = false;
+        self.resize(self.size);
+
+        let Some(&Session::Tmux { gateway, pane }) = self.pane(id).map(|pane| &pane.session) else {
+            return;
+        };
+        if let Some(gateway) = self.pane_mut(gateway) {
+            gateway.tmux_command(|control| control.select_pane(pane));
+        }
+    }
+
+    /// The pane the wheel scrolls, the one under the mouse
+    fn scrolled(&self) -> TabId {
+        let tab = &self.tabs[self.active];
+        match self.hovered {
+            Some(id) if tab.contains(id) && !tab.zoomed => id,
+            _ => tab.focus,
+        }
+    }
+
+    /// What a pane emitted. A click gives it the keyboard, and the mouse being over it the
+    /// wheel, or the keyboard too with `Config::focus_follows_mouse`
+    fn pane_message(&mut self, id: TabId, message: Message) -> Task<Message> {
+        match &message {
+            Message::Hover(Some(_)) => {
+                if self.config.focus_follows_mouse && self.hovered != Some(id) {
+                    self.focus(id);
+                }
+                self.hovered = Some(id);
+            }
+            Message::Hover(None) if self.hovered == Some(id) => self.hovered = None,
+            Message::SelectionStart(..)
+            | Message::OpenLink(_)
+            | Message::PastePrimary
+            | Message::ScrollTo(_)
+            | Message::Mouse(MouseReport {
+                button: MouseButton::Left | MouseButton::Middle | MouseButton::Right,
+                action: MouseAction::Press,
+                ..
+            }) => self.focus(id),
+            _ => {}
+        }
+        match self.pane_mut(id) {
+            Some(pane) => pane.update(message),
+            None => Task::none(),
+        }
+    }
+
+    fn switch_tab(&mut self, step: isize) {
+        let index = (self.active as isize + step).rem_euclid(self.tabs.len() as isize);
+        self.select(index as usize);
+    }
+
+    fn move_tab(&mut self, step: isize) {
+        let index = (self.active as isize + step).rem_euclid(self.tabs.len() as isize);
+        let tab = self.tabs.remove(self.active);
+        self.tabs.insert(index as usize, tab);
+        self.active = index as usize;
+    }
+
+    /// Hangs up on the children of a tab and drops it. Closing the last one closes the window,
+    /// which waits for the children
+    fn close_tab(&mut self, index: usize) -> Task<Message> {
+        if self.tabs.len() == 1 {
+            return self.close();
+        }
+
+        for pane in self.tab_panes(&self.tabs[index]) {
+            pane.signal(Signal::SIGHUP);
+        }
+        self.remove_tab(index)
+    }
+
+    /// Hangs up on the child of a pane and drops it, a pane of tmux is killed there. Closing
+    /// the last one closes the window
+    fn close_pane(&mut self, id: TabId) -> Task<Message> {
+        let Some(pane) = self.pane(id) else {
+            return Task::none();
+        };
+        if let Session::Tmux { gateway, pane } = pane.session {
+            if let Some(gateway) = self.pane_mut(gateway) {
+                gateway.tmux_command(|control| control.kill_pane(pane));
+            }
+            return Task::none();
+        }
+        if self.panes.len() == 1 {
+            return self.close();
+        }
+
+        pane.signal(Signal::SIGHUP);
+        self.remove_pane(id)
+    }
+
+    /// Splits the focused pane along `axis`, the new one runs the shell in the directory the
+    /// focused one is in and takes the keyboard. tmux splits its panes itself
+    fn split(&mut self, axis: Axis) {
+        let active = self.active();
+        if let Session::Tmux { gateway, pane } = active.session {
+            if let Some(gateway) = self.pane_mut(gateway) {
+                gateway.tmux_command(|control| control.split(pane, axis));
+            }
+            return;
+        }
+
+        let mut shell = self.shell.clone();
+        shell.working_directory = active.current_dir().map(Path::to_owned);
+        let pane = self.terminal(Session::Pty(shell), None);
+        self.tabs[self.active].split(pane.id, axis);
+        self.panes.push(pane);
+        self.resize(self.size);
+    }
+
+    /// tmux lays out its windows itself, the tab follows once it says how
+    fn change_layout(&mut self, change: Change) {
+        if let Session::Tmux { gateway, pane } = self.active().session {
+            if let Some(gateway) = self.pane_mut(gateway) {
+                gateway.tmux_command(|control| control.layout(pane, change));
+            }
+            return;
+        }
+        self.tabs[self.active].change(change);
+        self.resize(self.size);
+    }
+
+    /// The waiter threads of its panes are left to reap their children
+    fn remove_tab(&mut self, index: usize) -> Task<Message> {
+        let panes = self.tabs[index].panes();
+        self.remove_panes(|pane| panes.contains(&pane.id))
+    }
+
+    fn remove_pane(&mut self, id: TabId) -> Task<Message> {
+        self.remove_panes(|pane| pane.id == id)
+    }
+
+    /// Drops the panes `picked` says, along with the panes of a tmux attached in them, and the
+    /// tabs left without any. The active tab stays active if it's still there, or the one
+    /// now in its place takes over. The last pane goes with the window instead, there's
+    /// always one to show until it's gone
+    fn remove_panes(&mut self, picked: impl Fn(&Terminal) -> bool) -> Task<Message> {
+        let picked: Vec<TabId> = self
+            .panes
+            .iter()
+            .filter(|p| picked(p))
+            .map(|p| p.id)
+            .collect();
+        let gone = |pane: &Terminal| {
+            picked.contains(&pane.id) || picked.iter().any(|&id| pane.is_tmux_pane(id, None))
+        };
+        if self.panes.iter().all(gone) {
+            return window::close(self.window);
+        }
+        let gone: Vec<TabId> = self
+            .panes
+            .iter()
+            .filter(|p| gone(p))
+            .map(|p| p.id)
+            .collect();
+        self.panes.retain(|pane| !gone.contains(&pane.id));
+
+        let (mut active, mut before) = (None, 0);
+        for (i, tab) in std::mem::take(&mut self.tabs).into_iter().enumerate() {
+            let Some(tab) = gone.iter().try_fold(tab, |tab, &id| tab.without(id)) else {
+                continue;
+            };
+            match i.cmp(&self.active) {
+                std::cmp::Ordering::Less => before += 1,
+                std::cmp::Ordering::Equal => active = Some(self.tabs.len()),
+                std::cmp::Ordering::Greater => {}
+            }
+            self.tabs.push(tab);
+        }
+        self.active = active.unwrap_or(before.min(self.tabs.len() - 1));
+        self.hovered = self.hovered.filter(|id| !gone.contains(id));
+        self.seen();
+        self.resize(self.size);
+        Task::none()
+    }
+
+    /// Closing the window asks first while the shells have commands running, in the
+    /// foreground or as jobs. Asking again while the dialog is up closes it
+    fn close_requested(&mut self) -> Task<Message> {
+        if !self.config.confirm_close || self.confirm_close.take().is_some() {
+            return self.close();
+        }
+
+        let running: Vec<String> = self
+            .panes
+            .iter()
+            .flat_map(|tab| {
+                let members = tab.child.map(process::session_members);
+                members
+                    .into_iter()
+                    .flatten()
+                    .map(|(pid, name)| format!("{} (pid {}) in {}", name, pid, tab.title))
+            })
+            .collect();
+        if running.is_empty() {
+            return self.close();
+        }
+        self.confirm_close = Some(running);
+        Task::none()
+    }
+
+    /// Hangs up on every child and closes the window once they're gone, the waiters reap them
+    fn close(&mut self) -> Task<Message> {
+        self.saved = Some(self.saved());
+        if self.panes.iter().all(|pane| pane.child.is_none()) {
+            return window::close(self.window);
+        }
+
+        for pane in &self.panes {
+            pane.signal(Signal::SIGHUP);
+        }
+        self.closing = true;
+        Task::perform(async_std::task::sleep(CLOSE_TIMEOUT), |_| {
+            Message::CloseTimedOut
+        })
+    }
+
+    /// Shows a notification a tab asked for on the desktop and asks for attention to the window
+    fn notify_desktop(&self, title: &str, body: &str) -> Task<Message> {
+        if let Err(e) = notify::send(title, body) {
+            tracing::warn!("failed to show a notification: {}", e);
+        }
+        window::request_user_attention(self.window, Some(window::UserAttention::Informational))
+    }
+
+    /// Passes what tmux said on the tab `gateway` on to the panes of its windows
+    fn tmux(&mut self, gateway: TabId, bytes: &[u8]) -> Task<Message> {
+        let Some(index) = self.position(gateway) else {
+            return Task::none();
+        };
+
+        let mut tasks = vec![];
+        for notification in self.panes[index].tmux_control(bytes) {
+            match notification {
+                tmux::Notification::Output(pane, data) | tmux::Notification::Screen(pane, data) => {
+                    let pane = self
+                        .panes
+                        .iter()
+                        .find(|tab| tab.is_tmux_pane(gateway, Some(pane)));
+                    let Some(id) = pane.map(|pane| pane.id) else {
+                        continue;
+                    };
+                    let bytes = Bytes::from(data);
+                    tasks.push(self.update(Message::Output(id, Output::parse(&bytes), bytes)));
+                }
+                tmux::Notification::Windows(windows) => self.tmux_windows(gateway, windows),
+                tmux::Notification::Exit => self.close_tmux_panes(gateway),
+            }
+        }
+        Task::batch(tasks)
+    }
+
+    /// Lays out a tab for every window of the tmux attached on `gateway`, split the way tmux
+    /// splits it, after the gateway's tab. Panes new to the window are redrawn from what they
+    /// show, and the ones that are gone are dropped along with their windows
+    fn tmux_windows(&mut self, gateway: TabId, windows: Vec<tmux::Window>) {
+        let shown = |tab: &Tab| tab.window.is_some_and(|(g, _)| g == gateway);
+        let attaching = !self.tabs.iter().any(shown);
+        let kept: Vec<u32> = windows
+            .iter()
+            .flat_map(|window| window.panes.iter().map(|pane| pane.id))
+            .collect();
+        let _ = self.remove_panes(|pane| {
+            pane.is_tmux_pane(gateway, None)
+                && !kept.iter().any(|&id| pane.is_tmux_pane(gateway, Some(id)))
+        });
+
+        let active = self.tabs[self.active].focus;
+        let at = match self.tabs.iter().position(shown) {
+            Some(at) => at,
+            None => self.tab_of(gateway).map_or(self.tabs.len(), |at| at + 1),
+        };
+        self.tabs.retain(|tab| !shown(tab));
+
+        let mut tabs = vec![];
+        for window in windows {
+            let mut ids = vec![];
+            for pane in &window.panes {
+                let id = match self
+                    .panes
+                    .iter()
+                    .find(|tab| tab.is_tmux_pane(gateway, Some(pane.id)))
+                {
+                    Some(tab) => tab.id,
+                    None => {
+                        let session = Session::Tmux {
+                            gateway,
+                            pane: pane.id,
+                        };
+                        let tab = self.terminal(session, None);
+                        let id = tab.id;
+                        self.panes.push(tab);
+                        if let Some(gateway) = self.pane_mut(gateway) {
+                            gateway.tmux_command(|control| control.capture(pane.clone()));
+                        }
+                        id
+                    }
+                };
+                let tab = self.pane_mut(id).expect("just found or opened");
+                tab.title = window.name.clone();
+                tab.tmux_size = Some((pane.size.1 as usize, pane.size.0 as usize));
+                ids.push((pane.id, id));
+            }
+
+            let id = |pane: u32| ids.iter().find(|(p, _)| *p == pane).map(|(_, id)| *id);
+            // a layout naming a pane the list didn't have puts the panes side by side
+            let layout = window.layout.map(&id).unwrap_or_else(|| {
+                let panes = ids.iter().map(|(_, id)| (1, Layout::Pane(*id))).collect();
+                Layout::Split(Axis::Horizontal, panes)
+            });
+            let active = window.panes.iter().find(|pane| pane.active);
+            let focus = active.and_then(|pane| id(pane.id)).unwrap_or(ids[0].1);
+            tabs.push(Tab {
+                layout,
+                focus,
+                zoomed: window.zoomed,
+                window: Some((gateway, window.id)),
+            });
+        }
+
+        // attaching shows the first window
+        let first = tabs.first().map(|tab| tab.focus);
+        let at = at.min(self.tabs.len());
+        self.tabs.splice(at..at, tabs);
+        let active = match (attaching && active == gateway, first) {
+            (true, Some(first)) => first,
+            _ => active,
+        };
+        self.active = self.tab_of(active).unwrap_or(0);
+        self.seen();
+        self.resize(self.size);
+    }
+
+    /// Drops the tabs of the windows of the tmux attached on `gateway` once it detached,
+    /// going back to the gateway's tab if one of them was showing
+    fn close_tmux_panes(&mut self, gateway: TabId) {
+        if !self
+            .panes
+            .iter()
+            .any(|pane| pane.is_tmux_pane(gateway, None))
+        {
+            return;
+        }
+        let shown = self.tabs[self.active]
+            .window
+            .is_some_and(|(g, _)| g == gateway);
+        let _ = self.remove_panes(|pane| pane.is_tmux_pane(gateway, None));
+        if let Some(index) = self.tab_of(gateway).filter(|_| shown) {
+            self.select(index);
+        }
+    }
+
+    fn child_exited(&mut self, id: TabId, status: Option<ExitStatus>) -> Task<Message> {
+        self.close_tmux_panes(id);
+        let Some(index) = self.position(id) else {
+            return Task::none();
+        };
+
+        // one that never started stays, or the notice saying why would go with the window
+        let started = self.panes[index].writer.is_some();
+        self.panes[index].child_exited();
+        if self.closing {
+            if self.panes.iter().all(|pane| pane.child.is_none()) {
+                return window::close(self.window);
+            }
+            return Task::none();
+        }
+
+        if self.config.on_child_exit == ChildExit::Close && !self.panes[index].hold && started {
+            return self.remove_pane(id);
+        }
+        self.panes[index].hold(status);
+        Task::none()
+    }
+
+    pub fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::Pane(id, message) => return self.pane_message(id, *message),
+            Message::Init(id, handle, writer, child) => {
+                if let Some(pane) = self.pane_mut(id) {
+                    pane.init(handle, writer, child);
+                }
+            }
+            Message::Connected(id, connection) => {
+                if let Some(pane) = self.pane_mut(id) {
+                    pane.connection = Some(connection);
+                }
+            }
+            Message::Output(id, outputs, bytes) => {
+                let shown = self.tabs[self.active].contains(id);
+                if let Some(index) = self.position(id) {
+                    let tab = &mut self.panes[index];
+                    tab.output(outputs, bytes);
+                    if shown {
+                        tab.seen();
+                    } else {
+                        tab.activity = true;
+                    }
+                    let clipboard = match tab.clipboard.take() {
+                        Some((ClipboardKind::Clipboard, text)) => iced::clipboard::write(text),
+                        Some((ClipboardKind::Primary, text)) => {
+                            iced::clipboard::write_primary(text)
+                        }
+                        None => Task::none(),
+                    };
+                    let notification = match tab.notification.take() {
+                        Some((title, body)) => self.notify_desktop(&title, &body),
+                        None => Task::none(),
+                    };
+                    return Task::batch([clipboard, notification]);
+                }
+            }
+            Message::Tmux(id, bytes) => return self.tmux(id, &bytes),
+            Message::TmuxInput(gateway, pane, bytes) => {
+                if let Some(gateway) = self.pane_mut(gateway) {
+                    gateway.tmux_command(|control| control.send_keys(pane, &bytes));
+                }
+            }
+            Message::Layout(change) => self.change_layout(change),
+            Message::Split(axis) => self.split(axis),
+            Message::FocusPane(step) => {
+                let pane = self.tabs[self.active].next(step);
+                self.focus(pane);
+            }
+            Message::ChildExited(id, status) => return self.child_exited(id, status),
+            Message::CloseRequested => return self.close_requested(),
+            Message::ConfirmClose => return self.close(),
+            Message::CancelClose => self.confirm_close = None,
+            Message::RestoreSession => self.restore_session(),
+            Message::DismissRestore => self.restore = None,
+            Message::Write(Content::Key(Named::Enter, _)) if self.restore.is_some() => {
+                self.restore_session();
+            }
+            Message::Write(_) if self.restore.is_some() => self.restore = None,
+            Message::CloseTimedOut => {
+                for pane in &self.panes {
+                    pane.signal(Signal::SIGKILL);
+                }
+                return window::close(self.window);
+            }
+            Message::NewTab => self.new_tab(Session::Pty(self.shell.clone()), None),
+            Message::OpenInTab(argv, dir) => {
+                let mut shell = self.shell.run(&argv);
+                shell.working_directory = dir;
+                self.new_tab(Session::Pty(shell), None);
+            }
+            Message::NewProfileTab(index) => self.new_profile_tab(index),
+            Message::ToggleProfileMenu => self.profile_menu = !self.profile_menu,
+            Message::Write(_) if self.profile_menu => self.profile_menu = false,
+            Message::OpenSettings => self.settings = Some(Settings::new(&self.config)),
+            Message::Settings(SettingsEvent::Cancel) => self.settings = None,
+            Message::Settings(event) => {
+                if let Some(settings) = &mut self.settings {
+                    settings.update(event);
+                }
+            }
+            Message::Write(_) if self.settings.is_some() => self.settings = None,
+            Message::OpenQuickConnect => return self.open_quick_connect(),
+            Message::QuickConnect(event) => self.quick_connect(event),
+            // Escape and whatever else the text field doesn't take
+            Message::Write(_) if self.quick_connect.is_some() => self.quick_connect = None,
+            Message::OpenCommands => self.commands = Some(self.active().commands()),
+            Message::Commands(event) => return self.commands(event),
+            Message::Write(_) if self.commands.is_some() => self.commands = None,
+            Message::SaveScrollback => return self.open_dump(DumpKind::File),
+            Message::PipeScrollback => return self.open_dump(DumpKind::Command),
+            Message::SaveHtml => return self.open_dump(DumpKind::Html),
+            Message::Dump(event) => self.dump(event),
+            Message::Write(_) if self.dump.is_some() => self.dump = None,
+            Message::RestartTab => self.restart_tab(),
+            Message::SelectTab(id) => {
+                if let Some(index) = self.tab_of(id) {
+                    self.select(index);
+                }
+            }
+            Message::CloseTab(id) => {
+                if let Some(index) = self.tab_of(id) {
+                    return self.close_tab(index);
+                }
+            }
+            Message::CloseActiveTab => return self.close_tab(self.active),
+            Message::ClosePane(id) => return self.close_pane(id),
+            Message::CloseActivePane => return self.close_pane(self.active().id),
+            Message::SwitchTab(step) => self.switch_tab(step),
+            Message::MoveTab(step) => self.move_tab(step),
+            Message::Write(Content::Key(Named::Enter, _)) if self.confirm_close.is_some() => {
+                return self.close();
+            }
+            Message::Write(_) if self.confirm_close.is_some() => self.confirm_close = None,
+            Message::Write(_) if self.active().exited => return self.remove_pane(self.active().id),
+            Message::WindowResized(size) => self.resize(size),
+            Message::WindowFocused(focused) => {
+                for pane in &mut self.panes {
+                    pane.focused = focused;
+                }
+            }
+            Message::MouseEntered if self.config.focus_follows_mouse => {
+                return window::gain_focus(self.window);
+            }
+            Message::MouseEntered => {}
+            Message::BellDone => {
+                for pane in self.panes.iter_mut().filter(|pane| pane.bell_on) {
+                    pane.bell_done();
+                }
+            }
+            Message::PollForeground => self.panes.iter_mut().for_each(Terminal::poll_foreground),
+            Message::Scroll(delta) => {
+                let id = self.scrolled();
+                if let Some(pane) = self.pane_mut(id) {
+                    return pane.update(Message::Scroll(delta));
+                }
+            }
+            Message::ToggleDebug => self.show_debug = !self.show_debug,
+            Message::ToggleTransparency => self.transparent = !self.transparent,
+            Message::ToggleAlwaysOnTop => {
+                self.always_on_top = !self.always_on_top;
+                return window::change_level(self.window, window_level(self.always_on_top));
+            }
+            Message::ToggleFullscreen => {
+                self.fullscreen = !self.fullscreen;
+                return window::change_mode(self.window, window_mode(self.fullscreen));
+            }
+            Message::ToggleDecorations => return window::toggle_decorations(self.window),
+            message => return self.active_mut().update(message),
+        }
+
+        Task::none()
+    }
+}
+
+/// Every window of the process. They share the config and the colors of its theme, each runs
+/// its own children
+#[derive(Debug)]
+pub struct App {
+    windows: BTreeMap<window::Id, Screen>,
+    config: Config,
+    /// Watched for changes, even if it didn't exist at startup
+    config_path: Option<PathBuf>,
+    /// Modification time of the config when it was last read
+    config_modified: Option<SystemTime>,
+    /// Shown on top of every window, numbered so an old timer doesn't take down a newer one
+    notice: Option<(usize, String)>,
+    notices: usize,
+    /// Loaded once for all windows
+    theme: Palette,
+    /// The default font measured once at startup, every tab starts out with it
+    metrics: CellMetrics,
+    /// Started in new windows and tabs
+    shell: Shell,
+    title: String,
+    /// Where the output of the ptys is copied to, for `--log-pty`
+    pty_log: Option<PtyLog>,
+}
+
+impl App {
+    pub fn new(
+        config: Config,
+        config_path: Option<PathBuf>,
+        metrics: CellMetrics,
+        shell: Shell,
+        title: String,
+        pty_log: Option<PtyLog>,
+    ) -> Self {
+        Self {
+            windows: BTreeMap::new(),
+            theme: load_theme(&config),
+            metrics,
+            config,
+            config_modified: config_path.as_deref().and_then(modified),
+            config_path,
+            notice: None,
+            notices: 0,
+            shell,
+            title,
+            pty_log,
+        }
+    }
+
+    /// Records the first tab of the first window, for `--record`
+    fn record(&mut self, path: &Path) -> Task<Message> {
+        let Some(screen) = self.windows.values_mut().next() else {
+            return Task::none();
+        };
+        match screen.active_mut().start_recording(path) {
+            Ok(()) => Task::none(),
+            Err(e) => self.notify(e.to_string()),
+        }
+    }
+
+    /// Opens a window whose first tab runs `session`, with `profile`
+    fn open_window(&mut self, session: Session, profile: Option<String>) -> Task<Message> {
+        let (id, open) = window::open(window::Settings {
+            size: WINDOW_SIZE,
+            transparent: true,
+            decorations: self.config.decorations,
+            level: window_level(self.config.always_on_top),
+            exit_on_close_request: false,
+            ..window::Settings::default()
+        });
+
+        let screen = Screen::new(self, id, session, profile);
+        self.windows.insert(id, screen);
+        match self.config.fullscreen {
+            true => open.then(|id| window::change_mode(id, window::Mode::Fullscreen)),
+            false => open.discard(),
+        }
+    }
+
+    /// Offers the session saved on the last exit in the first window
+    fn offer_restore(&mut self, saved: restore::Saved) {
+        if let Some(screen) = self.windows.values_mut().next() {
+            screen.restore = Some(saved);
+        }
+    }
+
+    /// Writes the settings pane of a window to the config file, which is then reloaded like
+    /// any other edit. A failed save keeps the pane up with the reason
+    fn save_settings(&mut self, id: window::Id) -> Task<Message> {
+        let Some(screen) = self.windows.get_mut(&id) else {
+            return Task::none();
+        };
+        let Some(settings) = screen.settings.as_mut() else {
+            return Task::none();
+        };
+
+        let saved = match &self.config_path {
+            Some(path) => settings.save(path),
+            None => Err("there's no config file to save to".to_owned()),
+        };
+        match saved {
+            Ok(()) => {
+                screen.settings = None;
+                self.reload_config()
+            }
+            Err(e) => {
+                settings.error = Some(e);
+                Task::none()
+            }
+        }
+    }
+
+    /// Saves the window without its tab bar, encoded off the UI thread
+    fn save_screenshot(&mut self, id: window::Id, shot: window::Screenshot) -> Task<Message> {
+        let Some(screen) = self.windows.get_mut(&id) else {
+            return Task::none();
+        };
+        screen.capture = None;
+
+        let top = if screen.shows_tab_bar() {
+            TAB_BAR_HEIGHT
+        } else {
+            0.0
+        };
+        let path = screenshot::path();
+        let saved = path.clone();
+        Task::perform(
+            async_std::task::spawn_blocking(move || {
+                screenshot::save(&screenshot::below(shot, top), &saved)
+            }),
+            move |result| Message::ScreenshotSaved(path.clone(), result),
+        )
+    }
+
+    /// Applies the config to every window once the file changed. One that doesn't load leaves
+    /// the current settings in place and says why
+    fn reload_config(&mut self) -> Task<Message> {
+        let Some(path) = &self.config_path else {
+            return Task::none();
+        };
+        let modified = modified(path);
+        // a deleted config keeps what's running
+        if modified == self.config_modified || modified.is_none() {
+            return Task::none();
+        }
+        self.config_modified = modified;
+
+        match Config::load(path) {
+            Ok(config) => {
+                self.config = config;
+                self.theme = load_theme(&self.config);
+                for screen in self.windows.values_mut() {
+                    screen.reconfigure(&self.config, &self.theme);
+                }
+                self.notice = None;
+                Task::none()
+            }
+            Err(e) => self.notify(e.to_string()),
+        }
+    }
+
+    fn notify(&mut self, text: String) -> Task<Message> {
+        self.notices += 1;
+        let n = self.notices;
+        self.notice = Some((n, text));
+        Task::perform(async_std::task::sleep(NOTICE_DURATION), move |_| {
+            Message::DismissNotice(n)
+        })
+    }
+
+    /// What a key press does, the key bindings from the config go first
+    fn key_message(&self, press: KeyPress) -> Option<Message> {
+        let binding = self
+            .config
+            .keybindings
+            .iter()
+            .find(|binding| is_bound(binding, &press.base_key, press.mods));
+        if let Some(binding) = binding {
+            return Some(action_message(&binding.action));
+        }
+
+        let KeyPress {
+            key,
+            base_key,
+            text,
+            mods,
+            location,
+        } = press;
+        handle_key(key, base_key, text.as_deref(), mods, location)
+    }
+
+    pub fn title(&self, id: window::Id) -> String {
+        self.windows
+            .get(&id)
+            .map_or_else(|| self.title.clone(), Screen::title)
+    }
+
+    /// The window itself is always transparent, the background is painted by `view` so its
+    /// opacity can change at runtime
+    pub fn style(&self, theme: &Theme) -> application::Appearance {
+        application::Appearance {
+            background_color: Color::TRANSPARENT,
+            text_color: theme.palette().text,
+        }
+    }
+
+    pub fn view(&self, id: window::Id) -> Element<'_, Message> {
+        match self.windows.get(&id) {
+            Some(screen) => {
+                let capturing = screen.capture.is_some();
+                let screen = screen
+                    .view()
+                    .map(move |message| Message::Window(id, Box::new(message)));
+                match &self.notice {
+                    Some((_, text)) if !capturing => widget::stack![screen, notice(text)].into(),
+                    _ => screen,
+                }
+            }
+            // already closed, it goes away with the next frame
+            None => widget::horizontal_space().into(),
+        }
+    }
+
+    pub fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::Window(id, message) => match *message {
+                Message::KeyPressed(press) => match self.key_message(press) {
+                    Some(message) => self.update(Message::Window(id, Box::new(message))),
+                    None => Task::none(),
+                },
+                Message::NewWindow => self.open_window(Session::Pty(self.shell.clone()), None),
+                Message::Settings(SettingsEvent::Save) => self.save_settings(id),
+                Message::TakeScreenshot => {
+                    if let Some(screen) = self.windows.get_mut(&id) {
+                        screen.capture = Some(Capture::Drawing);
+                    }
+                    Task::none()
+                }
+                Message::Drawn => match self.windows.get_mut(&id) {
+                    Some(screen) if screen.capture == Some(Capture::Drawing) => {
+                        screen.capture = Some(Capture::Taking);
+                        window::screenshot(id).map(move |shot| {
+                            Message::Window(id, Box::new(Message::Screenshot(shot)))
+                        })
+                    }
+                    _ => Task::none(),
+                },
+                Message::Screenshot(shot) => self.save_screenshot(id, shot),
+                Message::Failed(e) => self.notify(e.to_string()),
+                message => {
+                    let Some(screen) = self.windows.get_mut(&id) else {
+                        return Task::none();
+                    };
+                    screen
+                        .update(message)
+                        .map(move |message| Message::Window(id, Box::new(message)))
+                }
+            },
+            Message::PollConfig => self.reload_config(),
+            Message::ScreenshotSaved(path, Ok(())) => {
+                self.notify(format!("saved the screenshot to {}", path.display()))
+            }
+            Message::ScreenshotSaved(path, Err(e)) => {
+                self.notify(format!("failed to save {}: {}", path.display(), e))
+            }
+            Message::DismissNotice(n) => {
+                if self
+                    .notice
+                    .as_ref()
+                    .is_some_and(|(current, _)| *current == n)
+                {
+                    self.notice = None;
+                }
+                Task::none()
+            }
+            Message::WindowClosed(id) => {
+                let closed = self.windows.remove(&id);
+                if self.windows.is_empty() {
+                    if self.config.restore_session {
+                        let saved = closed.and_then(|screen| screen.saved);
+                        restore::save(&saved.unwrap_or_default());
+                    }
+                    return iced::exit();
+                }
+                Task::none()
+            }
+            _ => Task::none(),
+        }
+    }
+}
+
+fn window_level(always_on_top: bool) -> window::Level {
+    match always_on_top {
+        true => window::Level::AlwaysOnTop,
+        false => window::Level::Normal,
+    }
+}
+
+fn window_mode(fullscreen: bool) -> window::Mode {
+    match fullscreen {
+        true => window::Mode::Fullscreen,
+        false => window::Mode::Windowed,
+    }
+}
+
+fn tab_message(click: TabClick) -> Message {
+    match click {
+        TabClick::Select(id) => Message::SelectTab(id),
+        TabClick::Close(id) => Message::CloseTab(id),
+        TabClick::New => Message::NewTab,
+        TabClick::ProfileMenu => Message::ToggleProfileMenu,
+        TabClick::Profile(index) => Message::NewProfileTab(index),
+    }
+}
+
+/// Colors and font a tab gets in place of the configured ones: the host profile of an ssh
+/// tab, or the profile it was opened with
+fn tab_look<'a>(config: &'a Config, tab: &Terminal) -> Option<&'a Look> {
+    match &tab.session {
+        Session::Ssh(target, _) => config.host_profile(&target.host).map(|host| &host.look),
+        _ => tab
+            .profile
+            .as_deref()
+            .and_then(|name| config.profile(name))
+            .map(|profile| &profile.look),
+    }
+}
+
+/// Puts `panel` in the middle of the window, over a backdrop that keeps the mouse away from
+/// the terminal
+fn dialog<'a, T: 'a>(panel: impl Into<Element<'a, T>>) -> Element<'a, T> {
+    let panel_style = Style::default()
+        .background(Color {
+            a: 0.9,
+            ..Color::BLACK
+        })
+        .color(Color::WHITE)
+        .border(Border::default().rounded(4));
+    let backdrop = Style::default().background(Color {
+        a: 0.4,
+        ..Color::BLACK
+    });
+
+    let dialog = container(
+        container(panel)
+            .padding(16)
+            .max_width(600)
+            .style(move |_| panel_style),
+    )
+    .center(Length::Fill)
+    .style(move |_| backdrop);
+    widget::opaque(dialog)
+}
+
+/// A message in the top right corner of the window, which doesn't take any input
+fn notice(text: &str) -> Element<'_, Message> {
+    let style = Style::default()
+        .background(Color {
+            a: 0.9,
+            ..Color::BLACK
+        })
+        .color(Color::WHITE)
+        .border(
+            Border::default()
+                .rounded(4)
+                .width(1)
+                .color(Color::from_rgb8(0xd0, 0x40, 0x40)),
+        );
+    let notice = container(widget::text(text).font(MONO).size(12))
+        .padding(8)
+        .max_width(600)
+        .style(move |_| style);
+
+    container(notice)
+        .padding(12)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .align_x(alignment::Horizontal::Right)
+        .align_y(alignment::Vertical::Top)
+        .into()
+}
+
+/// Lists the commands closing the window would kill. Enter confirms and any other key cancels
+fn close_dialog(running: &[String]) -> Element<'_, Message> {
+    let buttons = widget::row![
+        widget::button(widget::text("Cancel").size(12)).on_press(CloseChoice::Cancel),
+        widget::button(widget::text("Close").size(12)).on_press(CloseChoice::Close),
+    ]
+    .spacing(8);
+    let panel = widget::column![
+        widget::text("Close this window? This would kill:").size(14),
+        widget::text(running.join("\n")).font(MONO).size(12),
+        buttons,
+    ]
+    .spacing(12);
+
+    dialog(panel).map(|choice| match choice {
+        CloseChoice::Close => Message::ConfirmClose,
+        CloseChoice::Cancel => Message::CancelClose,
+    })
+}
+
+/// Lists the tabs saved on the last exit, restoring them is up to the user
+fn restore_dialog(saved: &restore::Saved) -> Element<'_, Message> {
+    let tabs: Vec<String> = saved
+        .tabs
+        .iter()
+        .map(|tab| match (&tab.ssh, &tab.dir) {
+            (Some(host), _) => format!("{} on {}", tab.title, host),
+            (None, Some(dir)) => format!("{} in {}", tab.title, process::tilde(dir)),
+            (None, None) => tab.title.clone(),
+        })
+        .collect();
+    let question = match tabs.len() {
+        1 => "Restore the tab of the last session?".to_owned(),
+        n => format!("Restore the {} tabs of the last session?", n),
+    };
+
+    let buttons = widget::row![
+        widget::button(widget::text("Not now").size(12)).on_press(RestoreChoice::Dismiss),
+        widget::button(widget::text("Restore").size(12)).on_press(RestoreChoice::Restore),
+    ]
+    .spacing(8);
+    let panel = widget::column![
+        widget::text(question).size(14),
+        widget::text(tabs.join("\n")).font(MONO).size(12),
+        buttons,
+    ]
+    .spacing(12);
+
+    dialog(panel).map(|choice| match choice {
+        RestoreChoice::Restore => Message::RestoreSession,
+        RestoreChoice::Dismiss => Message::DismissRestore,
+    })
+}
+
+/// Font, theme, scrollback and cursor, saved to the config file
+fn settings_dialog(settings: &Settings) -> Element<'_, Message> {
+    let fields = &settings.fields;
+    let field =
+        |label, input: Element<'static, SettingsEvent>| -> Element<'static, SettingsEvent> {
+            widget::row![widget::text(label).size(12).width(100), input]
+                .align_y(Alignment::Center)
+                .spacing(8)
+                .into()
+        };
+    let input = |placeholder, value: &str, on_input: fn(String) -> SettingsEvent| {
+        widget::text_input(placeholder, value)
+            .on_input(on_input)
+            .on_submit(SettingsEvent::Save)
+            .font(MONO)
+            .size(12)
+            .padding(6)
+            .into()
+    };
+
+    let cursor = widget::row![
+        widget::pick_list(
+            CURSOR_SHAPES,
+            Some(fields.cursor_shape),
+            SettingsEvent::CursorShape
+        )
+        .text_size(12),
+        widget::checkbox("Blinking", fields.cursor_blinking)
+            .on_toggle(SettingsEvent::CursorBlinking)
+            .text_size(12),
+    ]
+    .align_y(Alignment::Center)
+    .spacing(12);
+    let buttons = widget::row![
+        widget::button(widget::text("Cancel").size(12)).on_press(SettingsEvent::Cancel),
+        widget::button(widget::text("Save").size(12)).on_press(SettingsEvent::Save),
+    ]
+    .spacing(8);
+
+    let mut panel = widget::column![
+        widget::text("Settings").size(14),
+        field(
+            "Font",
+            input("monospace", &fields.font_family, SettingsEvent::FontFamily)
+        ),
+        field(
+            "Font size",
+            input("16", &fields.font_size, SettingsEvent::FontSize)
+        ),
+        field(
+            "Theme",
+            input("default colors", &fields.theme, SettingsEvent::Theme)
+        ),
+        field(
+            "Scrollback",
+            input("lines", &fields.scrollback, SettingsEvent::Scrollback)
+        ),
+        field("Cursor", cursor.into()),
+    ]
+    .spacing(8)
+    .width(480);
+    if let Some(error) = &settings.error {
+        panel = panel.push(
+            widget::text(error)
+                .size(12)
+                .color(Color::from_rgb8(0xd0, 0x40, 0x40)),
+        );
+    }
+
+    dialog(panel.push(buttons)).map(Message::Settings)
+}
+
+fn search_input() -> widget::text_input::Id {
+    widget::text_input::Id::new("search")
+}
+
+/// The query in the top right corner with how far along the matches the view is. It stays
+/// editable until Enter
+fn search_bar(search: &Search) -> Element<'_, Message> {
+    let query: Element<'_, SearchEvent> = if search.editing {
+        widget::text_input("search", &search.query)
+            .id(search_input())
+            .on_input(SearchEvent::Input)
+            .on_submit(SearchEvent::Submit)
+            .font(MONO)
+            .size(12)
+            .padding(4)
+            .width(240)
+            .into()
+    } else {
+        widget::text(&search.query)
+            .font(MONO)
+            .size(12)
+            .width(240)
+            .into()
+    };
+    let count = match (&search.error, search.current) {
+        (Some(_), _) => "invalid".to_owned(),
+        (None, Some(i)) => format!("{}/{}", i + 1, search.matches.len()),
+        (None, None) => format!("0/{}", search.matches.len()),
+    };
+    let row = widget::row![
+        query,
+        widget::checkbox(".*", search.regex)
+            .on_toggle(SearchEvent::Regex)
+            .size(12)
+            .text_size(12),
+        widget::text(count).font(MONO).size(12),
+    ]
+    .align_y(Alignment::Center)
+    .spacing(8);
+
+    let style = Style::default()
+        .background(Color {
+            a: 0.8,
+            ..Color::BLACK
+        })
+        .color(Color::WHITE)
+        .border(Border::default().rounded(4));
+    let bar: Element<'_, SearchEvent> = container(container(row).padding(6).style(move |_| style))
+        .padding(8)
+        .width(Length::Fill)
+        .align_x(alignment::Horizontal::Right)
+        .into();
+    bar.map(Message::Search)
+}
+
+fn dump_input() -> widget::text_input::Id {
+    widget::text_input::Id::new("dump")
+}
+
+/// A field for the file or the command, and for the scrollback whether to keep the colors.
+/// Escape cancels
+fn dump_dialog(dump: &Dump) -> Element<'_, Message> {
+    let (title, placeholder) = match dump.kind {
+        DumpKind::File => ("Save the scrollback to a file", "~/scrollback.txt"),
+        DumpKind::Command => ("Pipe the scrollback to a command", "less -R"),
+        DumpKind::Html => ("Save as HTML", "~/screen.html"),
+    };
+    let input = widget::text_input(placeholder, &dump.input)
+        .id(dump_input())
+        .on_input(DumpEvent::Input)
+        .on_submit(DumpEvent::Submit)
+        .font(MONO)
+        .size(12)
+        .padding(6);
+    let mut panel = widget::column![widget::text(title).size(14), input]
+        .spacing(8)
+        .width(400);
+    if dump.kind != DumpKind::Html {
+        panel = panel.push(
+            widget::checkbox("Keep the colors", dump.colors)
+                .on_toggle(DumpEvent::Colors)
+                .text_size(12),
+        );
+    }
+    if let Some(error) = &dump.error {
+        panel = panel.push(
+            widget::text(error)
+                .size(12)
+                .color(Color::from_rgb8(0xd0, 0x40, 0x40)),
+        );
+    }
+
+    dialog(panel).map(Message::Dump)
+}
+
+fn quick_connect_input() -> widget::text_input::Id {
+    widget::text_input::Id::new("quick-connect")
+}
+
+/// A field for `[user@]host[:port]` and the hosts connected to lately. Escape cancels
+fn quick_connect_dialog(quick_connect: &QuickConnect) -> Element<'_, Message> {
+    let input = widget::text_input("user@host:port", &quick_connect.input)
+        .id(quick_connect_input())
+        .on_input(QuickConnectEvent::Input)
+        .on_submit(QuickConnectEvent::Submit)
+        .font(MONO)
+        .size(12)
+        .padding(6);
+    let recent = quick_connect.recent.iter().map(|host| {
+        widget::button(widget::text(host).font(MONO).size(12))
+            .width(Length::Fill)
+            .style(button::text)
+            .on_press(QuickConnectEvent::Pick(host.clone()))
+            .into()
+    });
+    let panel = widget::column![
+        widget::text("Connect with ssh").size(14),
+        input,
+        widget::Column::with_children(recent),
+    ]
+    .spacing(8)
+    .width(400);
+
+    dialog(panel).map(Message::QuickConnect)
+}
+
+/// The latest commands of the tab with how they exited. Each can be run again, have its output
+/// copied, or be scrolled back to by clicking it. Escape closes the list
+fn commands_dialog(commands: &[CommandEntry]) -> Element<'_, Message> {
+    let entries = commands.iter().map(|entry| {
+        let (status, color) = match (entry.finished, entry.exit_status) {
+            (false, _) => ("…".to_owned(), Color::from_rgb8(0xa0, 0xa0, 0xa0)),
+            (true, Some(0)) => ("✓".to_owned(), Color::from_rgb8(0x40, 0xb0, 0x40)),
+            (true, Some(status)) => (status.to_string(), Color::from_rgb8(0xd0, 0x40, 0x40)),
+            (true, None) => ("?".to_owned(), Color::from_rgb8(0xa0, 0xa0, 0xa0)),
+        };
+        let command = widget::button(widget::text(&entry.command).font(MONO).size(12))
+            .width(Length::Fill)
+            .style(button::text)
+            .on_press(CommandsEvent::Show(entry.prompt));
+        widget::row![
+            widget::text(status)
+                .font(MONO)
+                .size(12)
+                .color(color)
+                .width(32),
+            command,
+            widget::button(widget::text("Run").size(12))
+                .on_press(CommandsEvent::Run(entry.command.clone())),
+            widget::button(widget::text("Copy output").size(12))
+                .on_press(CommandsEvent::CopyOutput(entry.prompt)),
+        ]
+        .align_y(Alignment::Center)
+        .spacing(8)
+        .into()
+    });
+    let list: Element<'_, CommandsEvent> = match commands.is_empty() {
+        true => widget::text("No commands yet, the shell has to mark them with OSC 133")
+            .size(12)
+            .into(),
+        false => container(widget::scrollable(
+            widget::Column::with_children(entries).spacing(4),
+        ))
+        .max_height(440)
+        .into(),
+    };
+    let panel = widget::column![widget::text("Commands").size(14), list]
+        .spacing(8)
+        .width(560);
+
+    dialog(panel).map(Message::Commands)
+}
+
+/// Ctrl+Shift plus `letter`, and Cmd plus it on macOS. Plain Ctrl is left to the application
+fn is_clipboard_shortcut(c: &str, letter: &str, mods: Modifiers) -> bool {
+    let macos = cfg!(target_os = "macos") && mods.logo() && !mods.shift();
+    c.eq_ignore_ascii_case(letter) && ((mods.control() && mods.shift()) || macos)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Whether `binding` is for `key`, taken without any modifier applied
+fn is_bound(binding: &KeyBinding, key: &Key, mods: Modifiers) -> bool {
+    if binding.mods != mods {
+        return false;
+    }
+    match key {
+        Key::Character(c) => c.to_lowercase() == binding.key,
+        Key::Named(named) => format!("{:?}", named).to_lowercase() == binding.key,
+        Key::Unidentified => false,
+    }
+}
+
+fn action_message(action: &Action) -> Message {
+    match action {
+        Action::Copy => Message::Copy,
+        Action::CopyLastOutput => Message::CopyLastOutput,
+        Action::Paste => Message::Paste,
+        Action::NewTab => Message::NewTab,
+        Action::CloseTab => Message::CloseActiveTab,
+        Action::NextTab => Message::SwitchTab(1),
+        Action::PreviousTab => Message::SwitchTab(-1),
+        Action::MoveTabLeft => Message::MoveTab(-1),
+        Action::MoveTabRight => Message::MoveTab(1),
+        Action::RestartTab => Message::RestartTab,
+        Action::NewWindow => Message::NewWindow,
+        Action::QuickConnect => Message::OpenQuickConnect,
+        Action::PreviousPrompt => Message::JumpToPrompt(-1),
+        Action::NextPrompt => Message::JumpToPrompt(1),
+        Action::CommandHistory => Message::OpenCommands,
+        Action::SplitRight => Message::Split(Axis::Horizontal),
+        Action::SplitDown => Message::Split(Axis::Vertical),
+        Action::ClosePane => Message::CloseActivePane,
+        Action::NextPane => Message::FocusPane(1),
+        Action::PreviousPane => Message::FocusPane(-1),
+        Action::ZoomPane => Message::Layout(Change::Zoom),
+        Action::SwapPanePrevious => Message::Layout(Change::SwapPrevious),
+        Action::SwapPaneNext => Message::Layout(Change::SwapNext),
+        Action::RotatePanes => Message::Layout(Change::Rotate),
+        Action::Settings => Message::OpenSettings,
+        Action::Search => Message::OpenSearch,
+        Action::ClearScreen => Message::ClearScreen,
+        Action::ClearScrollback => Message::ClearScrollback,
+        Action::SaveScrollback => Message::SaveScrollback,
+        Action::PipeScrollback => Message::PipeScrollback,
+        Action::CopyHtml => Message::CopyHtml,
+        Action::SaveHtml => Message::SaveHtml,
+        Action::Screenshot => Message::TakeScreenshot,
+        Action::ToggleRecording => Message::ToggleRecording,
+        Action::ZoomIn => Message::FontZoom(Zoom::In),
+        Action::ZoomOut => Message::FontZoom(Zoom::Out),
+        Action::ZoomReset => Message::FontZoom(Zoom::Reset),
+        Action::ToggleHints => Message::ToggleHints,
+        Action::ToggleReadOnly => Message::ToggleReadOnly,
+        Action::ToggleTransparency => Message::ToggleTransparency,
+        Action::ToggleAlwaysOnTop => Message::ToggleAlwaysOnTop,
+        Action::ToggleFullscreen => Message::ToggleFullscreen,
+        Action::ToggleDecorations => Message::ToggleDecorations,
+        Action::ToggleDebug => Message::ToggleDebug,
+        Action::Chars(text) => Message::write(text.as_str()),
+    }
+}
+
+/// `key` has the layout and every modifier but Ctrl applied, `base_key` has none of them, and
+/// `text` is what the OS produced for the press (dead keys and compose sequences included)
+fn handle_key(
+    key: Key,
+    base_key: Key,
+    text: Option<&str>,
+    mods: Modifiers,
+    location: Location,
+) -> Option<Message> {
+    use iced::keyboard::Key as IKey;
+    use Content::*;
+    use Message::*;
+
+    match key {
+        IKey::Character(c) if mods.control() && matches!(c.as_str(), "=" | "+") => {
+            Some(FontZoom(Zoom::In))
+        }
+        IKey::Character(c) if mods.control() && c.as_str() == "-" => Some(FontZoom(Zoom::Out)),
+        IKey::Character(c) if mods.control() && c.as_str() == "0" => Some(FontZoom(Zoom::Reset)),
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("o") => {
+            Some(ToggleTransparency)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("u") => {
+            Some(ToggleHints)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("r") => {
+            Some(ToggleReadOnly)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("t") => {
+            Some(NewTab)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("w") => {
+            Some(CloseActiveTab)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("n") => {
+            Some(NewWindow)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("s") => {
+            Some(OpenQuickConnect)
+        }
+        IKey::Character(c) if mods.control() && c.as_str() == "," => Some(OpenSettings),
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("f") => {
+            Some(OpenSearch)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("l") => {
+            Some(ClearScreen)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("k") => {
+            Some(ClearScrollback)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("e") => {
+            Some(SaveScrollback)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("p") => {
+            Some(PipeScrollback)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("h") => {
+            Some(CopyHtml)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("i") => {
+            Some(TakeScreenshot)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("y") => {
+            Some(ToggleRecording)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("z") => {
+            Some(JumpToPrompt(-1))
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("x") => {
+            Some(JumpToPrompt(1))
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("g") => {
+            Some(OpenCommands)
+        }
+        IKey::Character(c) if mods.control() && mods.shift() && c.eq_ignore_ascii_case("j") => {
+            Some(CopyLastOutput)
+        }
+        IKey::Character(c) if is_clipboard_shortcut(&c, "v", mods) => Some(Paste),
+        IKey::Character(c) if is_clipboard_shortcut(&c, "c", mods) => Some(Copy),
+        IKey::Character(c) if mods.alt() && location != Location::Numpad => {
+            // macOS applies Option to the character, Meta bindings want the key underneath
+            let meta = match base_key {
+                IKey::Character(base) if cfg!(target_os = "macos") => base,
+                _ => c.clone(),
+            };
+            let meta = match keys::control_byte(&meta).filter(|_| mods.control()) {
+                Some(byte) => vec![byte],
+                None => meta.as_bytes().to_vec(),
+            };
+            Some(Write(Meta(meta, text.unwrap_or(&c).to_string())))
+        }
+        IKey::Character(c) if mods.control() => match keys::control_byte(&c) {
+            Some(byte) => Some(Write(Bytes(vec![byte]))),
+            None => Some(Message::write(text.unwrap_or(&c))),
+        },
+        // plain F12 goes to the application like the other function keys
+        IKey::Named(Named::F12) if mods.control() && mods.shift() => Some(ToggleDebug),
+        IKey::Named(Named::F11) if mods.control() && mods.shift() => Some(ToggleFullscreen),
+        IKey::Named(Named::F5) if mods.control() && mods.shift() => Some(RestartTab),
+        IKey::Named(Named::ScrollLock) => Some(ToggleReadOnly),
+        IKey::Named(named @ (Named::PageUp | Named::PageDown)) if mods.control() => {
+            let step = if named == Named::PageUp { -1 } else { 1 };
+            Some(if mods.shift() {
+                MoveTab(step)
+            } else {
+                SwitchTab(step)
+            })
+        }
+        IKey::Named(Named::Tab) if mods.control() => {
+            Some(SwitchTab(if mods.shift() { -1 } else { 1 }))
+        }
+        key if location == Location::Numpad => Some(Write(Keypad(key, mods))),
+        IKey::Character(c) => Some(Message::write(text.unwrap_or(&c))),
+        IKey::Named(named) => Some(Write(Content::Key(named, mods))),
+        // a compose sequence can finish on a key the layout has no character for, what it
+        // composed still comes through as text. Dead keys themselves carry none
+        _ => text
+            .filter(|text| !text.chars().any(char::is_control))
+            .map(Message::write),
+    }
+}
+
+/// Starts the session of the tab `id` and streams what comes out of it
+fn pcomms(
+    id: TabId,
+    session: Session,
+    winsize: winsize,
+    log: Option<PtyLog>,
+    detachable: bool,
+) -> impl Stream<Item = Message> {
+    stream::channel(OUTPUT_QUEUE, move |mut output| async move {
+        let (tx, mut rx) = channel::<Message>(OUTPUT_QUEUE);
+        let name = session.to_string();
+        let started = match session {
+            Session::Pty(shell) | Session::Ssh(_, shell) => {
+                start_pty(id, shell, winsize, tx, log, detachable).await
+            }
+            Session::Attach(session) => attach(id, session, tx, log).await,
+            Session::Replay { recording, timing } => replay(id, &recording, timing, tx).await,
+            Session::Stdin => page(id, tx).await,
+            Session::Tmux { gateway, pane } => tmux_pane(id, gateway, pane, tx).await,
+            session => connect(id, &session, tx, log).await,
+        };
+        if let Err(e) = started {
+            tracing::error!(tab = id.0, "failed to start {}: {}", name, e);
+            let _ = output.send(Message::Failed(e)).await;
+            let _ = output.send(Message::ChildExited(id, None)).await;
+            return;
+        }
+
+        // the application is going away when the output can't be sent
+        while let Some(message) = rx.recv().await {
+            if output.send(message).await.is_err() || output.flush().await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Forks `shell` on a pty of `winsize`. Only returns once the child got as far as exec, so a
+/// program that doesn't exist is an error here rather than a tab that exits right away. A
+/// `detachable` one is handed to the daemon, the tab doesn't hang up on it
+async fn start_pty(
+    id: TabId,
+    shell: Shell,
+    winsize: winsize,
+    tx: Sender<Message>,
+    log: Option<PtyLog>,
+    detachable: bool,
+) -> std::result::Result<(), EmuError> {
+    // spawning waits for the exec to go through
+    let spawned = {
+        let shell = shell.clone();
+        async_std::task::spawn_blocking(move || shell.spawn(&winsize)).await
+    };
+    let (master, child) = spawned.map_err(|e| EmuError::Spawn(shell.program.clone(), e))?;
+
+    let whandle: File = master.into();
+    let kept = match detachable {
+        true => match keep(&whandle, shell.program.display().to_string()).await {
+            Ok(kept) => Some(kept),
+            Err(e) => {
+                tracing::warn!(tab = id.0, "failed to hand the pty to the daemon: {}", e);
+                None
+            }
+        },
+        false => None,
+    };
+    let rhandle = tokio::fs::File::from(whandle.try_clone().map_err(EmuError::Pty)?);
+    let writer = PtyWriter::spawn(whandle.try_clone().map_err(EmuError::Pty)?);
+    let child_of_tab = kept.is_none().then_some(child);
+    let _ = tx
+        .send(Message::Init(id, Some(whandle), writer, child_of_tab))
+        .await;
+
+    // the pty may still hold output when the child exits, so the exit is only passed on
+    // once the reader is done with it
+    let (exit_tx, exit_rx) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = exit_tx.send(shell::wait(child));
+    });
+
+    async_std::task::spawn(async move {
+        if !read_kept_pty(id, rhandle, &tx, log, kept).await {
+            return;
+        }
+        let status = exit_rx.await.ok().flatten();
+        let _ = tx.send(Message::ChildExited(id, status)).await;
+    });
+    Ok(())
+}
+
+/// Hands the pty to the daemon off the executor, it may have to be started and waited for
+async fn keep(master: &File, title: String) -> std::io::Result<UnixStream> {
+    let master = master.try_clone()?;
+    async_std::task::spawn_blocking(move || daemon::keep(&master, &title)).await
+}
+
+/// Takes a detached session from the daemon, and shows what it printed while detached
+async fn attach(
+    id: TabId,
+    session: u32,
+    tx: Sender<Message>,
+    log: Option<PtyLog>,
+) -> std::result::Result<(), EmuError> {
+    let open = |e| EmuError::Open(Session::Attach(session).to_string(), e);
+    // connecting to the daemon blocks
+    let attached = async_std::task::spawn_blocking(move || daemon::attach(session)).await;
+    let (master, tail, kept) = attached.map_err(open)?;
+    let rhandle = tokio::fs::File::from(master.try_clone().map_err(open)?);
+    let writer = PtyWriter::spawn(master.try_clone().map_err(open)?);
+    let _ = tx.send(Message::Init(id, Some(master), writer, None)).await;
+    if !tail.is_empty() {
+        let tail = Bytes::from(tail);
+        let _ = tx
+            .send(Message::Output(id, Output::parse(&tail), tail))
+            .await;
+    }
+
+    async_std::task::spawn(async move {
+        if read_kept_pty(id, rhandle, &tx, log, Some(kept)).await {
+            let _ = tx.send(Message::ChildExited(id, None)).await;
+        }
+    });
+    Ok(())
+}
+
+/// `read_pty`, except that a pty the daemon `kept` is let go of as soon as the tab is gone, the
+/// daemon reads it from then on. False when it was, the child is still running
+async fn read_kept_pty(
+    id: TabId,
+    file: tokio::fs::File,
+    tx: &Sender<Message>,
+    log: Option<PtyLog>,
+    kept: Option<UnixStream>,
+) -> bool {
+    let read = read_pty(id, file, tx, log);
+    if kept.is_none() {
+        read.await;
+        return true;
+    }
+    tokio::select! {
+        _ = read => true,
+        _ = tx.closed() => false,
+    }
+}
+
+/// Opens a socket or a serial port, which ends once the other side hangs up
+async fn connect(
+    id: TabId,
+    session: &Session,
+    tx: Sender<Message>,
+    log: Option<PtyLog>,
+) -> std::result::Result<(), EmuError> {
+    let open = |e| EmuError::Open(session.to_string(), e);
+    // resolving a host and connecting to it block, and so can opening a serial port
+    let opened = session.clone();
+    let file = async_std::task::spawn_blocking(move || opened.open())
+        .await
+        .map_err(open)?;
+    let rhandle = tokio::fs::File::from(file.try_clone().map_err(open)?);
+    let connection = session.connection(&file).map_err(open)?;
+    let writer = PtyWriter::spawn(file);
+    let _ = tx.send(Message::Init(id, None, writer, None)).await;
+    if let Some(connection) = connection {
+        let _ = tx.send(Message::Connected(id, connection)).await;
+    }
+
+    async_std::task::spawn(async move {
+        read_pty(id, rhandle, &tx, log).await;
+        let _ = tx.send(Message::ChildExited(id, None)).await;
+    });
+    Ok(())
+}
+
+/// Plays a recording back as if it was output, keys typed in the tab control the playback. It
+/// ends on `q` rather than once everything was shown, so it can still be seeked back
+async fn replay(
+    id: TabId,
+    recording: &Path,
+    timing: Option<PathBuf>,
+    tx: Sender<Message>,
+) -> std::result::Result<(), EmuError> {
+    let recording = Recording::load(recording, timing.as_deref())?;
+    let (writer, mut keys) = PtyWriter::channel();
+    let _ = tx.send(Message::Init(id, None, writer, None)).await;
+
+    async_std::task::spawn(async move {
+        let mut player = Player::new(recording);
+        loop {
+            let started = std::time::Instant::now();
+            let key = match player.wait() {
+                Some(wait) => async_std::future::timeout(wait, keys.recv()).await.ok(),
+                None => Some(keys.recv().await),
+            };
+            let mut output = player.advance(started.elapsed());
+            match key.map(|key| key.map(|key| player.key(&key))) {
+                // the tab is gone
+                Some(None) => return,
+                Some(Some(Step::Quit)) => break,
+                Some(Some(Step::Show(shown))) => output.extend(shown),
+                None => {}
+            }
+            if output.is_empty() {
+                continue;
+            }
+
+            let output = Bytes::from(output);
+            let items = Output::parse(&output);
+            if tx.send(Message::Output(id, items, output)).await.is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(Message::ChildExited(id, None)).await;
+    });
+    Ok(())
+}
+
+/// A pane of tmux in control mode. Its output comes through the tab tmux runs in, only the
+/// keys are passed on from here
+async fn tmux_pane(
+    id: TabId,
+    gateway: TabId,
+    pane: u32,
+    tx: Sender<Message>,
+) -> std::result::Result<(), EmuError> {
+    let (writer, mut keys) = PtyWriter::channel();
+    let _ = tx.send(Message::Init(id, None, writer, None)).await;
+
+    async_std::task::spawn(async move {
+        while let Some(bytes) = keys.recv().await {
+            if tx
+                .send(Message::TmuxInput(gateway, pane, bytes))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Shows what was piped to the emulator. The tab stays open once the input ends, its keys
+/// scroll through it instead
+async fn page(id: TabId, tx: Sender<Message>) -> std::result::Result<(), EmuError> {
+    let stdin = Session::Stdin.open();
+    let stdin = stdin.map_err(|e| EmuError::Open(Session::Stdin.to_string(), e))?;
+    let mut input = tokio::fs::File::from(stdin);
+    let (writer, _) = PtyWriter::channel();
+    let _ = tx.send(Message::Init(id, None, writer, None)).await;
+
+    async_std::task::spawn(async move {
+        let mut output = b"\x1b[?25l".to_vec();
+        let mut buf = [0u8; 1024];
+        loop {
+            let n = match input.read(&mut buf).await {
+                Ok(0) => return,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    tracing::error!("failed to read the input: {}", e);
+                    return;
+                }
+            };
+            // there's no line discipline to turn newlines into CRLF as a pty would
+            for &b in &buf[..n] {
+                if b == b'\n' {
+                    output.push(b'\r');
+                }
+                output.push(b);
+            }
+
+            let bytes = Bytes::from(std::mem::take(&mut output));
+            let items = Output::parse(&bytes);
+            if tx.send(Message::Output(id, items, bytes)).await.is_err() {
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Parses what the child prints until it closes its end of the pty. Interrupted reads are
+/// retried, EOF and EIO mean the child is gone, and anything else is reported before giving up
+async fn read_pty(id: TabId, mut file: tokio::fs::File, tx: &Sender<Message>, log: Option<PtyLog>) {
+    let mut log = log.and_then(|log| match log.open() {
+        Ok(writer) => Some(writer),
+        Err(e) => {
+            tracing::error!("failed to open {}: {}", log.path.display(), e);
+            None
+        }
+    });
+    // read but not sent yet, a fast child is sent at most once per frame. Reads go straight
+    // in and what's sent is split off it, so the bytes are never copied on the way to the tab
+    let mut pending = BytesMut::new();
+    let mut read_size = READ_SIZE;
+    let mut next_frame = tokio::time::Instant::now();
+    // tmux is in control mode, see `tmux::START`
+    let mut control = false;
+    // a sequence too long to hold is being dropped, see `ansi::Unterminated::TooLong`
+    let mut dropping = false;
+    loop {
+        pending.reserve(read_size);
+        let room = pending.capacity() - pending.len();
+        let read = if pending.is_empty() {
+            file.read_buf(&mut pending).await
+        } else {
+            // a read cut short by the timeout isn't lost, the file hands it out next time
+            match tokio::time::timeout_at(next_frame, file.read_buf(&mut pending)).await {
+                Ok(read) => read,
+                Err(_) => {
+                    if !send_output(
+                        id,
+                        &mut pending,
+                        &mut next_frame,
+                        tx,
+                        false,
+                        &mut control,
+                        &mut dropping,
+                    )
+                    .await
+                    {
+                        return;
+                    }
+                    continue;
+                }
+            }
+        };
+        let n = match read {
+            Ok(0) => {
+                tracing::debug!(tab = id.0, "the child closed the pty");
+                send_output(
+                    id,
+                    &mut pending,
+                    &mut next_frame,
+                    tx,
+                    true,
+                    &mut control,
+                    &mut dropping,
+                )
+                .await;
+                return;
+            }
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            // Linux reports a pty whose other end was closed as EIO rather than EOF
+            Err(e) if e.raw_os_error() == Some(libc::EIO) => {
+                tracing::debug!(tab = id.0, "the child closed the pty");
+                send_output(
+                    id,
+                    &mut pending,
+                    &mut next_frame,
+                    tx,
+                    true,
+                    &mut control,
+                    &mut dropping,
+                )
+                .await;
+                return;
+            }
+            Err(e) => {
+                tracing::error!(tab = id.0, "failed to read from the pty: {}", e);
+                send_output(
+                    id,
+                    &mut pending,
+                    &mut next_frame,
+                    tx,
+                    true,
+                    &mut control,
+                    &mut dropping,
+                )
+                .await;
+                let _ = tx.send(Message::Failed(EmuError::Read(e))).await;
+                return;
+            }
+        };
+        tracing::trace!(tab = id.0, bytes = n, "read");
+        if let Some(writer) = &mut log {
+            if let Err(e) = writer.write(&pending[pending.len() - n..]) {
+                tracing::error!("failed to write {}: {}", writer.path.display(), e);
+                log = None;
+            }
+        }
+
+        // a read that filled the room there was means more is waiting
+        if n == room {
+            read_size = (read_size * 2).min(OUTPUT_MAX_BYTES);
+        } else if n < read_size / 4 {
+            read_size = (read_size / 2).max(READ_SIZE);
+        }
+
+        // output after a quiet frame, like an echoed key, goes out right away
+        let due = tokio::time::Instant::now() >= next_frame;
+        if (due || pending.len() >= OUTPUT_MAX_BYTES)
+            && !send_output(
+                id,
+                &mut pending,
+                &mut next_frame,
+                tx,
+                false,
+                &mut control,
+                &mut dropping,
+            )
+            .await
+        {
+            return;
+        }
+    }
+}
+
+/// Parses and sends what was read since the last frame, false once the window is gone.
+///
+/// While the window is still busy with the output it was sent before, this output is held and
+/// merged with what's read next, to go out as one once there's room. Only past `OUTPUT_BACKLOG`,
+/// or with `wait` for the last of it, does the reader wait for the window. The child then
+/// blocks on a full pty, the same as XOFF would stop it, instead of the window working through
+/// a queue of frames that are long out of date.
+///
+/// An escape sequence cut off at the end is held back the same way, to be parsed once it's
+/// whole, or dropped up to its terminator once it's too long to hold. Except for the one tmux
+/// starts control mode with, which lasts as long as it's attached and is sent as it comes, up to
+/// its ST, instead of being parsed
+async fn send_output(
+    id: TabId,
+    pending: &mut BytesMut,
+    next_frame: &mut tokio::time::Instant,
+    tx: &Sender<Message>,
+    wait: bool,
+    control: &mut bool,
+    dropping: &mut bool,
+) -> bool {
+    if *dropping {
+        let end = ansi::string_end(pending);
+        *dropping = end.is_none();
+        let _ = pending.split_to(end.unwrap_or(pending.len()));
+    }
+    let start = match *control {
+        true => None,
+        false => find(pending, tmux::START),
+    };
+    *control |= start == Some(0);
+    let len = match start {
+        _ if *control => {
+            find(pending, tmux::END).map_or(pending.len(), |end| end + tmux::END.len())
+        }
+        // what comes before it is output like any other
+        Some(start) => start,
+        None => match ansi::unterminated(pending) {
+            Some(Unterminated::Held(start)) if !wait => start,
+            Some(Unterminated::TooLong(start)) => {
+                tracing::warn!(
+                    tab = id.0,
+                    bytes = pending.len() - start,
+                    "dropped a sequence too long to hold"
+                );
+                pending.truncate(start);
+                *dropping = true;
+                start
+            }
+            _ => pending.len(),
+        },
+    };
+    if len == 0 {
+        *next_frame = tokio::time::Instant::now() + OUTPUT_FRAME;
+        return true;
+    }
+
+    let permit = match tx.try_reserve() {
+        Ok(permit) => permit,
+        Err(TrySendError::Full(())) if !wait && pending.len() < OUTPUT_BACKLOG => {
+            tracing::trace!(
+                tab = id.0,
+                bytes = pending.len(),
+                "held, the window is busy"
+            );
+            *next_frame = tokio::time::Instant::now() + OUTPUT_FRAME;
+            return true;
+        }
+        Err(TrySendError::Full(())) => match tx.reserve().await {
+            Ok(permit) => permit,
+            Err(_) => return false,
+        },
+        Err(TrySendError::Closed(())) => return false,
+    };
+
+    let bytes = pending.split_to(len).freeze();
+    if *control {
+        *control = !bytes.ends_with(tmux::END);
+        *next_frame = tokio::time::Instant::now() + OUTPUT_FRAME;
+        permit.send(Message::Tmux(id, bytes));
+        return true;
+    }
+    let items = Output::parse(&bytes);
+    tracing::trace!(
+        tab = id.0,
+        bytes = bytes.len(),
+        outputs = items.len(),
+        "parsed"
+    );
+    *next_frame = tokio::time::Instant::now() + OUTPUT_FRAME;
+    permit.send(Message::Output(id, items, bytes));
+    true
+}
+
+/// Where `needle` first shows up in `haystack`
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn subscription(app: &App) -> Subscription<Message> {
+    use event::Event as AppEvent;
+
+    fn keyboard_sub() -> Subscription<Message> {
+        event::listen_with(|event, status, id| match event {
+            AppEvent::Keyboard(keyboard::Event::KeyPressed {
+                key,
+                modified_key,
+                modifiers,
+                text,
+                location,
+                ..
+            }) if status == event::Status::Ignored => {
+                let press = KeyPress {
+                    key: modified_key,
+                    base_key: key,
+                    text: text.map(|text| text.to_string()),
+                    mods: modifiers,
+                    location,
+                };
+                Some(Message::Window(id, Box::new(Message::KeyPressed(press))))
+            }
+            _ => None,
+        })
+    }
+
+    fn process_comm_sub(s: &Terminal, log: Option<&PtyLog>) -> Subscription<Message> {
+        let stream = pcomms(
+            s.id,
+            s.session.clone(),
+            s.winsize(),
+            log.cloned(),
+            s.config.detachable,
+        );
+        Subscription::run_with_id(("pty", s.id), stream)
+    }
+
+    fn window_events() -> Subscription<Message> {
+        event::listen_with(|event, _status, id| {
+            let message = match event {
+                AppEvent::Window(window::Event::Resized(size)) => Message::WindowResized(size),
+                AppEvent::Window(window::Event::Focused) => Message::WindowFocused(true),
+                AppEvent::Window(window::Event::Unfocused) => Message::WindowFocused(false),
+                AppEvent::Mouse(mouse::Event::CursorEntered) => Message::MouseEntered,
+                _ => return None,
+            };
+            Some(Message::Window(id, Box::new(message)))
+        })
+    }
+
+    fn mouse_sub() -> Subscription<Message> {
+        // the canvas captures the wheel while it's reported to the application
+        event::listen_with(|e, status, id| match e {
+            AppEvent::Mouse(mouse::Event::WheelScrolled { delta })
+                if status == event::Status::Ignored =>
+            {
+                Some(Message::Window(id, Box::new(Message::Scroll(delta))))
+            }
+            _ => None,
+        })
+    }
+
+    fn cursor_blink_sub(s: &Terminal) -> Subscription<Message> {
+        if !s.state.cursor_visible() || !s.state.cursor_style().blinking {
+            return Subscription::none();
+        }
+
+        time::every(CURSOR_BLINK_INTERVAL).map(|_| Message::CursorBlink)
+    }
+
+    fn frames_sub(s: &Screen) -> Subscription<Message> {
+        if !s.show_debug {
+            return Subscription::none();
+        }
+
+        window::frames().map(Message::Frame)
+    }
+
+    fn bell_sub(s: &Screen) -> Subscription<Message> {
+        if !s.panes.iter().any(|pane| pane.bell_on) {
+            return Subscription::none();
+        }
+
+        time::every(s.config.visual_bell_duration).map(|_| Message::BellDone)
+    }
+
+    fn foreground_sub(s: &Screen) -> Subscription<Message> {
+        if s.panes.iter().all(|pane| pane.child.is_none()) {
+            return Subscription::none();
+        }
+
+        time::every(FOREGROUND_POLL_INTERVAL).map(|_| Message::PollForeground)
+    }
+
+    fn text_blink_sub(s: &Terminal) -> Subscription<Message> {
+        if !s.has_blinking_text() {
+            return Subscription::none();
+        }
+
+        time::every(TEXT_BLINK_INTERVAL).map(|_| Message::TextBlink)
+    }
+
+    /// Everything a window needs on its own, tagged with its id
+    fn screen_sub(id: window::Id, s: &Screen, log: Option<&PtyLog>) -> Subscription<Message> {
+        let ptys = s.panes.iter().map(|pane| process_comm_sub(pane, log));
+        Subscription::batch(ptys.chain([
+            cursor_blink_sub(s.active()),
+            text_blink_sub(s.active()),
+            bell_sub(s),
+            frames_sub(s),
+            foreground_sub(s),
+        ]))
+        .with(id)
+        .map(|(id, message)| Message::Window(id, Box::new(message)))
+    }
+
+    fn config_sub(app: &App) -> Subscription<Message> {
+        if app.config_path.is_none() {
+            return Subscription::none();
+        }
+
+        time::every(CONFIG_POLL_INTERVAL).map(|_| Message::PollConfig)
+    }
+
+    /// Until the window that's taking a screenshot draws a frame without the overlays
+    fn capture_sub(app: &App) -> Subscription<Message> {
+        let drawing = Some(Capture::Drawing);
+        if !app.windows.values().any(|s| s.capture == drawing) {
+            return Subscription::none();
+        }
+
+        // the frame is drawn by the time its event goes out
+        event::listen_raw(|event, _status, id| match event {
+            AppEvent::Window(window::Event::RedrawRequested(_)) => {
+                Some(Message::Window(id, Box::new(Message::Drawn)))
+            }
+            _ => None,
+        })
+    }
+
+    let windows = app
+        .windows
+        .iter()
+        .map(|(id, s)| screen_sub(*id, s, app.pty_log.as_ref()));
+    Subscription::batch(windows.chain([
+        config_sub(app),
+        capture_sub(app),
+        keyboard_sub(),
+        mouse_sub(),
+        window_events(),
+        window::close_requests().map(|id| Message::Window(id, Box::new(Message::CloseRequested))),
+        window::close_events().map(Message::WindowClosed),
+    ]))
+}
+
+#[tokio::main]
+pub async fn main() -> iced::Result {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    logging::init();
+
+    if args.daemon {
+        if let Err(e) = daemon::run() {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if args.sessions {
+        match daemon::list() {
+            Ok(list) if list.is_empty() => println!("no sessions"),
+            Ok(list) => print!("{}", list),
+            Err(e) => {
+                eprintln!("failed to ask the daemon: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(dir) = args.working_directory.as_ref().filter(|dir| !dir.is_dir()) {
+        eprintln!("{} is not a directory", dir.display());
+        std::process::exit(2);
+    }
+
+    // a missing config.toml is fine, a missing --config isn't
+    let path = args.config.clone().or_else(config::default_path);
+    let load = path
+        .as_deref()
+        .filter(|path| args.config.is_some() || path.is_file());
+    let config = match load.map(Config::load) {
+        Some(Ok(config)) => config,
+        Some(Err(e)) => {
+            eprintln!("{}, using the default config", e);
+            Config::default()
+        }
+        None => Config::default(),
+    };
+    if let Some(name) = args
+        .profile
+        .as_ref()
+        .filter(|name| config.profile(name).is_none())
+    {
+        eprintln!("no profile named {:?} in the config", name);
+        std::process::exit(2);
+    }
+
+    if args.stdin && std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        eprintln!("--stdin needs something piped in");
+        std::process::exit(2);
+    }
+
+    // there's no grid to lay out without it, the fonts in the config fall back to it
+    let metrics = match CellMetrics::measure(&FontConfig::default()) {
+        Ok(metrics) => metrics,
+        Err(e) => {
+            eprintln!("{}", EmuError::Font(e));
+            std::process::exit(1);
+        }
+    };
+
+    let term = terminfo::term();
+    let mut first = args.session(&config);
+    // only offered to a plain launch, not over a command or a session asked for
+    let saved = match config.restore_session && args.command.is_none() {
+        true if matches!(first, Session::Pty(_)) => restore::load(),
+        _ => None,
+    };
+    if let Session::Pty(shell) | Session::Ssh(_, shell) = &mut first {
+        shell.term = term;
+    }
+    // the command only runs in the first tab, the ones opened later start the shell
+    let mut later = Args {
+        command: None,
+        profile: None,
+        ..args.clone()
+    }
+    .shell(&config);
+    later.term = term;
+    let title = args.title.unwrap_or(DEFAULT_TITLE.to_owned());
+    let mut app = App::new(config, path, metrics, later, title, args.pty_log.clone());
+
+    iced::daemon(App::title, App::update, App::view)
+        .subscription(subscription)
+        .style(App::style)
+        .run_with(move || {
+            let open = app.open_window(first, args.profile);
+            if let Some(saved) = saved {
+                app.offer_restore(saved);
+            }
+            let record = match &args.record {
+                Some(path) => app.record(path),
+                None => Task::none(),
+            };
+            (app, Task::batch([open, record]))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(key: &str, text: Option<&str>, mods: Modifiers) -> Option<Message> {
+        let key = Key::Character(key.into());
+        handle_key(key.clone(), key, text, mods, Location::Standard)
+    }
+
+    #[test]
+    fn typing_sends_the_text_of_the_layout() {
+        // Shift+2 where the layout puts a quote over the 2
+        assert!(matches!(
+            typed("2", Some("\""), Modifiers::SHIFT),
+            Some(Message::Write(Content::Text(text))) if text == "\""
+        ));
+        assert!(matches!(
+            typed("q", None, Modifiers::empty()),
+            Some(Message::Write(Content::Text(text))) if text == "q"
+        ));
+    }
+
+    #[test]
+    fn alt_sends_the_key_after_escape() {
+        assert!(matches!(
+            typed("x", Some("x"), Modifiers::ALT),
+            Some(Message::Write(Content::Meta(bytes, text))) if bytes == b"x" && text == "x"
+        ));
+        assert!(matches!(
+            typed("c", None, Modifiers::ALT | Modifiers::CTRL),
+            Some(Message::Write(Content::Meta(bytes, _))) if bytes == [0x03]
+        ));
+    }
+
+    fn screen() -> Screen {
+        let metrics = CellMetrics::measure(&FontConfig::default()).unwrap();
+        let shell = Shell::resolve(false);
+        let app = App::new(
+            Config::default(),
+            None,
+            metrics,
+            shell,
+            "emu-term".to_owned(),
+            None,
+        );
+        Screen::new(&app, window::Id::unique(), Session::Stdin, None)
+    }
+
+    #[test]
+    fn removing_the_last_tab_keeps_it_until_the_window_closes() {
+        let mut screen = screen();
+        let _ = screen.remove_tab(0);
+        assert_eq!(screen.tabs.len(), 1);
+        assert_eq!(screen.active().id, screen.tabs[0].focus);
+    }
+
+    #[test]
+    fn removing_a_tab_activates_a_neighbour() {
+        let mut screen = screen();
+        screen.new_tab(Session::Pty(screen.shell.clone()), None);
+        assert_eq!(screen.active, 1);
+        let _ = screen.remove_tab(1);
+        assert_eq!(screen.tabs.len(), 1);
+        assert_eq!(screen.active, 0);
+    }
 }
@@ -0,0 +1,35 @@
+//! Desktop notifications for the ones applications ask for, through `notify-send` or, on
+//! macOS, `osascript`
+
+use crate::links;
+use std::io;
+use std::process::Command;
+
+/// Shown when the notification and the tab both have no title
+const APP_NAME: &str = "emu-term";
+
+pub fn send(title: &str, body: &str) -> io::Result<()> {
+    let title = match title.is_empty() {
+        true => APP_NAME,
+        false => title,
+    };
+
+    let mut command = if cfg!(target_os = "macos") {
+        // passed as arguments so nothing in them is taken as AppleScript
+        let mut command = Command::new("osascript");
+        command.args([
+            "-e",
+            "on run argv",
+            "-e",
+            "display notification (item 2 of argv) with title (item 1 of argv)",
+            "-e",
+            "end run",
+        ]);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.arg(format!("--app-name={}", APP_NAME)).arg("--");
+        command
+    };
+    links::spawn(command.arg(title).arg(body))
+}
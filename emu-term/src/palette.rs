@@ -0,0 +1,321 @@
+use emu_core::grid::TermColor;
+use iced::Color;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+
+pub type Rgb = (u8, u8, u8);
+
+/// The colors `TermColor::Ansi`, `Foreground` and `Background` resolve to. Cells keep the
+/// index rather than the color so a palette change (OSC 4, a new theme) recolors what's
+/// already on screen
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub colors: [Rgb; 256],
+    pub foreground: Rgb,
+    pub background: Rgb,
+    pub selection_foreground: CellColor,
+    pub selection_background: CellColor,
+}
+
+/// A color for cells drawn with a highlight, either fixed or taken from the cell itself. The
+/// selection defaults to swapping the cell colors
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CellColor {
+    Fixed(Rgb),
+    CellForeground,
+    CellBackground,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            colors: std::array::from_fn(|i| ansi_colours::rgb_from_ansi256(i as u8)),
+            foreground: (255, 255, 255),
+            background: (30, 30, 30),
+            selection_foreground: CellColor::CellBackground,
+            selection_background: CellColor::CellForeground,
+        }
+    }
+}
+
+impl Palette {
+    pub fn rgb(&self, color: TermColor) -> Rgb {
+        match color {
+            TermColor::Rgb(r, g, b) => (r, g, b),
+            TermColor::Ansi(i) => self.colors[i as usize],
+            TermColor::Foreground => self.foreground,
+            TermColor::Background => self.background,
+        }
+    }
+
+    pub fn resolve(&self, color: TermColor) -> Color {
+        let (r, g, b) = self.rgb(color);
+        Color::from_rgb8(r, g, b)
+    }
+
+    /// Resolves a highlight color against the colors the cell would otherwise be drawn with
+    pub fn cell_color(color: CellColor, fg: Rgb, bg: Rgb) -> Rgb {
+        match color {
+            CellColor::Fixed(rgb) => rgb,
+            CellColor::CellForeground => fg,
+            CellColor::CellBackground => bg,
+        }
+    }
+
+    /// Loads a theme on top of the default palette, entries the file doesn't mention keep
+    /// their default. The format is picked from the extension, and for YAML from the content
+    pub fn load(path: &Path) -> Result<Self, ThemeError> {
+        let content = std::fs::read_to_string(path).map_err(ThemeError::Io)?;
+        let mut palette = Self::default();
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => {
+                let theme: AlacrittyTheme =
+                    toml::from_str(&content).map_err(|e| ThemeError::Parse(e.to_string()))?;
+                palette.apply_alacritty(theme.colors)?;
+            }
+            Some("yaml" | "yml") => {
+                let value: serde_yaml::Value =
+                    serde_yaml::from_str(&content).map_err(|e| ThemeError::Parse(e.to_string()))?;
+                if value.get("colors").is_some() {
+                    let theme: AlacrittyTheme = serde_yaml::from_value(value)
+                        .map_err(|e| ThemeError::Parse(e.to_string()))?;
+                    palette.apply_alacritty(theme.colors)?;
+                } else {
+                    let theme: Base16Theme = serde_yaml::from_value(value)
+                        .map_err(|e| ThemeError::Parse(e.to_string()))?;
+                    palette.apply_base16(theme)?;
+                }
+            }
+            _ => return Err(ThemeError::UnknownFormat),
+        }
+
+        Ok(palette)
+    }
+
+    /// Colors written inline in the config, in the layout of an Alacritty theme
+    pub fn with_colors(mut self, colors: AlacrittyColors) -> Result<Self, ThemeError> {
+        self.apply_alacritty(colors)?;
+        Ok(self)
+    }
+
+    fn apply_alacritty(&mut self, colors: AlacrittyColors) -> Result<(), ThemeError> {
+        if let Some(primary) = colors.primary {
+            if let Some(fg) = primary.foreground {
+                self.foreground = theme_color(&fg)?;
+            }
+            if let Some(bg) = primary.background {
+                self.background = theme_color(&bg)?;
+            }
+        }
+
+        if let Some(selection) = colors.selection {
+            if let Some(text) = selection.text {
+                self.selection_foreground = theme_cell_color(&text)?;
+            }
+            if let Some(bg) = selection.background {
+                self.selection_background = theme_cell_color(&bg)?;
+            }
+        }
+
+        for (offset, set) in [(0, colors.normal), (8, colors.bright)] {
+            let Some(set) = set else {
+                continue;
+            };
+            for (i, color) in set.colors().into_iter().enumerate() {
+                if let Some(color) = color {
+                    self.colors[offset + i] = theme_color(&color)?;
+                }
+            }
+        }
+
+        for indexed in colors.indexed_colors {
+            self.colors[indexed.index as usize] = theme_color(&indexed.color)?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_base16(&mut self, theme: Base16Theme) -> Result<(), ThemeError> {
+        // the usual base16-shell mapping, bright colors reuse the normal accents
+        const ANSI: [&str; 16] = [
+            "base00", "base08", "base0B", "base0A", "base0D", "base0E", "base0C", "base05",
+            "base03", "base08", "base0B", "base0A", "base0D", "base0E", "base0C", "base07",
+        ];
+
+        // newer schemes nest the colors under `palette`, older ones have them at the top level
+        // next to the name and author
+        let colors: HashMap<String, String> = match theme.palette {
+            Some(palette) => palette,
+            None => theme
+                .rest
+                .into_iter()
+                .filter_map(|(key, value)| Some((key, value.as_str()?.to_string())))
+                .collect(),
+        };
+        let get = |name: &str| -> Result<Rgb, ThemeError> {
+            let color = colors
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, color)| color)
+                .ok_or_else(|| ThemeError::Parse(format!("missing {}", name)))?;
+            theme_color(color)
+        };
+
+        for (i, name) in ANSI.iter().enumerate() {
+            self.colors[i] = get(name)?;
+        }
+        self.foreground = get("base05")?;
+        self.background = get("base00")?;
+
+        Ok(())
+    }
+}
+
+/// Parses the color specs xterm accepts in OSC 4/10/11: `rgb:r/g/b` with 1 to 4 hex digits per
+/// channel, and `#rgb`/`#rrggbb`
+pub fn parse_color_spec(spec: &str) -> Option<Rgb> {
+    if let Some(channels) = spec.strip_prefix("rgb:") {
+        let mut channels = channels.split('/').map(|c| {
+            if !(1..=4).contains(&c.len()) {
+                return None;
+            }
+            let value = u32::from_str_radix(c, 16).ok()?;
+            let max = (1u32 << (4 * c.len())) - 1;
+            Some((value * 255 / max) as u8)
+        });
+        let (r, g, b) = (channels.next()??, channels.next()??, channels.next()??);
+        return channels.next().is_none().then_some((r, g, b));
+    }
+
+    let hex = spec.strip_prefix('#')?;
+    match hex.len() {
+        3 => {
+            let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok().map(|d| d * 17);
+            Some((digit(0)?, digit(1)?, digit(2)?))
+        }
+        6 => parse_hex(hex),
+        _ => None,
+    }
+}
+
+/// Formats a color the way xterm answers OSC color queries
+pub fn format_color_spec((r, g, b): Rgb) -> String {
+    format!("rgb:{r:02x}{r:02x}/{g:02x}{g:02x}/{b:02x}{b:02x}")
+}
+
+/// Theme files write colors as `#rrggbb`, `0xrrggbb` or (base16) bare `rrggbb`
+fn theme_color(color: &str) -> Result<Rgb, ThemeError> {
+    let hex = color
+        .strip_prefix('#')
+        .or_else(|| color.strip_prefix("0x"))
+        .unwrap_or(color);
+
+    parse_hex(hex).ok_or_else(|| ThemeError::InvalidColor(color.to_string()))
+}
+
+/// Alacritty's `CellForeground`/`CellBackground` or a regular theme color
+fn theme_cell_color(color: &str) -> Result<CellColor, ThemeError> {
+    match color {
+        "CellForeground" => Ok(CellColor::CellForeground),
+        "CellBackground" => Ok(CellColor::CellBackground),
+        _ => theme_color(color).map(CellColor::Fixed),
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Rgb> {
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Io(std::io::Error),
+    Parse(String),
+    InvalidColor(String),
+    UnknownFormat,
+}
+
+impl Display for ThemeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read theme: {}", e),
+            Self::Parse(e) => write!(f, "failed to parse theme: {}", e),
+            Self::InvalidColor(c) => write!(f, "invalid theme color {:?}", c),
+            Self::UnknownFormat => write!(f, "unknown theme format, expected .toml, .yaml or .yml"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AlacrittyTheme {
+    colors: AlacrittyColors,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlacrittyColors {
+    primary: Option<AlacrittyPrimary>,
+    normal: Option<AlacrittyAnsi>,
+    bright: Option<AlacrittyAnsi>,
+    selection: Option<AlacrittySelection>,
+    #[serde(default)]
+    indexed_colors: Vec<AlacrittyIndexed>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct AlacrittyPrimary {
+    foreground: Option<String>,
+    background: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct AlacrittySelection {
+    text: Option<String>,
+    background: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct AlacrittyAnsi {
+    black: Option<String>,
+    red: Option<String>,
+    green: Option<String>,
+    yellow: Option<String>,
+    blue: Option<String>,
+    magenta: Option<String>,
+    cyan: Option<String>,
+    white: Option<String>,
+}
+
+impl AlacrittyAnsi {
+    fn colors(self) -> [Option<String>; 8] {
+        [
+            self.black,
+            self.red,
+            self.green,
+            self.yellow,
+            self.blue,
+            self.magenta,
+            self.cyan,
+            self.white,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct AlacrittyIndexed {
+    index: u8,
+    color: String,
+}
+
+#[derive(Deserialize)]
+struct Base16Theme {
+    palette: Option<HashMap<String, String>>,
+    #[serde(flatten)]
+    rest: HashMap<String, serde_yaml::Value>,
+}
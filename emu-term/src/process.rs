@@ -0,0 +1,62 @@
+use nix::unistd::{tcgetpgrp, Pid};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// The process group in the foreground of a pty, whatever the user is talking to right now
+#[derive(Debug, Clone, PartialEq)]
+pub struct Foreground {
+    pub pgrp: Pid,
+    /// Program name as the kernel has it, cut to 15 bytes on Linux
+    pub name: String,
+    pub cwd: Option<PathBuf>,
+}
+
+/// Looks up the foreground process group of the pty behind `master`. This goes through
+/// `/proc`, elsewhere it finds nothing
+pub fn foreground(master: &File) -> Option<Foreground> {
+    let pgrp = tcgetpgrp(master).ok()?;
+    let proc = Path::new("/proc").join(pgrp.to_string());
+    let name = std::fs::read_to_string(proc.join("comm")).ok()?;
+
+    Some(Foreground {
+        pgrp,
+        name: name.trim_end().to_owned(),
+        cwd: std::fs::read_link(proc.join("cwd")).ok(),
+    })
+}
+
+/// `path` with the home directory shortened to `~`
+pub fn tilde(path: &Path) -> String {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    match home
+        .as_deref()
+        .and_then(|home| path.strip_prefix(home).ok())
+    {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_owned(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// Every process in the session led by `leader` except the leader itself: what a shell has
+/// running in the foreground and as jobs. Sorted by pid, and like `foreground` it needs `/proc`
+pub fn session_members(leader: Pid) -> Vec<(Pid, String)> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return vec![];
+    };
+
+    let mut members: Vec<(Pid, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid = Pid::from_raw(entry.file_name().to_str()?.parse().ok()?);
+            let stat = std::fs::read_to_string(entry.path().join("stat")).ok()?;
+            let (pid_and_name, rest) = stat.rsplit_once(')')?;
+            let (_, name) = pid_and_name.split_once('(')?;
+            // after the name come state, ppid, pgrp and then the session
+            let session: i32 = rest.split_whitespace().nth(3)?.parse().ok()?;
+            (session == leader.as_raw() && pid != leader).then(|| (pid, name.to_owned()))
+        })
+        .collect();
+    members.sort_by_key(|(pid, _)| pid.as_raw());
+    members
+}
@@ -0,0 +1,125 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Sessions that opened a log so far, each gets a file of its own
+static OPENED: AtomicUsize = AtomicUsize::new(0);
+
+/// Where `--log-pty` copies everything read from the ptys. A raw log plays back with `--play`
+#[derive(Debug, Clone)]
+pub struct PtyLog {
+    pub path: PathBuf,
+    pub format: LogFormat,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum LogFormat {
+    /// The bytes as they were read
+    #[default]
+    Raw,
+    /// Printable ASCII as is and everything else escaped, with a line break after `\n`
+    Escaped,
+    /// Offsets, hex and ASCII columns like `hexdump -C`
+    Hex,
+}
+
+impl LogFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "raw" => Some(LogFormat::Raw),
+            "escaped" => Some(LogFormat::Escaped),
+            "hex" => Some(LogFormat::Hex),
+            _ => None,
+        }
+    }
+}
+
+impl PtyLog {
+    /// Opens the log of one session. The first goes to `path` and the later ones to `path.1`,
+    /// `path.2` and so on
+    pub fn open(&self) -> io::Result<LogWriter> {
+        let n = OPENED.fetch_add(1, Ordering::Relaxed);
+        let path = match n {
+            0 => self.path.clone(),
+            n => PathBuf::from(format!("{}.{}", self.path.display(), n)),
+        };
+
+        Ok(LogWriter {
+            file: File::create(&path)?,
+            path,
+            format: self.format,
+            offset: 0,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct LogWriter {
+    file: File,
+    pub path: PathBuf,
+    format: LogFormat,
+    /// Bytes logged so far, for the offsets of the hex format
+    offset: usize,
+}
+
+impl LogWriter {
+    /// Unbuffered, the output that came right before a crash is usually the interesting part
+    pub fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let offset = self.offset;
+        self.offset += bytes.len();
+        match self.format {
+            LogFormat::Raw => self.file.write_all(bytes),
+            LogFormat::Escaped => self.file.write_all(escape(bytes).as_bytes()),
+            LogFormat::Hex => self.file.write_all(hexdump(bytes, offset).as_bytes()),
+        }
+    }
+}
+
+fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    for &b in bytes {
+        match b {
+            b'\n' => escaped.push_str("\\n\n"),
+            b'\r' => escaped.push_str("\\r"),
+            b'\t' => escaped.push_str("\\t"),
+            0x1b => escaped.push_str("\\e"),
+            b'\\' => escaped.push_str("\\\\"),
+            0x20..=0x7e => escaped.push(b as char),
+            b => {
+                let _ = write!(escaped, "\\x{:02x}", b);
+            }
+        }
+    }
+    escaped
+}
+
+/// One line per 16 bytes. Each read starts a line of its own, so the offsets show where the
+/// reads were split
+fn hexdump(bytes: &[u8], offset: usize) -> String {
+    let mut dump = String::new();
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let _ = write!(dump, "{:08x} ", offset + i * 16);
+        for column in 0..16 {
+            match line.get(column) {
+                Some(b) => {
+                    let _ = write!(dump, " {:02x}", b);
+                }
+                None => dump.push_str("   "),
+            }
+            if column == 7 {
+                dump.push(' ');
+            }
+        }
+        let ascii: String = line
+            .iter()
+            .map(|&b| match b {
+                0x20..=0x7e => b as char,
+                _ => '.',
+            })
+            .collect();
+        let _ = writeln!(dump, "  |{}|", ascii);
+    }
+    dump
+}
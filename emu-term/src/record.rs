@@ -0,0 +1,118 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Writes a session as an asciicast v2 file: a JSON header on the first line, then one
+/// `[seconds, code, data]` event per line, `o` for output, `i` for input and `r` for resizes
+#[derive(Debug)]
+pub struct Recorder {
+    file: BufWriter<File>,
+    path: PathBuf,
+    start: Instant,
+    /// The tail of the last output when it ended in the middle of a UTF-8 sequence, events
+    /// carry text and it's finished by the next read
+    partial: Vec<u8>,
+}
+
+impl Recorder {
+    /// Starts the file at `path`, replacing it, with a terminal of `cols` by `rows`
+    pub fn create(path: &Path, cols: usize, rows: usize) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        writeln!(
+            file,
+            "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}}}",
+            cols, rows, timestamp,
+        )?;
+        file.flush()?;
+
+        Ok(Self {
+            file,
+            path: path.to_owned(),
+            start: Instant::now(),
+            partial: vec![],
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Bytes read from the child
+    pub fn output(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.partial.extend_from_slice(bytes);
+        let complete = match std::str::from_utf8(&self.partial) {
+            Ok(_) => self.partial.len(),
+            // an incomplete sequence at the very end waits for the rest of it
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.partial.len(),
+        };
+        let rest = self.partial.split_off(complete);
+        let text = String::from_utf8_lossy(&self.partial).into_owned();
+        self.partial = rest;
+        self.event("o", &text)
+    }
+
+    /// Bytes written to the child, keys and pastes
+    pub fn input(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.event("i", &String::from_utf8_lossy(bytes))
+    }
+
+    pub fn resize(&mut self, cols: usize, rows: usize) -> io::Result<()> {
+        self.event("r", &format!("{}x{}", cols, rows))
+    }
+
+    /// Flushed right away, so a crash doesn't lose what was recorded until then
+    fn event(&mut self, code: &str, data: &str) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let time = self.start.elapsed().as_secs_f64();
+        writeln!(
+            self.file,
+            "[{:.6}, \"{}\", {}]",
+            time,
+            code,
+            json_string(data)
+        )?;
+        self.file.flush()
+    }
+}
+
+/// Where a recording started from the keyboard goes, the home directory. It's named after the
+/// time it started
+pub fn path() -> PathBuf {
+    let home = std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    home.join(format!("emu-term-{}.cast", secs))
+}
+
+/// `text` as a quoted JSON string, control characters escaped
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
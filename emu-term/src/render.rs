@@ -0,0 +1,750 @@
+use crate::config::{FontConfig, FontError, HighlightStyle};
+use crate::keys::{MouseAction, MouseButton, MouseReport};
+use crate::palette::{Palette, Rgb};
+use crate::selection::SelectionKind;
+use crate::Message;
+use emu_core::grid::{Cell, CursorShape, GridRow, ImagePlacement, Style, Styles, TermColor};
+use emu_core::modes::MouseTracking;
+use iced::advanced::graphics::text::Paragraph;
+use iced::advanced::mouse::click;
+use iced::advanced::text::{self, Paragraph as _};
+use iced::widget::canvas::{self, event, Cache, Frame, Geometry, Text};
+use iced::widget::image;
+use iced::widget::text::LineHeight;
+use iced::{keyboard, mouse, Color, Font, Pixels, Point, Rectangle, Renderer, Size, Theme};
+use std::borrow::Cow;
+
+/// Size of a single cell, every glyph of a monospace font shares the same advance so the grid
+/// is just these repeated
+#[derive(Debug, Copy, Clone)]
+pub struct CellMetrics {
+    pub width: f32,
+    pub height: f32,
+    pub font: Font,
+    pub font_size: f32,
+}
+
+impl CellMetrics {
+    /// Lays out long runs of a wide and a narrow glyph, they only come out the same length on
+    /// a monospaced font. Averaging over the run also smooths out the sub-pixel rounding a
+    /// single glyph measurement would carry
+    pub fn measure(config: &FontConfig) -> Result<Self, FontError> {
+        const SAMPLE: usize = 100;
+
+        config.validate()?;
+        let font = config.font();
+        let line_height = LineHeight::Relative(config.line_height);
+        let measure = |glyph: &str| {
+            let content = glyph.repeat(SAMPLE);
+            Paragraph::with_text(text::Text {
+                content: content.as_str(),
+                bounds: Size::INFINITY,
+                size: Pixels(config.size),
+                line_height,
+                font,
+                horizontal_alignment: iced::alignment::Horizontal::Left,
+                vertical_alignment: iced::alignment::Vertical::Top,
+                shaping: text::Shaping::Basic,
+                wrapping: text::Wrapping::None,
+            })
+            .min_bounds()
+        };
+
+        let wide = measure("M");
+        let narrow = measure("i");
+        if (wide.width - narrow.width).abs() > wide.width * 0.01 {
+            return Err(FontError::NotMonospace(config.family_name().to_string()));
+        }
+
+        // a font that failed to load measures as nothing, keep the grid math finite regardless
+        Ok(Self {
+            width: (wide.width / SAMPLE as f32).max(1.0),
+            height: wide.height.max(1.0),
+            font,
+            font_size: config.size,
+        })
+    }
+
+    /// Rows and columns of whole cells that fit in `size`, never less than one of each
+    pub fn grid_size(&self, size: Size) -> (usize, usize) {
+        let rows = (size.height / self.height).floor() as usize;
+        let cols = (size.width / self.width).floor() as usize;
+        (rows.max(1), cols.max(1))
+    }
+
+    /// 1-based screen line and column under `point`, relative to the top-left of the grid
+    pub fn cell_at(&self, point: Point) -> (usize, usize) {
+        let line = (point.y.max(0.0) / self.height).floor() as usize;
+        let col = (point.x.max(0.0) / self.width).floor() as usize;
+        (line + 1, col + 1)
+    }
+}
+
+/// Thickness of the bar and underline cursors
+const CURSOR_THICKNESS: f32 = 2.0;
+
+/// Thickness of the line under hovered links
+const UNDERLINE_THICKNESS: f32 = 1.0;
+
+/// Backgrounds of search matches, the current one stands out. Their text is drawn in black
+const MATCH_BACKGROUND: Rgb = (0xc8, 0xa0, 0x40);
+const CURRENT_MATCH_BACKGROUND: Rgb = (0xff, 0x80, 0x20);
+
+/// Opacity of the visual bell layer
+const FLASH_ALPHA: f32 = 0.3;
+
+const SCROLLBAR_WIDTH: f32 = 6.0;
+/// Keeps the thumb grabbable with a long scrollback
+const SCROLLBAR_MIN_THUMB: f32 = 20.0;
+
+/// Position of the view within the scrollback
+#[derive(Debug, Copy, Clone)]
+pub struct ScrollbarView {
+    /// Lines scrolled back from the bottom
+    pub offset: usize,
+    /// Lines of scrollback above the screen, never 0
+    pub history: usize,
+    pub rows: usize,
+}
+
+impl ScrollbarView {
+    fn thumb_height(&self, track: f32) -> f32 {
+        let total = (self.history + self.rows) as f32;
+        (track * self.rows as f32 / total)
+            .max(SCROLLBAR_MIN_THUMB)
+            .min(track)
+    }
+
+    /// Top of the thumb in a track `track` pixels tall
+    fn thumb_top(&self, track: f32) -> f32 {
+        let free = track - self.thumb_height(track);
+        free * (self.history - self.offset) as f32 / self.history as f32
+    }
+
+    /// Offset that puts the top of the thumb at `top`
+    fn offset_at(&self, top: f32, track: f32) -> usize {
+        let free = (track - self.thumb_height(track)).max(1.0);
+        let line = ((top / free).clamp(0.0, 1.0) * self.history as f32).round() as usize;
+        self.history - line
+    }
+}
+
+/// What gets drawn over a screen line on top of its cells, as inclusive 1-based column ranges
+#[derive(Debug, Default, Clone)]
+pub struct LineDecorations {
+    pub selected: Option<(usize, usize)>,
+    pub underlined: Option<(usize, usize)>,
+    /// Search matches, `current` is also among them
+    pub found: Vec<(usize, usize)>,
+    pub current: Option<(usize, usize)>,
+    /// Matches of the highlight rules, the first one over a cell wins
+    pub highlighted: Vec<(usize, usize, HighlightStyle)>,
+}
+
+impl LineDecorations {
+    /// Last column anything is drawn over, 0 for none
+    fn right(&self) -> usize {
+        let ranges = [self.selected, self.underlined, self.current];
+        let found = self.found.iter().copied().map(Some);
+        let highlighted = self
+            .highlighted
+            .iter()
+            .map(|&(left, right, _)| Some((left, right)));
+        ranges
+            .into_iter()
+            .chain(found)
+            .chain(highlighted)
+            .flatten()
+            .map(|(_, right)| right)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// A hint label, drawn from the first column of its target. 1-based screen coordinates
+#[derive(Debug, Clone)]
+pub struct HintLabel {
+    pub line: usize,
+    pub col: usize,
+    /// The part of the label that is left to type
+    pub label: String,
+}
+
+/// Where and how to draw the cursor, 1-based screen coordinates
+#[derive(Debug, Copy, Clone)]
+pub struct CursorView {
+    pub line: usize,
+    pub col: usize,
+    pub shape: CursorShape,
+}
+
+/// Draws the screen lines straight into a canvas. Every line gets its own cached layer, so a
+/// frame only re-tessellates the lines the grid reported as damaged
+pub struct TermCanvas<'a> {
+    /// Only the rows in view, as many as the screen has
+    pub lines: Vec<Cow<'a, GridRow>>,
+    /// Columns of the screen, cells past them are left from a wider window and aren't drawn
+    pub cols: usize,
+    pub caches: &'a [Cache],
+    pub metrics: CellMetrics,
+    pub palette: &'a Palette,
+    /// One per line, changing them requires clearing the caches of the lines involved
+    pub decorations: Vec<LineDecorations>,
+    /// Every image on the grid, the ones outside of the view are skipped
+    pub images: &'a [ImagePlacement],
+    /// What the styles of the cells in `lines` stand for
+    pub styles: &'a Styles,
+    /// Number of grid rows above the first line drawn
+    pub origin: usize,
+    /// DECSCNM, every cell drawn with its colors swapped
+    pub reverse_video: bool,
+    /// Phase of blinking text, hidden while `false`
+    pub blink_on: bool,
+    /// `None` while hidden or blinked out
+    pub cursor: Option<CursorView>,
+    /// `None` when disabled or there's nothing to scroll
+    pub scrollbar: Option<ScrollbarView>,
+    /// Labels of the keyboard hints mode, empty while it's off
+    pub hints: Vec<HintLabel>,
+    /// Visual bell in its flash style
+    pub flash: bool,
+    /// While on, mouse input goes to the application unless Shift is held
+    pub mouse_tracking: MouseTracking,
+    /// Opacity of the black layer laid over everything, 0 for none
+    pub dim: f32,
+}
+
+/// Mouse state kept by the canvas between events
+#[derive(Debug, Default)]
+pub struct Pointer {
+    selecting: bool,
+    hovered: Option<(usize, usize)>,
+    /// Where the scrollbar thumb was grabbed, from its top
+    thumb_grab: Option<f32>,
+    /// To tell double and triple clicks apart
+    last_click: Option<click::Click>,
+    /// Button held while reporting to the application, drags report it along with the motion
+    reported_button: Option<MouseButton>,
+    modifiers: keyboard::Modifiers,
+}
+
+impl canvas::Program<Message> for TermCanvas<'_> {
+    type State = Pointer;
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (event::Status, Option<Message>) {
+        let event = match event {
+            canvas::Event::Mouse(event) => event,
+            canvas::Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                state.modifiers = modifiers;
+                return (event::Status::Ignored, None);
+            }
+            _ => return (event::Status::Ignored, None),
+        };
+        let hovered = cursor
+            .position_in(bounds)
+            .map(|point| self.metrics.cell_at(point));
+
+        let reporting = self.mouse_tracking != MouseTracking::Off && !state.modifiers.shift();
+        if reporting && !state.selecting && state.thumb_grab.is_none() {
+            if let Some(message) = self.report(state, event, hovered) {
+                state.hovered = hovered;
+                return (event::Status::Captured, Some(message));
+            }
+        }
+
+        let track = bounds.height;
+        match event {
+            mouse::Event::ButtonPressed(mouse::Button::Left) => {
+                let position = cursor.position_in(bounds);
+                if let Some((scrollbar, position)) = self.scrollbar.zip(position) {
+                    if position.x >= bounds.width - SCROLLBAR_WIDTH {
+                        let top = scrollbar.thumb_top(track);
+                        let height = scrollbar.thumb_height(track);
+                        // clicking the track centers the thumb there
+                        let grab = if (top..top + height).contains(&position.y) {
+                            position.y - top
+                        } else {
+                            height / 2.0
+                        };
+                        state.thumb_grab = Some(grab);
+                        let offset = scrollbar.offset_at(position.y - grab, track);
+                        return (event::Status::Captured, Some(Message::ScrollTo(offset)));
+                    }
+                }
+
+                let (Some(cell), Some(position)) = (hovered, position) else {
+                    return (event::Status::Ignored, None);
+                };
+                if state.modifiers.control() {
+                    return (event::Status::Captured, Some(Message::OpenLink(cell)));
+                }
+                let click = click::Click::new(position, mouse::Button::Left, state.last_click);
+                state.last_click = Some(click);
+                let kind = match click.kind() {
+                    click::Kind::Single if state.modifiers.alt() => SelectionKind::Block,
+                    click::Kind::Single => SelectionKind::Simple,
+                    click::Kind::Double => SelectionKind::Word,
+                    click::Kind::Triple => SelectionKind::Line,
+                };
+                state.selecting = true;
+                (
+                    event::Status::Captured,
+                    Some(Message::SelectionStart(cell, kind)),
+                )
+            }
+            mouse::Event::ButtonPressed(mouse::Button::Middle)
+                if cfg!(target_os = "linux") && hovered.is_some() =>
+            {
+                (event::Status::Captured, Some(Message::PastePrimary))
+            }
+            mouse::Event::ButtonReleased(mouse::Button::Left)
+                if state.selecting || state.thumb_grab.is_some() =>
+            {
+                let message = state.selecting.then_some(Message::SelectionEnd);
+                state.selecting = false;
+                state.thumb_grab = None;
+                (event::Status::Captured, message)
+            }
+            mouse::Event::CursorMoved { position } if state.thumb_grab.is_some() => {
+                let (Some(scrollbar), Some(grab)) = (self.scrollbar, state.thumb_grab) else {
+                    return (event::Status::Ignored, None);
+                };
+                let offset = scrollbar.offset_at(position.y - bounds.y - grab, track);
+                (event::Status::Captured, Some(Message::ScrollTo(offset)))
+            }
+            // keep extending while the drag goes past the edges of the window
+            mouse::Event::CursorMoved { position } if state.selecting => {
+                let point = Point::new(position.x - bounds.x, position.y - bounds.y);
+                let cell = self.metrics.cell_at(point);
+                (
+                    event::Status::Captured,
+                    Some(Message::SelectionExtend(cell)),
+                )
+            }
+            mouse::Event::CursorMoved { .. } | mouse::Event::CursorLeft
+                if hovered != state.hovered =>
+            {
+                state.hovered = hovered;
+                (event::Status::Ignored, Some(Message::Hover(hovered)))
+            }
+            _ => (event::Status::Ignored, None),
+        }
+    }
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let _span = tracing::trace_span!("draw", lines = self.lines.len()).entered();
+        let mut geometry: Vec<Geometry> = self
+            .lines
+            .iter()
+            .zip(self.caches)
+            .enumerate()
+            .map(|(y, (line, cache))| {
+                let decorations = self.decorations.get(y).cloned().unwrap_or_default();
+                // only runs for the lines whose cache was cleared
+                cache.draw(renderer, bounds.size(), |frame| {
+                    tracing::trace!(line = y, "redrawn");
+                    self.draw_line(frame, y, line, &decorations)
+                })
+            })
+            .collect();
+
+        if !self.images.is_empty() {
+            let mut frame = Frame::new(renderer, bounds.size());
+            self.draw_images(&mut frame);
+            geometry.push(frame.into_geometry());
+        }
+
+        // the cursor moves on nearly every update, so it isn't worth caching
+        if let Some(cursor) = self.cursor {
+            let mut frame = Frame::new(renderer, bounds.size());
+            self.draw_cursor(&mut frame, cursor);
+            geometry.push(frame.into_geometry());
+        }
+
+        if !self.hints.is_empty() {
+            let mut frame = Frame::new(renderer, bounds.size());
+            self.draw_hints(&mut frame);
+            geometry.push(frame.into_geometry());
+        }
+
+        if self.flash {
+            let mut color = rgb_color(self.palette.rgb(TermColor::default_fg()));
+            color.a = FLASH_ALPHA;
+            let mut frame = Frame::new(renderer, bounds.size());
+            frame.fill_rectangle(Point::ORIGIN, bounds.size(), color);
+            geometry.push(frame.into_geometry());
+        }
+
+        if let Some(scrollbar) = self.scrollbar {
+            let mut frame = Frame::new(renderer, bounds.size());
+            self.draw_scrollbar(&mut frame, scrollbar, bounds.height);
+            geometry.push(frame.into_geometry());
+        }
+
+        if self.dim > 0.0 {
+            let mut frame = Frame::new(renderer, bounds.size());
+            let color = Color {
+                a: self.dim,
+                ..Color::BLACK
+            };
+            frame.fill_rectangle(Point::ORIGIN, bounds.size(), color);
+            geometry.push(frame.into_geometry());
+        }
+
+        geometry
+    }
+}
+
+/// Final colors of a cell once its attributes and the decorations are applied. Neighbouring
+/// cells are drawn together as long as what's being drawn looks the same for them, see
+/// `TermCanvas::draw_line`
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct CellStyle {
+    fg: Rgb,
+    /// `None` for the screen background, which the container already paints
+    bg: Option<Rgb>,
+    underline: bool,
+}
+
+fn rgb_color((r, g, b): Rgb) -> Color {
+    Color::from_rgb8(r, g, b)
+}
+
+impl TermCanvas<'_> {
+    /// Turns a mouse event over the grid into a report for the application
+    fn report(
+        &self,
+        state: &mut Pointer,
+        event: mouse::Event,
+        hovered: Option<(usize, usize)>,
+    ) -> Option<Message> {
+        let modifiers = state.modifiers;
+        let report = |button, action, cell| {
+            Message::Mouse(MouseReport {
+                button,
+                action,
+                cell,
+                modifiers,
+            })
+        };
+
+        match event {
+            mouse::Event::ButtonPressed(button) => {
+                let button = match button {
+                    mouse::Button::Left => MouseButton::Left,
+                    mouse::Button::Middle => MouseButton::Middle,
+                    mouse::Button::Right => MouseButton::Right,
+                    _ => return None,
+                };
+                let cell = hovered?;
+                state.reported_button = Some(button);
+                Some(report(button, MouseAction::Press, cell))
+            }
+            // released outside of the window, on the last cell it was over
+            mouse::Event::ButtonReleased(_) => {
+                let button = state.reported_button.take()?;
+                let cell = hovered.or(state.hovered)?;
+                Some(report(button, MouseAction::Release, cell))
+            }
+            mouse::Event::CursorMoved { .. } => {
+                let cell = hovered.filter(|cell| Some(*cell) != state.hovered)?;
+                let button = state.reported_button.unwrap_or(MouseButton::None);
+                Some(report(button, MouseAction::Motion, cell))
+            }
+            mouse::Event::WheelScrolled { delta } => {
+                let (mouse::ScrollDelta::Lines { y, .. } | mouse::ScrollDelta::Pixels { y, .. }) =
+                    delta;
+                let button = match y {
+                    y if y > 0.0 => MouseButton::WheelUp,
+                    y if y < 0.0 => MouseButton::WheelDown,
+                    _ => return None,
+                };
+                Some(report(button, MouseAction::Press, hovered?))
+            }
+            _ => None,
+        }
+    }
+
+    fn draw_cursor(&self, frame: &mut Frame, cursor: CursorView) {
+        let CellMetrics { width, height, .. } = self.metrics;
+        let left = (cursor.col - 1) as f32 * width;
+        let top = (cursor.line - 1) as f32 * height;
+        let cell = self
+            .lines
+            .get(cursor.line - 1)
+            .and_then(|line| line.cells.get(cursor.col - 1))
+            .copied()
+            .unwrap_or_default();
+        let style = self.cell_style(self.styles.get(cell.style), false, None, None, false);
+        let color = rgb_color(style.fg);
+
+        match cursor.shape {
+            CursorShape::Block => {
+                frame.fill_rectangle(Point::new(left, top), Size::new(width, height), color);
+                // redraw the glyph underneath inverted so it stays readable
+                let bg = style.bg.unwrap_or_else(|| self.default_background());
+                frame.fill_text(Text {
+                    content: cell.c.to_string(),
+                    position: Point::new(left, top),
+                    color: rgb_color(bg),
+                    size: Pixels(self.metrics.font_size),
+                    line_height: LineHeight::Absolute(Pixels(height)),
+                    font: self.metrics.font,
+                    ..Text::default()
+                });
+            }
+            CursorShape::Underline => {
+                let top = top + height - CURSOR_THICKNESS;
+                let size = Size::new(width, CURSOR_THICKNESS);
+                frame.fill_rectangle(Point::new(left, top), size, color);
+            }
+            CursorShape::Bar => {
+                let size = Size::new(CURSOR_THICKNESS, height);
+                frame.fill_rectangle(Point::new(left, top), size, color);
+            }
+        }
+    }
+
+    /// Images only cost a texture quad each, they're redrawn every frame rather than cached
+    /// per line since one can span many of them
+    fn draw_images(&self, frame: &mut Frame) {
+        let CellMetrics { width, height, .. } = self.metrics;
+        let view = Rectangle::with_size(frame.size());
+        let (first, last) = (self.origin + 1, self.origin + self.lines.len());
+
+        frame.with_clip(view, |frame| {
+            let visible = self
+                .images
+                .iter()
+                .filter(|image| image.row <= last && image.row + image.rows > first);
+            for image in visible {
+                let top = (image.row as f32 - first as f32) * height;
+                let bounds = Rectangle::new(
+                    Point::new((image.col - 1) as f32 * width, top),
+                    Size::new(image.cols as f32 * width, image.rows as f32 * height),
+                );
+                if let Some(handle) = image.handle.downcast_ref::<image::Handle>() {
+                    frame.draw_image(bounds, handle);
+                }
+            }
+        });
+    }
+
+    /// Labels in black on yellow, covering the start of their targets
+    fn draw_hints(&self, frame: &mut Frame) {
+        let metrics = self.metrics;
+        let background = rgb_color(self.palette.rgb(TermColor::Ansi(3)));
+        let foreground = rgb_color(self.palette.rgb(TermColor::Ansi(0)));
+
+        for hint in &self.hints {
+            let position = Point::new(
+                (hint.col - 1) as f32 * metrics.width,
+                (hint.line - 1) as f32 * metrics.height,
+            );
+            let width = hint.label.chars().count() as f32 * metrics.width;
+            frame.fill_rectangle(position, Size::new(width, metrics.height), background);
+            frame.fill_text(Text {
+                content: hint.label.clone(),
+                position,
+                color: foreground,
+                size: Pixels(metrics.font_size),
+                line_height: LineHeight::Absolute(Pixels(metrics.height)),
+                font: metrics.font,
+                ..Text::default()
+            });
+        }
+    }
+
+    fn draw_scrollbar(&self, frame: &mut Frame, scrollbar: ScrollbarView, track: f32) {
+        let mut color = rgb_color(self.palette.rgb(TermColor::default_fg()));
+        color.a = 0.4;
+        let top = scrollbar.thumb_top(track);
+        let position = Point::new(frame.width() - SCROLLBAR_WIDTH, top);
+        let size = Size::new(SCROLLBAR_WIDTH, scrollbar.thumb_height(track));
+        frame.fill_rectangle(position, size, color);
+    }
+
+    /// Color the container paints behind the grid
+    fn default_background(&self) -> Rgb {
+        self.palette.rgb(self.screen_background())
+    }
+
+    fn screen_background(&self) -> TermColor {
+        if self.reverse_video {
+            TermColor::default_fg()
+        } else {
+            TermColor::default_bg()
+        }
+    }
+
+    /// `found` is the background of a search match under the cell, the selection goes over it.
+    /// Both go over a highlight
+    fn cell_style(
+        &self,
+        style: Style,
+        selected: bool,
+        found: Option<Rgb>,
+        highlight: Option<HighlightStyle>,
+        underline: bool,
+    ) -> CellStyle {
+        let (mut fg, mut bg) = (style.fg_color, style.bg_color);
+        if style.attrs.reverse != self.reverse_video {
+            std::mem::swap(&mut fg, &mut bg);
+        }
+
+        if selected {
+            let (fg, bg) = (self.palette.rgb(fg), self.palette.rgb(bg));
+            return CellStyle {
+                fg: Palette::cell_color(self.palette.selection_foreground, fg, bg),
+                bg: Some(Palette::cell_color(
+                    self.palette.selection_background,
+                    fg,
+                    bg,
+                )),
+                underline,
+            };
+        }
+        if let Some(bg) = found {
+            return CellStyle {
+                fg: (0, 0, 0),
+                bg: Some(bg),
+                underline,
+            };
+        }
+
+        let cell = CellStyle {
+            fg: self.palette.rgb(fg),
+            bg: (bg != self.screen_background()).then(|| self.palette.rgb(bg)),
+            underline,
+        };
+        match highlight {
+            Some(highlight) => CellStyle {
+                fg: highlight.foreground.unwrap_or(cell.fg),
+                bg: highlight.background.or(cell.bg),
+                underline: underline || highlight.underline,
+            },
+            None => cell,
+        }
+    }
+
+    fn draw_line(
+        &self,
+        frame: &mut Frame,
+        y: usize,
+        line: &GridRow,
+        decorations: &LineDecorations,
+    ) {
+        let metrics = &self.metrics;
+        let within = |range: Option<(usize, usize)>, x: usize| {
+            range.is_some_and(|(left, right)| (left..=right).contains(&x))
+        };
+        // blanks at the end draw nothing, unless a decoration goes over them. It goes by
+        // column, past the cells the row has it's over blanks all the same
+        let blank = Cell::default();
+        let text = line.cells.iter().rposition(|cell| *cell != blank);
+        let end = text.map_or(0, |x| x + 1).max(decorations.right());
+        let cells: Vec<(char, CellStyle)> = (0..end.min(self.cols))
+            .map(|x| (x, line.cells.get(x).unwrap_or(&blank)))
+            .map(|(x, cell)| {
+                let selected = within(decorations.selected, x + 1);
+                let underline = within(decorations.underlined, x + 1);
+                let found = if within(decorations.current, x + 1) {
+                    Some(CURRENT_MATCH_BACKGROUND)
+                } else if decorations
+                    .found
+                    .iter()
+                    .any(|&range| within(Some(range), x + 1))
+                {
+                    Some(MATCH_BACKGROUND)
+                } else {
+                    None
+                };
+                let highlight = decorations
+                    .highlighted
+                    .iter()
+                    .find(|&&(left, right, _)| within(Some((left, right)), x + 1))
+                    .map(|&(_, _, highlight)| highlight);
+                // blinked out text leaves its background behind
+                let style = self.styles.get(cell.style);
+                let c = if style.attrs.blink && !self.blink_on {
+                    ' '
+                } else {
+                    cell.c
+                };
+                (
+                    c,
+                    self.cell_style(style, selected, found, highlight, underline),
+                )
+            })
+            .collect();
+
+        let top = y as f32 * metrics.height;
+        // backgrounds, underlines and text are each batched on what they depend on alone, a
+        // run of text goes on across cells whose backgrounds differ and the other way round
+        for (x, run) in runs(&cells, |a, b| a.bg == b.bg) {
+            if let Some(bg) = run[0].1.bg {
+                let size = Size::new(run.len() as f32 * metrics.width, metrics.height);
+                frame.fill_rectangle(
+                    Point::new(x as f32 * metrics.width, top),
+                    size,
+                    rgb_color(bg),
+                );
+            }
+        }
+
+        let underlined = |a: &CellStyle, b: &CellStyle| (a.underline, a.fg) == (b.underline, b.fg);
+        for (x, run) in runs(&cells, underlined) {
+            let style = run[0].1;
+            if style.underline {
+                let left = x as f32 * metrics.width;
+                let position = Point::new(left, top + metrics.height - UNDERLINE_THICKNESS);
+                let size = Size::new(run.len() as f32 * metrics.width, UNDERLINE_THICKNESS);
+                frame.fill_rectangle(position, size, rgb_color(style.fg));
+            }
+        }
+
+        for (x, run) in runs(&cells, |a, b| a.fg == b.fg) {
+            let content: String = run.iter().map(|(c, _)| *c).collect();
+            let content = content.trim_end();
+            if content.is_empty() {
+                continue;
+            }
+
+            frame.fill_text(Text {
+                content: content.to_owned(),
+                position: Point::new(x as f32 * metrics.width, top),
+                color: rgb_color(run[0].1.fg),
+                size: Pixels(metrics.font_size),
+                line_height: LineHeight::Absolute(Pixels(metrics.height)),
+                font: metrics.font,
+                ..Text::default()
+            });
+        }
+    }
+}
+
+/// Runs of neighbouring cells `same` holds for, with the column each starts at, 0-based
+fn runs<'a>(
+    cells: &'a [(char, CellStyle)],
+    same: impl Fn(&CellStyle, &CellStyle) -> bool + 'a,
+) -> impl Iterator<Item = (usize, &'a [(char, CellStyle)])> {
+    let runs = cells.chunk_by(move |a, b| same(&a.1, &b.1));
+    runs.scan(0, |x, run| {
+        let start = *x;
+        *x += run.len();
+        Some((start, run))
+    })
+}
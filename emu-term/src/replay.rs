@@ -0,0 +1,287 @@
+use serde::Deserialize;
+use std::fmt::Display;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How far the arrow keys seek, in seconds of the recording
+const SEEK: f64 = 5.0;
+const MAX_SPEED: f64 = 16.0;
+/// Leaves the alternate screen and clears everything, to play from the start again
+const RESET: &[u8] = b"\x1b[?1049l\x1b[0m\x1b[H\x1b[2J\x1b[3J\x1b[?25h";
+
+/// Output of a recorded session, each chunk at its time in seconds from the start
+#[derive(Debug, Default)]
+pub struct Recording {
+    events: Vec<(f64, Vec<u8>)>,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(PathBuf, io::Error),
+    /// File, line and what's wrong with it
+    Parse(PathBuf, usize, String),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            LoadError::Parse(path, line, e) => write!(f, "{}:{}: {}", path.display(), line, e),
+        }
+    }
+}
+
+/// The first line of an asciicast v2 file
+#[derive(Deserialize)]
+struct CastHeader {
+    version: u32,
+    /// Longer pauses are cut down to this many seconds
+    idle_time_limit: Option<f64>,
+}
+
+impl Recording {
+    /// Reads an asciicast v2 file, or a typescript from `script` when it doesn't start with a
+    /// JSON header. A typescript only has times with its `timing` file, without one it shows
+    /// all at once
+    pub fn load(path: &Path, timing: Option<&Path>) -> Result<Self, LoadError> {
+        let read = |path: &Path| std::fs::read(path).map_err(|e| LoadError::Io(path.into(), e));
+        let data = read(path)?;
+        if data.starts_with(b"{") {
+            return Self::cast(path, &String::from_utf8_lossy(&data));
+        }
+
+        let timing = match timing {
+            Some(timing) => Some((timing, read(timing)?)),
+            None => None,
+        };
+        Self::typescript(&data, timing)
+    }
+
+    fn cast(path: &Path, text: &str) -> Result<Self, LoadError> {
+        let error = |line: usize, e: String| LoadError::Parse(path.into(), line + 1, e);
+        let mut lines = text.lines().enumerate();
+        let header = lines.next().map_or("", |(_, line)| line);
+        // JSON is close enough to YAML for the header
+        let header: CastHeader =
+            serde_yaml::from_str(header).map_err(|e| error(0, e.to_string()))?;
+        if header.version != 2 {
+            let e = format!(
+                "asciicast version {} isn't supported, only 2 is",
+                header.version
+            );
+            return Err(error(0, e));
+        }
+
+        let limit = header.idle_time_limit.unwrap_or(f64::INFINITY);
+        let (mut last, mut cut) = (0.0, 0.0);
+        let mut events = vec![];
+        for (i, line) in lines.filter(|(_, line)| !line.trim().is_empty()) {
+            let (time, code, data) =
+                parse_event(line).ok_or_else(|| error(i, "expected [time, code, data]".into()))?;
+            cut += (time - last - limit).max(0.0);
+            last = time;
+            if code == "o" {
+                events.push((time - cut, data.into_bytes()));
+            }
+        }
+        Ok(Self { events })
+    }
+
+    /// The classic timing format has a delay and a byte count on each line, the one from
+    /// `script -T` puts the stream in front and only `O` is output
+    fn typescript(data: &[u8], timing: Option<(&Path, Vec<u8>)>) -> Result<Self, LoadError> {
+        let data = match data.strip_prefix(b"Script started on") {
+            Some(rest) => rest.splitn(2, |&b| b == b'\n').nth(1).unwrap_or_default(),
+            None => data,
+        };
+        let Some((path, timing)) = timing else {
+            return Ok(Self {
+                events: vec![(0.0, data.to_vec())],
+            });
+        };
+
+        let (mut time, mut at) = (0.0, 0);
+        let mut events = vec![];
+        for (i, line) in String::from_utf8_lossy(&timing).lines().enumerate() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (output, delay, len) = match fields[..] {
+                [delay, len] => (true, delay, Some(len)),
+                ["O", delay, len] => (true, delay, Some(len)),
+                [_, delay, ..] => (false, delay, None),
+                _ => continue,
+            };
+            let expected =
+                || LoadError::Parse(path.into(), i + 1, "expected a delay and a length".into());
+            time += delay.parse::<f64>().map_err(|_| expected())?;
+            if !output {
+                continue;
+            }
+
+            let len: usize = len.and_then(|len| len.parse().ok()).ok_or_else(expected)?;
+            let end = (at + len).min(data.len());
+            events.push((time, data[at..end].to_vec()));
+            at = end;
+        }
+        Ok(Self { events })
+    }
+
+    fn end(&self) -> f64 {
+        self.events.last().map_or(0.0, |(time, _)| *time)
+    }
+}
+
+/// What a key does to the playback
+#[derive(Debug)]
+pub enum Step {
+    /// Output to show right away, for a seek
+    Show(Vec<u8>),
+    Quit,
+}
+
+/// Plays a recording back on its own clock. Space pauses, `+` and `-` double and halve the
+/// speed, the arrow keys seek, `0` starts over and `q` quits
+#[derive(Debug)]
+pub struct Player {
+    recording: Recording,
+    /// Index of the next event to show
+    next: usize,
+    /// Seconds into the recording
+    position: f64,
+    speed: f64,
+    paused: bool,
+}
+
+impl Player {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            next: 0,
+            position: 0.0,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    /// Time until the next event is due at the current speed, `None` while paused or once
+    /// everything was shown
+    pub fn wait(&self) -> Option<Duration> {
+        let (time, _) = self
+            .recording
+            .events
+            .get(self.next)
+            .filter(|_| !self.paused)?;
+        Some(Duration::from_secs_f64(
+            ((time - self.position) / self.speed).max(0.0),
+        ))
+    }
+
+    /// Moves the clock along by `elapsed` real time and returns the output that came due
+    pub fn advance(&mut self, elapsed: Duration) -> Vec<u8> {
+        if !self.paused {
+            let end = self.recording.end();
+            self.position = (self.position + elapsed.as_secs_f64() * self.speed).min(end);
+        }
+        self.due()
+    }
+
+    /// Handles input typed in the tab
+    pub fn key(&mut self, key: &[u8]) -> Step {
+        match key {
+            b" " => self.paused = !self.paused,
+            b"+" | b"=" => self.speed = (self.speed * 2.0).min(MAX_SPEED),
+            b"-" => self.speed = (self.speed / 2.0).max(1.0 / MAX_SPEED),
+            b"\x1b[C" | b"\x1bOC" => return Step::Show(self.seek(self.position + SEEK)),
+            b"\x1b[D" | b"\x1bOD" => return Step::Show(self.seek(self.position - SEEK)),
+            b"0" => return Step::Show(self.seek(0.0)),
+            b"q" => return Step::Quit,
+            _ => {}
+        }
+        Step::Show(vec![])
+    }
+
+    /// Going back means playing everything from the start again, up to `target`
+    fn seek(&mut self, target: f64) -> Vec<u8> {
+        let target = target.clamp(0.0, self.recording.end());
+        let mut output = vec![];
+        if target < self.position {
+            output.extend_from_slice(RESET);
+            self.next = 0;
+        }
+
+        self.position = target;
+        output.extend(self.due());
+        output
+    }
+
+    fn due(&mut self) -> Vec<u8> {
+        let mut output = vec![];
+        let events = &self.recording.events;
+        while let Some((_, data)) = events
+            .get(self.next)
+            .filter(|(time, _)| *time <= self.position)
+        {
+            output.extend_from_slice(data);
+            self.next += 1;
+        }
+        output
+    }
+}
+
+/// `[time, "code", "data"]`
+fn parse_event(line: &str) -> Option<(f64, String, String)> {
+    let rest = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+    let (time, rest) = rest.split_once(',')?;
+    let (code, rest) = json_string(rest.trim_start())?;
+    let rest = rest.trim_start().strip_prefix(',')?;
+    let (data, rest) = json_string(rest.trim_start())?;
+    if !rest.trim().is_empty() {
+        return None;
+    }
+    Some((time.trim().parse().ok()?, code, data))
+}
+
+/// Decodes the JSON string `input` starts with, returning it and what follows
+fn json_string(input: &str) -> Option<(String, &str)> {
+    let mut chars = input.strip_prefix('"')?.char_indices();
+    let mut decoded = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            // `i` counts from after the opening quote
+            '"' => return Some((decoded, &input[i + 2..])),
+            '\\' => {
+                let escaped = match chars.next()?.1 {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'u' => {
+                        let high = code_unit(&mut chars)?;
+                        // outside the BMP a character is a surrogate pair
+                        let code = match high {
+                            0xd800..=0xdbff => {
+                                let escape: String =
+                                    chars.by_ref().take(2).map(|(_, c)| c).collect();
+                                let low = code_unit(&mut chars).filter(|_| escape == "\\u")?;
+                                0x10000 + ((high - 0xd800) << 10) + low.checked_sub(0xdc00)?
+                            }
+                            _ => high,
+                        };
+                        char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                    }
+                    c => c,
+                };
+                decoded.push(escaped);
+            }
+            c => decoded.push(c),
+        }
+    }
+    None
+}
+
+/// The four hex digits after `\u`
+fn code_unit(chars: &mut std::str::CharIndices) -> Option<u32> {
+    let hex: String = chars.take(4).map(|(_, c)| c).collect();
+    u32::from_str_radix(&hex, 16).ok()
+}
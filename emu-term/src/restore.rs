@@ -0,0 +1,75 @@
+//! The tabs of the last window closed, saved on the way out when `restore_session` is on and
+//! offered back on the next launch. A tab comes back as a fresh shell in the directory it was
+//! last in, under what its scrollback showed, ssh tabs connect to their host again
+
+use crate::config::config_dir;
+use crate::dump;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Saved {
+    /// The tab that was active, an index into `tabs`
+    #[serde(default)]
+    pub active: usize,
+    #[serde(default)]
+    pub tabs: Vec<SavedTab>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedTab {
+    pub title: String,
+    pub dir: Option<PathBuf>,
+    /// The profile from the config the tab was opened with
+    pub profile: Option<String>,
+    /// The host of an ssh tab, as the quick connect dialog takes it
+    pub ssh: Option<String>,
+    /// The scrollback and the screen with their colors, as `dump::text` writes them
+    #[serde(default)]
+    pub scrollback: String,
+}
+
+fn path() -> Option<PathBuf> {
+    Some(config_dir()?.join("session.toml"))
+}
+
+/// The session saved on the last exit, `None` when there's none or it has no tabs
+pub fn load() -> Option<Saved> {
+    let path = path()?;
+    let contents = std::fs::read_to_string(&path).ok()?;
+    match toml::from_str::<Saved>(&contents) {
+        Ok(saved) if saved.tabs.is_empty() => None,
+        Ok(saved) => Some(saved),
+        Err(e) => {
+            tracing::warn!("failed to read {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Replaces the saved session, a window closed without tabs worth saving leaves none
+pub fn save(saved: &Saved) {
+    let Some(path) = path() else {
+        return;
+    };
+    if saved.tabs.is_empty() {
+        let _ = std::fs::remove_file(&path);
+        return;
+    }
+
+    let contents = match toml::to_string(saved) {
+        Ok(contents) => contents,
+        Err(e) => {
+            tracing::warn!("failed to save the session: {}", e);
+            return;
+        }
+    };
+    // only readable by the user, like a scrollback saved by hand
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| dump::save(&path, &contents));
+    if let Err(e) = written {
+        tracing::warn!("failed to save the session to {}: {}", path.display(), e);
+    }
+}
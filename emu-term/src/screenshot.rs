@@ -0,0 +1,46 @@
+use iced::window::Screenshot;
+use iced::{Rectangle, Size};
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where a screenshot goes, `~/Pictures` when there's one and the home directory otherwise.
+/// It's named after the time it was taken
+pub fn path() -> PathBuf {
+    let home = std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let pictures = home.join("Pictures");
+    let dir = if pictures.is_dir() { pictures } else { home };
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    dir.join(format!("emu-term-{}.png", secs))
+}
+
+/// Cuts off the `top` logical pixels of the window, where the tab bar is
+pub fn below(screenshot: Screenshot, top: f32) -> Screenshot {
+    let top = (top as f64 * screenshot.scale_factor).round() as u32;
+    let Size { width, height } = screenshot.size;
+    let region = Rectangle {
+        x: 0,
+        y: top,
+        width,
+        height: height.saturating_sub(top),
+    };
+    screenshot.crop(region).unwrap_or(screenshot)
+}
+
+/// Encodes the pixels as an RGBA PNG
+pub fn save(screenshot: &Screenshot, path: &Path) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let Size { width, height } = screenshot.size;
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&screenshot.bytes)?;
+    Ok(writer.finish()?)
+}
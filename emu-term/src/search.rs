@@ -0,0 +1,166 @@
+use crate::selection::Point;
+use emu_core::grid::{Grid, GridRow};
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+
+/// A match, first and last cell in reading order. It can run over rows that were wrapped
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Match {
+    pub start: Point,
+    pub end: Point,
+}
+
+impl Match {
+    /// Inclusive range of columns covered on `row`, rows in the middle are covered whole
+    pub fn columns(&self, row: usize, cols: usize) -> Option<(usize, usize)> {
+        if row < self.start.row || row > self.end.row {
+            return None;
+        }
+        let left = if row == self.start.row {
+            self.start.col
+        } else {
+            1
+        };
+        let right = if row == self.end.row {
+            self.end.col
+        } else {
+            cols
+        };
+        Some((left, right))
+    }
+}
+
+/// The search overlay of a tab. Matches are in grid coordinates and in reading order, the
+/// search starts at the bottom and `n` goes further back
+#[derive(Debug, Default)]
+pub struct Search {
+    pub query: String,
+    /// Take the query as a regex rather than literally
+    pub regex: bool,
+    /// Typing the query, the keys go to the input instead of stepping through the matches
+    pub editing: bool,
+    pub matches: Vec<Match>,
+    /// Index in `matches` of the one the view is on
+    pub current: Option<usize>,
+    /// The query doesn't compile
+    pub error: Option<String>,
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self {
+            editing: true,
+            ..Self::default()
+        }
+    }
+
+    /// Finds every match in `grid`. The search ignores case unless the query has an uppercase
+    /// letter. The current match is the last one starting at or above `row`
+    pub fn run(&mut self, grid: &Grid, row: usize) {
+        self.matches.clear();
+        self.current = None;
+        self.error = None;
+        if self.query.is_empty() {
+            return;
+        }
+
+        let pattern = match self.regex {
+            true => self.query.clone(),
+            false => regex::escape(&self.query),
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!self.query.chars().any(char::is_uppercase))
+            .build();
+        match regex {
+            Ok(regex) => self.matches = find_all(grid, &regex),
+            Err(e) => self.error = Some(e.to_string()),
+        }
+        self.current = self.matches.iter().rposition(|m| m.start.row <= row);
+    }
+
+    /// Runs the search again over a grid that changed, staying on the same match if it's
+    /// still there
+    pub fn refresh(&mut self, grid: &Grid) {
+        let current = self.current().map(|m| m.start);
+        self.run(grid, current.map_or(grid.len(), |start| start.row));
+
+        let Some(start) = current else {
+            return;
+        };
+        if let Some(i) = self.matches.iter().position(|m| m.start == start) {
+            self.current = Some(i);
+        }
+    }
+
+    /// Moves the matches up along with the grid once its first `rows` are dropped, the ones
+    /// that started on them go away
+    pub fn scroll_off(&mut self, rows: usize) {
+        let gone = self.matches.partition_point(|m| m.start.row <= rows);
+        self.matches.drain(..gone);
+        for m in &mut self.matches {
+            m.start.row -= rows;
+            m.end.row -= rows;
+        }
+        self.current = self.current.and_then(|i| i.checked_sub(gone));
+    }
+
+    pub fn current(&self) -> Option<&Match> {
+        self.matches.get(self.current?)
+    }
+
+    /// Moves to an older match when going `back` and a newer one otherwise, wrapping around.
+    /// Without a current one it starts from the bottom
+    pub fn step(&mut self, back: bool) -> Option<&Match> {
+        let len = self.matches.len();
+        if len == 0 {
+            return None;
+        }
+
+        self.current = Some(match (self.current, back) {
+            (Some(i), true) => (i + len - 1) % len,
+            (Some(i), false) => (i + 1) % len,
+            (None, _) => len - 1,
+        });
+        self.current()
+    }
+}
+
+/// Searches each logical line as a whole, so matches carry over wrapped rows
+fn find_all(grid: &Grid, regex: &Regex) -> Vec<Match> {
+    find_lines(grid.iter(), 1, regex)
+}
+
+/// Matches `regex` on the logical lines of `rows`, the first of them being grid row `first`.
+/// A line still wrapped at the last row is matched as far as it goes
+pub fn find_lines<'a>(
+    rows: impl Iterator<Item = Cow<'a, GridRow>>,
+    first: usize,
+    regex: &Regex,
+) -> Vec<Match> {
+    let mut matches = vec![];
+    let mut text = String::new();
+    // cell of every char in `text`, with its byte offset
+    let mut cells: Vec<(usize, Point)> = vec![];
+    let mut rows = rows.enumerate().peekable();
+
+    while let Some((i, row)) = rows.next() {
+        for (x, cell) in row.cells.iter().enumerate() {
+            cells.push((text.len(), Point::new(first + i, x + 1)));
+            text.push(cell.c);
+        }
+        if row.wrapped && rows.peek().is_some() {
+            continue;
+        }
+
+        for found in regex.find_iter(&text).filter(|found| !found.is_empty()) {
+            let char_at = |byte: usize| cells.partition_point(|(offset, _)| *offset <= byte) - 1;
+            matches.push(Match {
+                start: cells[char_at(found.start())].1,
+                end: cells[char_at(found.end() - 1)].1,
+            });
+        }
+        text.clear();
+        cells.clear();
+    }
+    matches
+}
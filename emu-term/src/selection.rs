@@ -0,0 +1,211 @@
+use emu_core::grid::Grid;
+
+/// A cell in grid coordinates, 1-based. The row is absolute so a point stays on the same
+/// content while the screen scrolls. Points order the way text reads, row first
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Point {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl Point {
+    pub fn new(row: usize, col: usize) -> Self {
+        Self { row, col }
+    }
+
+    /// The same cell once `rows` rows were dropped from the top of the grid, if it's still there
+    fn up(self, rows: usize) -> Option<Self> {
+        (self.row > rows).then(|| Self::new(self.row - rows, self.col))
+    }
+}
+
+/// How a selection grows as the mouse is dragged
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SelectionKind {
+    /// Cell by cell, in reading order
+    Simple,
+    /// Whole words, started with a double-click
+    Word,
+    /// Whole logical lines, started with a triple-click
+    Line,
+    /// The rectangle between both ends, started with Alt held
+    Block,
+}
+
+/// First and last cell of a word or line, or the same cell twice for the kinds that don't
+/// snap to anything
+pub type Span = (Point, Point);
+
+/// Cells between where the selection started and where it was extended to. Either end can
+/// come first, and both ends cover the whole word or line under the mouse when the kind asks
+/// for it
+#[derive(Debug, Copy, Clone)]
+pub struct Selection {
+    kind: SelectionKind,
+    anchor: Span,
+    head: Span,
+}
+
+impl Selection {
+    pub fn new(kind: SelectionKind, at: Span) -> Self {
+        Self {
+            kind,
+            anchor: at,
+            head: at,
+        }
+    }
+
+    /// Follows the cells up as scrollback is dropped, gone when either end was in it
+    pub fn scrolled_off(self, rows: usize) -> Option<Self> {
+        let up = |(start, end): Span| Some((start.up(rows)?, end.up(rows)?));
+        Some(Self {
+            kind: self.kind,
+            anchor: up(self.anchor)?,
+            head: up(self.head)?,
+        })
+    }
+
+    pub fn kind(&self) -> SelectionKind {
+        self.kind
+    }
+
+    pub fn extend(&mut self, to: Span) {
+        self.head = to;
+    }
+
+    /// A click that was never dragged doesn't select anything, unless it picked a word or line
+    pub fn is_empty(&self) -> bool {
+        matches!(self.kind, SelectionKind::Simple | SelectionKind::Block)
+            && self.anchor == self.head
+    }
+
+    /// First and last selected cells, both inclusive. For block selections these are the
+    /// top-left and bottom-right corners
+    pub fn bounds(&self) -> (Point, Point) {
+        if self.kind == SelectionKind::Block {
+            let (a, h) = (self.anchor.0, self.head.0);
+            return (
+                Point::new(a.row.min(h.row), a.col.min(h.col)),
+                Point::new(a.row.max(h.row), a.col.max(h.col)),
+            );
+        }
+        (
+            self.anchor.0.min(self.head.0),
+            self.anchor.1.max(self.head.1),
+        )
+    }
+
+    /// Inclusive range of columns selected on `row`. Outside of block selections, lines in the
+    /// middle span all `cols` columns
+    pub fn columns(&self, row: usize, cols: usize) -> Option<(usize, usize)> {
+        let (start, end) = self.bounds();
+        if self.is_empty() || row < start.row || row > end.row {
+            return None;
+        }
+        if self.kind == SelectionKind::Block {
+            return Some((start.col, end.col));
+        }
+
+        let left = if row == start.row { start.col } else { 1 };
+        let right = if row == end.row { end.col } else { cols };
+        Some((left, right))
+    }
+
+    /// The selected text. Blanks at the end of each line are dropped, and lines only break
+    /// where the logical line ends, rows that were wrapped are joined back together. Block
+    /// selections break on every row
+    pub fn text(&self, grid: &Grid, cols: usize) -> String {
+        let (start, end) = self.bounds();
+        let mut text = String::new();
+        for (row, line) in (start.row..=end.row).zip(grid.rows(start.row - 1..end.row)) {
+            let Some((left, right)) = self.columns(row, cols) else {
+                continue;
+            };
+
+            let selected = line.cells.iter().take(right).skip(left - 1);
+            let chars: String = selected.map(|cell| cell.c).collect();
+            let continues = self.kind != SelectionKind::Block && line.wrapped && right == cols;
+            if continues {
+                text.push_str(&chars);
+            } else {
+                text.push_str(chars.trim_end());
+                if row != end.row {
+                    text.push('\n');
+                }
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emu_core::Terminal;
+
+    const COLS: usize = 10;
+
+    fn terminal(output: &str) -> Terminal {
+        let mut terminal = Terminal::new(4, COLS);
+        terminal.process_bytes(output.as_bytes());
+        terminal
+    }
+
+    fn text(output: &str, kind: SelectionKind, from: (usize, usize), to: (usize, usize)) -> String {
+        let terminal = terminal(output);
+        let point = |(row, col)| Point::new(row, col);
+        let mut selection = Selection::new(kind, (point(from), point(from)));
+        selection.extend((point(to), point(to)));
+        selection.text(terminal.grid(), COLS)
+    }
+
+    #[test]
+    fn simple_selection_breaks_where_lines_end() {
+        let output = "one two\r\nthree four";
+        assert_eq!(
+            text(output, SelectionKind::Simple, (1, 5), (2, 5)),
+            "two\nthree"
+        );
+        // either end can come first
+        assert_eq!(
+            text(output, SelectionKind::Simple, (2, 5), (1, 5)),
+            "two\nthree"
+        );
+    }
+
+    #[test]
+    fn blanks_at_the_end_of_a_line_are_dropped() {
+        let output = "ab    \r\n  cd";
+        assert_eq!(
+            text(output, SelectionKind::Simple, (1, 1), (2, COLS)),
+            "ab\n  cd"
+        );
+    }
+
+    #[test]
+    fn wrapped_rows_are_joined() {
+        let output = "abcdefghijklm";
+        assert_eq!(
+            text(output, SelectionKind::Simple, (1, 3), (2, 2)),
+            "cdefghijkl"
+        );
+    }
+
+    #[test]
+    fn word_selections_cover_both_ends() {
+        let word = |col| (Point::new(1, col), Point::new(1, col + 2));
+        let mut selection = Selection::new(SelectionKind::Word, word(5));
+        assert!(!selection.is_empty());
+        selection.extend(word(1));
+        assert_eq!(selection.bounds(), (Point::new(1, 1), Point::new(1, 7)));
+    }
+
+    #[test]
+    fn block_selection_breaks_on_every_row() {
+        let output = "abcdefghijklm\r\nnopq";
+        assert_eq!(
+            text(output, SelectionKind::Block, (1, 2), (3, 3)),
+            "bc\nlm\nop"
+        );
+    }
+}
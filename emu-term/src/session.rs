@@ -0,0 +1,222 @@
+use crate::shell::Shell;
+use crate::ssh::SshTarget;
+use crate::TabId;
+use nix::sys::socket::{self, Shutdown};
+use nix::sys::termios::{self, BaudRate, SetArg};
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long connecting to each address a TCP session's host resolves to may take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a tab talks to. Only a pty has a child, the others have nothing to resize or to wait
+/// for and end when the other side hangs up
+#[derive(Debug, Clone)]
+pub enum Session {
+    /// A child on a pty of its own
+    Pty(Shell),
+    /// `ssh` on a pty, which runs with the profile of the host
+    Ssh(SshTarget, Shell),
+    Unix(PathBuf),
+    /// `host:port`
+    Tcp(String),
+    Serial {
+        device: PathBuf,
+        baud: u32,
+    },
+    /// A recorded session played back, with the timing file of a typescript
+    Replay {
+        recording: PathBuf,
+        timing: Option<PathBuf>,
+    },
+    /// Whatever was piped to the emulator, shown like in a pager
+    Stdin,
+    /// A session the daemon holds, by its number, see `daemon`
+    Attach(u32),
+    /// A pane of the tmux attached in control mode on the tab `gateway`, which its output
+    /// and input go through, see `tmux`
+    Tmux {
+        gateway: TabId,
+        pane: u32,
+    },
+}
+
+/// The tab's hold on the socket of a session, shut down once the tab is gone. The reader has
+/// a descriptor of its own, closing the tab's wouldn't end the connection
+#[derive(Debug)]
+pub struct Connection(OwnedFd);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Err(e) = socket::shutdown(self.0.as_raw_fd(), Shutdown::Both) {
+            tracing::debug!("failed to shut down a connection: {}", e);
+        }
+    }
+}
+
+impl Session {
+    /// Parses the `--connect` address, `unix:<path>` for a Unix socket and `host:port` for TCP
+    pub fn connect_to(address: &str) -> Self {
+        match address.strip_prefix("unix:") {
+            Some(path) => Session::Unix(path.into()),
+            None => Session::Tcp(address.trim_start_matches("tcp:").to_owned()),
+        }
+    }
+
+    /// What the tab is called until the application names it, `None` keeps the default for
+    /// local shells
+    pub fn name(&self) -> Option<String> {
+        match self {
+            Session::Pty(_) => None,
+            session => Some(session.to_string()),
+        }
+    }
+
+    /// Opens a session that isn't a pty. Sockets and serial devices are all just a file
+    /// descriptor that's read from and written to
+    pub fn open(&self) -> io::Result<File> {
+        let fd: OwnedFd = match self {
+            Session::Pty(_) | Session::Ssh(..) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "a pty is opened with the shell on it",
+                ))
+            }
+            Session::Replay { .. } => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "a recording is loaded whole",
+                ))
+            }
+            Session::Tmux { .. } => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "a tmux pane goes through the tab tmux runs in",
+                ))
+            }
+            Session::Attach(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "the daemon hands over the pty",
+                ))
+            }
+            Session::Stdin => io::stdin().as_fd().try_clone_to_owned()?,
+            Session::Unix(path) => UnixStream::connect(path)?.into(),
+            Session::Tcp(address) => {
+                let stream = connect_tcp(address)?;
+                // keystrokes go out one at a time
+                stream.set_nodelay(true)?;
+                stream.into()
+            }
+            Session::Serial { device, baud } => open_serial(device, *baud)?,
+        };
+
+        Ok(File::from(fd))
+    }
+
+    /// What `open` opened for a socket, for the tab to shut it down
+    pub fn connection(&self, file: &File) -> io::Result<Option<Connection>> {
+        match self {
+            Session::Unix(_) | Session::Tcp(_) => {
+                Ok(Some(Connection(file.as_fd().try_clone_to_owned()?)))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Tries each address `address` resolves to in turn, giving up on one after `CONNECT_TIMEOUT`
+fn connect_tcp(address: &str) -> io::Result<TcpStream> {
+    let mut error = None;
+    for addr in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => error = Some(e),
+        }
+    }
+    Err(error
+        .unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the host has no addresses")))
+}
+
+impl Display for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Session::Pty(shell) => write!(f, "{}", shell.program.display()),
+            Session::Ssh(target, _) => write!(f, "{}", target),
+            Session::Unix(path) => write!(f, "{}", path.display()),
+            Session::Tcp(address) => write!(f, "{}", address),
+            Session::Serial { device, baud } => write!(f, "{} {}", device.display(), baud),
+            Session::Replay { recording, .. } => write!(f, "{}", recording.display()),
+            Session::Stdin => write!(f, "stdin"),
+            Session::Attach(session) => write!(f, "session {}", session),
+            Session::Tmux { pane, .. } => write!(f, "tmux %{}", pane),
+        }
+    }
+}
+
+/// Opens the device raw, 8N1 at `baud`, the remote end does its own echo and line editing
+fn open_serial(device: &Path, baud: u32) -> io::Result<OwnedFd> {
+    let speed = baud_rate(baud).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported baud rate {}", baud),
+        )
+    })?;
+
+    // a serial port shouldn't become the controlling terminal of the emulator
+    let fd: OwnedFd = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(device)?
+        .into();
+
+    let mut attrs = termios::tcgetattr(&fd)?;
+    termios::cfmakeraw(&mut attrs);
+    termios::cfsetspeed(&mut attrs, speed)?;
+    // ignore the modem control lines, most boards don't wire them
+    attrs.control_flags |= termios::ControlFlags::CLOCAL | termios::ControlFlags::CREAD;
+    termios::tcsetattr(&fd, SetArg::TCSANOW, &attrs)?;
+    Ok(fd)
+}
+
+fn baud_rate(baud: u32) -> Option<BaudRate> {
+    Some(match baud {
+        1200 => BaudRate::B1200,
+        2400 => BaudRate::B2400,
+        4800 => BaudRate::B4800,
+        9600 => BaudRate::B9600,
+        19200 => BaudRate::B19200,
+        38400 => BaudRate::B38400,
+        57600 => BaudRate::B57600,
+        115200 => BaudRate::B115200,
+        230400 => BaudRate::B230400,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn dropping_the_connection_ends_the_reader() {
+        let (ours, mut theirs) = UnixStream::pair().unwrap();
+        let mut reader = File::from(OwnedFd::from(ours));
+        let connection = Session::Unix("socket".into()).connection(&reader).unwrap();
+        assert!(connection.is_some());
+
+        drop(connection);
+        let mut buf = [0; 8];
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(theirs.read(&mut buf).unwrap(), 0);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::config::Config;
+use emu_core::grid::CursorShape;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, Table};
+
+pub const CURSOR_SHAPES: [CursorShape; 3] =
+    [CursorShape::Block, CursorShape::Underline, CursorShape::Bar];
+
+/// The settings pane. Fields hold what's typed until it's saved, and only the ones that
+/// changed are written
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub fields: Fields,
+    initial: Fields,
+    /// Why the last save didn't go through
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fields {
+    /// Empty for the system's monospace font
+    pub font_family: String,
+    pub font_size: String,
+    /// Empty for the default colors
+    pub theme: String,
+    pub scrollback: String,
+    pub cursor_shape: CursorShape,
+    pub cursor_blinking: bool,
+}
+
+/// What the settings pane emits
+#[derive(Debug, Clone)]
+pub enum SettingsEvent {
+    FontFamily(String),
+    FontSize(String),
+    Theme(String),
+    Scrollback(String),
+    CursorShape(CursorShape),
+    CursorBlinking(bool),
+    Save,
+    Cancel,
+}
+
+impl Settings {
+    pub fn new(config: &Config) -> Self {
+        let fields = Fields {
+            font_family: config.font.family.clone().unwrap_or_default(),
+            font_size: config.font.size.to_string(),
+            theme: config
+                .theme
+                .as_ref()
+                .map(|theme| theme.display().to_string())
+                .unwrap_or_default(),
+            scrollback: config.scrollback.to_string(),
+            cursor_shape: config.cursor.shape,
+            cursor_blinking: config.cursor.blinking,
+        };
+
+        Self {
+            initial: fields.clone(),
+            fields,
+            error: None,
+        }
+    }
+
+    /// Takes an edit, saving and cancelling are up to the window
+    pub fn update(&mut self, event: SettingsEvent) {
+        let fields = &mut self.fields;
+        match event {
+            SettingsEvent::FontFamily(family) => fields.font_family = family,
+            SettingsEvent::FontSize(size) => fields.font_size = size,
+            SettingsEvent::Theme(theme) => fields.theme = theme,
+            SettingsEvent::Scrollback(lines) => fields.scrollback = lines,
+            SettingsEvent::CursorShape(shape) => fields.cursor_shape = shape,
+            SettingsEvent::CursorBlinking(blinking) => fields.cursor_blinking = blinking,
+            SettingsEvent::Save | SettingsEvent::Cancel => {}
+        }
+    }
+
+    /// Writes what changed into the config at `path`, creating it if needed. The rest of the
+    /// file is left as it is, comments included
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let (fields, initial) = (&self.fields, &self.initial);
+        let size: f32 = fields
+            .font_size
+            .trim()
+            .parse()
+            .ok()
+            .filter(|size: &f32| size.is_finite() && *size > 0.0)
+            .ok_or_else(|| format!("invalid font size {:?}", fields.font_size))?;
+        let scrollback: usize =
+            fields.scrollback.trim().parse().map_err(|_| {
+                format!("invalid scrollback {:?}, expected lines", fields.scrollback)
+            })?;
+
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+        };
+        let mut doc: DocumentMut = content
+            .parse()
+            .map_err(|e| format!("failed to parse {}: {}", path.display(), e))?;
+
+        if fields.font_family != initial.font_family {
+            let family = fields.font_family.trim();
+            set(
+                &mut doc,
+                &["font", "family"],
+                (!family.is_empty()).then(|| family.into()),
+            );
+        }
+        if fields.font_size != initial.font_size {
+            set(&mut doc, &["font", "size"], Some((size as f64).into()));
+        }
+        if fields.theme != initial.theme {
+            let theme = fields.theme.trim();
+            set(
+                &mut doc,
+                &["theme"],
+                (!theme.is_empty()).then(|| theme.into()),
+            );
+        }
+        if fields.scrollback != initial.scrollback {
+            set(&mut doc, &["scrollback"], Some((scrollback as i64).into()));
+        }
+        if fields.cursor_shape != initial.cursor_shape {
+            let shape = fields.cursor_shape.to_string();
+            set(&mut doc, &["cursor", "shape"], Some(shape.into()));
+        }
+        if fields.cursor_blinking != initial.cursor_blinking {
+            let blinking = fields.cursor_blinking;
+            set(&mut doc, &["cursor", "blinking"], Some(blinking.into()));
+        }
+
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, doc.to_string()));
+        written.map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// Sets the key at the end of `keys`, creating the tables on the way, or removes it for `None`
+fn set(doc: &mut DocumentMut, keys: &[&str], value: Option<toml_edit::Value>) {
+    let (key, tables) = keys.split_last().expect("a key");
+    let mut item = doc.as_item_mut();
+    for table in tables {
+        item = &mut item[*table];
+        // a new `[table]` rather than the inline one toml_edit would make
+        if item.is_none() {
+            *item = Item::Table(Table::new());
+        }
+    }
+
+    match value {
+        Some(value) => item[*key] = Item::Value(value),
+        None => {
+            if let Some(table) = item.as_table_like_mut() {
+                table.remove(key);
+            }
+        }
+    }
+}
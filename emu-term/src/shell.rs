@@ -0,0 +1,268 @@
+use crate::terminfo;
+use libc::winsize;
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+use nix::pty::{openpty, OpenptyResult};
+use nix::sys::signal::{killpg, Signal};
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::{getuid, tcgetpgrp, Pid, User};
+use std::fmt::Display;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Left behind by whatever started the emulator, typically another terminal, and wrong for
+/// anything running inside of it
+const INHERITED: [&str; 14] = [
+    "COLUMNS",
+    "LINES",
+    "TERMINFO",
+    "TERM_PROGRAM",
+    "TERM_PROGRAM_VERSION",
+    "COLORFGBG",
+    "WINDOWID",
+    "VTE_VERSION",
+    "ALACRITTY_WINDOW_ID",
+    "KITTY_WINDOW_ID",
+    "KITTY_PID",
+    "WEZTERM_PANE",
+    "TMUX",
+    "TMUX_PANE",
+];
+
+/// Used when neither `$SHELL` nor the passwd entry point to something that exists
+const FALLBACK: &str = "/bin/sh";
+
+/// The program started on the pty
+#[derive(Debug, Clone)]
+pub struct Shell {
+    pub program: PathBuf,
+    /// Arguments after `argv[0]`, only commands given with `-e` have any
+    pub args: Vec<String>,
+    /// Start it as a login shell, which is signalled by a `-` in front of `argv[0]`
+    pub login: bool,
+    /// Where it starts, the current directory when `None`
+    pub working_directory: Option<PathBuf>,
+    /// Exported as `TERM`, see `terminfo::term`
+    pub term: &'static str,
+    /// Set on top of the inherited environment, after `TERM` and friends so they can be
+    /// overridden
+    pub env: Vec<(String, String)>,
+    pub unset_env: Vec<String>,
+    /// Start `/bin/sh` if the program can't be, only for the user's shell
+    fallback: bool,
+}
+
+impl Shell {
+    /// Looks at `$SHELL` first and then the user's passwd entry. This does lookups that aren't
+    /// safe between fork and exec, so it has to run before forking
+    pub fn resolve(login: bool) -> Self {
+        let from_passwd = || {
+            User::from_uid(getuid())
+                .ok()
+                .flatten()
+                .map(|user| user.shell)
+        };
+        let program = std::env::var_os("SHELL")
+            .map(PathBuf::from)
+            .filter(|shell| shell.is_file())
+            .or_else(|| from_passwd().filter(|shell| shell.is_file()))
+            .unwrap_or_else(|| PathBuf::from(FALLBACK));
+
+        Self {
+            program,
+            args: vec![],
+            login,
+            working_directory: None,
+            term: terminfo::FALLBACK,
+            env: vec![],
+            unset_env: vec![],
+            fallback: true,
+        }
+    }
+
+    /// Runs `argv` instead of a shell, it's looked up in `$PATH` like a shell would
+    pub fn command(argv: &[String]) -> Self {
+        let (program, args) = argv.split_first().expect("a command has a program");
+        Self {
+            program: PathBuf::from(program),
+            args: args.to_vec(),
+            login: false,
+            working_directory: None,
+            term: terminfo::FALLBACK,
+            env: vec![],
+            unset_env: vec![],
+            fallback: false,
+        }
+    }
+
+    /// Runs `argv` with the environment and directory this one would get
+    pub fn run(&self, argv: &[String]) -> Self {
+        Self {
+            working_directory: self.working_directory.clone(),
+            term: self.term,
+            env: self.env.clone(),
+            unset_env: self.unset_env.clone(),
+            ..Self::command(argv)
+        }
+    }
+
+    /// Starts the shell on a new pty of `winsize`, falling back to `/bin/sh` if it can't be
+    /// started. Returns the master side and the child, which leads a session with the pty as
+    /// its controlling terminal. Blocks until the program is exec'd
+    pub fn spawn(&self, winsize: &winsize) -> io::Result<(OwnedFd, Pid)> {
+        let OpenptyResult { master, slave } = openpty(winsize, None)?;
+        // neither side is for the child's children, it gets the slave as its stdio
+        for fd in [&master, &slave] {
+            fcntl(fd.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+        }
+
+        let child = match self.spawn_on(&self.program, &slave) {
+            Err(e) if self.fallback && self.program != Path::new(FALLBACK) => {
+                tracing::error!("failed to start {}: {}", self.program.display(), e);
+                self.spawn_on(Path::new(FALLBACK), &slave)?
+            }
+            child => child?,
+        };
+        Ok((master, Pid::from_raw(child.id() as i32)))
+    }
+
+    fn spawn_on(&self, program: &Path, slave: &OwnedFd) -> io::Result<std::process::Child> {
+        let mut command = self.command_for(program);
+        command
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave.try_clone()?));
+        // only async-signal-safe calls between fork and exec, everything else was done before
+        unsafe {
+            command.pre_exec(|| {
+                nix::unistd::setsid()?;
+                if libc::ioctl(libc::STDIN_FILENO, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        command.spawn()
+    }
+
+    fn command_for(&self, program: &Path) -> Command {
+        let mut command = Command::new(program);
+        command.args(&self.args);
+        if self.login {
+            let name = program.file_name().unwrap_or(program.as_os_str());
+            command.arg0(format!("-{}", name.to_string_lossy()));
+        }
+        let unset = self.unset_env.iter().map(String::as_str);
+        for name in INHERITED.into_iter().chain(unset) {
+            command.env_remove(name);
+        }
+        command
+            .env("TERM", self.term)
+            .env("COLORTERM", "truecolor")
+            .env("TERM_PROGRAM", "emu-term")
+            .env("TERM_PROGRAM_VERSION", env!("CARGO_PKG_VERSION"))
+            .envs(self.env.iter().cloned());
+        if let Some(dir) = &self.working_directory {
+            command.current_dir(dir);
+        }
+        command
+    }
+}
+
+/// How the child ended
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExitStatus {
+    Code(i32),
+    Signal(Signal),
+}
+
+impl Display for ExitStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExitStatus::Code(code) => write!(f, "exited with code {}", code),
+            ExitStatus::Signal(signal) => write!(f, "was killed by {}", signal.as_str()),
+        }
+    }
+}
+
+/// Sends `signal` to the child's process group, and to the job in the foreground of the pty
+/// when that's another one, like the kernel does on a hangup
+pub fn signal(pty: Option<impl AsFd>, child: Pid, signal: Signal) {
+    if let Err(e) = killpg(child, signal) {
+        tracing::warn!("failed to send {} to the child: {}", signal.as_str(), e);
+    }
+    let foreground = pty.and_then(|pty| tcgetpgrp(pty).ok());
+    if let Some(group) = foreground.filter(|&group| group != child) {
+        if let Err(e) = killpg(group, signal) {
+            tracing::warn!(
+                "failed to send {} to the foreground job: {}",
+                signal.as_str(),
+                e
+            );
+        }
+    }
+}
+
+/// Blocks until the child exits and reaps it, `None` if it can't be waited on
+pub fn wait(child: Pid) -> Option<ExitStatus> {
+    loop {
+        match waitpid(child, None) {
+            Ok(WaitStatus::Exited(_, code)) => return Some(ExitStatus::Code(code)),
+            Ok(WaitStatus::Signaled(_, signal, _)) => return Some(ExitStatus::Signal(signal)),
+            // stopped and continued, it's still around
+            Ok(_) => continue,
+            Err(nix::errno::Errno::EINTR) => continue,
+            Err(e) => {
+                tracing::error!("failed to wait for the child: {}", e);
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read as _;
+
+    fn output(shell: &Shell) -> (String, Option<ExitStatus>) {
+        let winsize = winsize {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let (master, child) = shell.spawn(&winsize).expect("should spawn");
+        let status = wait(child);
+        let mut output = vec![];
+        // reading past the output fails with EIO once the slave is closed
+        let _ = std::fs::File::from(master).read_to_end(&mut output);
+        (String::from_utf8_lossy(&output).into_owned(), status)
+    }
+
+    #[test]
+    fn spawns_on_a_controlling_pty() {
+        // /dev/tty only opens with a controlling terminal
+        let argv = ["/bin/sh", "-c", "stty size; : </dev/tty && echo tty"];
+        let argv: Vec<String> = argv.iter().map(|s| s.to_string()).collect();
+        let (output, status) = output(&Shell::command(&argv));
+        assert_eq!(status, Some(ExitStatus::Code(0)));
+        assert_eq!(output, "24 80\r\ntty\r\n");
+    }
+
+    #[test]
+    fn falls_back_to_sh() {
+        let shell = Shell {
+            program: PathBuf::from("/nonexistent/shell"),
+            args: vec!["-c".to_owned(), "echo fallback".to_owned()],
+            ..Shell::resolve(false)
+        };
+        assert_eq!(output(&shell).0, "fallback\r\n");
+
+        let argv = ["/nonexistent/program".to_owned()];
+        let winsize = unsafe { std::mem::zeroed() };
+        assert!(Shell::command(&argv).spawn(&winsize).is_err());
+    }
+}
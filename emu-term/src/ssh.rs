@@ -0,0 +1,180 @@
+use crate::config::config_dir;
+use std::fmt::Display;
+use std::path::PathBuf;
+
+/// How many hosts the quick-connect dialog remembers
+const MAX_RECENT: usize = 10;
+
+/// A host to `ssh` into
+#[derive(Debug, Clone, PartialEq)]
+pub struct SshTarget {
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl SshTarget {
+    /// Parses `ssh://user@host:port`, where everything but the host is optional and so is the
+    /// scheme. IPv6 addresses go in brackets when there's a port. A user or host starting with
+    /// `-` would be taken for an option of `ssh`, and isn't one
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let s = s.strip_prefix("ssh://").unwrap_or(s).trim_end_matches('/');
+        let (user, rest) = match s.rsplit_once('@') {
+            Some((user, rest)) => (Some(user.to_owned()), rest),
+            None => (None, s),
+        };
+
+        let (host, port) = if let Some(bracketed) = rest.strip_prefix('[') {
+            let (host, port) = bracketed.split_once(']')?;
+            (host, port.strip_prefix(':'))
+        } else if rest.matches(':').count() > 1 {
+            // an IPv6 address, only the bracketed ones can have a port
+            (rest, None)
+        } else {
+            match rest.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (rest, None),
+            }
+        };
+        let port = match port {
+            Some(port) => Some(port.parse().ok()?),
+            None => None,
+        };
+
+        let option = |s: &str| s.starts_with('-');
+        if host.is_empty()
+            || host.contains(char::is_whitespace)
+            || option(host)
+            || user
+                .as_deref()
+                .is_some_and(|user| user.is_empty() || option(user))
+        {
+            return None;
+        }
+        Some(Self {
+            user,
+            host: host.to_owned(),
+            port,
+        })
+    }
+
+    /// `ssh` and its arguments, the destination after `--` so it's never read as an option
+    pub fn command(&self) -> Vec<String> {
+        let mut argv = vec!["ssh".to_owned()];
+        if let Some(port) = self.port {
+            argv.extend(["-p".to_owned(), port.to_string()]);
+        }
+        let destination = match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        };
+        argv.extend(["--".to_owned(), destination]);
+        argv
+    }
+}
+
+impl Display for SshTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(user) = &self.user {
+            write!(f, "{}@", user)?;
+        }
+        match self.port {
+            Some(port) if self.host.contains(':') => write!(f, "[{}]:{}", self.host, port),
+            Some(port) => write!(f, "{}:{}", self.host, port),
+            None => write!(f, "{}", self.host),
+        }
+    }
+}
+
+fn recent_file() -> Option<PathBuf> {
+    Some(config_dir()?.join("recent-hosts"))
+}
+
+/// Hosts connected to through the dialog, the latest first
+pub fn recent_hosts() -> Vec<String> {
+    let Some(contents) = recent_file().and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return vec![];
+    };
+
+    contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .take(MAX_RECENT)
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Puts `target` at the top of the recent hosts
+pub fn remember(target: &SshTarget) {
+    let Some(path) = recent_file() else {
+        return;
+    };
+
+    let target = target.to_string();
+    let mut hosts = recent_hosts();
+    hosts.retain(|host| *host != target);
+    hosts.insert(0, target);
+    hosts.truncate(MAX_RECENT);
+
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, hosts.join("\n") + "\n"));
+    if let Err(e) = written {
+        tracing::warn!("failed to save the recent hosts: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(user: Option<&str>, host: &str, port: Option<u16>) -> SshTarget {
+        SshTarget {
+            user: user.map(str::to_owned),
+            host: host.to_owned(),
+            port,
+        }
+    }
+
+    #[test]
+    fn parse_with_and_without_scheme() {
+        let parsed = SshTarget::parse("ssh://me@example.com:2222/");
+        assert_eq!(parsed, Some(target(Some("me"), "example.com", Some(2222))));
+        let parsed = SshTarget::parse(" example.com ");
+        assert_eq!(parsed, Some(target(None, "example.com", None)));
+    }
+
+    #[test]
+    fn parse_ipv6() {
+        let parsed = SshTarget::parse("root@[::1]:22");
+        assert_eq!(parsed, Some(target(Some("root"), "::1", Some(22))));
+        assert_eq!(parsed.unwrap().to_string(), "root@[::1]:22");
+        let parsed = SshTarget::parse("fe80::1");
+        assert_eq!(parsed, Some(target(None, "fe80::1", None)));
+        let parsed = SshTarget::parse("user@::1");
+        assert_eq!(parsed, Some(target(Some("user"), "::1", None)));
+        assert_eq!(parsed.unwrap().to_string(), "user@::1");
+    }
+
+    #[test]
+    fn parse_rejects_what_isnt_a_host() {
+        for bad in [
+            "",
+            "@host",
+            "host:port",
+            "two words",
+            "-oProxyCommand=x",
+            "-l@host",
+        ] {
+            assert_eq!(SshTarget::parse(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn command_ends_the_options() {
+        let argv = target(Some("me"), "example.com", Some(2222)).command();
+        assert_eq!(argv, ["ssh", "-p", "2222", "--", "me@example.com"]);
+    }
+}
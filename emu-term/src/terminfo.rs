@@ -0,0 +1,154 @@
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// The entry shipped in `terminfo/emu-term.terminfo`
+const NAME: &str = "emu-term";
+const SOURCE: &str = include_str!("../terminfo/emu-term.terminfo");
+/// Close enough for most applications when the entry can't be installed
+pub const FALLBACK: &str = "xterm-256color";
+
+/// The `TERM` to give the child. The entry is compiled into `~/.terminfo` the first time
+/// around, using `tic` from ncurses, and `xterm-256color` stands in if that isn't possible
+pub fn term() -> &'static str {
+    if is_installed() || install() {
+        NAME
+    } else {
+        FALLBACK
+    }
+}
+
+fn is_installed() -> bool {
+    Command::new("infocmp")
+        .arg(NAME)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn install() -> bool {
+    let Some(home) = std::env::var_os("HOME") else {
+        return false;
+    };
+    let target = PathBuf::from(home).join(".terminfo");
+    // the source goes in on stdin, `-` for tic, there's no file of it anyone could swap
+    let child = Command::new("tic")
+        .arg("-x")
+        .arg("-o")
+        .arg(&target)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn();
+    let result = child.and_then(|mut child| {
+        let written = child
+            .stdin
+            .take()
+            .map_or(Ok(()), |mut stdin| stdin.write_all(SOURCE.as_bytes()));
+        // stdin is closed by now so tic sees the end of it, and it's waited for either way
+        let status = child.wait()?;
+        written.map(|_| status)
+    });
+
+    match result {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            tracing::warn!("tic failed to compile the terminfo entry: {}", status);
+            false
+        }
+        Err(e) => {
+            tracing::warn!("failed to run tic, is ncurses installed? {}", e);
+            false
+        }
+    }
+}
+
+/// The answer to an XTGETTCAP query: `DCS 1 + r name=value ST` for each capability the shipped
+/// entry has, with the value as the application would get it from terminfo, and
+/// `DCS 0 + r name ST` for the rest. `TN` asks for the name of the entry
+pub fn xtgettcap_reply(names: &[String]) -> Vec<u8> {
+    let mut reply = String::new();
+    for name in names {
+        let value = match name.as_str() {
+            "TN" => Some(Some(NAME.to_owned())),
+            name => capability(name),
+        };
+        match value {
+            Some(Some(value)) => {
+                reply.push_str(&format!("\x1bP1+r{}={}\x1b\\", hex(name), hex(&value)))
+            }
+            Some(None) => reply.push_str(&format!("\x1bP1+r{}\x1b\\", hex(name))),
+            None => reply.push_str(&format!("\x1bP0+r{}\x1b\\", hex(name))),
+        }
+    }
+    reply.into_bytes()
+}
+
+/// Looks `name` up in the shipped entry. `Some(None)` for a boolean, numbers come as their
+/// decimal digits
+fn capability(name: &str) -> Option<Option<String>> {
+    let source: String = SOURCE
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect();
+    // the first field holds the names of the entry
+    let value = fields(&source).skip(1).find_map(|field| {
+        if field == name {
+            return Some(None);
+        }
+        let (key, value) = field.split_at(field.find(['=', '#'])?);
+        match key == name {
+            true => Some(Some(unescape(&value[1..]))),
+            false => None,
+        }
+    });
+    value
+}
+
+/// The fields of an entry, split at the commas that aren't escaped
+fn fields(source: &str) -> impl Iterator<Item = &str> {
+    let mut escaped = false;
+    source
+        .split(move |c| {
+            let split = c == ',' && !escaped;
+            escaped = c == '\\' && !escaped;
+            split
+        })
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+}
+
+/// Expands the escapes of a terminfo string, `\E`, `^X`, octal and the like
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.next_if(|_| c == '\\' || c == '^')) {
+            ('\\', Some('E' | 'e')) => unescaped.push('\x1b'),
+            ('\\', Some('n')) => unescaped.push('\n'),
+            ('\\', Some('r')) => unescaped.push('\r'),
+            ('\\', Some('t')) => unescaped.push('\t'),
+            ('\\', Some('s')) => unescaped.push(' '),
+            ('\\', Some(digit @ '0'..='7')) => {
+                let mut code = digit.to_digit(8).unwrap_or(0);
+                for _ in 0..2 {
+                    match chars.next_if(|c| c.is_digit(8)) {
+                        Some(digit) => code = code * 8 + digit.to_digit(8).unwrap_or(0),
+                        None => break,
+                    }
+                }
+                unescaped.push(char::from_u32(code).unwrap_or('\0'));
+            }
+            ('\\', Some(c)) => unescaped.push(c),
+            ('^', Some('?')) => unescaped.push('\x7f'),
+            ('^', Some(c)) => unescaped.push((c as u8 & 0x1f) as char),
+            (c, _) => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn hex(s: &str) -> String {
+    s.bytes().map(|b| format!("{:02X}", b)).collect()
+}
@@ -0,0 +1,428 @@
+//! tmux control mode, what `tmux -CC` speaks. Commands go to tmux as lines and it answers
+//! with lines, the output of every pane among them. Each window gets a tab of its own with its
+//! panes split the way tmux lays them out, the tab tmux runs in, the gateway, only says that
+//! it's attached
+//!
+//! Answers come between `%begin` and `%end` (or `%error`) in the order the commands were
+//! sent, so every command sent is queued along with what to do with its answer
+
+use std::collections::VecDeque;
+
+use crate::layout::{self, Axis, Change};
+
+/// tmux goes into control mode with this DCS, which lasts until it detaches
+pub const START: &[u8] = b"\x1bP1000p";
+/// And ends it with ST
+pub const END: &[u8] = b"\x1b\\";
+/// Input for a pane goes in `send-keys` commands of up to this many bytes
+const KEYS_PER_COMMAND: usize = 256;
+/// Asked about every pane on attaching, and again whenever the windows change. The name goes
+/// last, it may have spaces in it
+const PANE_FORMAT: &str = "#{pane_id} #{cursor_x} #{cursor_y} #{pane_width} #{pane_height} \
+     #{pane_active} #{window_id} #{window_zoomed_flag} #{window_layout} #{window_name}";
+
+#[derive(Debug, Clone)]
+pub struct Pane {
+    /// `%N`, what `%output` and `send-keys -t` go by
+    pub id: u32,
+    /// Column and line, 0-based
+    pub cursor: (u32, u32),
+    /// Columns and lines
+    pub size: (u32, u32),
+    /// Has the keyboard in its window
+    pub active: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Window {
+    /// `@N`
+    pub id: u32,
+    pub name: String,
+    /// How the panes are split, by their ids
+    pub layout: layout::Layout<u32>,
+    /// The active pane takes the whole window
+    pub zoomed: bool,
+    pub panes: Vec<Pane>,
+}
+
+#[derive(Debug)]
+pub enum Notification {
+    /// Output of a pane
+    Output(u32, Vec<u8>),
+    /// The windows of the session as they are now
+    Windows(Vec<Window>),
+    /// What a pane shows, redrawn from the top, for the tab just opened for it
+    Screen(u32, Vec<u8>),
+    /// tmux detached or exited, control mode is over
+    Exit,
+}
+
+/// What a command was sent for
+#[derive(Debug)]
+enum Pending {
+    Panes,
+    Capture(Pane),
+    /// Nothing to do with the answer, only an error is logged
+    Other,
+}
+
+#[derive(Debug, Default)]
+pub struct Control {
+    /// A line that hasn't ended yet
+    line: Vec<u8>,
+    /// The answer being read
+    answer: Option<Answer>,
+    pending: VecDeque<Pending>,
+}
+
+#[derive(Debug)]
+struct Answer {
+    /// Of the command, its `%end` carries it too, so a line of output that happens to start
+    /// with `%end` doesn't end it
+    number: String,
+    /// To a command of ours rather than the one tmux was started with
+    ours: bool,
+    lines: Vec<String>,
+}
+
+impl Control {
+    /// Takes output of tmux, the control mode DCS with it, and hands back what it says once
+    /// whole lines are in. The commands returned are to be written to tmux
+    pub fn feed(&mut self, bytes: &[u8]) -> (Vec<Notification>, Vec<u8>) {
+        let mut notifications = vec![];
+        let mut commands = vec![];
+        if bytes.starts_with(START) {
+            // the session is listed first thing, it's attached already
+            commands.extend(self.list_panes());
+        }
+        let bytes = bytes.strip_prefix(START).unwrap_or(bytes);
+        let (bytes, ended) = match bytes.strip_suffix(END) {
+            Some(bytes) => (bytes, true),
+            None => (bytes, false),
+        };
+
+        for &b in bytes {
+            if b != b'\n' {
+                self.line.push(b);
+                continue;
+            }
+            let line = std::mem::take(&mut self.line);
+            let line = line.strip_suffix(b"\r").unwrap_or(&line);
+            self.line(line, &mut notifications, &mut commands);
+        }
+        if ended
+            && !notifications
+                .iter()
+                .any(|n| matches!(n, Notification::Exit))
+        {
+            notifications.push(Notification::Exit);
+        }
+        (notifications, commands)
+    }
+
+    fn line(&mut self, line: &[u8], notifications: &mut Vec<Notification>, commands: &mut Vec<u8>) {
+        let text = String::from_utf8_lossy(line);
+        let mut words = text.splitn(2, ' ');
+        let (name, rest) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+
+        // `%begin time number flags`, and the same for `%end` and `%error`
+        let mut args = rest.split(' ').skip(1);
+        let (number, flags) = (args.next().unwrap_or(""), args.next().unwrap_or(""));
+
+        if let Some(answer) = self.answer.as_mut() {
+            let end = matches!(name, "%end" | "%error") && number == answer.number;
+            if !end {
+                answer.lines.push(text.into_owned());
+                return;
+            }
+            if let Some(answer) = self.answer.take().filter(|answer| answer.ours) {
+                self.answered(answer.lines, name == "%error", notifications);
+            }
+            return;
+        }
+
+        match name {
+            "%begin" => {
+                self.answer = Some(Answer {
+                    number: number.to_owned(),
+                    // flag 1 is set for the commands of this client
+                    ours: flags.parse::<u32>().is_ok_and(|flags| flags & 1 == 1),
+                    lines: vec![],
+                });
+            }
+            "%output" => {
+                // taken from the bytes rather than the text, so what isn't UTF-8 gets through
+                let rest = line.get("%output ".len()..).unwrap_or_default();
+                let Some(space) = rest.iter().position(|&b| b == b' ') else {
+                    return;
+                };
+                let pane = std::str::from_utf8(&rest[..space]).ok();
+                if let Some(pane) = pane.and_then(|pane| id(pane, '%')) {
+                    let data = unescape(&rest[space + 1..]);
+                    notifications.push(Notification::Output(pane, data));
+                }
+            }
+            "%window-add"
+            | "%window-close"
+            | "%unlinked-window-close"
+            | "%window-renamed"
+            | "%layout-change"
+            | "%window-pane-changed"
+            | "%session-changed" => commands.extend(self.list_panes()),
+            "%exit" => notifications.push(Notification::Exit),
+            _ => {}
+        }
+    }
+
+    fn answered(&mut self, lines: Vec<String>, error: bool, notifications: &mut Vec<Notification>) {
+        let pending = self.pending.pop_front().unwrap_or(Pending::Other);
+        if error {
+            tracing::debug!(?pending, "tmux refused a command: {}", lines.join(" "));
+            return;
+        }
+
+        match pending {
+            Pending::Panes => notifications.push(Notification::Windows(windows(&lines))),
+            Pending::Capture(pane) => {
+                let (x, y) = pane.cursor;
+                let screen = format!(
+                    "\x1b[H\x1b[2J{}\x1b[0m\x1b[{};{}H",
+                    lines.join("\r\n"),
+                    y + 1,
+                    x + 1
+                );
+                notifications.push(Notification::Screen(pane.id, screen.into_bytes()));
+            }
+            Pending::Other => {}
+        }
+    }
+
+    fn command(&mut self, pending: Pending, command: String) -> Vec<u8> {
+        self.pending.push_back(pending);
+        format!("{}\n", command).into_bytes()
+    }
+
+    pub fn list_panes(&mut self) -> Vec<u8> {
+        let command = format!("list-panes -s -F \"{}\"", PANE_FORMAT);
+        self.command(Pending::Panes, command)
+    }
+
+    /// Asks for what `pane` shows, with its colors
+    pub fn capture(&mut self, pane: Pane) -> Vec<u8> {
+        let command = format!("capture-pane -p -e -t %{}", pane.id);
+        self.command(Pending::Capture(pane), command)
+    }
+
+    /// Makes the windows of the session as big as the tabs
+    pub fn resize(&mut self, cols: usize, rows: usize) -> Vec<u8> {
+        self.command(
+            Pending::Other,
+            format!("refresh-client -C {},{}", cols, rows),
+        )
+    }
+
+    /// Gives `pane` the keyboard, when it's clicked
+    pub fn select_pane(&mut self, pane: u32) -> Vec<u8> {
+        self.command(Pending::Other, format!("select-pane -t %{}", pane))
+    }
+
+    /// Changes the layout of the window `pane` is in, tmux says how it looks after
+    pub fn layout(&mut self, pane: u32, change: Change) -> Vec<u8> {
+        let command = match change {
+            Change::Zoom => "resize-pane -Z",
+            Change::SwapPrevious => "swap-pane -U",
+            Change::SwapNext => "swap-pane -D",
+            Change::Rotate => "rotate-window",
+        };
+        self.command(Pending::Other, format!("{} -t %{}", command, pane))
+    }
+
+    /// Opens a pane next to `pane`
+    pub fn split(&mut self, pane: u32, axis: Axis) -> Vec<u8> {
+        let flag = match axis {
+            Axis::Horizontal => "-h",
+            Axis::Vertical => "-v",
+        };
+        self.command(
+            Pending::Other,
+            format!("split-window {} -t %{}", flag, pane),
+        )
+    }
+
+    pub fn kill_pane(&mut self, pane: u32) -> Vec<u8> {
+        self.command(Pending::Other, format!("kill-pane -t %{}", pane))
+    }
+
+    /// Input typed in the tab of `pane`
+    pub fn send_keys(&mut self, pane: u32, bytes: &[u8]) -> Vec<u8> {
+        let mut commands = vec![];
+        for chunk in bytes.chunks(KEYS_PER_COMMAND) {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let command = format!("send-keys -t %{} -H {}", pane, hex.join(" "));
+            commands.extend(self.command(Pending::Other, command));
+        }
+        commands
+    }
+}
+
+/// The lines of `list-panes` with `PANE_FORMAT`, the panes of a window come one after the
+/// other
+fn windows(lines: &[String]) -> Vec<Window> {
+    let mut windows: Vec<Window> = vec![];
+    for line in lines {
+        let Some((pane, window)) = pane(line) else {
+            tracing::debug!("unexpected line from list-panes: {}", line);
+            continue;
+        };
+        match windows.last_mut().filter(|last| last.id == window.id) {
+            Some(last) => last.panes.push(pane),
+            None => windows.push(Window {
+                panes: vec![pane],
+                ..window
+            }),
+        }
+    }
+    windows
+}
+
+/// A line of `list-panes` with `PANE_FORMAT`, and the window the pane is in
+fn pane(line: &str) -> Option<(Pane, Window)> {
+    let mut fields = line.splitn(10, ' ');
+    let mut next = || fields.next();
+    let pane = Pane {
+        id: id(next()?, '%')?,
+        cursor: (next()?.parse().ok()?, next()?.parse().ok()?),
+        size: (next()?.parse().ok()?, next()?.parse().ok()?),
+        active: next()? == "1",
+    };
+    let window = Window {
+        id: id(next()?, '@')?,
+        zoomed: next()? == "1",
+        layout: window_layout(next()?)?,
+        name: next().unwrap_or("").to_owned(),
+        panes: vec![],
+    };
+    Some((pane, window))
+}
+
+/// `#{window_layout}`, a checksum and then the cells of the window: `WxH,X,Y` followed by
+/// the id of its pane, or by the cells it's split into between `{}` side by side or `[]` one
+/// above the other, separated by commas
+fn window_layout(s: &str) -> Option<layout::Layout<u32>> {
+    let (_checksum, cells) = s.split_once(',')?;
+    let (layout, _size, rest) = cell(cells)?;
+    rest.is_empty().then_some(layout)
+}
+
+/// The cell at the start of `s`, its columns and lines and what comes after it
+fn cell(s: &str) -> Option<(layout::Layout<u32>, (u32, u32), &str)> {
+    let (width, s) = number(s)?;
+    let (height, s) = number(s.strip_prefix('x')?)?;
+    let (_x, s) = number(s.strip_prefix(',')?)?;
+    let (_y, s) = number(s.strip_prefix(',')?)?;
+
+    let (axis, close, mut s) = match s.chars().next() {
+        Some('{') => (Axis::Horizontal, '}', &s[1..]),
+        Some('[') => (Axis::Vertical, ']', &s[1..]),
+        _ => {
+            let (pane, s) = number(s.strip_prefix(',')?)?;
+            return Some((layout::Layout::Pane(pane), (width, height), s));
+        }
+    };
+    let mut children = vec![];
+    loop {
+        let (child, (w, h), rest) = cell(s)?;
+        let weight = match axis {
+            Axis::Horizontal => w,
+            Axis::Vertical => h,
+        };
+        children.push((weight.clamp(1, u16::MAX as u32) as u16, child));
+        match rest.strip_prefix(',') {
+            Some(rest) => s = rest,
+            None => {
+                let split = layout::Layout::Split(axis, children);
+                return Some((split, (width, height), rest.strip_prefix(close)?));
+            }
+        }
+    }
+}
+
+/// The digits at the start of `s` and what comes after them
+fn number(s: &str) -> Option<(u32, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    Some((s[..end].parse().ok()?, &s[end..]))
+}
+
+/// `%3` as 3
+fn id(s: &str, sigil: char) -> Option<u32> {
+    s.strip_prefix(sigil)?.parse().ok()
+}
+
+/// Output comes with the control characters and backslashes as octal escapes, `\033`
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let octal = data
+            .get(i + 1..i + 4)
+            .filter(|digits| data[i] == b'\\' && digits.iter().all(|d| (b'0'..=b'7').contains(d)));
+        match octal {
+            Some(digits) => {
+                let value = digits.iter().fold(0u32, |n, d| n * 8 + (d - b'0') as u32);
+                unescaped.push(value as u8);
+                i += 4;
+            }
+            None => {
+                unescaped.push(data[i]);
+                i += 1;
+            }
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use layout::Layout;
+
+    #[test]
+    fn windows_are_split_the_way_tmux_lays_them_out() {
+        let layout =
+            window_layout("bb62,159x48,0,0{79x48,0,0,1,79x48,80,0[79x24,80,0,2,79x23,80,25,3]}");
+        let expected = Layout::Split(
+            Axis::Horizontal,
+            vec![
+                (79, Layout::Pane(1)),
+                (
+                    79,
+                    Layout::Split(
+                        Axis::Vertical,
+                        vec![(24, Layout::Pane(2)), (23, Layout::Pane(3))],
+                    ),
+                ),
+            ],
+        );
+        assert_eq!(layout, Some(expected));
+        assert_eq!(window_layout("c6e2,80x24,0,0,0"), Some(Layout::Pane(0)));
+        assert_eq!(window_layout("c6e2,80x24,0,0{"), None);
+    }
+
+    #[test]
+    fn panes_are_grouped_by_window() {
+        let lines = [
+            "%0 0 0 40 24 1 @0 0 b8a1,81x24,0,0{40x24,0,0,0,40x24,41,0,1} my shell",
+            "%1 5 3 40 24 0 @0 0 b8a1,81x24,0,0{40x24,0,0,0,40x24,41,0,1} my shell",
+            "%2 0 0 81 24 1 @1 1 c6e2,81x24,0,0,2 logs",
+        ]
+        .map(str::to_owned);
+        let windows = windows(&lines);
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].name, "my shell");
+        assert_eq!(windows[0].layout.panes(), vec![0, 1]);
+        assert_eq!(windows[0].panes[1].cursor, (5, 3));
+        assert!(windows[0].panes[0].active && !windows[0].zoomed);
+        assert_eq!((windows[1].id, windows[1].zoomed), (1, true));
+        assert_eq!(windows[1].panes[0].size, (81, 24));
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt as _;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// Writes are split up so a big paste doesn't hold the pty for one long write
+const CHUNK: usize = 4096;
+/// Bytes allowed to wait for the child to read them, past this writes are refused rather than
+/// buffering without bound. Pastes don't count, they'd have typed input refused until they're
+/// through
+const MAX_QUEUED: usize = 4 << 20;
+
+/// Feeds the pty from a task of its own, the UI only ever queues bytes and never waits on a
+/// child that isn't reading its input
+#[derive(Debug, Clone)]
+pub struct PtyWriter {
+    tx: UnboundedSender<Queued>,
+    /// Bytes sent with `send` and not written yet
+    queued: Arc<AtomicUsize>,
+}
+
+/// Bytes on their way to the child, `counted` when they're in `PtyWriter::queued`
+#[derive(Debug)]
+struct Queued {
+    bytes: Vec<u8>,
+    counted: bool,
+}
+
+#[derive(Debug)]
+pub enum WriteError {
+    /// The writer stopped, the child is gone
+    Closed,
+    /// The child hasn't kept up with `MAX_QUEUED` bytes already
+    Full,
+}
+
+impl Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::Closed => write!(f, "the pty is closed"),
+            WriteError::Full => write!(f, "too much input is waiting for the child"),
+        }
+    }
+}
+
+impl PtyWriter {
+    pub fn spawn(file: File) -> Self {
+        let (tx, mut rx) = unbounded_channel::<Queued>();
+        let queued = Arc::new(AtomicUsize::new(0));
+        let pending = queued.clone();
+        let mut file = tokio::fs::File::from(file);
+
+        async_std::task::spawn(async move {
+            while let Some(Queued { bytes, counted }) = rx.recv().await {
+                for chunk in bytes.chunks(CHUNK) {
+                    let written = file.write_all(chunk).await.and(file.flush().await);
+                    if counted {
+                        pending.fetch_sub(chunk.len(), Ordering::Relaxed);
+                    }
+                    if let Err(e) = written {
+                        tracing::error!("failed to write to the pty: {}", e);
+                        return;
+                    }
+                }
+            }
+        });
+
+        Self { tx, queued }
+    }
+
+    /// A writer for sessions without a file, the input comes out of the receiver instead
+    pub fn channel() -> (Self, UnboundedReceiver<Vec<u8>>) {
+        let (tx, mut rx) = unbounded_channel::<Queued>();
+        let (input, received) = unbounded_channel();
+        let queued = Arc::new(AtomicUsize::new(0));
+        let pending = queued.clone();
+
+        async_std::task::spawn(async move {
+            while let Some(Queued { bytes, counted }) = rx.recv().await {
+                if counted {
+                    pending.fetch_sub(bytes.len(), Ordering::Relaxed);
+                }
+                if input.send(bytes).is_err() {
+                    return;
+                }
+            }
+        });
+
+        (Self { tx, queued }, received)
+    }
+
+    /// Queues a paste whatever its size. The text is in memory already so there's no point in
+    /// refusing it, it goes out a chunk at a time as fast as the child reads it. It isn't
+    /// counted against `MAX_QUEUED`, so typing and replies still go through behind it
+    pub fn send_paste(&self, bytes: Vec<u8>) -> Result<(), WriteError> {
+        let queued = Queued {
+            bytes,
+            counted: false,
+        };
+        self.tx.send(queued).map_err(|_| WriteError::Closed)
+    }
+
+    /// Queues typed input or a reply, refused once the child is `MAX_QUEUED` bytes behind on
+    /// them
+    pub fn send(&self, bytes: Vec<u8>) -> Result<(), WriteError> {
+        let len = bytes.len();
+        if self.queued.load(Ordering::Relaxed) + len > MAX_QUEUED {
+            return Err(WriteError::Full);
+        }
+
+        self.queued.fetch_add(len, Ordering::Relaxed);
+        let queued = Queued {
+            bytes,
+            counted: true,
+        };
+        self.tx.send(queued).map_err(|_| WriteError::Closed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pastes_leave_room_for_typing() {
+        let (writer, mut received) = PtyWriter::channel();
+        writer.send_paste(vec![b'x'; MAX_QUEUED * 2]).unwrap();
+        assert_eq!(writer.queued.load(Ordering::Relaxed), 0);
+        writer.send(b"\x03".to_vec()).unwrap();
+
+        let paste = received.blocking_recv().unwrap();
+        assert_eq!(paste.len(), MAX_QUEUED * 2);
+        assert_eq!(received.blocking_recv().unwrap(), b"\x03");
+        assert_eq!(writer.queued.load(Ordering::Relaxed), 0);
+    }
+}